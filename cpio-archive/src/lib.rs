@@ -3,7 +3,7 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 pub mod newc;
-pub use newc::{NewcHeader, NewcReader};
+pub use newc::{CrcBuilder, CrcReader, NewcBuilder, NewcHeader, NewcReader};
 pub mod odc;
 pub use odc::{OdcBuilder, OdcHeader, OdcReader};
 
@@ -41,6 +41,9 @@ pub enum Error {
 
     #[error("path is not a file: {0}")]
     NotAFile(PathBuf),
+
+    #[error("checksum mismatch for {0}: expected {1:08x}, got {2:08x}")]
+    ChecksumMismatch(String, u32, u32),
 }
 
 /// Result type for this crate.
@@ -129,6 +132,7 @@ pub fn reader<T: 'static + Read + Sized>(mut reader: T) -> CpioResult<Box<Chaine
 
     match magic.as_ref() {
         crate::newc::MAGIC => Ok(Box::new(NewcReader::new(Cursor::new(magic).chain(reader)))),
+        crate::newc::CRC_MAGIC => Ok(Box::new(CrcReader::new(Cursor::new(magic).chain(reader)))),
         crate::odc::MAGIC => Ok(Box::new(OdcReader::new(Cursor::new(magic).chain(reader)))),
         _ => Err(Error::BadMagic),
     }
@@ -3,17 +3,45 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 //! New ASCII format support.
+//!
+//! This module implements support for the *New ASCII format* (`newc`) and
+//! its variant with checksums (`crc`). These are the formats used by Linux
+//! initramfs images and RPM payloads.
+//!
+//! The two variants share an identical header layout and only differ in
+//! their header magic and in whether the checksum field is populated. So
+//! readers and builders are generic over a [NewcVariant] and the
+//! [NewcReader], [CrcReader], [NewcBuilder], and [CrcBuilder] aliases
+//! should be used.
 
 use {
     crate::{CpioHeader, CpioReader, CpioResult, Error},
+    chrono::{DateTime, TimeZone, Utc},
+    is_executable::IsExecutable,
     std::{
+        collections::HashSet,
         ffi::CStr,
-        io::{Read, Take},
+        io::{Read, Seek, SeekFrom, Take, Write},
+        marker::PhantomData,
+        path::Path,
+    },
+    tugger_file_manifest::{
+        FileManifest, Reproducibility, S_IFDIR, S_IFREG, S_IRGRP, S_IROTH, S_IRUSR, S_IWUSR,
+        S_IXGRP, S_IXOTH, S_IXUSR,
     },
 };
 
+/// Header magic for newc entries.
 pub const MAGIC: &[u8] = b"070701";
 
+/// Header magic for crc entries.
+pub const CRC_MAGIC: &[u8] = b"070702";
+
+const TRAILER: &str = "TRAILER!!!";
+
+/// Size of a header, including its magic but excluding the file name.
+const HEADER_SIZE: usize = 6 + 13 * 8;
+
 fn u32_from_hex(data: &[u8]) -> CpioResult<u32> {
     let s = std::str::from_utf8(data).map_err(|_| Error::BadHeaderString)?;
     u32::from_str_radix(s, 16).map_err(|_| Error::BadHeaderHex(s.to_string()))
@@ -36,6 +64,73 @@ fn read_hex_u64(reader: &mut impl Read, count: usize) -> CpioResult<u64> {
     u64_from_hex(&buffer)
 }
 
+fn write_hex(value: u64, writer: &mut impl Write) -> CpioResult<()> {
+    if value > u32::MAX as u64 {
+        return Err(Error::ValueTooLarge);
+    }
+
+    writer.write_all(format!("{:08x}", value).as_bytes())?;
+
+    Ok(())
+}
+
+/// Number of padding bytes needed to align a length to 4 bytes.
+fn pad_length(length: usize) -> usize {
+    (4 - length % 4) % 4
+}
+
+/// Compute the checksum of data as stored in crc archives.
+///
+/// The checksum is the sum of all bytes, truncated to 32 bits.
+pub fn checksum(data: &[u8]) -> u32 {
+    data.iter()
+        .fold(0u32, |sum, byte| sum.wrapping_add(*byte as u32))
+}
+
+fn checksum_reader(reader: &mut impl Read) -> CpioResult<u32> {
+    let mut sum = 0u32;
+    let mut buffer = vec![0u8; 32768];
+
+    loop {
+        let count = reader.read(&mut buffer)?;
+        if count == 0 {
+            break;
+        }
+
+        sum = sum.wrapping_add(checksum(&buffer[0..count]));
+    }
+
+    Ok(sum)
+}
+
+/// Describes a variant of the *New ASCII format*.
+pub trait NewcVariant {
+    /// Header magic identifying entries of this variant.
+    const MAGIC: &'static [u8];
+
+    /// Whether the header checksum field holds the checksum of file data.
+    const CHECKSUM: bool;
+}
+
+/// The `newc` variant, without checksums.
+#[derive(Clone, Copy, Debug)]
+pub struct Newc;
+
+impl NewcVariant for Newc {
+    const MAGIC: &'static [u8] = MAGIC;
+    const CHECKSUM: bool = false;
+}
+
+/// The `crc` variant, with checksums of file data.
+#[derive(Clone, Copy, Debug)]
+pub struct Crc;
+
+impl NewcVariant for Crc {
+    const MAGIC: &'static [u8] = CRC_MAGIC;
+    const CHECKSUM: bool = true;
+}
+
+/// Parsed New ASCII format header.
 #[derive(Clone, Debug)]
 pub struct NewcHeader {
     pub inode: u32,
@@ -54,6 +149,9 @@ pub struct NewcHeader {
 }
 
 impl NewcHeader {
+    /// Parse a header from a reader.
+    ///
+    /// The reader should be positioned just after the header magic.
     pub fn from_reader(reader: &mut impl Read) -> CpioResult<Self> {
         let inode = read_hex_u32(reader, 8)?;
         let mode = read_hex_u32(reader, 8)?;
@@ -77,8 +175,8 @@ impl NewcHeader {
             .to_string_lossy()
             .to_string();
 
-        // Pad to 4 byte boundary.
-        let mut pad = vec![0u8; name_data.len() % 4];
+        // The header and file name are padded to a 4 byte boundary.
+        let mut pad = vec![0u8; pad_length(HEADER_SIZE + name_data.len())];
         reader.read_exact(&mut pad)?;
 
         Ok(Self {
@@ -97,6 +195,39 @@ impl NewcHeader {
             name,
         })
     }
+
+    /// Write the binary header content to a writer.
+    ///
+    /// `magic` is the header magic to write. It should be [MAGIC] or [CRC_MAGIC].
+    ///
+    /// Padding following the file name is written. Padding following file data
+    /// is not.
+    pub fn write(&self, writer: &mut impl Write, magic: &[u8]) -> CpioResult<u64> {
+        let name_length = self.name.len() + 1;
+
+        writer.write_all(magic)?;
+        write_hex(self.inode as _, writer)?;
+        write_hex(self.mode as _, writer)?;
+        write_hex(self.uid as _, writer)?;
+        write_hex(self.gid as _, writer)?;
+        write_hex(self.nlink as _, writer)?;
+        write_hex(self.mtime as _, writer)?;
+        write_hex(self.file_size, writer)?;
+        write_hex(self.dev_major as _, writer)?;
+        write_hex(self.dev_minor as _, writer)?;
+        write_hex(self.rdev_major as _, writer)?;
+        write_hex(self.rdev_minor as _, writer)?;
+        write_hex(name_length as _, writer)?;
+        write_hex(self.checksum as _, writer)?;
+
+        writer.write_all(self.name.as_bytes())?;
+        writer.write_all(b"\0")?;
+
+        let pad = pad_length(HEADER_SIZE + name_length);
+        writer.write_all(&[0u8; 3][0..pad])?;
+
+        Ok((HEADER_SIZE + name_length + pad) as u64)
+    }
 }
 
 impl CpioHeader for NewcHeader {
@@ -141,21 +272,46 @@ impl CpioHeader for NewcHeader {
     }
 }
 
+/// Tracks the checksum of the current entry's data as it is read.
+struct EntryChecksum {
+    name: String,
+    expected: u32,
+    actual: u32,
+}
+
 /// A cpio archive reader for *New ASCII format* archives.
-pub struct NewcReader<T: Read + Sized> {
+///
+/// Use the [NewcReader] and [CrcReader] aliases to read `newc` and `crc`
+/// archives, respectively.
+///
+/// When reading `crc` archives, the checksum of each entry's data is
+/// validated once the entry has been consumed. A mismatch results in
+/// [Error::ChecksumMismatch] being returned when advancing to the next
+/// entry.
+pub struct NewcVariantReader<T: Read + Sized, V: NewcVariant> {
     archive_reader: Option<T>,
     entry_reader: Option<Take<T>>,
     entry_data_pad: usize,
+    entry_checksum: Option<EntryChecksum>,
     seen_trailer: bool,
+    variant: PhantomData<V>,
 }
 
-impl<T: Read + Sized> CpioReader<T> for NewcReader<T> {
+/// A cpio archive reader for `newc` archives.
+pub type NewcReader<T> = NewcVariantReader<T, Newc>;
+
+/// A cpio archive reader for `crc` archives which validates checksums.
+pub type CrcReader<T> = NewcVariantReader<T, Crc>;
+
+impl<T: Read + Sized, V: NewcVariant> CpioReader<T> for NewcVariantReader<T, V> {
     fn new(reader: T) -> Self {
         Self {
             archive_reader: Some(reader),
             entry_reader: None,
             entry_data_pad: 0,
+            entry_checksum: None,
             seen_trailer: false,
+            variant: PhantomData,
         }
     }
 
@@ -175,18 +331,25 @@ impl<T: Read + Sized> CpioReader<T> for NewcReader<T> {
                 }
             }
 
-            if magic != MAGIC {
+            if magic != V::MAGIC {
                 return Err(Error::BadMagic);
             }
 
             let header = NewcHeader::from_reader(&mut reader)?;
 
-            if header.name == "TRAILER!!!" {
+            if header.name == TRAILER {
                 self.seen_trailer = true;
                 Ok(None)
             } else {
                 self.entry_reader = Some(reader.take(header.file_size as _));
-                self.entry_data_pad = header.file_size as usize % 4;
+                self.entry_data_pad = pad_length(header.file_size as usize);
+                if V::CHECKSUM {
+                    self.entry_checksum = Some(EntryChecksum {
+                        name: header.name.clone(),
+                        expected: header.checksum,
+                        actual: 0,
+                    });
+                }
                 Ok(Some(Box::new(header)))
             }
         } else {
@@ -196,12 +359,7 @@ impl<T: Read + Sized> CpioReader<T> for NewcReader<T> {
 
     fn finish(&mut self) -> CpioResult<()> {
         if let Some(mut reader) = self.entry_reader.take() {
-            let mut buffer = vec![0u8; 32768];
-            loop {
-                if reader.read(&mut buffer)? == 0 {
-                    break;
-                }
-            }
+            let remaining = checksum_reader(&mut reader)?;
 
             let mut reader = reader.into_inner();
 
@@ -209,6 +367,18 @@ impl<T: Read + Sized> CpioReader<T> for NewcReader<T> {
             reader.read_exact(&mut pad)?;
             self.entry_data_pad = 0;
 
+            if let Some(mut entry) = self.entry_checksum.take() {
+                entry.actual = entry.actual.wrapping_add(remaining);
+
+                if entry.actual != entry.expected {
+                    return Err(Error::ChecksumMismatch(
+                        entry.name,
+                        entry.expected,
+                        entry.actual,
+                    ));
+                }
+            }
+
             // Only restore the archive reader if we haven't seen the trailer,
             // as the trailer indicates end of archive.
             if !self.seen_trailer {
@@ -220,7 +390,7 @@ impl<T: Read + Sized> CpioReader<T> for NewcReader<T> {
     }
 }
 
-impl<T: Read + Sized> Iterator for NewcReader<T> {
+impl<T: Read + Sized, V: NewcVariant> Iterator for NewcVariantReader<T, V> {
     type Item = CpioResult<Box<dyn CpioHeader>>;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -232,10 +402,16 @@ impl<T: Read + Sized> Iterator for NewcReader<T> {
     }
 }
 
-impl<T: Read + Sized> Read for NewcReader<T> {
+impl<T: Read + Sized, V: NewcVariant> Read for NewcVariantReader<T, V> {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
         if let Some(reader) = &mut self.entry_reader {
-            reader.read(buf)
+            let count = reader.read(buf)?;
+
+            if let Some(entry) = &mut self.entry_checksum {
+                entry.actual = entry.actual.wrapping_add(checksum(&buf[0..count]));
+            }
+
+            Ok(count)
         } else {
             Err(std::io::Error::new(
                 std::io::ErrorKind::Other,
@@ -244,3 +420,535 @@ impl<T: Read + Sized> Read for NewcReader<T> {
         }
     }
 }
+
+/// Iteratively create a cpio archive using the *New ASCII format*.
+///
+/// Use the [NewcBuilder] and [CrcBuilder] aliases to write `newc` and `crc`
+/// archives, respectively. When writing `crc` archives, checksums of file
+/// data are computed automatically, except when appending raw headers.
+///
+/// cpio archives logically consist of 2-tuples of (file header, data), so
+/// data can be streamed by iteratively feeding new entries to write.
+///
+/// cpio archives contain a special file header denoting the end of the
+/// archive. This is emitted by calling [Self::finish]. So consumers should
+/// always call this method when done writing new files.
+///
+/// By default, missing parent directories are automatically emitted when
+/// writing files. Instances track which directories have been emitted. Upon
+/// encountering a file path in a directory that has not yet been emitted,
+/// a directory entry will be emitted. This behavior can be disabled by
+/// calling [Self::auto_write_dirs].
+pub struct NewcVariantBuilder<W: Write + Sized, V: NewcVariant> {
+    writer: W,
+    default_uid: u32,
+    default_gid: u32,
+    default_mtime: DateTime<Utc>,
+    default_mode_file: u32,
+    default_mode_dir: u32,
+    auto_write_dirs: bool,
+    seen_dirs: HashSet<String>,
+    entry_count: u32,
+    finished: bool,
+    variant: PhantomData<V>,
+}
+
+/// A builder of `newc` cpio archives.
+pub type NewcBuilder<W> = NewcVariantBuilder<W, Newc>;
+
+/// A builder of `crc` cpio archives.
+pub type CrcBuilder<W> = NewcVariantBuilder<W, Crc>;
+
+impl<W: Write + Sized, V: NewcVariant> NewcVariantBuilder<W, V> {
+    /// Construct a new instance which will write data to a writer.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            default_uid: 0,
+            default_gid: 0,
+            default_mtime: Utc::now(),
+            default_mode_file: S_IFREG | S_IRUSR | S_IWUSR | S_IRGRP | S_IROTH,
+            default_mode_dir: S_IFDIR
+                | S_IRUSR
+                | S_IWUSR
+                | S_IXUSR
+                | S_IRGRP
+                | S_IXGRP
+                | S_IROTH
+                | S_IXOTH,
+            auto_write_dirs: true,
+            seen_dirs: HashSet::new(),
+            entry_count: 0,
+            finished: false,
+            variant: PhantomData,
+        }
+    }
+
+    /// Set the default file mode to use for files.
+    pub fn default_mode_file(&mut self, mode: u32) {
+        self.default_mode_file = mode;
+    }
+
+    /// Set the default file mode to use for directories.
+    pub fn default_mode_directory(&mut self, mode: u32) {
+        self.default_mode_dir = mode;
+    }
+
+    /// Set the default user ID (UID).
+    pub fn default_user_id(&mut self, uid: u32) {
+        self.default_uid = uid;
+    }
+
+    /// Set the default group ID (GID).
+    pub fn default_group_id(&mut self, gid: u32) {
+        self.default_gid = gid;
+    }
+
+    /// Set the default modified time.
+    pub fn default_mtime(&mut self, mtime: DateTime<Utc>) {
+        self.default_mtime = mtime;
+    }
+
+    /// Normalize entry metadata so archive content is reproducible.
+    ///
+    /// This sets the default modified time, ownership, and modes to values
+    /// from a [Reproducibility]. Entries are written in the order they are
+    /// appended, so callers should append them in a stable order.
+    /// [Self::append_file_manifest] does so.
+    pub fn reproducibility(&mut self, value: Reproducibility) {
+        self.default_mtime = Utc.timestamp(value.mtime() as i64, 0);
+        self.default_uid = value.owner_id();
+        self.default_gid = value.owner_id();
+        self.default_mode_file = S_IFREG | value.file_mode(false);
+        self.default_mode_dir = S_IFDIR | value.directory_mode();
+    }
+
+    /// Set the behavior for auto writing directory entries.
+    pub fn auto_write_dirs(&mut self, value: bool) {
+        self.auto_write_dirs = value;
+    }
+
+    /// Obtain a header record representing the next header in the archive.
+    ///
+    /// The header has fields set to default values. Callers should likely
+    /// update at least the name and possibly the file size, mode, and checksum.
+    ///
+    /// This will increment the inode sequence number when called.
+    pub fn next_header(&mut self) -> NewcHeader {
+        let inode = self.entry_count;
+        self.entry_count += 1;
+
+        NewcHeader {
+            inode,
+            mode: self.default_mode_file,
+            uid: self.default_uid,
+            gid: self.default_gid,
+            nlink: 1,
+            mtime: self.default_mtime.timestamp() as _,
+            file_size: 0,
+            dev_major: 0,
+            dev_minor: 0,
+            rdev_major: 0,
+            rdev_minor: 0,
+            checksum: 0,
+            name: "".to_string(),
+        }
+    }
+
+    fn normalize_archive_path(&self, path: &str) -> String {
+        if path.starts_with("./") {
+            path.to_string()
+        } else {
+            format!("./{}", path)
+        }
+    }
+
+    /// Write padding following file data of the given length.
+    fn write_data_padding(&mut self, length: u64) -> CpioResult<u64> {
+        let pad = pad_length(length as usize);
+        self.writer.write_all(&[0u8; 3][0..pad])?;
+
+        Ok(pad as u64)
+    }
+
+    /// Write missing parent directory entries for a given file path.
+    fn emit_parent_directories(&mut self, file_path: &str) -> CpioResult<u64> {
+        if !self.auto_write_dirs {
+            return Ok(0);
+        }
+
+        let parts = file_path.split('/').collect::<Vec<_>>();
+
+        let mut bytes_written = 0;
+
+        for idx in 1..parts.len() {
+            let dir = parts
+                .clone()
+                .into_iter()
+                .take(idx)
+                .collect::<Vec<&str>>()
+                .join("/");
+
+            if !self.seen_dirs.contains(&dir) {
+                let mut header = self.next_header();
+                header.mode = self.default_mode_dir;
+                header.nlink = 2;
+                header.name = dir.clone();
+
+                bytes_written += header.write(&mut self.writer, V::MAGIC)?;
+                self.seen_dirs.insert(dir);
+            }
+        }
+
+        Ok(bytes_written)
+    }
+
+    /// Append a raw header and corresponding file data to the writer.
+    ///
+    /// The writer and data are written as-is. This includes the header's
+    /// checksum field.
+    ///
+    /// Only simple validation that the data length matches the length advertised
+    /// in the header is performed.
+    ///
+    /// Automatic directory emission is not processed in this mode.
+    pub fn append_header_with_data(
+        &mut self,
+        header: NewcHeader,
+        data: impl AsRef<[u8]>,
+    ) -> CpioResult<u64> {
+        let data = data.as_ref();
+
+        if header.file_size as usize != data.len() {
+            return Err(Error::SizeMismatch);
+        }
+
+        let written = header.write(&mut self.writer, V::MAGIC)?;
+        self.writer.write_all(data)?;
+        let pad = self.write_data_padding(data.len() as _)?;
+
+        Ok(written + data.len() as u64 + pad)
+    }
+
+    /// Append a raw header and corresponding data from a reader to the writer.
+    ///
+    /// The header's file size must match the length of data available in the reader
+    /// or errors could occur. This method will copy all data available in the reader
+    /// to the output stream. If the number of bytes written does not match what is
+    /// reported by the header, the cpio archive stream is effectively corrupted
+    /// and an error is returned.
+    ///
+    /// The header's checksum field is written as-is.
+    pub fn append_header_with_reader(
+        &mut self,
+        header: NewcHeader,
+        reader: &mut impl Read,
+    ) -> CpioResult<u64> {
+        let written = header.write(&mut self.writer, V::MAGIC)?;
+        let copied = std::io::copy(reader, &mut self.writer)?;
+
+        if copied != header.file_size {
+            Err(Error::SizeMismatch)
+        } else {
+            let pad = self.write_data_padding(copied)?;
+
+            Ok(written + copied + pad)
+        }
+    }
+
+    /// Write a regular file to the cpio archive with provided file data and file mode.
+    pub fn append_file_from_data(
+        &mut self,
+        archive_path: impl ToString,
+        data: impl AsRef<[u8]>,
+        mode: u32,
+    ) -> CpioResult<u64> {
+        let archive_path = self.normalize_archive_path(&archive_path.to_string());
+        let data = data.as_ref();
+
+        let mut bytes_written = self.emit_parent_directories(&archive_path)?;
+
+        let mut header = self.next_header();
+        header.name = archive_path;
+        header.file_size = data.len() as _;
+        header.mode = mode;
+        if V::CHECKSUM {
+            header.checksum = checksum(data);
+        }
+
+        bytes_written += self.append_header_with_data(header, data)?;
+
+        Ok(bytes_written)
+    }
+
+    /// Write a regular file to the cpio archive.
+    ///
+    /// This takes the relative path in the archive and the filesystem path of
+    /// the file to write. It resolves header metadata automatically given filesystem
+    /// attributes. However, the UID, GID, and mtime defaults specified on this
+    /// builder are used so archive construction is more deterministic.
+    pub fn append_file_from_path(
+        &mut self,
+        archive_path: impl ToString,
+        path: impl AsRef<Path>,
+    ) -> CpioResult<u64> {
+        let archive_path = self.normalize_archive_path(&archive_path.to_string());
+        let path = path.as_ref();
+
+        let mut fh = std::fs::File::open(path)?;
+        let metadata = fh.metadata()?;
+
+        if !metadata.is_file() {
+            return Err(Error::NotAFile(path.to_path_buf()));
+        }
+
+        // Emit parent directories first, so inode number is sequential.
+        let mut bytes_written = self.emit_parent_directories(&archive_path)?;
+
+        let mut header = self.next_header();
+        header.name = archive_path;
+        header.file_size = metadata.len();
+
+        if path.is_executable() {
+            header.mode |= S_IXUSR | S_IXGRP | S_IXOTH;
+        }
+
+        if V::CHECKSUM {
+            header.checksum = checksum_reader(&mut fh)?;
+            fh.seek(SeekFrom::Start(0))?;
+        }
+
+        bytes_written += self.append_header_with_reader(header, &mut fh)?;
+
+        Ok(bytes_written)
+    }
+
+    /// Append a [FileManifest] to the archive.
+    pub fn append_file_manifest(&mut self, manifest: &FileManifest) -> CpioResult<u64> {
+        let mut bytes_written = 0;
+
+        for (path, entry) in manifest.iter_entries() {
+            let mode = if entry.is_executable() { 0o755 } else { 0o644 };
+            let data = entry.resolve_content()?;
+
+            bytes_written +=
+                self.append_file_from_data(path.display().to_string(), data, S_IFREG | mode)?;
+        }
+
+        Ok(bytes_written)
+    }
+
+    /// Finish writing the archive.
+    ///
+    /// This will emit a special header denoting the end of archive.
+    ///
+    /// Failure to call this method will result in a malformed cpio archive.
+    /// Readers may or may not handle the missing trailer correctly.
+    pub fn finish(&mut self) -> CpioResult<u64> {
+        if !self.finished {
+            let mut header = self.next_header();
+            header.name = TRAILER.to_string();
+            let count = header.write(&mut self.writer, V::MAGIC)?;
+            self.finished = true;
+
+            Ok(count)
+        } else {
+            Ok(0)
+        }
+    }
+
+    /// Consume self and return the original writer this instance was constructed from.
+    ///
+    /// This will automatically finish the archive if needed.
+    pub fn into_inner(mut self) -> CpioResult<W> {
+        self.finish()?;
+
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::io::Cursor, tugger_file_manifest::FileEntry};
+
+    fn sample_manifest() -> FileManifest {
+        let mut manifest = FileManifest::default();
+        manifest
+            .add_file_entry(
+                "usr/bin/myapp",
+                FileEntry::new_from_data(b"#!/bin/sh\necho hello\n".to_vec(), true),
+            )
+            .unwrap();
+        manifest
+            .add_file_entry(
+                "usr/share/doc/README",
+                FileEntry::new_from_data(b"read me".to_vec(), false),
+            )
+            .unwrap();
+
+        manifest
+    }
+
+    fn read_entries<V: NewcVariant>(
+        data: Vec<u8>,
+    ) -> CpioResult<Vec<(NewcHeaderSummary, Vec<u8>)>> {
+        let mut reader = NewcVariantReader::<_, V>::new(Cursor::new(data));
+
+        let mut entries = vec![];
+        while let Some(header) = reader.read_next()? {
+            let mut file_data = Vec::<u8>::with_capacity(header.file_size() as _);
+            reader.read_to_end(&mut file_data)?;
+
+            entries.push((
+                NewcHeaderSummary {
+                    name: header.name().to_string(),
+                    mode: header.mode(),
+                },
+                file_data,
+            ));
+        }
+
+        Ok(entries)
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct NewcHeaderSummary {
+        name: String,
+        mode: u32,
+    }
+
+    fn summary(name: &str, mode: u32) -> NewcHeaderSummary {
+        NewcHeaderSummary {
+            name: name.to_string(),
+            mode,
+        }
+    }
+
+    #[test]
+    fn write_single_file() {
+        let mut builder = NewcBuilder::new(Cursor::new(Vec::<u8>::new()));
+
+        let current_exe = std::env::current_exe().unwrap();
+        let current_exe_data = std::fs::read(&current_exe).unwrap();
+        builder
+            .append_file_from_path("child/grandchild/exe", current_exe)
+            .unwrap();
+
+        let data = builder.into_inner().unwrap().into_inner();
+        assert_eq!(data.len() % 4, 0);
+
+        let entries = read_entries::<Newc>(data).unwrap();
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[0].0.name, ".");
+        assert_eq!(entries[1].0.name, "./child");
+        assert_eq!(entries[2].0.name, "./child/grandchild");
+        assert_eq!(entries[3].0.name, "./child/grandchild/exe");
+
+        for (header, data) in &entries[0..3] {
+            assert!(data.is_empty());
+            assert_ne!(header.mode & S_IFDIR, 0);
+        }
+
+        assert_eq!(&entries[3].1, &current_exe_data);
+    }
+
+    #[test]
+    fn newc_file_manifest() -> CpioResult<()> {
+        let mut builder = NewcBuilder::new(vec![]);
+        builder.append_file_manifest(&sample_manifest())?;
+        let data = builder.into_inner()?;
+
+        assert_eq!(&data[0..6], MAGIC);
+
+        let entries = read_entries::<Newc>(data)?;
+        assert_eq!(
+            entries,
+            vec![
+                (summary(".", S_IFDIR | 0o755), vec![]),
+                (summary("./usr", S_IFDIR | 0o755), vec![]),
+                (summary("./usr/bin", S_IFDIR | 0o755), vec![]),
+                (
+                    summary("./usr/bin/myapp", S_IFREG | 0o755),
+                    b"#!/bin/sh\necho hello\n".to_vec()
+                ),
+                (summary("./usr/share", S_IFDIR | 0o755), vec![]),
+                (summary("./usr/share/doc", S_IFDIR | 0o755), vec![]),
+                (
+                    summary("./usr/share/doc/README", S_IFREG | 0o644),
+                    b"read me".to_vec()
+                ),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn crc_file_manifest() -> CpioResult<()> {
+        let mut builder = CrcBuilder::new(vec![]);
+        builder.auto_write_dirs(false);
+        builder.append_file_manifest(&sample_manifest())?;
+        let data = builder.into_inner()?;
+
+        assert_eq!(&data[0..6], CRC_MAGIC);
+
+        let entries = read_entries::<Crc>(data.clone())?;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].0.name, "./usr/bin/myapp");
+        assert_eq!(entries[1].1, b"read me".to_vec());
+
+        // Reading with the wrong variant fails.
+        assert!(matches!(read_entries::<Newc>(data), Err(Error::BadMagic)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn crc_checksum_mismatch() -> CpioResult<()> {
+        let mut builder = CrcBuilder::new(vec![]);
+        builder.auto_write_dirs(false);
+        builder.append_file_from_data("file", b"abc", S_IFREG | 0o644)?;
+        let mut data = builder.into_inner()?;
+
+        // Corrupt the file data, which immediately follows the padded header.
+        let offset = HEADER_SIZE + "./file\0".len();
+        let offset = offset + pad_length(offset);
+        assert_eq!(&data[offset..offset + 3], b"abc");
+        data[offset] = b'x';
+
+        let mut reader = CrcReader::new(Cursor::new(data.clone()));
+        reader.read_next()?.unwrap();
+        let res = reader.read_next();
+        assert!(matches!(
+            res,
+            Err(Error::ChecksumMismatch(ref name, expected, actual))
+                if name == "./file" && expected == 0x126 && actual == 0x13d
+        ));
+
+        // Mismatches are also detected when data isn't consumed.
+        let mut reader = CrcReader::new(Cursor::new(data));
+        reader.read_next()?.unwrap();
+        let mut buf = [0u8; 1];
+        reader.read_exact(&mut buf)?;
+        assert!(reader.read_next().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn reproducibility() -> CpioResult<()> {
+        let mut builder = NewcBuilder::new(vec![]);
+        builder.reproducibility(Reproducibility::new(1_600_000_000));
+        builder.append_file_manifest(&sample_manifest())?;
+        let data = builder.into_inner()?;
+
+        let mut reader = NewcReader::new(Cursor::new(data));
+        while let Some(header) = reader.read_next()? {
+            assert_eq!(header.mtime(), 1_600_000_000);
+            assert_eq!(header.uid(), 0);
+            assert_eq!(header.gid(), 0);
+        }
+
+        Ok(())
+    }
+}
@@ -10,7 +10,7 @@
 
 use {
     crate::{CpioHeader, CpioReader, CpioResult, Error},
-    chrono::{DateTime, Utc},
+    chrono::{DateTime, TimeZone, Utc},
     is_executable::IsExecutable,
     std::{
         collections::HashSet,
@@ -19,7 +19,8 @@ use {
         path::Path,
     },
     tugger_file_manifest::{
-        FileManifest, S_IFDIR, S_IRGRP, S_IROTH, S_IRUSR, S_IWUSR, S_IXGRP, S_IXOTH, S_IXUSR,
+        FileManifest, Reproducibility, S_IFDIR, S_IRGRP, S_IROTH, S_IRUSR, S_IWUSR, S_IXGRP,
+        S_IXOTH, S_IXUSR,
     },
 };
 
@@ -355,6 +356,20 @@ impl<W: Write + Sized> OdcBuilder<W> {
         self.default_mtime = mtime;
     }
 
+    /// Normalize entry metadata so archive content is reproducible.
+    ///
+    /// This sets the default modified time, ownership, and modes to values
+    /// from a [Reproducibility]. Entries are written in the order they are
+    /// appended, so callers should append them in a stable order.
+    /// [Self::append_file_manifest] does so.
+    pub fn reproducibility(&mut self, value: Reproducibility) {
+        self.default_mtime = Utc.timestamp(value.mtime() as i64, 0);
+        self.default_uid = value.owner_id();
+        self.default_gid = value.owner_id();
+        self.default_mode_file = value.file_mode(false);
+        self.default_mode_dir = S_IFDIR | value.directory_mode();
+    }
+
     /// Set the behavior for auto writing directory entries.
     pub fn auto_write_dirs(&mut self, value: bool) {
         self.auto_write_dirs = value;
@@ -28,6 +28,14 @@ pub const OID_CONTENT_TYPE_TST_INFO: ConstOid = Oid(&[42, 134, 72, 134, 247, 13,
 /// 1.2.840.113549.1.9.16.2.14
 pub const OID_TIME_STAMP_TOKEN: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 16, 2, 14]);
 
+/// Microsoft's `szOID_RFC3161_counterSign`.
+///
+/// Authenticode signatures hold time-stamp tokens in an unsigned attribute
+/// of this type instead of `id-aa-timeStampToken`.
+///
+/// 1.3.6.1.4.1.311.3.3.1
+pub const OID_MS_RFC3161_COUNTER_SIGNATURE: ConstOid = Oid(&[43, 6, 1, 4, 1, 130, 55, 3, 3, 1]);
+
 /// A time-stamp request.
 ///
 /// ```ASN.1
@@ -302,10 +302,17 @@ impl SignerInfos {
 ///   eContentType ContentType,
 ///   eContent [0] EXPLICIT OCTET STRING OPTIONAL }
 /// ```
+///
+/// Some producers, notably Authenticode, embed a `SEQUENCE` in `eContent`
+/// instead of an `OCTET STRING`. When `content_is_sequence` is set, `content`
+/// holds the content octets of that `SEQUENCE` (its encoding minus the tag
+/// and length), which is also the data covered by the `message-digest`
+/// signed attribute.
 #[derive(Clone, Eq, PartialEq)]
 pub struct EncapsulatedContentInfo {
     pub content_type: ContentType,
     pub content: Option<OctetString>,
+    pub content_is_sequence: bool,
 }
 
 impl Debug for EncapsulatedContentInfo {
@@ -321,6 +328,7 @@ impl Debug for EncapsulatedContentInfo {
                     .map(|x| hex::encode(x.clone().to_bytes().as_ref()))
             ),
         );
+        s.field("content_is_sequence", &self.content_is_sequence);
         s.finish()
     }
 }
@@ -329,12 +337,24 @@ impl EncapsulatedContentInfo {
     pub fn take_from<S: Source>(cons: &mut Constructed<S>) -> Result<Self, S::Err> {
         cons.take_sequence(|cons| {
             let content_type = ContentType::take_from(cons)?;
-            let content =
-                cons.take_opt_constructed_if(Tag::CTX_0, |cons| OctetString::take_from(cons))?;
+            let content = cons.take_opt_constructed_if(Tag::CTX_0, |cons| {
+                cons.take_value(|tag, content| {
+                    if tag == Tag::OCTET_STRING {
+                        Ok((OctetString::from_content(content)?, false))
+                    } else if tag == Tag::SEQUENCE {
+                        let captured = content.as_constructed()?.capture_all()?;
+
+                        Ok((OctetString::new(captured.into_bytes()), true))
+                    } else {
+                        Err(Unimplemented.into())
+                    }
+                })
+            })?;
 
             Ok(Self {
                 content_type,
-                content,
+                content_is_sequence: matches!(content, Some((_, true))),
+                content: content.map(|(content, _)| content),
             })
         })
     }
@@ -342,13 +362,33 @@ impl EncapsulatedContentInfo {
     pub fn encode_ref(&self) -> impl Values + '_ {
         encode::sequence((
             self.content_type.encode_ref(),
-            self.content
-                .as_ref()
-                .map(|content| encode::sequence_as(Tag::CTX_0, content.encode_ref())),
+            self.content.as_ref().map(|content| {
+                encode::sequence_as(
+                    Tag::CTX_0,
+                    if self.content_is_sequence {
+                        encode::Choice2::One(encode::sequence(RawContent(content)))
+                    } else {
+                        encode::Choice2::Two(content.encode_ref())
+                    },
+                )
+            }),
         ))
     }
 }
 
+/// Writes the octets of an `OctetString` verbatim, without a tag or length.
+struct RawContent<'a>(&'a OctetString);
+
+impl<'a> Values for RawContent<'a> {
+    fn encoded_len(&self, _: Mode) -> usize {
+        self.0.len()
+    }
+
+    fn write_encoded<W: Write>(&self, _: Mode, target: &mut W) -> Result<(), std::io::Error> {
+        target.write_all(self.0.to_bytes().as_ref())
+    }
+}
+
 /// Per-signer information.
 ///
 /// ```ASN.1
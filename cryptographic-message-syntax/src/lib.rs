@@ -82,7 +82,7 @@ pub use {
 
 use {
     crate::asn1::{
-        rfc3161::OID_TIME_STAMP_TOKEN,
+        rfc3161::{OID_MS_RFC3161_COUNTER_SIGNATURE, OID_TIME_STAMP_TOKEN},
         rfc5652::{
             CertificateChoices, SignerIdentifier, Time, OID_CONTENT_TYPE, OID_MESSAGE_DIGEST,
             OID_SIGNING_TIME,
@@ -916,7 +916,10 @@ impl TryFrom<&crate::asn1::rfc5652::SignerInfo> for SignerInfo {
                 let time_stamp_token =
                     attributes
                         .iter()
-                        .find(|attr| attr.typ == OID_TIME_STAMP_TOKEN)
+                        .find(|attr| {
+                            attr.typ == OID_TIME_STAMP_TOKEN
+                                || attr.typ == OID_MS_RFC3161_COUNTER_SIGNATURE
+                        })
                         .map(|attr| {
                             if attr.values.len() != 1 {
                                 Err(CmsError::MalformedUnsignedAttributeTimeStampToken)
@@ -62,6 +62,9 @@ pub struct SignerBuilder<'a> {
 
     /// Time-Stamp Protocol (TSP) server HTTP URL to use.
     time_stamp_url: Option<reqwest::Url>,
+
+    /// The type of the unsigned attribute holding the time-stamp token.
+    time_stamp_attribute: Oid,
 }
 
 impl<'a> SignerBuilder<'a> {
@@ -80,6 +83,7 @@ impl<'a> SignerBuilder<'a> {
             content_type: Oid(Bytes::copy_from_slice(OID_ID_DATA.as_ref())),
             extra_signed_attributes: Vec::new(),
             time_stamp_url: None,
+            time_stamp_attribute: Oid(Bytes::copy_from_slice(OID_TIME_STAMP_TOKEN.as_ref())),
         }
     }
 
@@ -135,6 +139,16 @@ impl<'a> SignerBuilder<'a> {
         self.time_stamp_url = Some(url.into_url()?);
         Ok(self)
     }
+
+    /// Define the type of the unsigned attribute holding the time-stamp token.
+    ///
+    /// The default is `id-aa-timeStampToken`, as defined by RFC 3161. Some
+    /// formats use their own OID. e.g. Authenticode uses
+    /// `1.3.6.1.4.1.311.3.3.1`.
+    pub fn time_stamp_attribute(mut self, typ: Oid) -> Self {
+        self.time_stamp_attribute = typ;
+        self
+    }
 }
 
 /// Entity for incrementally deriving a SignedData primitive.
@@ -144,6 +158,9 @@ pub struct SignedDataBuilder<'a> {
     /// Encapsulated content to sign.
     signed_content: Option<Vec<u8>>,
 
+    /// Whether the encapsulated content is embedded as a `SEQUENCE`.
+    signed_content_is_sequence: bool,
+
     /// Entities who will generated signatures.
     signers: Vec<SignerBuilder<'a>>,
 
@@ -162,6 +179,7 @@ impl<'a> Default for SignedDataBuilder<'a> {
     fn default() -> Self {
         Self {
             signed_content: None,
+            signed_content_is_sequence: false,
             signers: vec![],
             certificates: vec![],
             content_type: Oid(OID_ID_SIGNED_DATA.as_ref().into()),
@@ -175,6 +193,19 @@ impl<'a> SignedDataBuilder<'a> {
     /// This content will be embedded in the generated payload.
     pub fn signed_content(mut self, data: Vec<u8>) -> Self {
         self.signed_content = Some(data);
+        self.signed_content_is_sequence = false;
+        self
+    }
+
+    /// Define the content to sign as the content of an ASN.1 `SEQUENCE`.
+    ///
+    /// RFC 5652 embeds content as an `OCTET STRING`. But some formats, notably
+    /// Authenticode, embed a `SEQUENCE` instead. `data` is the DER encoded
+    /// content of that `SEQUENCE`, without its tag and length. This is also
+    /// the data the `message-digest` attribute is computed from.
+    pub fn signed_content_sequence(mut self, data: Vec<u8>) -> Self {
+        self.signed_content = Some(data);
+        self.signed_content_is_sequence = true;
         self
     }
 
@@ -336,7 +367,7 @@ impl<'a> SignedDataBuilder<'a> {
 
                 let mut unsigned_attributes = UnsignedAttributes::default();
                 unsigned_attributes.push(Attribute {
-                    typ: Oid(Bytes::copy_from_slice(OID_TIME_STAMP_TOKEN.as_ref())),
+                    typ: signer.time_stamp_attribute.clone(),
                     values: vec![AttributeValue::new(Captured::from_values(
                         Mode::Der,
                         signed_data.encode_ref(),
@@ -381,6 +412,7 @@ impl<'a> SignedDataBuilder<'a> {
                     .signed_content
                     .as_ref()
                     .map(|content| OctetString::new(Bytes::copy_from_slice(content))),
+                content_is_sequence: self.signed_content_is_sequence,
             },
             certificates: if certificates.is_empty() {
                 None
@@ -437,6 +469,43 @@ mod tests {
         }
     }
 
+    #[test]
+    fn sequence_content() {
+        let key = rsa_private_key();
+        let cert = rsa_cert();
+
+        // The content of `SEQUENCE { INTEGER 42 }`.
+        let content = vec![2, 1, 42];
+
+        let ber = SignedDataBuilder::default()
+            .signed_content_sequence(content.clone())
+            .signer(SignerBuilder::new(&key, cert))
+            .build_der()
+            .unwrap();
+
+        let raw = crate::asn1::rfc5652::SignedData::decode_ber(&ber).unwrap();
+        assert!(raw.content_info.content_is_sequence);
+
+        let mut buffer = Vec::new();
+        raw.encode_ref()
+            .write_encoded(Mode::Ber, &mut buffer)
+            .unwrap();
+        let raw2 = crate::asn1::rfc5652::SignedData::decode_ber(&buffer).unwrap();
+        assert_eq!(raw, raw2);
+
+        let signed_data = crate::SignedData::parse_ber(&ber).unwrap();
+        assert_eq!(signed_data.signed_content(), Some(content.as_ref()));
+
+        for signer in signed_data.signers() {
+            signer
+                .verify_message_digest_with_signed_data(&signed_data)
+                .unwrap();
+            signer
+                .verify_signature_with_signed_data(&signed_data)
+                .unwrap();
+        }
+    }
+
     #[test]
     fn time_stamp_url() {
         let key = rsa_private_key();
@@ -5,11 +5,11 @@
 //! Data structures for configuring a Python interpreter.
 
 use {
-    crate::NewInterpreterError,
+    crate::{config_overlay::apply_config_overlay_file, NewInterpreterError},
     python3_sys as pyffi,
     python_packaging::interpreter::{
-        MemoryAllocatorBackend, MultiprocessingStartMethod, PythonInterpreterConfig,
-        PythonInterpreterProfile, TerminfoResolution,
+        MemoryAllocatorBackend, MultiprocessingStartMethod, PackedResourcesVerification,
+        PythonInterpreterConfig, PythonInterpreterProfile, TerminfoResolution,
     },
     std::{
         convert::TryFrom,
@@ -195,6 +195,24 @@ pub struct OxidizedPythonInterpreterConfig<'a> {
     /// relative to the process's current working directory.
     pub packed_resources: Vec<PackedResourcesSource<'a>>,
 
+    /// How to verify the integrity of `packed_resources`.
+    ///
+    /// When not `None`, every packed resources source must carry per-resource
+    /// content digests. `Startup` verifies all resources (and the integrity
+    /// trailer's payload digest, if present) before the interpreter starts and
+    /// refuses to start on failure. `Import` verifies each resource's content
+    /// digest the first time it is loaded and refuses to load resources that
+    /// fail verification.
+    pub packed_resources_verification: PackedResourcesVerification,
+
+    /// PEM encoded X.509 certificate used to verify packed resources signatures.
+    ///
+    /// If set, every packed resources source must end with an integrity
+    /// trailer whose payload digest and signature verify against this
+    /// certificate, otherwise the interpreter refuses to start. Signature
+    /// verification requires the `packed-resources-signatures` crate feature.
+    pub packed_resources_signing_certificate: Option<String>,
+
     /// Extra extension modules to make available to the interpreter.
     ///
     /// The values will effectively be passed to ``PyImport_ExtendInitTab()``.
@@ -224,6 +242,16 @@ pub struct OxidizedPythonInterpreterConfig<'a> {
     /// How to call `multiprocessing.set_start_method()` when `multiprocessing` is imported.
     pub multiprocessing_start_method: MultiprocessingStartMethod,
 
+    /// Whether `pkg_resources` may extract in-memory resources to the filesystem.
+    ///
+    /// `pkg_resources.resource_filename()` and friends need a filesystem path.
+    /// Resources backed by files resolve to those files. If this is true,
+    /// in-memory resources are written to a per-process temporary directory
+    /// on first request and their path in that directory is returned. The
+    /// directory is deleted on interpreter shutdown. If false, requesting the
+    /// filename of an in-memory resource raises `NotImplementedError`.
+    pub pkg_resources_extract_in_memory_resources: bool,
+
     /// Whether to set sys.frozen=True.
     ///
     /// Setting this will enable Python to emulate "frozen" binaries, such as
@@ -255,6 +283,22 @@ pub struct OxidizedPythonInterpreterConfig<'a> {
     /// the directory specified containing a ``\n`` delimited list of modules
     /// loaded in ``sys.modules``.
     pub write_modules_directory_env: Option<String>,
+
+    /// Path to a file overriding configuration fields at run-time.
+    ///
+    /// If set and the file exists, its fields are applied to this
+    /// configuration when it is resolved, before the interpreter is
+    /// initialized. Files with a `.json` extension are parsed as JSON and
+    /// other files as TOML. Parsing requires the `config-overlay` crate feature.
+    ///
+    /// `$ORIGIN` in the path is expanded to the directory of the current
+    /// executable.
+    pub config_overlay_path: Option<PathBuf>,
+
+    /// Names of fields the file at `config_overlay_path` may override.
+    ///
+    /// The file is rejected if it sets any other field.
+    pub config_overlay_fields: Vec<String>,
 }
 
 impl<'a> Default for OxidizedPythonInterpreterConfig<'a> {
@@ -278,16 +322,21 @@ impl<'a> Default for OxidizedPythonInterpreterConfig<'a> {
             oxidized_importer: false,
             filesystem_importer: true,
             packed_resources: vec![],
+            packed_resources_verification: PackedResourcesVerification::None,
+            packed_resources_signing_certificate: None,
             extra_extension_modules: None,
             argv: None,
             argvb: false,
             multiprocessing_auto_dispatch: true,
             multiprocessing_start_method: MultiprocessingStartMethod::Auto,
+            pkg_resources_extract_in_memory_resources: false,
             sys_frozen: false,
             sys_meipass: false,
             terminfo_resolution: TerminfoResolution::Dynamic,
             tcl_library: None,
             write_modules_directory_env: None,
+            config_overlay_path: None,
+            config_overlay_fields: vec![],
         }
     }
 }
@@ -295,9 +344,9 @@ impl<'a> Default for OxidizedPythonInterpreterConfig<'a> {
 impl<'a> OxidizedPythonInterpreterConfig<'a> {
     /// Create a new type with all values resolved.
     pub fn resolve(
-        self,
+        mut self,
     ) -> Result<ResolvedOxidizedPythonInterpreterConfig<'a>, NewInterpreterError> {
-        let argv = if let Some(args) = self.argv {
+        let argv = if let Some(args) = self.argv.take() {
             Some(args)
         } else if self.interpreter_config.argv.is_some() {
             None
@@ -305,7 +354,7 @@ impl<'a> OxidizedPythonInterpreterConfig<'a> {
             Some(std::env::args_os().collect::<Vec<_>>())
         };
 
-        let exe = if let Some(exe) = self.exe {
+        let exe = if let Some(exe) = self.exe.take() {
             exe
         } else {
             std::env::current_exe()
@@ -318,7 +367,7 @@ impl<'a> OxidizedPythonInterpreterConfig<'a> {
         let exe = dunce::canonicalize(exe)
             .map_err(|_| NewInterpreterError::Simple("could not obtain current executable path"))?;
 
-        let origin = if let Some(origin) = self.origin {
+        let origin = if let Some(origin) = self.origin.take() {
             origin
         } else {
             exe.parent()
@@ -330,6 +379,17 @@ impl<'a> OxidizedPythonInterpreterConfig<'a> {
 
         let origin_string = origin.display().to_string();
 
+        // The overlay is applied first so paths it defines have $ORIGIN expanded.
+        let config_overlay_path = self
+            .config_overlay_path
+            .as_ref()
+            .map(|x| PathBuf::from(x.display().to_string().replace("$ORIGIN", &origin_string)));
+
+        if let Some(path) = &config_overlay_path {
+            let fields = self.config_overlay_fields.clone();
+            apply_config_overlay_file(&mut self, path, &fields)?;
+        }
+
         let packed_resources = self
             .packed_resources
             .into_iter()
@@ -371,6 +431,7 @@ impl<'a> OxidizedPythonInterpreterConfig<'a> {
                 argv,
                 packed_resources,
                 tcl_library,
+                config_overlay_path,
                 ..self
             },
         })
@@ -462,6 +523,26 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_config_overlay_missing() -> Result<()> {
+        let config = OxidizedPythonInterpreterConfig {
+            origin: Some(PathBuf::from("/other/origin")),
+            config_overlay_path: Some(PathBuf::from("$ORIGIN/config-overlay.toml")),
+            config_overlay_fields: vec!["sys_frozen".to_string()],
+            ..Default::default()
+        };
+
+        let resolved = config.resolve()?;
+
+        assert_eq!(
+            resolved.config_overlay_path,
+            Some(PathBuf::from("/other/origin/config-overlay.toml"))
+        );
+        assert!(!resolved.sys_frozen);
+
+        Ok(())
+    }
+
     #[test]
     fn test_packed_resources_explicit_origin() -> Result<()> {
         let mut config = OxidizedPythonInterpreterConfig::default();
@@ -0,0 +1,429 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Override interpreter configuration at run-time from a file.
+
+use {
+    crate::{config::OxidizedPythonInterpreterConfig, NewInterpreterError},
+    std::path::Path,
+};
+
+#[cfg(feature = "config-overlay")]
+use {
+    python_packaging::interpreter::{
+        Allocator, MultiprocessingStartMethod, TerminfoResolution, CONFIG_OVERLAY_FIELDS,
+    },
+    std::{convert::TryFrom, path::PathBuf, str::FromStr},
+};
+
+/// A value in a configuration overlay file.
+#[cfg(feature = "config-overlay")]
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum OverlayValue {
+    Null,
+    Bool(bool),
+    String(String),
+    List(Vec<OverlayValue>),
+}
+
+#[cfg(feature = "config-overlay")]
+impl OverlayValue {
+    fn as_bool(&self, field: &str) -> Result<bool, String> {
+        match self {
+            Self::Bool(v) => Ok(*v),
+            _ => Err(format!("{} must be a boolean", field)),
+        }
+    }
+
+    fn as_optional_bool(&self, field: &str) -> Result<Option<bool>, String> {
+        match self {
+            Self::Null => Ok(None),
+            _ => Ok(Some(self.as_bool(field)?)),
+        }
+    }
+
+    fn as_str(&self, field: &str) -> Result<&str, String> {
+        match self {
+            Self::String(v) => Ok(v),
+            _ => Err(format!("{} must be a string", field)),
+        }
+    }
+
+    fn as_optional_string(&self, field: &str) -> Result<Option<String>, String> {
+        match self {
+            Self::Null => Ok(None),
+            _ => Ok(Some(self.as_str(field)?.to_string())),
+        }
+    }
+
+    fn as_optional_strings(&self, field: &str) -> Result<Option<Vec<String>>, String> {
+        match self {
+            Self::Null => Ok(None),
+            Self::List(values) => Ok(Some(
+                values
+                    .iter()
+                    .map(|v| v.as_str(field).map(|s| s.to_string()))
+                    .collect::<Result<Vec<_>, String>>()
+                    .map_err(|_| format!("{} must be a list of strings", field))?,
+            )),
+            _ => Err(format!("{} must be a list of strings", field)),
+        }
+    }
+}
+
+/// Apply field values to a config.
+///
+/// Only fields in `allowed` can be set. Values are applied in order.
+#[cfg(feature = "config-overlay")]
+pub(crate) fn apply_config_overlay(
+    config: &mut OxidizedPythonInterpreterConfig,
+    values: Vec<(String, OverlayValue)>,
+    allowed: &[String],
+) -> Result<(), String> {
+    for (field, value) in values {
+        if !CONFIG_OVERLAY_FIELDS.contains(&field.as_str()) {
+            return Err(format!(
+                "{} is not a configuration field that can be overridden",
+                field
+            ));
+        }
+        if !allowed.contains(&field) {
+            return Err(format!("{} is not allowed to be overridden", field));
+        }
+
+        let f = field.as_str();
+        let interpreter_config = &mut config.interpreter_config;
+
+        match f {
+            "allocator" => {
+                interpreter_config.allocator = match value {
+                    OverlayValue::Null => None,
+                    _ => Some(Allocator::try_from(value.as_str(f)?)?),
+                }
+            }
+            "allocator_debug" => config.allocator_debug = value.as_bool(f)?,
+            "allocator_mem" => config.allocator_mem = value.as_bool(f)?,
+            "allocator_obj" => config.allocator_obj = value.as_bool(f)?,
+            "allocator_pymalloc_arena" => config.allocator_pymalloc_arena = value.as_bool(f)?,
+            "allocator_raw" => config.allocator_raw = value.as_bool(f)?,
+            "argvb" => config.argvb = value.as_bool(f)?,
+            "buffered_stdio" => interpreter_config.buffered_stdio = value.as_optional_bool(f)?,
+            "development_mode" => {
+                interpreter_config.development_mode = value.as_optional_bool(f)?
+            }
+            "fault_handler" => interpreter_config.fault_handler = value.as_optional_bool(f)?,
+            "filesystem_importer" => config.filesystem_importer = value.as_bool(f)?,
+            "import_time" => interpreter_config.import_time = value.as_optional_bool(f)?,
+            "inspect" => interpreter_config.inspect = value.as_optional_bool(f)?,
+            "malloc_stats" => interpreter_config.malloc_stats = value.as_optional_bool(f)?,
+            "module_search_paths" => {
+                interpreter_config.module_search_paths = value
+                    .as_optional_strings(f)?
+                    .map(|paths| paths.into_iter().map(PathBuf::from).collect())
+            }
+            "multiprocessing_auto_dispatch" => {
+                config.multiprocessing_auto_dispatch = value.as_bool(f)?
+            }
+            "multiprocessing_start_method" => {
+                config.multiprocessing_start_method =
+                    MultiprocessingStartMethod::from_str(value.as_str(f)?)?
+            }
+            "parser_debug" => interpreter_config.parser_debug = value.as_optional_bool(f)?,
+            "pkg_resources_extract_in_memory_resources" => {
+                config.pkg_resources_extract_in_memory_resources = value.as_bool(f)?
+            }
+            "pycache_prefix" => {
+                interpreter_config.pycache_prefix = value.as_optional_string(f)?.map(PathBuf::from)
+            }
+            "quiet" => interpreter_config.quiet = value.as_optional_bool(f)?,
+            "show_ref_count" => interpreter_config.show_ref_count = value.as_optional_bool(f)?,
+            "site_import" => interpreter_config.site_import = value.as_optional_bool(f)?,
+            "sys_frozen" => config.sys_frozen = value.as_bool(f)?,
+            "sys_meipass" => config.sys_meipass = value.as_bool(f)?,
+            "tcl_library" => config.tcl_library = value.as_optional_string(f)?.map(PathBuf::from),
+            "terminfo_resolution" => {
+                config.terminfo_resolution = TerminfoResolution::try_from(value.as_str(f)?)?
+            }
+            "tracemalloc" => interpreter_config.tracemalloc = value.as_optional_bool(f)?,
+            "use_environment" => interpreter_config.use_environment = value.as_optional_bool(f)?,
+            "user_site_directory" => {
+                interpreter_config.user_site_directory = value.as_optional_bool(f)?
+            }
+            "utf8_mode" => interpreter_config.utf8_mode = value.as_optional_bool(f)?,
+            "verbose" => interpreter_config.verbose = value.as_optional_bool(f)?,
+            "warn_options" => interpreter_config.warn_options = value.as_optional_strings(f)?,
+            "write_bytecode" => interpreter_config.write_bytecode = value.as_optional_bool(f)?,
+            "write_modules_directory_env" => {
+                config.write_modules_directory_env = value.as_optional_string(f)?
+            }
+            "x_options" => interpreter_config.x_options = value.as_optional_strings(f)?,
+            _ => return Err(format!("{} cannot be overridden (this is a bug)", f)),
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse the content of a configuration overlay file.
+///
+/// Files with a `.json` extension are parsed as JSON. Everything else is
+/// parsed as TOML. The document must be a table/object mapping field names to
+/// values.
+#[cfg(feature = "config-overlay")]
+fn parse_config_overlay(path: &Path, data: &str) -> Result<Vec<(String, OverlayValue)>, String> {
+    fn from_json(field: &str, value: serde_json::Value) -> Result<OverlayValue, String> {
+        match value {
+            serde_json::Value::Null => Ok(OverlayValue::Null),
+            serde_json::Value::Bool(v) => Ok(OverlayValue::Bool(v)),
+            serde_json::Value::String(v) => Ok(OverlayValue::String(v)),
+            serde_json::Value::Array(values) => Ok(OverlayValue::List(
+                values
+                    .into_iter()
+                    .map(|v| from_json(field, v))
+                    .collect::<Result<Vec<_>, String>>()?,
+            )),
+            _ => Err(format!("{} has an unsupported value type", field)),
+        }
+    }
+
+    fn from_toml(field: &str, value: toml::Value) -> Result<OverlayValue, String> {
+        match value {
+            toml::Value::Boolean(v) => Ok(OverlayValue::Bool(v)),
+            toml::Value::String(v) => Ok(OverlayValue::String(v)),
+            toml::Value::Array(values) => Ok(OverlayValue::List(
+                values
+                    .into_iter()
+                    .map(|v| from_toml(field, v))
+                    .collect::<Result<Vec<_>, String>>()?,
+            )),
+            _ => Err(format!("{} has an unsupported value type", field)),
+        }
+    }
+
+    if path.extension().and_then(|x| x.to_str()) == Some("json") {
+        match serde_json::from_str(data).map_err(|e| e.to_string())? {
+            serde_json::Value::Object(values) => values
+                .into_iter()
+                .map(|(k, v)| from_json(&k, v).map(|v| (k, v)))
+                .collect(),
+            _ => Err("document must be a JSON object".to_string()),
+        }
+    } else {
+        match data.parse::<toml::Value>().map_err(|e| e.to_string())? {
+            toml::Value::Table(values) => values
+                .into_iter()
+                .map(|(k, v)| from_toml(&k, v).map(|v| (k, v)))
+                .collect(),
+            _ => Err("document must be a TOML table".to_string()),
+        }
+    }
+}
+
+/// Parse the content of a configuration overlay file and apply it to a config.
+#[cfg(feature = "config-overlay")]
+fn apply_config_overlay_data(
+    config: &mut OxidizedPythonInterpreterConfig,
+    path: &Path,
+    data: &str,
+    allowed: &[String],
+) -> Result<(), String> {
+    apply_config_overlay(config, parse_config_overlay(path, data)?, allowed)
+}
+
+#[cfg(not(feature = "config-overlay"))]
+fn apply_config_overlay_data(
+    _config: &mut OxidizedPythonInterpreterConfig,
+    _path: &Path,
+    _data: &str,
+    _allowed: &[String],
+) -> Result<(), String> {
+    Err("reading configuration overlay files requires the config-overlay feature".to_string())
+}
+
+/// Apply a configuration overlay file to a config.
+///
+/// Nothing happens if the file does not exist.
+pub(crate) fn apply_config_overlay_file(
+    config: &mut OxidizedPythonInterpreterConfig,
+    path: &Path,
+    allowed: &[String],
+) -> Result<(), NewInterpreterError> {
+    let data = match std::fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => {
+            return Err(NewInterpreterError::Dynamic(format!(
+                "unable to read configuration overlay {}: {}",
+                path.display(),
+                e
+            )))
+        }
+    };
+
+    apply_config_overlay_data(config, path, &data, allowed).map_err(|e| {
+        NewInterpreterError::Dynamic(format!(
+            "error applying configuration overlay {}: {}",
+            path.display(),
+            e
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowed(fields: &[&str]) -> Vec<String> {
+        fields.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[cfg(feature = "config-overlay")]
+    #[test]
+    fn test_apply_config_overlay() -> Result<(), String> {
+        let mut config = OxidizedPythonInterpreterConfig::default();
+
+        apply_config_overlay(
+            &mut config,
+            vec![
+                ("allocator_debug".to_string(), OverlayValue::Bool(true)),
+                ("fault_handler".to_string(), OverlayValue::Bool(true)),
+                (
+                    "module_search_paths".to_string(),
+                    OverlayValue::List(vec![OverlayValue::String("$ORIGIN/lib".to_string())]),
+                ),
+                (
+                    "write_modules_directory_env".to_string(),
+                    OverlayValue::String("MODULES_DIR".to_string()),
+                ),
+            ],
+            &allowed(&[
+                "allocator_debug",
+                "fault_handler",
+                "module_search_paths",
+                "write_modules_directory_env",
+            ]),
+        )?;
+
+        assert!(config.allocator_debug);
+        assert_eq!(config.interpreter_config.fault_handler, Some(true));
+        assert_eq!(
+            config.interpreter_config.module_search_paths,
+            Some(vec![PathBuf::from("$ORIGIN/lib")])
+        );
+        assert_eq!(
+            config.write_modules_directory_env,
+            Some("MODULES_DIR".to_string())
+        );
+
+        apply_config_overlay(
+            &mut config,
+            vec![("fault_handler".to_string(), OverlayValue::Null)],
+            &allowed(&["fault_handler"]),
+        )?;
+        assert_eq!(config.interpreter_config.fault_handler, None);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "config-overlay")]
+    #[test]
+    fn test_apply_config_overlay_errors() {
+        let mut config = OxidizedPythonInterpreterConfig::default();
+
+        assert_eq!(
+            apply_config_overlay(
+                &mut config,
+                vec![("sys_frozen".to_string(), OverlayValue::Bool(true))],
+                &allowed(&["allocator_debug"]),
+            ),
+            Err("sys_frozen is not allowed to be overridden".to_string())
+        );
+        assert_eq!(
+            apply_config_overlay(
+                &mut config,
+                vec![("oxidized_importer".to_string(), OverlayValue::Bool(false))],
+                &allowed(&["oxidized_importer"]),
+            ),
+            Err(
+                "oxidized_importer is not a configuration field that can be overridden".to_string()
+            )
+        );
+        assert_eq!(
+            apply_config_overlay(
+                &mut config,
+                vec![(
+                    "sys_frozen".to_string(),
+                    OverlayValue::String("yes".to_string())
+                )],
+                &allowed(&["sys_frozen"]),
+            ),
+            Err("sys_frozen must be a boolean".to_string())
+        );
+        assert!(!config.sys_frozen);
+    }
+
+    #[cfg(feature = "config-overlay")]
+    #[test]
+    fn test_all_fields_supported() {
+        let allowed = allowed(CONFIG_OVERLAY_FIELDS);
+
+        for field in CONFIG_OVERLAY_FIELDS {
+            let mut config = OxidizedPythonInterpreterConfig::default();
+
+            let res = apply_config_overlay(
+                &mut config,
+                vec![(field.to_string(), OverlayValue::Null)],
+                &allowed,
+            );
+
+            assert!(
+                !matches!(&res, Err(e) if e.contains("this is a bug")),
+                "{} is not handled",
+                field
+            );
+        }
+    }
+
+    #[test]
+    fn test_missing_file() -> Result<(), NewInterpreterError> {
+        let mut config = OxidizedPythonInterpreterConfig::default();
+        apply_config_overlay_file(
+            &mut config,
+            Path::new("/does/not/exist/config-overlay.toml"),
+            &allowed(&["sys_frozen"]),
+        )
+    }
+
+    #[cfg(feature = "config-overlay")]
+    #[test]
+    fn test_parse_config_overlay() -> Result<(), String> {
+        assert_eq!(
+            parse_config_overlay(
+                Path::new("overlay.toml"),
+                "fault_handler = true\nx_options = [\"dev\"]\n"
+            )?,
+            vec![
+                ("fault_handler".to_string(), OverlayValue::Bool(true)),
+                (
+                    "x_options".to_string(),
+                    OverlayValue::List(vec![OverlayValue::String("dev".to_string())])
+                ),
+            ]
+        );
+        assert_eq!(
+            parse_config_overlay(
+                Path::new("overlay.json"),
+                "{\"tcl_library\": null, \"sys_frozen\": false}"
+            )?,
+            vec![
+                ("sys_frozen".to_string(), OverlayValue::Bool(false)),
+                ("tcl_library".to_string(), OverlayValue::Null),
+            ]
+        );
+        assert!(parse_config_overlay(Path::new("overlay.toml"), "verbose = 1").is_err());
+        assert!(parse_config_overlay(Path::new("overlay.json"), "[]").is_err());
+
+        Ok(())
+    }
+}
@@ -198,6 +198,11 @@ fn module_init(py: Python, m: &PyModule) -> PyResult<()> {
         "OxidizedDistribution",
         py.get_type::<crate::package_metadata::OxidizedDistribution>(),
     )?;
+    m.add(
+        py,
+        "OxidizedDistributionFile",
+        py.get_type::<crate::package_metadata::OxidizedDistributionFile>(),
+    )?;
     m.add(py, "OxidizedFinder", py.get_type::<OxidizedFinder>())?;
     m.add(py, "OxidizedResource", py.get_type::<OxidizedResource>())?;
     m.add(
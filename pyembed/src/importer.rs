@@ -9,12 +9,10 @@ This module defines a Python meta path importer and associated functionality
 for importing Python modules from memory.
 */
 
+#[cfg(target_os = "linux")]
+use crate::memfd_dll::{free_library_memory, get_proc_address_memory, load_library_memory};
 #[cfg(windows)]
-use {
-    crate::memory_dll::{free_library_memory, get_proc_address_memory, load_library_memory},
-    cpython::exc::SystemError,
-    std::ffi::{c_void, CString},
-};
+use crate::memory_dll::{free_library_memory, get_proc_address_memory, load_library_memory};
 use {
     crate::{
         conversion::{path_to_pyobject, pyobject_to_pathbuf},
@@ -26,17 +24,29 @@ use {
         },
     },
     cpython::{
-        exc::{FileNotFoundError, ImportError, ValueError},
+        exc::{
+            FileNotFoundError, ImportError, IsADirectoryError, NotADirectoryError, OSError,
+            TypeError, ValueError,
+        },
         {
             py_class, NoArgs, ObjectProtocol, PyBytes, PyCapsule, PyClone, PyDict, PyErr, PyList,
             PyModule, PyObject, PyResult, PyString, PyTuple, Python, PythonObject, ToPyObject,
         },
     },
     python3_sys as pyffi,
-    std::sync::Arc,
+    std::{
+        borrow::Cow,
+        path::PathBuf,
+        sync::{Arc, Mutex},
+    },
+};
+#[cfg(any(windows, target_os = "linux"))]
+use {
+    cpython::exc::SystemError,
+    std::ffi::{c_void, CString},
 };
 
-#[cfg(windows)]
+#[cfg(any(windows, target_os = "linux"))]
 #[allow(non_camel_case_types)]
 type py_init_fn = extern "C" fn() -> *mut pyffi::PyObject;
 
@@ -56,6 +66,7 @@ type py_init_fn = extern "C" fn() -> *mut pyffi::PyObject;
 /// `FILE*` for the extension location, so we can't call it. So we need to
 /// reimplement it. Documentation of that is inline.
 #[cfg(windows)]
+#[allow(clippy::too_many_arguments)]
 fn extension_module_shared_library_create_module(
     resources_state: &PythonResourcesState<u8>,
     py: Python,
@@ -64,6 +75,7 @@ fn extension_module_shared_library_create_module(
     name_py: PyObject,
     name: &str,
     library_data: &[u8],
+    _dependency_names: Option<&[Cow<str>]>,
 ) -> PyResult<PyObject> {
     let origin = PyString::new(py, "memory");
 
@@ -104,7 +116,61 @@ fn extension_module_shared_library_create_module(
     })
 }
 
-#[cfg(unix)]
+/// Implementation of `Loader.create_module()` for in-memory extension modules on Linux.
+///
+/// This is the same as the Windows implementation except the library is
+/// materialized in a `memfd_create()` file and loaded with `dlopen()`. Shared
+/// library dependencies available in memory are loaded first so the dynamic
+/// linker can resolve them.
+#[cfg(target_os = "linux")]
+#[allow(clippy::too_many_arguments)]
+fn extension_module_shared_library_create_module(
+    resources_state: &PythonResourcesState<u8>,
+    py: Python,
+    sys_modules: PyObject,
+    spec: &PyObject,
+    name_py: PyObject,
+    name: &str,
+    library_data: &[u8],
+    dependency_names: Option<&[Cow<str>]>,
+) -> PyResult<PyObject> {
+    let origin = PyString::new(py, "memory");
+
+    let existing_module = unsafe {
+        pyffi::_PyImport_FindExtensionObject(name_py.as_ptr(), origin.as_object().as_ptr())
+    };
+
+    if !existing_module.is_null() {
+        return Ok(unsafe { PyObject::from_owned_ptr(py, existing_module) });
+    }
+
+    if !unsafe { pyffi::PyErr_Occurred() }.is_null() {
+        return Err(PyErr::fetch(py));
+    }
+
+    let module = load_library_memory(resources_state, name, library_data, dependency_names)
+        .map_err(|e| {
+            PyErr::new::<ImportError, _>(
+                py,
+                (
+                    format!("unable to load extension module library from memory: {}", e),
+                    name,
+                ),
+            )
+        })?;
+
+    // Any error past this point should call `dlclose()` to unload the library.
+
+    load_dynamic_library(py, sys_modules, spec, name_py, name, module).map_err(|e| {
+        unsafe {
+            free_library_memory(module);
+        }
+        e
+    })
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+#[allow(clippy::too_many_arguments)]
 fn extension_module_shared_library_create_module(
     _resources_state: &PythonResourcesState<u8>,
     _py: Python,
@@ -113,12 +179,13 @@ fn extension_module_shared_library_create_module(
     _name_py: PyObject,
     _name: &str,
     _library_data: &[u8],
+    _dependency_names: Option<&[Cow<str>]>,
 ) -> PyResult<PyObject> {
-    panic!("should only be called on Windows");
+    panic!("should only be called on Windows or Linux");
 }
 
 /// Reimplementation of `_PyImport_LoadDynamicModuleWithSpec()`.
-#[cfg(windows)]
+#[cfg(any(windows, target_os = "linux"))]
 fn load_dynamic_library(
     py: Python,
     sys_modules: PyObject,
@@ -273,6 +340,12 @@ pub(crate) struct ImporterState {
     multiprocessing_set_start_method: Option<String>,
     /// Whether to automatically register ourself with `pkg_resources` when it is imported.
     pkg_resources_import_auto_register: bool,
+    /// Whether `pkg_resources` may extract in-memory resources to the filesystem.
+    pkg_resources_extract_in_memory_resources: bool,
+    /// Per-process directory holding extracted resources.
+    ///
+    /// Created on first use and deleted on interpreter shutdown.
+    resource_extraction_directory: Mutex<Option<PathBuf>>,
     /// Holds state about importable resources.
     ///
     /// This field is a PyCapsule and is a glorified wrapper around
@@ -400,6 +473,8 @@ impl ImporterState {
             multiprocessing_set_start_method: None,
             // TODO value should come from config.
             pkg_resources_import_auto_register: true,
+            pkg_resources_extract_in_memory_resources: false,
+            resource_extraction_directory: Mutex::new(None),
             resources_state: capsule,
         })
     }
@@ -439,6 +514,110 @@ impl ImporterState {
     pub fn set_multiprocessing_set_start_method(&mut self, value: Option<String>) {
         self.multiprocessing_set_start_method = value;
     }
+
+    /// Set whether `pkg_resources` may extract in-memory resources to the filesystem.
+    pub fn set_pkg_resources_extract_in_memory_resources(&mut self, value: bool) {
+        self.pkg_resources_extract_in_memory_resources = value;
+    }
+
+    /// Resolve a filesystem path for a package resource or resource directory.
+    ///
+    /// Resources backed by files resolve to those files. Otherwise, if
+    /// extraction is enabled, resources are extracted to the resource
+    /// extraction directory. `Ok(None)` is returned if the resource has no
+    /// filesystem path.
+    pub fn resolve_package_resource_filename(
+        &self,
+        py: Python,
+        package: &str,
+        name: &str,
+    ) -> PyResult<Option<PathBuf>> {
+        let resources_state = self.get_resources_state();
+
+        if let Some(path) = resources_state.package_resource_filesystem_path(package, name) {
+            return Ok(Some(path));
+        }
+
+        if !self.pkg_resources_extract_in_memory_resources {
+            return Ok(None);
+        }
+
+        let dest_dir = self.resource_extraction_directory(py)?;
+
+        resources_state
+            .extract_package_resources(package, name, &dest_dir)
+            .map_err(|e| PyErr::new::<OSError, _>(py, format!("error extracting resource: {}", e)))
+    }
+
+    /// Obtain the directory to extract resources to, creating it if necessary.
+    fn resource_extraction_directory(&self, py: Python) -> PyResult<PathBuf> {
+        let mut directory = self
+            .resource_extraction_directory
+            .lock()
+            .map_err(|_| PyErr::new::<OSError, _>(py, "resource extraction lock poisoned"))?;
+
+        if let Some(path) = directory.as_ref() {
+            return Ok(path.clone());
+        }
+
+        let path = create_resource_extraction_directory().map_err(|e| {
+            PyErr::new::<OSError, _>(
+                py,
+                format!("error creating resource extraction directory: {}", e),
+            )
+        })?;
+
+        // Delete the directory when the interpreter shuts down. We may not
+        // get dropped before the process exits, so we can't rely on Drop.
+        let rmtree = py.import("shutil")?.get(py, "rmtree")?;
+        py.import("atexit")?.call(
+            py,
+            "register",
+            (rmtree, path_to_pyobject(py, &path)?, true),
+            None,
+        )?;
+
+        directory.replace(path.clone());
+
+        Ok(path)
+    }
+}
+
+/// Create a private, per-process directory for extracted resources.
+fn create_resource_extraction_directory() -> std::io::Result<PathBuf> {
+    let temp_dir = std::env::temp_dir();
+
+    let mut builder = std::fs::DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+
+    let seed = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.subsec_nanos())
+        .unwrap_or(0);
+
+    for attempt in 0..100u32 {
+        let path = temp_dir.join(format!(
+            "pyembed-resources-{}-{}-{}",
+            std::process::id(),
+            seed,
+            attempt
+        ));
+
+        match builder.create(&path) {
+            Ok(()) => return Ok(path),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(std::io::Error::new(
+        std::io::ErrorKind::AlreadyExists,
+        "unable to find unused directory name",
+    ))
 }
 
 impl Drop for ImporterState {
@@ -452,6 +631,12 @@ impl Drop for ImporterState {
                 Box::from_raw(ptr as *mut PythonResourcesState<u8>);
             }
         }
+
+        if let Ok(directory) = self.resource_extraction_directory.get_mut() {
+            if let Some(path) = directory.take() {
+                let _ = std::fs::remove_dir_all(path);
+            }
+        }
     }
 }
 
@@ -548,8 +733,12 @@ py_class!(pub(crate) class OxidizedFinder |py| {
         self.origin_impl(py)
     }
 
-    def __new__(_cls, relative_path_origin: Option<PyObject> = None) -> PyResult<OxidizedFinder> {
-        oxidized_finder_new(py, relative_path_origin)
+    def __new__(
+        _cls,
+        relative_path_origin: Option<PyObject> = None,
+        pkg_resources_extract_in_memory_resources: bool = false
+    ) -> PyResult<OxidizedFinder> {
+        oxidized_finder_new(py, relative_path_origin, pkg_resources_extract_in_memory_resources)
     }
 
     def path_hook(&self, path: PyObject) -> PyResult<OxidizedPathEntryFinder> {
@@ -693,6 +882,7 @@ impl OxidizedFinder {
                     name,
                     &key,
                     library_data,
+                    module.shared_library_dependency_names().as_deref(),
                 )
             } else {
                 // Call `imp.create_dynamic()` for dynamic extension modules.
@@ -1132,10 +1322,11 @@ impl OxidizedFinder {
     }
 }
 
-/// OxidizedFinder.__new__(relative_path_origin=None))
+/// OxidizedFinder.__new__(relative_path_origin=None, pkg_resources_extract_in_memory_resources=False))
 fn oxidized_finder_new(
     py: Python,
     relative_path_origin: Option<PyObject>,
+    pkg_resources_extract_in_memory_resources: bool,
 ) -> PyResult<OxidizedFinder> {
     // We need to obtain an ImporterState instance. This requires handles on a
     // few items...
@@ -1153,15 +1344,11 @@ fn oxidized_finder_new(
         resources_state.origin = pyobject_to_pathbuf(py, py_origin)?;
     }
 
-    let importer = OxidizedFinder::create_instance(
-        py,
-        Arc::new(ImporterState::new(
-            py,
-            &m,
-            &bootstrap_module,
-            resources_state,
-        )?),
-    )?;
+    let mut importer_state = ImporterState::new(py, &m, &bootstrap_module, resources_state)?;
+    importer_state
+        .set_pkg_resources_extract_in_memory_resources(pkg_resources_extract_in_memory_resources);
+
+    let importer = OxidizedFinder::create_instance(py, Arc::new(importer_state))?;
 
     Ok(importer)
 }
@@ -1403,6 +1590,10 @@ py_class!(pub(crate) class OxidizedResourceReader |py| {
     def contents(&self) -> PyResult<PyObject> {
         self.contents_impl(py)
     }
+
+    def files(&self) -> PyResult<PyObject> {
+        self.files_impl(py)
+    }
 });
 
 impl OxidizedResourceReader {
@@ -1469,15 +1660,49 @@ impl OxidizedResourceReader {
             .get_resources_state()
             .package_resource_names(py, &package)
     }
+
+    /// Returns a `Traversable` for the resources of the package.
+    ///
+    /// This implements `importlib.resources.abc.TraversableResources.files()`.
+    fn files_impl(&self, py: Python) -> PyResult<PyObject> {
+        let state = self.state(py);
+        let package = self.package(py);
+
+        Ok(PyOxidizerTraversable::create_instance(
+            py,
+            state.clone(),
+            package.clone(),
+            String::new(),
+        )?
+        .into_object())
+    }
 }
 
 // Path-like object facilitating Python resource access.
 //
 // This implements importlib.abc.Traversable.
+//
+// Instances are bound to a package and a `/` delimited path of a resource
+// or resource directory within that package. The empty path refers to the
+// package itself.
 py_class!(pub(crate) class PyOxidizerTraversable |py| {
     data state: Arc<ImporterState>;
+    data package: String;
     data path: String;
 
+    // The base name of this object without any parent references.
+    @property def name(&self) -> PyResult<PyObject> {
+        Ok(self.name_impl(py).to_py_object(py).into_object())
+    }
+
+    def __repr__(&self) -> PyResult<String> {
+        Ok(format!(
+            "<PyOxidizerTraversable package={:?} path={:?}>",
+            self.package(py),
+            self.path(py)
+        ))
+    }
+
     // Yield Traversable objects in self.
     def iterdir(&self) -> PyResult<PyObject> {
         self.iterdir_impl(py)
@@ -1489,8 +1714,8 @@ py_class!(pub(crate) class PyOxidizerTraversable |py| {
     }
 
     // Read contents of self as text.
-    def read_text(&self) -> PyResult<PyObject> {
-        self.read_text_impl(py)
+    def read_text(&self, encoding: Option<PyObject> = None) -> PyResult<PyObject> {
+        self.read_text_impl(py, encoding)
     }
 
     // Return True if self is a dir.
@@ -1504,13 +1729,20 @@ py_class!(pub(crate) class PyOxidizerTraversable |py| {
     }
 
     // Return Traversable child in self.
-    def joinpath(&self, child: &PyObject) -> PyResult<PyObject> {
-        self.joinpath_impl(py, child)
+    def joinpath(&self, *args, **kwargs) -> PyResult<PyObject> {
+        if kwargs.map(|kwargs| kwargs.len(py) > 0).unwrap_or(false) {
+            return Err(PyErr::new::<TypeError, _>(
+                py,
+                "joinpath() does not accept keyword arguments",
+            ));
+        }
+
+        self.joinpath_impl(py, args.as_slice(py))
     }
 
     /// Return Traversable child in self.
     def __truediv__(&self, child: &PyObject) -> PyResult<PyObject> {
-        self.joinpath_impl(py, child)
+        self.joinpath_impl(py, std::slice::from_ref(child))
     }
 
     // mode may be 'r' or 'rb' to open as text or binary. Return a handle
@@ -1524,53 +1756,326 @@ py_class!(pub(crate) class PyOxidizerTraversable |py| {
 });
 
 impl PyOxidizerTraversable {
-    fn iterdir_impl(&self, _py: Python) -> PyResult<PyObject> {
-        unimplemented!();
-    }
-
-    fn read_bytes_impl(&self, _py: Python) -> PyResult<PyObject> {
-        unimplemented!();
-    }
+    fn name_impl(&self, py: Python) -> String {
+        let package = self.package(py);
+        let path = self.path(py);
 
-    fn read_text_impl(&self, _py: Python) -> PyResult<PyObject> {
-        unimplemented!();
+        if path.is_empty() {
+            match package.rfind('.') {
+                Some(index) => package[index + 1..].to_string(),
+                None => package.clone(),
+            }
+        } else {
+            match path.rfind('/') {
+                Some(index) => path[index + 1..].to_string(),
+                None => path.clone(),
+            }
+        }
     }
 
-    fn is_dir_impl(&self, py: Python) -> PyObject {
+    /// Whether this instance refers to the package itself and the package exists.
+    fn is_package(&self, py: Python) -> bool {
         let state = self.state(py);
-        let path = self.path(py);
+
+        if !self.path(py).is_empty() {
+            return false;
+        }
 
         // We are a directory if the current path is a known package.
-        // TODO We may need to expand this definition in the future to cover
-        // virtual subdirectories in addressable resources. But this will require
-        // changes to the resources data format to capture said annotations.
         if let Some(entry) = state
             .get_resources_state()
-            .resolve_importable_module(&path, state.optimize_level)
+            .resolve_importable_module(self.package(py), state.optimize_level)
         {
-            if entry.is_package {
-                return py.True().into_object();
+            entry.is_package
+        } else {
+            false
+        }
+    }
+
+    fn is_directory(&self, py: Python) -> bool {
+        let path = self.path(py);
+
+        if path.is_empty() {
+            self.is_package(py)
+        } else {
+            self.state(py)
+                .get_resources_state()
+                .is_package_resource_directory(self.package(py), path)
+        }
+    }
+
+    fn is_resource_file(&self, py: Python) -> bool {
+        let path = self.path(py);
+
+        !path.is_empty()
+            && self
+                .state(py)
+                .get_resources_state()
+                .is_package_resource(self.package(py), path)
+    }
+
+    fn child(&self, py: Python, package: String, path: String) -> PyResult<PyObject> {
+        Ok(
+            PyOxidizerTraversable::create_instance(py, self.state(py).clone(), package, path)?
+                .into_object(),
+        )
+    }
+
+    fn iterdir_impl(&self, py: Python) -> PyResult<PyObject> {
+        let state = self.state(py);
+        let resources_state = state.get_resources_state();
+        let package = self.package(py);
+        let path = self.path(py);
+
+        if !self.is_directory(py) {
+            return if self.is_resource_file(py) {
+                Err(PyErr::new::<NotADirectoryError, _>(
+                    py,
+                    format!("{} is not a directory", path),
+                ))
+            } else {
+                Err(PyErr::new::<FileNotFoundError, _>(
+                    py,
+                    format!("{} not found in {}", path, package),
+                ))
+            };
+        }
+
+        let join = |name: &str| -> String {
+            if path.is_empty() {
+                name.to_string()
+            } else {
+                format!("{}/{}", path, name)
             }
+        };
+
+        let mut children = vec![];
+
+        // Sub-packages are children of the package root.
+        if path.is_empty() {
+            let prefix = format!("{}.", package);
+
+            let mut packages = resources_state
+                .resources
+                .values()
+                .filter(|entry| entry.is_package)
+                .filter_map(|entry| {
+                    let name = entry.name.strip_prefix(&prefix)?;
+
+                    if name.contains('.') {
+                        None
+                    } else {
+                        Some(entry.name.to_string())
+                    }
+                })
+                .collect::<Vec<_>>();
+            packages.sort();
+
+            for name in packages {
+                children.push(self.child(py, name, String::new())?);
+            }
+        }
+
+        for name in resources_state.package_resources_list_subdirectories(package, path) {
+            children.push(self.child(py, package.clone(), join(&name))?);
         }
 
-        py.False().into_object()
+        for name in resources_state.package_resources_list_directory(package, path) {
+            children.push(self.child(py, package.clone(), join(&name))?);
+        }
+
+        let builtins = py.import("builtins")?;
+
+        builtins.call(py, "iter", (PyList::new(py, &children),), None)
     }
 
-    fn is_file_impl(&self, _py: Python) -> PyResult<PyObject> {
-        unimplemented!();
+    /// Obtain a file-like object for binary reading of the resource.
+    fn open_binary(&self, py: Python) -> PyResult<PyObject> {
+        let package = self.package(py);
+        let path = self.path(py);
+
+        if !path.is_empty() {
+            if let Some(file) = self
+                .state(py)
+                .get_resources_state()
+                .get_package_resource_file(py, package, path)?
+            {
+                return Ok(file);
+            }
+        }
+
+        if self.is_directory(py) {
+            Err(PyErr::new::<IsADirectoryError, _>(
+                py,
+                format!("{} is a directory", self.name_impl(py)),
+            ))
+        } else {
+            Err(PyErr::new::<FileNotFoundError, _>(
+                py,
+                format!("{} not found in {}", path, package),
+            ))
+        }
     }
 
-    fn joinpath_impl(&self, _py: Python, _child: &PyObject) -> PyResult<PyObject> {
-        unimplemented!();
+    fn read_bytes_impl(&self, py: Python) -> PyResult<PyObject> {
+        let file = self.open_binary(py)?;
+        let data = file.call_method(py, "read", NoArgs, None);
+        file.call_method(py, "close", NoArgs, None)?;
+
+        data
     }
 
-    fn open_impl(
-        &self,
-        _py: Python,
-        _args: &PyTuple,
-        _kwargs: Option<&PyDict>,
-    ) -> PyResult<PyObject> {
-        unimplemented!();
+    fn read_text_impl(&self, py: Python, encoding: Option<PyObject>) -> PyResult<PyObject> {
+        let data = self.read_bytes_impl(py)?;
+
+        let encoding = match encoding {
+            Some(encoding) if encoding != py.None() => encoding,
+            _ => "utf-8".to_py_object(py).into_object(),
+        };
+
+        data.call_method(py, "decode", (encoding,), None)
+    }
+
+    fn is_dir_impl(&self, py: Python) -> PyObject {
+        if self.is_directory(py) {
+            py.True().into_object()
+        } else {
+            py.False().into_object()
+        }
+    }
+
+    fn is_file_impl(&self, py: Python) -> PyResult<PyObject> {
+        Ok(if self.is_resource_file(py) {
+            py.True().into_object()
+        } else {
+            py.False().into_object()
+        })
+    }
+
+    fn joinpath_impl(&self, py: Python, children: &[PyObject]) -> PyResult<PyObject> {
+        let resources_state = self.state(py).get_resources_state();
+
+        let mut package = self.package(py).clone();
+        let mut path = self.path(py).clone();
+
+        for child in children {
+            let child = child.str(py)?.to_string(py)?.to_string();
+
+            for segment in child.split(&['/', '\\'][..]) {
+                if segment.is_empty() || segment == "." {
+                    continue;
+                }
+
+                if segment == ".." {
+                    return Err(PyErr::new::<ValueError, _>(
+                        py,
+                        format!(
+                            "rejecting resource path with parent directory reference: {}",
+                            child
+                        ),
+                    ));
+                }
+
+                if path.is_empty() {
+                    // Descend into a sub-package unless a resource shadows it.
+                    let candidate = format!("{}.{}", package, segment);
+
+                    if !resources_state.is_package_resource(&package, segment)
+                        && !resources_state.is_package_resource_directory(&package, segment)
+                    {
+                        if let Some(entry) = resources_state.resources.get(candidate.as_str()) {
+                            if entry.is_package {
+                                package = candidate;
+                                continue;
+                            }
+                        }
+                    }
+
+                    path = segment.to_string();
+                } else {
+                    path = format!("{}/{}", path, segment);
+                }
+            }
+        }
+
+        self.child(py, package, path)
+    }
+
+    fn open_impl(&self, py: Python, args: &PyTuple, kwargs: Option<&PyDict>) -> PyResult<PyObject> {
+        open_with_mode(py, args, kwargs, |py| self.open_binary(py))
+    }
+}
+
+/// Open a file-like object using `pathlib.Path.open()` semantics.
+///
+/// `args` and `kwargs` are the arguments to `open()`. `mode` may be `r` or
+/// `rb`. `open_binary` is called to obtain a file-like object for binary
+/// reading. When opening as text, this object is wrapped by an
+/// `io.TextIOWrapper` receiving the remaining arguments.
+pub(crate) fn open_with_mode(
+    py: Python,
+    args: &PyTuple,
+    kwargs: Option<&PyDict>,
+    open_binary: impl FnOnce(Python) -> PyResult<PyObject>,
+) -> PyResult<PyObject> {
+    let mut args = args
+        .iter(py)
+        .map(|arg| arg.clone_ref(py))
+        .collect::<Vec<_>>();
+    let kwargs = match kwargs {
+        Some(kwargs) => Some(kwargs.copy(py)?),
+        None => None,
+    };
+
+    let mode = if args.is_empty() {
+        match kwargs
+            .as_ref()
+            .and_then(|kwargs| kwargs.get_item(py, "mode"))
+        {
+            Some(mode) => {
+                kwargs.as_ref().unwrap().del_item(py, "mode")?;
+                mode.extract::<String>(py)?
+            }
+            None => "r".to_string(),
+        }
+    } else {
+        args.remove(0).extract::<String>(py)?
+    };
+
+    match mode.as_str() {
+        "rb" => {
+            if !args.is_empty()
+                || kwargs
+                    .as_ref()
+                    .map(|kwargs| kwargs.len(py) > 0)
+                    .unwrap_or(false)
+            {
+                return Err(PyErr::new::<ValueError, _>(
+                    py,
+                    "binary mode doesn't take encoding arguments",
+                ));
+            }
+
+            open_binary(py)
+        }
+        "r" => {
+            let file = open_binary(py)?;
+
+            let mut wrapper_args = vec![file];
+            wrapper_args.extend(args);
+
+            let io_module = py.import("io")?;
+            io_module.call(
+                py,
+                "TextIOWrapper",
+                PyTuple::new(py, &wrapper_args),
+                kwargs.as_ref(),
+            )
+        }
+        _ => Err(PyErr::new::<ValueError, _>(
+            py,
+            format!("invalid mode: {}", mode),
+        )),
     }
 }
 
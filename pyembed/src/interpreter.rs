@@ -200,31 +200,35 @@ impl<'python, 'interpreter, 'resources> MainPythonInterpreter<'python, 'interpre
                 NewInterpreterError::new_from_pyerr(py, err, "import of oxidized importer module")
             })?;
 
-            let cb = |importer_state: &mut ImporterState| match self
-                .config
-                .multiprocessing_start_method
-            {
-                MultiprocessingStartMethod::None => {}
-                MultiprocessingStartMethod::Fork
-                | MultiprocessingStartMethod::ForkServer
-                | MultiprocessingStartMethod::Spawn => {
-                    importer_state.set_multiprocessing_set_start_method(Some(
-                        self.config.multiprocessing_start_method.to_string(),
-                    ));
-                }
-                MultiprocessingStartMethod::Auto => {
-                    // Windows uses "spawn" because "fork" isn't available.
-                    // Everywhere else uses "fork." The default on macOS is "spawn." This
-                    // is due to https://bugs.python.org/issue33725, which only affects
-                    // Python framework builds. Our assumption is we aren't using a Python
-                    // framework, so "spawn" is safe.
-                    let method = if cfg!(target_family = "windows") {
-                        "spawn"
-                    } else {
-                        "fork"
-                    };
-
-                    importer_state.set_multiprocessing_set_start_method(Some(method.to_string()));
+            let cb = |importer_state: &mut ImporterState| {
+                importer_state.set_pkg_resources_extract_in_memory_resources(
+                    self.config.pkg_resources_extract_in_memory_resources,
+                );
+
+                match self.config.multiprocessing_start_method {
+                    MultiprocessingStartMethod::None => {}
+                    MultiprocessingStartMethod::Fork
+                    | MultiprocessingStartMethod::ForkServer
+                    | MultiprocessingStartMethod::Spawn => {
+                        importer_state.set_multiprocessing_set_start_method(Some(
+                            self.config.multiprocessing_start_method.to_string(),
+                        ));
+                    }
+                    MultiprocessingStartMethod::Auto => {
+                        // Windows uses "spawn" because "fork" isn't available.
+                        // Everywhere else uses "fork." The default on macOS is "spawn." This
+                        // is due to https://bugs.python.org/issue33725, which only affects
+                        // Python framework builds. Our assumption is we aren't using a Python
+                        // framework, so "spawn" is safe.
+                        let method = if cfg!(target_family = "windows") {
+                            "spawn"
+                        } else {
+                            "fork"
+                        };
+
+                        importer_state
+                            .set_multiprocessing_set_start_method(Some(method.to_string()));
+                    }
                 }
             };
 
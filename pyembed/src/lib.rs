@@ -44,7 +44,9 @@ At this time, we have required direct dependencies on published versions of the
 list is extended by `memory-module-sys` and `winapi`, which are required to
 support loading DLLs from memory. We also have an optional direct dependency
 on the `jemalloc-sys`, `libmimalloc-sys`, and `snmalloc-sys` crates for custom
-memory allocators.
+memory allocators, on the `x509-certificate` crate for verifying signatures
+of packed resources data, and on the `serde_json` and `toml` crates for
+reading configuration overlay files.
 
 This crate requires linking against a library providing CPython C symbols.
 (This dependency is via the `python3-sys` crate.) On Windows, this library
@@ -66,6 +68,10 @@ The optional `snmalloc` feature controls support for using
 [snmalloc](https://github.com/microsoft/snmalloc) as Python's memory allocator.
 The feature behaves similarly to `jemalloc`, which is documented above.
 
+The optional `config-overlay` feature controls support for reading the file
+defined by `OxidizedPythonInterpreterConfig.config_overlay_path`, which
+overrides configuration fields at run-time.
+
 There exist mutually exclusive `build-mode-*` features to control how the
 `build.rs` build script works.
 
@@ -95,6 +101,7 @@ That crate's build script will attempt to find a `libpython` from the
 
 #[allow(unused)]
 mod config;
+mod config_overlay;
 mod conversion;
 mod error;
 #[allow(clippy::manual_strip, clippy::transmute_ptr_to_ptr, clippy::zero_ptr)]
@@ -105,6 +112,8 @@ mod importer;
 mod interpreter;
 #[cfg(not(library_mode = "extension"))]
 mod interpreter_config;
+#[cfg(target_os = "linux")]
+mod memfd_dll;
 #[cfg(windows)]
 mod memory_dll;
 #[cfg(not(library_mode = "extension"))]
@@ -149,8 +158,8 @@ pub use crate::extension::PyInit_oxidized_importer;
 pub use python_packaging::{
     interpreter::{
         Allocator, BytesWarning, CheckHashPycsMode, CoerceCLocale, MemoryAllocatorBackend,
-        MultiprocessingStartMethod, PythonInterpreterConfig, PythonInterpreterProfile,
-        TerminfoResolution,
+        MultiprocessingStartMethod, PackedResourcesVerification, PythonInterpreterConfig,
+        PythonInterpreterProfile, TerminfoResolution,
     },
     resource::BytecodeOptimizationLevel,
 };
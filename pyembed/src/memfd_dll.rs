@@ -0,0 +1,175 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Functionality for loading Linux shared libraries from memory.
+
+glibc's `dlopen()` can only load libraries from a path. So we copy the
+library data into an anonymous file created with `memfd_create()` and
+`dlopen()` it via its `/proc/self/fd/N` path. The file descriptor can be
+closed once the library is mapped.
+*/
+
+use {
+    crate::python_resources::PythonResourcesState,
+    once_cell::sync::Lazy,
+    std::{
+        borrow::Cow,
+        collections::HashMap,
+        ffi::{c_void, CStr, CString},
+        sync::Mutex,
+    },
+};
+
+/// Handles of shared libraries loaded from memory, indexed by name.
+///
+/// These are loaded with `RTLD_GLOBAL` so the dynamic linker can satisfy
+/// `DT_NEEDED` entries of subsequently loaded libraries from them. They are
+/// never unloaded.
+///
+/// Handles are stored as `usize` because raw pointers aren't `Send`.
+static SHARED_LIBRARIES: Lazy<Mutex<HashMap<String, usize>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Obtain the error message from the last failed `dl*()` call.
+fn dl_error() -> String {
+    let err = unsafe { libc::dlerror() };
+
+    if err.is_null() {
+        "unknown dlopen() error".to_string()
+    } else {
+        unsafe { CStr::from_ptr(err) }.to_string_lossy().to_string()
+    }
+}
+
+/// Write library data to an anonymous memory file and `dlopen()` it.
+fn dlopen_memory(name: &str, data: &[u8], flags: libc::c_int) -> Result<*mut c_void, String> {
+    let memfd_name = CString::new(name).map_err(|e| e.to_string())?;
+
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_memfd_create,
+            memfd_name.as_ptr(),
+            libc::MFD_CLOEXEC,
+        )
+    } as libc::c_int;
+
+    if fd < 0 {
+        return Err(format!(
+            "memfd_create() failed: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+
+    let mut offset = 0;
+    while offset < data.len() {
+        let written = unsafe {
+            libc::write(
+                fd,
+                data[offset..].as_ptr() as *const c_void,
+                data.len() - offset,
+            )
+        };
+
+        if written < 0 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() == std::io::ErrorKind::Interrupted {
+                continue;
+            }
+
+            unsafe { libc::close(fd) };
+            return Err(format!("error writing to memfd: {}", err));
+        }
+
+        offset += written as usize;
+    }
+
+    let path = CString::new(format!("/proc/self/fd/{}", fd)).unwrap();
+    let handle = unsafe { libc::dlopen(path.as_ptr(), flags) };
+
+    // The mapping created by dlopen() outlives the file descriptor.
+    unsafe { libc::close(fd) };
+
+    if handle.is_null() {
+        Err(dl_error())
+    } else {
+        Ok(handle)
+    }
+}
+
+/// Load shared libraries that a library depends on.
+///
+/// Dependencies are loaded in the order they are declared. Names not
+/// backed by an in-memory shared library are left to the dynamic linker.
+fn load_dependencies(
+    resources_state: &PythonResourcesState<u8>,
+    dependency_names: &[Cow<str>],
+) -> Result<(), String> {
+    for dependency in dependency_names {
+        load_shared_library_memory(resources_state, dependency)?;
+    }
+
+    Ok(())
+}
+
+/// Load a named shared library from resources state into the global namespace.
+fn load_shared_library_memory(
+    resources_state: &PythonResourcesState<u8>,
+    name: &str,
+) -> Result<(), String> {
+    if SHARED_LIBRARIES.lock().unwrap().contains_key(name) {
+        return Ok(());
+    }
+
+    let entry = match resources_state.resources.get(name) {
+        Some(entry) => entry,
+        None => return Ok(()),
+    };
+
+    let library_data = match &entry.in_memory_shared_library {
+        Some(data) => data,
+        None => return Ok(()),
+    };
+
+    // The lock isn't held here because of potential recursion.
+    if let Some(names) = &entry.shared_library_dependency_names {
+        load_dependencies(resources_state, names)?;
+    }
+
+    let handle = dlopen_memory(name, library_data, libc::RTLD_NOW | libc::RTLD_GLOBAL)
+        .map_err(|e| format!("unable to load shared library {} from memory: {}", name, e))?;
+
+    SHARED_LIBRARIES
+        .lock()
+        .unwrap()
+        .insert(name.to_string(), handle as usize);
+
+    Ok(())
+}
+
+/// Load an extension module library from memory.
+///
+/// Shared libraries named by `dependency_names` that are available as
+/// in-memory shared libraries in resources state are loaded first, in order.
+pub(crate) fn load_library_memory(
+    resources_state: &PythonResourcesState<u8>,
+    name: &str,
+    data: &[u8],
+    dependency_names: Option<&[Cow<str>]>,
+) -> Result<*const c_void, String> {
+    if let Some(names) = dependency_names {
+        load_dependencies(resources_state, names)?;
+    }
+
+    dlopen_memory(name, data, libc::RTLD_NOW | libc::RTLD_LOCAL).map(|handle| handle as *const _)
+}
+
+/// Free a library that was loaded from memory.
+pub(crate) unsafe fn free_library_memory(module: *const c_void) {
+    libc::dlclose(module as *mut _);
+}
+
+/// Find the address of a symbol in a memory loaded library.
+pub(crate) unsafe fn get_proc_address_memory(module: *const c_void, name: &CStr) -> *mut c_void {
+    libc::dlsym(module as *mut _, name.as_ptr())
+}
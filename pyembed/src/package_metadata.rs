@@ -4,19 +4,21 @@
 
 use {
     crate::{
-        importer::ImporterState,
+        conversion::path_to_pathlib_path,
+        importer::{open_with_mode, ImporterState},
         pkg_resources::create_oxidized_pkg_resources_provider,
-        python_resources::{name_at_package_hierarchy, name_within_package_hierarchy},
+        python_resources::{
+            name_at_package_hierarchy, name_within_package_hierarchy, IndexedResources,
+        },
     },
     cpython::{
-        exc::{IOError, NotImplementedError, ValueError},
+        exc::{FileNotFoundError, IOError, ValueError},
         py_class, NoArgs, ObjectProtocol, PyBytes, PyClone, PyDict, PyErr, PyList, PyObject,
-        PyResult, PyString, PyType, Python, PythonObject, ToPyObject,
+        PyResult, PyString, PyTuple, PyType, Python, PythonObject, ToPyObject,
     },
-    python_packed_resources::data::Resource,
     std::{
         borrow::Cow,
-        collections::{BTreeMap, BTreeSet, HashMap},
+        collections::{BTreeMap, BTreeSet},
         path::Path,
         sync::Arc,
     },
@@ -78,6 +80,10 @@ py_class!(pub(crate) class OxidizedDistribution |py| {
     @property def requires(&self) -> PyResult<PyObject> {
         self.requires_impl(py)
     }
+
+    def locate_file(&self, path: &PyObject) -> PyResult<PyObject> {
+        self.locate_file_impl(py, path)
+    }
 });
 
 impl OxidizedDistribution {
@@ -248,8 +254,75 @@ impl OxidizedDistribution {
         entry_point.call_method(py, "_from_text", (text,), None)
     }
 
+    /// Files in this distribution, as `importlib.metadata.PackagePath` instances.
+    ///
+    /// Files are derived from the `RECORD` distribution resource, falling back
+    /// to `SOURCES.txt` for egg metadata. Returns `None` if neither exists.
     fn files_impl(&self, py: Python) -> PyResult<PyObject> {
-        Err(PyErr::new::<NotImplementedError, _>(py, NoArgs))
+        let record = self.read_text_impl(py, &"RECORD".to_py_object(py))?;
+
+        let lines = if record != py.None() {
+            record.call_method(py, "splitlines", NoArgs, None)?
+        } else {
+            let sources = self.read_text_impl(py, &"SOURCES.txt".to_py_object(py))?;
+
+            if sources == py.None() {
+                return Ok(py.None());
+            }
+
+            // SOURCES.txt entries are bare paths. Quote them, doubling any
+            // quotes they contain, so they survive CSV parsing like RECORD
+            // entries.
+            let lines = sources
+                .call_method(py, "splitlines", NoArgs, None)?
+                .iter(py)?
+                .map(|line| {
+                    let line = line?.str(py)?.to_string(py)?.replace('"', "\"\"");
+
+                    Ok(format!("\"{}\"", line).to_py_object(py).into_object())
+                })
+                .collect::<PyResult<Vec<_>>>()?;
+
+            PyList::new(py, &lines).into_object()
+        };
+
+        let importlib_metadata = py.import("importlib.metadata")?;
+        let package_path_type = importlib_metadata.get(py, "PackagePath")?;
+        let file_hash_type = importlib_metadata.get(py, "FileHash")?;
+        let builtins = py.import("builtins")?;
+
+        let reader = py.import("csv")?.call(py, "reader", (lines,), None)?;
+
+        let mut files = vec![];
+
+        for row in reader.iter(py)? {
+            let row = row?.iter(py)?.collect::<PyResult<Vec<_>>>()?;
+
+            let name = if let Some(name) = row.first() {
+                name
+            } else {
+                continue;
+            };
+
+            let hash = match row.get(1) {
+                Some(hash) if hash.is_true(py)? => file_hash_type.call(py, (hash,), None)?,
+                _ => py.None(),
+            };
+
+            let size = match row.get(2) {
+                Some(size) if size.is_true(py)? => builtins.call(py, "int", (size,), None)?,
+                _ => py.None(),
+            };
+
+            let path = package_path_type.call(py, (name,), None)?;
+            path.setattr(py, "hash", hash)?;
+            path.setattr(py, "size", size)?;
+            path.setattr(py, "dist", self.as_object())?;
+
+            files.push(path);
+        }
+
+        Ok(PyList::new(py, &files).into_object())
     }
 
     fn requires_impl(&self, py: Python) -> PyResult<PyObject> {
@@ -282,6 +355,128 @@ impl OxidizedDistribution {
             Ok(res)
         }
     }
+
+    /// Resolve a path relative to the installation root of the distribution.
+    ///
+    /// Files backed by filesystem-relative resources resolve to a
+    /// `pathlib.Path`. Otherwise an `OxidizedDistributionFile` reading from
+    /// the resources data is returned, even if the file does not exist.
+    fn locate_file_impl(&self, py: Python, path: &PyObject) -> PyResult<PyObject> {
+        let state: &Arc<ImporterState> = self.state(py);
+        let package: &str = self.package(py);
+        let resources_state = state.get_resources_state();
+
+        let path = path.str(py)?.to_string(py)?.to_string();
+
+        if let Some(DistributionFile::RelativePath(relative_path)) =
+            resolve_distribution_file(&resources_state.resources, package, &path)
+        {
+            path_to_pathlib_path(py, &resources_state.origin.join(relative_path))
+        } else {
+            Ok(OxidizedDistributionFile::create_instance(
+                py,
+                state.clone(),
+                package.to_string(),
+                path,
+            )?
+            .into_object())
+        }
+    }
+}
+
+// A file belonging to an OxidizedDistribution.
+//
+// Instances are returned by OxidizedDistribution.locate_file() and provide
+// enough of the pathlib.Path interface for importlib.metadata.PackagePath
+// to read file content.
+py_class!(pub(crate) class OxidizedDistributionFile |py| {
+    data state: Arc<ImporterState>;
+    data package: String;
+    data path: String;
+
+    @property def name(&self) -> PyResult<String> {
+        let path = self.path(py);
+
+        Ok(match path.rfind('/') {
+            Some(index) => path[index + 1..].to_string(),
+            None => path.clone(),
+        })
+    }
+
+    def __str__(&self) -> PyResult<String> {
+        Ok(self.path(py).clone())
+    }
+
+    def __repr__(&self) -> PyResult<String> {
+        Ok(format!("<OxidizedDistributionFile {:?}>", self.path(py)))
+    }
+
+    def exists(&self) -> PyResult<bool> {
+        Ok(self.resolve_data(py)?.is_some())
+    }
+
+    def is_file(&self) -> PyResult<bool> {
+        Ok(self.resolve_data(py)?.is_some())
+    }
+
+    def read_bytes(&self) -> PyResult<PyObject> {
+        self.read_bytes_impl(py)
+    }
+
+    def read_text(&self, encoding: Option<PyObject> = None) -> PyResult<PyObject> {
+        let encoding = match encoding {
+            Some(encoding) if encoding != py.None() => encoding,
+            _ => "utf-8".to_py_object(py).into_object(),
+        };
+
+        self.read_bytes_impl(py)?
+            .call_method(py, "decode", (encoding,), None)
+    }
+
+    def open(&self, *args, **kwargs) -> PyResult<PyObject> {
+        self.open_impl(py, args, kwargs)
+    }
+});
+
+impl OxidizedDistributionFile {
+    fn resolve_data(&self, py: Python) -> PyResult<Option<Vec<u8>>> {
+        let resources_state = self.state(py).get_resources_state();
+
+        let data = match resolve_distribution_file(
+            &resources_state.resources,
+            self.package(py),
+            self.path(py),
+        ) {
+            Some(DistributionFile::InMemory(data)) => Some(data.to_vec()),
+            Some(DistributionFile::RelativePath(path)) => Some(
+                std::fs::read(resources_state.origin.join(path)).map_err(|e| {
+                    PyErr::new::<IOError, _>(py, format!("error when resolving resource: {}", e))
+                })?,
+            ),
+            None => None,
+        };
+
+        Ok(data)
+    }
+
+    fn read_bytes_impl(&self, py: Python) -> PyResult<PyObject> {
+        if let Some(data) = self.resolve_data(py)? {
+            Ok(PyBytes::new(py, &data).into_object())
+        } else {
+            Err(PyErr::new::<FileNotFoundError, _>(
+                py,
+                format!("{} not found in distribution", self.path(py)),
+            ))
+        }
+    }
+
+    fn open_impl(&self, py: Python, args: &PyTuple, kwargs: Option<&PyDict>) -> PyResult<PyObject> {
+        open_with_mode(py, args, kwargs, |py| {
+            let data = self.read_bytes_impl(py)?;
+
+            py.import("io")?.call(py, "BytesIO", (data,), None)
+        })
+    }
 }
 
 /// Find package metadata distributions given search criteria.
@@ -297,9 +492,8 @@ pub(crate) fn find_distributions(
         // Python normalizes the name. We do the same.
         let name = name.str(py)?.to_string(py)?.to_string();
         let name = name.to_lowercase().replace('-', "_");
-        let name_cow = Cow::Borrowed::<str>(&name);
 
-        if let Some(resource) = resources.get(&name_cow) {
+        if let Some(resource) = resources.get(&name) {
             if resource.is_package
                 && (resource.in_memory_distribution_resources.is_some()
                     || resource.relative_path_distribution_resources.is_some())
@@ -406,7 +600,7 @@ pub(crate) fn find_pkg_resources_distributions(
 }
 
 pub(crate) fn resolve_package_distribution_resource<'a>(
-    resources: &'a HashMap<Cow<'a, str>, Resource<'a, u8>>,
+    resources: &'a IndexedResources<'a, u8>,
     origin: &Path,
     package: &str,
     name: &str,
@@ -433,9 +627,106 @@ pub(crate) fn resolve_package_distribution_resource<'a>(
     }
 }
 
+/// Location of the data of a file installed by a distribution.
+pub(crate) enum DistributionFile<'a> {
+    /// File content is available in memory.
+    InMemory(&'a [u8]),
+    /// File content is in a file at a path relative to the resources origin.
+    RelativePath(&'a Path),
+}
+
+/// Resolve a path from a distribution's `RECORD` to resource data.
+///
+/// `path` is relative to the directory the distribution was installed into.
+/// Paths into the `.dist-info` / `.egg-info` directory resolve to distribution
+/// resources of `package`. `.py` paths resolve to module source. Remaining
+/// paths resolve to package resources of the deepest containing package.
+pub(crate) fn resolve_distribution_file<'a>(
+    resources: &'a IndexedResources<'a, u8>,
+    package: &str,
+    path: &str,
+) -> Option<DistributionFile<'a>> {
+    let path = path.replace('\\', "/");
+
+    let components = path
+        .split('/')
+        .filter(|c| !c.is_empty() && *c != ".")
+        .collect::<Vec<_>>();
+
+    if components.is_empty() || components.contains(&"..") {
+        return None;
+    }
+
+    if components[0].ends_with(".dist-info") || components[0].ends_with(".egg-info") {
+        let entry = resources.get(package)?;
+        let name = components[1..].join("/");
+
+        if let Some(resources) = &entry.in_memory_distribution_resources {
+            if let Some(data) = resources.get(name.as_str()) {
+                return Some(DistributionFile::InMemory(data));
+            }
+        }
+
+        if let Some(resources) = &entry.relative_path_distribution_resources {
+            if let Some(path) = resources.get(name.as_str()) {
+                return Some(DistributionFile::RelativePath(path));
+            }
+        }
+
+        return None;
+    }
+
+    let (parents, leaf) = components.split_at(components.len() - 1);
+
+    if let Some(stem) = leaf[0].strip_suffix(".py") {
+        let mut parts = parents.to_vec();
+        let is_package = stem == "__init__";
+
+        if !is_package {
+            parts.push(stem);
+        }
+
+        if let Some(entry) = resources.get(parts.join(".").as_str()) {
+            if entry.is_package == is_package {
+                if let Some(data) = &entry.in_memory_source {
+                    return Some(DistributionFile::InMemory(data));
+                }
+
+                if let Some(path) = &entry.relative_path_module_source {
+                    return Some(DistributionFile::RelativePath(path));
+                }
+            }
+        }
+    }
+
+    for index in (1..components.len()).rev() {
+        let entry = if let Some(entry) = resources.get(components[0..index].join(".").as_str()) {
+            entry
+        } else {
+            continue;
+        };
+
+        let name = components[index..].join("/");
+
+        if let Some(resources) = &entry.in_memory_package_resources {
+            if let Some(data) = resources.get(name.as_str()) {
+                return Some(DistributionFile::InMemory(data));
+            }
+        }
+
+        if let Some(resources) = &entry.relative_path_package_resources {
+            if let Some(path) = resources.get(name.as_str()) {
+                return Some(DistributionFile::RelativePath(path));
+            }
+        }
+    }
+
+    None
+}
+
 /// Whether a metadata resource name is a directory.
 pub(crate) fn metadata_name_is_directory<'a>(
-    resources: &'a HashMap<Cow<'a, str>, Resource<'a, u8>>,
+    resources: &'a IndexedResources<'a, u8>,
     package: &str,
     name: &str,
 ) -> bool {
@@ -468,7 +759,7 @@ pub(crate) fn metadata_name_is_directory<'a>(
 
 /// List contents of a metadata directory.
 pub(crate) fn metadata_list_directory<'a>(
-    resources: &'a HashMap<Cow<'a, str>, Resource<'a, u8>>,
+    resources: &'a IndexedResources<'a, u8>,
     package: &str,
     name: &str,
 ) -> Vec<&'a str> {
@@ -4,6 +4,7 @@
 
 use {
     crate::{
+        conversion::path_to_pyobject,
         importer::{ImporterState, OxidizedFinder, OxidizedPathEntryFinder},
         package_metadata::{
             find_pkg_resources_distributions, metadata_list_directory, metadata_name_is_directory,
@@ -207,15 +208,19 @@ impl OxidizedPkgResourcesProvider {
         &self,
         py: Python,
         _manager: PyObject,
-        _resource_name: PyString,
+        resource_name: PyString,
     ) -> PyResult<PyObject> {
+        let state = self.state(py);
+        let package = self.package(py);
+        let resource_name = resource_name.to_string_lossy(py);
+
         // Raising NotImplementedError seems allowed per the implementation of
         // pkg_resources.ZipProvider, which also raises this error when resources
         // aren't backed by the filesystem.
-        //
-        // We could potentially expose the filename if the resource is backed
-        // by a file. But we keep things simple for now.
-        Err(PyErr::new::<NotImplementedError, _>(py, NoArgs))
+        match state.resolve_package_resource_filename(py, package, &resource_name)? {
+            Some(path) => path_to_pyobject(py, &path),
+            None => Err(PyErr::new::<NotImplementedError, _>(py, NoArgs)),
+        }
     }
 
     fn get_resource_stream_impl(
@@ -16,15 +16,24 @@ use {
         },
         error::NewInterpreterError,
     },
-    anyhow::Result,
+    anyhow::{anyhow, Result},
     cpython::{
         buffer::PyBuffer,
         exc::{ImportError, OSError, TypeError, ValueError},
         py_class, NoArgs, ObjectProtocol, PyBytes, PyDict, PyErr, PyList, PyModule, PyObject,
         PyResult, PyString, PyTuple, Python, PythonObject, ToPyObject,
     },
+    once_cell::unsync::OnceCell,
     python3_sys as pyffi,
-    python_packed_resources::data::Resource,
+    python_packaging::interpreter::PackedResourcesVerification,
+    python_packed_resources::{
+        data::{Resource, ResourceField},
+        integrity::{find_integrity_trailer, verify_resource_content_sha256},
+        parser::{
+            load_resources_deferred, resolve_deferred_resource, DecompressedBlobSections,
+            DeferredResource,
+        },
+    },
     std::{
         borrow::Cow,
         cell::RefCell,
@@ -38,6 +47,35 @@ use {
 
 const ENOENT: c_int = 2;
 
+/// Write an extracted package resource to `<package_dir>/<name>`.
+///
+/// Existing files are assumed to hold the resource and are left alone. Data
+/// is written to a temporary file and renamed into place so concurrent
+/// extractions never observe partial content.
+fn write_extracted_resource(package_dir: &Path, name: &str, data: &[u8]) -> Result<()> {
+    if name.split('/').any(|component| component == "..") {
+        return Err(anyhow!("resource name {} escapes its package", name));
+    }
+
+    let path = package_dir.join(name);
+
+    if path.exists() {
+        return Ok(());
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut temp_path = path.clone().into_os_string();
+    temp_path.push(format!(".tmp{}", std::process::id()));
+
+    std::fs::write(&temp_path, data)?;
+    std::fs::rename(&temp_path, &path)?;
+
+    Ok(())
+}
+
 /// Python bytecode optimization level.
 #[derive(Clone, Copy, Debug)]
 pub(crate) enum OptimizeLevel {
@@ -388,6 +426,186 @@ impl<'a> ImportablePythonModule<'a, u8> {
     pub fn in_memory_extension_module_shared_library(&self) -> &'a Option<Cow<'a, [u8]>> {
         &self.resource.in_memory_extension_module_shared_library
     }
+
+    /// Names of shared libraries this extension module depends on.
+    pub fn shared_library_dependency_names(&self) -> &'a Option<Vec<Cow<'a, str>>> {
+        &self.resource.shared_library_dependency_names
+    }
+}
+
+/// A resource whose deferred fields haven't been read.
+struct PendingResource<'a> {
+    /// Packed resources data the resource came from.
+    data: &'a [u8],
+    sections: &'a DecompressedBlobSections,
+    resource: DeferredResource<'a>,
+}
+
+impl<'a> std::fmt::Debug for PendingResource<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PendingResource")
+            .field("resource", &self.resource)
+            .finish()
+    }
+}
+
+/// A resource indexed by name.
+///
+/// Fields of packed resources in compressed blob sections are read, and
+/// their blob sections decompressed, the first time the resource is accessed.
+#[derive(Debug)]
+struct IndexedResource<'a, X>
+where
+    [X]: ToOwned<Owned = Vec<X>>,
+{
+    resource: OnceCell<Resource<'a, X>>,
+    pending: RefCell<Option<PendingResource<'a>>>,
+}
+
+impl<'a, X> From<Resource<'a, X>> for IndexedResource<'a, X>
+where
+    [X]: ToOwned<Owned = Vec<X>>,
+{
+    fn from(resource: Resource<'a, X>) -> Self {
+        Self {
+            resource: OnceCell::from(resource),
+            pending: RefCell::new(None),
+        }
+    }
+}
+
+impl<'a> IndexedResource<'a, u8> {
+    fn deferred(
+        data: &'a [u8],
+        sections: &'a DecompressedBlobSections,
+        resource: DeferredResource<'a>,
+    ) -> Self {
+        if resource.is_resolved() {
+            return Self::from(resource.resource);
+        }
+
+        Self {
+            resource: OnceCell::new(),
+            pending: RefCell::new(Some(PendingResource {
+                data,
+                sections,
+                resource,
+            })),
+        }
+    }
+
+    /// Obtain the resource, reading its deferred fields if needed.
+    fn try_get(&self) -> Result<&Resource<'a, u8>, &'static str> {
+        self.resource.get_or_try_init(|| {
+            let pending = self
+                .pending
+                .borrow_mut()
+                .take()
+                .ok_or("error reading packed resources data")?;
+
+            resolve_deferred_resource(pending.data, pending.sections, pending.resource)
+        })
+    }
+
+    /// Obtain the resource, reading its deferred fields if needed.
+    ///
+    /// Panics if deferred fields can't be read, which only happens when the
+    /// packed resources data is corrupt.
+    fn get(&self) -> &Resource<'a, u8> {
+        self.try_get().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_get_mut(&mut self) -> Result<&mut Resource<'a, u8>, &'static str> {
+        self.try_get()?;
+
+        Ok(self.resource.get_mut().unwrap())
+    }
+
+    fn get_mut(&mut self) -> &mut Resource<'a, u8> {
+        self.try_get_mut().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_into_resource(self) -> Result<Resource<'a, u8>, &'static str> {
+        self.try_get()?;
+
+        Ok(self.resource.into_inner().unwrap())
+    }
+}
+
+/// Resources indexed by name.
+#[derive(Debug)]
+pub(crate) struct IndexedResources<'a, X>
+where
+    [X]: ToOwned<Owned = Vec<X>>,
+{
+    resources: HashMap<Cow<'a, str>, IndexedResource<'a, X>>,
+}
+
+impl<'a, X> Default for IndexedResources<'a, X>
+where
+    [X]: ToOwned<Owned = Vec<X>>,
+{
+    fn default() -> Self {
+        Self {
+            resources: HashMap::new(),
+        }
+    }
+}
+
+impl<'a> IndexedResources<'a, u8> {
+    /// Obtain a resource by name.
+    pub fn get(&self, name: &str) -> Option<&Resource<'a, u8>> {
+        self.resources.get(name).map(IndexedResource::get)
+    }
+
+    fn get_mut(&mut self, name: &str) -> Option<&mut Resource<'a, u8>> {
+        self.resources.get_mut(name).map(IndexedResource::get_mut)
+    }
+
+    /// Iterate over resource names and resources.
+    ///
+    /// This reads the deferred fields of every resource.
+    pub fn iter(&self) -> impl Iterator<Item = (&Cow<'a, str>, &Resource<'a, u8>)> {
+        self.resources
+            .iter()
+            .map(|(name, resource)| (name, resource.get()))
+    }
+
+    /// Iterate over resources.
+    ///
+    /// This reads the deferred fields of every resource.
+    pub fn values(&self) -> impl Iterator<Item = &Resource<'a, u8>> {
+        self.resources.values().map(IndexedResource::get)
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.resources.reserve(additional);
+    }
+
+    /// Insert a resource, replacing an existing resource with the same name.
+    fn insert(&mut self, resource: Resource<'a, u8>) {
+        self.resources
+            .insert(resource.name.clone(), IndexedResource::from(resource));
+    }
+
+    /// Insert a resource, merging it into an existing resource with the same name.
+    fn merge(
+        &mut self,
+        name: Cow<'a, str>,
+        resource: IndexedResource<'a, u8>,
+    ) -> Result<(), &'static str> {
+        match self.resources.entry(name) {
+            Entry::Occupied(existing) => existing
+                .into_mut()
+                .try_get_mut()?
+                .merge_from(resource.try_into_resource()?),
+            Entry::Vacant(vacant) => {
+                vacant.insert(resource);
+
+                Ok(())
+            }
+        }
+    }
 }
 
 /// Defines Python resources available for import.
@@ -405,7 +623,7 @@ where
     pub origin: PathBuf,
 
     /// Named resources available for loading.
-    pub resources: HashMap<Cow<'a, str>, Resource<'a, X>>,
+    pub resources: IndexedResources<'a, X>,
 
     /// List of `PyObject` that back indexed data.
     ///
@@ -415,6 +633,23 @@ where
 
     /// Holds memory mapped file instances that resources data came from.
     backing_mmaps: Vec<memmap::Mmap>,
+
+    /// Holds decompressed blob sections of indexed data.
+    ///
+    /// Resources in compressed blob sections borrow from these for `'a`. Each
+    /// section is boxed so it doesn't move when this grows. Sections are never
+    /// removed and are dropped after `resources`, which is declared first.
+    #[allow(clippy::vec_box)]
+    backing_blob_sections: Vec<Box<DecompressedBlobSections>>,
+
+    /// How indexed packed resources data is verified.
+    verification: PackedResourcesVerification,
+
+    /// PEM encoded certificate that packed resources data must be signed with.
+    signing_certificate: Option<String>,
+
+    /// Outcome of lazy content digest verification, keyed by resource name.
+    verified_resources: RefCell<HashMap<String, bool>>,
 }
 
 impl<'a> Default for PythonResourcesState<'a, u8> {
@@ -422,13 +657,41 @@ impl<'a> Default for PythonResourcesState<'a, u8> {
         Self {
             current_exe: PathBuf::new(),
             origin: PathBuf::new(),
-            resources: HashMap::new(),
+            resources: IndexedResources::default(),
             backing_py_objects: vec![],
             backing_mmaps: vec![],
+            backing_blob_sections: vec![],
+            verification: PackedResourcesVerification::None,
+            signing_certificate: None,
+            verified_resources: RefCell::new(HashMap::new()),
         }
     }
 }
 
+/// Verify a signature over packed resources data.
+#[cfg(feature = "packed-resources-signatures")]
+fn verify_packed_resources_signature(
+    certificate: &str,
+    message: &[u8],
+    signature: &[u8],
+) -> Result<(), &'static str> {
+    let certificate = x509_certificate::CapturedX509Certificate::from_pem(certificate)
+        .map_err(|_| "unable to parse packed resources signing certificate")?;
+
+    certificate
+        .verify_signed_data(message, signature)
+        .map_err(|_| "packed resources signature verification failed")
+}
+
+#[cfg(not(feature = "packed-resources-signatures"))]
+fn verify_packed_resources_signature(
+    _certificate: &str,
+    _message: &[u8],
+    _signature: &[u8],
+) -> Result<(), &'static str> {
+    Err("verifying packed resources signatures requires the packed-resources-signatures feature")
+}
+
 impl<'a, 'config: 'a> TryFrom<&ResolvedOxidizedPythonInterpreterConfig<'config>>
     for PythonResourcesState<'a, u8>
 {
@@ -440,6 +703,8 @@ impl<'a, 'config: 'a> TryFrom<&ResolvedOxidizedPythonInterpreterConfig<'config>>
         let mut state = Self {
             current_exe: config.exe().clone(),
             origin: config.origin().clone(),
+            verification: config.packed_resources_verification,
+            signing_certificate: config.packed_resources_signing_certificate.clone(),
             ..Default::default()
         };
 
@@ -488,8 +753,23 @@ impl<'a> PythonResourcesState<'a, u8> {
     /// on the incoming entry will overwrite fields on the existing entry.
     ///
     /// If an entry doesn't exist, the resource will be inserted as-is.
+    ///
+    /// If verification is enabled, the data is verified according to the
+    /// configured verification mode before any resources are indexed.
     pub fn index_data(&mut self, data: &'a [u8]) -> Result<(), &'static str> {
-        let resources = python_packed_resources::parser::load_resources(data)?;
+        self.verify_packed_resources_data(data)?;
+
+        // Compressed blob sections are decompressed into memory owned by this
+        // instance the first time a resource in them is accessed. See
+        // `backing_blob_sections` for why borrowing it for `'a` is sound.
+        self.backing_blob_sections
+            .push(Box::new(DecompressedBlobSections::default()));
+        let sections = unsafe {
+            &*(self.backing_blob_sections.last().unwrap().as_ref()
+                as *const DecompressedBlobSections)
+        };
+
+        let resources = load_resources_deferred(data, sections)?;
 
         // Reserve space for expected number of incoming items so we can avoid extra
         // allocations.
@@ -497,20 +777,69 @@ impl<'a> PythonResourcesState<'a, u8> {
 
         for resource in resources {
             let resource = resource?;
+            let name = resource.resource.name.clone();
 
-            match self.resources.entry(resource.name.clone()) {
-                Entry::Occupied(existing) => {
-                    existing.into_mut().merge_from(resource)?;
-                }
-                Entry::Vacant(vacant) => {
-                    vacant.insert(resource);
-                }
+            if self.verification != PackedResourcesVerification::None
+                && resource.resource.content_sha256.is_none()
+                && !resource.is_deferred(ResourceField::ContentSha256)
+            {
+                return Err("packed resources data lacks resource content digests");
+            }
+
+            let resource = if self.verification == PackedResourcesVerification::Startup {
+                let resource = resolve_deferred_resource(data, sections, resource)?;
+                verify_resource_content_sha256(&resource)?;
+
+                IndexedResource::from(resource)
+            } else {
+                IndexedResource::deferred(data, sections, resource)
+            };
+
+            self.resources.merge(name, resource)?;
+        }
+
+        Ok(())
+    }
+
+    /// Verify the integrity trailer of packed resources data.
+    fn verify_packed_resources_data(&self, data: &[u8]) -> Result<(), &'static str> {
+        let trailer = find_integrity_trailer(data)?;
+
+        if let Some(certificate) = &self.signing_certificate {
+            let trailer = trailer.ok_or("packed resources data is not signed")?;
+            let signature = trailer
+                .signature
+                .ok_or("packed resources data is not signed")?;
+
+            trailer.verify_payload_digest()?;
+            verify_packed_resources_signature(certificate, trailer.payload_sha256, signature)?;
+        } else if self.verification == PackedResourcesVerification::Startup {
+            if let Some(trailer) = trailer {
+                trailer.verify_payload_digest()?;
             }
         }
 
         Ok(())
     }
 
+    /// Whether a resource passes lazy content digest verification.
+    ///
+    /// Always true unless verification happens at import time. Results are
+    /// cached so each resource is only hashed once.
+    fn resource_passes_verification(&self, resource: &Resource<u8>) -> bool {
+        if self.verification != PackedResourcesVerification::Import
+            || resource.content_sha256.is_none()
+        {
+            return true;
+        }
+
+        *self
+            .verified_resources
+            .borrow_mut()
+            .entry(resource.name.to_string())
+            .or_insert_with(|| verify_resource_content_sha256(resource).is_ok())
+    }
+
     /// Load resources data from a filesystem path using memory mapped I/O.
     pub fn index_path_memory_mapped(&mut self, path: impl AsRef<Path>) -> Result<(), String> {
         let path = path.as_ref();
@@ -560,16 +889,15 @@ impl<'a> PythonResourcesState<'a, u8> {
                 }
             };
 
-            self.resources
-                .entry(name_str.into())
-                .and_modify(|r| {
-                    r.is_builtin_extension_module = true;
-                })
-                .or_insert_with(|| Resource {
+            if let Some(resource) = self.resources.get_mut(name_str) {
+                resource.is_builtin_extension_module = true;
+            } else {
+                self.resources.insert(Resource {
                     is_builtin_extension_module: true,
                     name: Cow::Owned(name_str.to_string()),
                     ..Resource::default()
                 });
+            }
         }
 
         Ok(())
@@ -592,16 +920,15 @@ impl<'a> PythonResourcesState<'a, u8> {
                 }
             };
 
-            self.resources
-                .entry(name_str.into())
-                .and_modify(|r| {
-                    r.is_frozen_module = true;
-                })
-                .or_insert_with(|| Resource {
+            if let Some(resource) = self.resources.get_mut(name_str) {
+                resource.is_frozen_module = true;
+            } else {
+                self.resources.insert(Resource {
                     is_frozen_module: true,
                     name: Cow::Owned(name_str.to_string()),
                     ..Resource::default()
                 });
+            }
         }
 
         Ok(())
@@ -627,7 +954,7 @@ impl<'a> PythonResourcesState<'a, u8> {
         &mut self,
         resource: Resource<'resource, u8>,
     ) -> Result<(), &'static str> {
-        self.resources.insert(resource.name.clone(), resource);
+        self.resources.insert(resource);
 
         Ok(())
     }
@@ -668,6 +995,10 @@ impl<'a> PythonResourcesState<'a, u8> {
             None => return None,
         };
 
+        if !self.resource_passes_verification(resource) {
+            return None;
+        }
+
         // Since resources can exist as multiple types and it is possible
         // that a single resource will express itself as multiple types
         // (e.g. we have both bytecode and an extension module available),
@@ -751,6 +1082,13 @@ impl<'a> PythonResourcesState<'a, u8> {
             None => return Ok(None),
         };
 
+        if !self.resource_passes_verification(entry) {
+            return Err(PyErr::new::<OSError, _>(
+                py,
+                format!("resources of {} failed integrity verification", package),
+            ));
+        }
+
         if let Some(resources) = &entry.in_memory_package_resources {
             if let Some(data) = resources.get(resource_name) {
                 let io_module = py.import("io")?;
@@ -905,6 +1243,162 @@ impl<'a> PythonResourcesState<'a, u8> {
         entries.into_iter().collect::<Vec<_>>()
     }
 
+    /// Resolve subdirectories containing package resources in a directory.
+    ///
+    /// Unlike `package_resources_list_directory()`, this returns the names of
+    /// directories that contain resources, either directly or in a descendant
+    /// directory.
+    pub fn package_resources_list_subdirectories(&self, package: &str, name: &str) -> Vec<String> {
+        let name = name.replace('\\', "/");
+
+        let prefix = if name.is_empty() || name.ends_with('/') {
+            name
+        } else {
+            format!("{}/", name)
+        };
+
+        let filter_map_resource = |path: &'_ Cow<'_, str>| -> Option<String> {
+            let name = path.strip_prefix(&prefix)?;
+
+            name.find('/').map(|index| name[0..index].to_string())
+        };
+
+        let mut entries = BTreeSet::new();
+
+        if let Some(entry) = self.resources.get(package) {
+            if let Some(resources) = &entry.in_memory_package_resources {
+                entries.extend(resources.keys().filter_map(filter_map_resource));
+            }
+
+            if let Some(resources) = &entry.relative_path_package_resources {
+                entries.extend(resources.keys().filter_map(filter_map_resource));
+            }
+        }
+
+        entries.into_iter().collect::<Vec<_>>()
+    }
+
+    /// Resolve the filesystem path of a package resource or resource directory.
+    ///
+    /// Returns `Some` if `name` is a resource backed by a file relative to the
+    /// origin or if `name` is a directory whose resources are all backed by
+    /// files in a common directory. Returns `None` otherwise, including when
+    /// the resource does not exist.
+    pub fn package_resource_filesystem_path(&self, package: &str, name: &str) -> Option<PathBuf> {
+        let entry = self.resources.get(package)?;
+
+        let name = name.replace('\\', "/");
+        let name = name.trim_end_matches('/');
+
+        if let Some(resources) = &entry.relative_path_package_resources {
+            if let Some(path) = resources.get(name) {
+                return Some(self.origin.join(path));
+            }
+        }
+
+        if name.is_empty() {
+            return None;
+        }
+
+        let prefix = format!("{}/", name);
+
+        // In-memory resources aren't on the filesystem. So a directory
+        // containing them can't be represented by a filesystem path.
+        if let Some(resources) = &entry.in_memory_package_resources {
+            if resources.keys().any(|key| key.starts_with(&prefix)) {
+                return None;
+            }
+        }
+
+        let mut directory = None;
+
+        for (key, path) in entry.relative_path_package_resources.as_ref()?.iter() {
+            let relative = match key.strip_prefix(&prefix) {
+                Some(relative) => relative,
+                None => continue,
+            };
+
+            // The resource's path must end with its name within the directory.
+            // Strip those components to arrive at the directory's path.
+            if !path.ends_with(relative) {
+                return None;
+            }
+
+            let mut candidate = path.to_path_buf();
+            for _ in relative.split('/') {
+                candidate.pop();
+            }
+
+            match &directory {
+                Some(existing) if existing != &candidate => return None,
+                Some(_) => {}
+                None => directory = Some(candidate),
+            }
+        }
+
+        directory.map(|directory| self.origin.join(directory))
+    }
+
+    /// Write package resources to a directory on the filesystem.
+    ///
+    /// `name` is a resource or a directory of resources. Resources are written
+    /// to `<dest_dir>/<package>/<name>`. Files written by a previous call are
+    /// reused. Returns the path of `name` within `dest_dir` or `None` if no
+    /// resources match `name`.
+    pub fn extract_package_resources(
+        &self,
+        package: &str,
+        name: &str,
+        dest_dir: &Path,
+    ) -> Result<Option<PathBuf>> {
+        let entry = match self.resources.get(package) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+
+        if !self.resource_passes_verification(entry) {
+            return Err(anyhow!(
+                "resources of {} failed integrity verification",
+                package
+            ));
+        }
+
+        let name = name.replace('\\', "/");
+        let name = name.trim_end_matches('/');
+
+        if name.is_empty() {
+            return Ok(None);
+        }
+
+        let prefix = format!("{}/", name);
+        let package_dir = dest_dir.join(package);
+
+        let matches = |key: &str| key == name || key.starts_with(&prefix);
+
+        let mut found = false;
+
+        if let Some(resources) = &entry.in_memory_package_resources {
+            for (key, data) in resources.iter().filter(|(key, _)| matches(key)) {
+                write_extracted_resource(&package_dir, key, data)?;
+                found = true;
+            }
+        }
+
+        if let Some(resources) = &entry.relative_path_package_resources {
+            for (key, path) in resources.iter().filter(|(key, _)| matches(key)) {
+                let data = std::fs::read(self.origin.join(path))?;
+                write_extracted_resource(&package_dir, key, &data)?;
+                found = true;
+            }
+        }
+
+        Ok(if found {
+            Some(package_dir.join(name))
+        } else {
+            None
+        })
+    }
+
     /// Attempt to resolve a PyBytes for resource data given a relative path.
     ///
     /// Raises OSerror on failure.
@@ -1122,7 +1616,21 @@ impl<'a> PythonResourcesState<'a, u8> {
 
         let mut buffer = Vec::new();
 
-        python_packed_resources::writer::write_packed_resources_v3(&resources, &mut buffer, None)?;
+        // Content digests require version 4 of the format.
+        if resources.iter().any(|r| r.content_sha256.is_some()) {
+            python_packed_resources::writer::write_packed_resources_v4(
+                &resources,
+                &mut buffer,
+                None,
+                None,
+            )?;
+        } else {
+            python_packed_resources::writer::write_packed_resources_v3(
+                &resources,
+                &mut buffer,
+                None,
+            )?;
+        }
 
         Ok(buffer)
     }
@@ -1592,8 +2100,45 @@ mod tests {
         resources.index_data(&data0).unwrap();
         resources.index_data(&data1).unwrap();
 
-        assert!(resources.resources.contains_key("foo".into()));
-        assert!(resources.resources.contains_key("bar".into()));
+        assert!(resources.resources.get("foo").is_some());
+        assert!(resources.resources.get("bar").is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn compressed_resources_are_resolved_on_access() -> Result<()> {
+        let source = b"import os\n".repeat(64);
+        let indexed = (0..4)
+            .map(|i| Resource {
+                name: format!("module{}", i).into(),
+                is_module: true,
+                in_memory_source: Some(source.clone().into()),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+
+        let mut data = vec![];
+        python_packed_resources::writer::write_packed_resources_v4(
+            &indexed,
+            &mut data,
+            None,
+            Some(python_packed_resources::data::BlobSectionCompression::Zstd),
+        )?;
+
+        let mut resources = PythonResourcesState::default();
+        resources.index_data(&data).unwrap();
+
+        let sections = &resources.backing_blob_sections[0];
+        assert!(!sections.is_decompressed(ResourceField::InMemorySource));
+
+        let resource = resources.resources.get("module2").unwrap();
+        assert!(resource.is_module);
+        assert_eq!(
+            resource.in_memory_source.as_deref(),
+            Some(source.as_slice())
+        );
+        assert!(sections.is_decompressed(ResourceField::InMemorySource));
 
         Ok(())
     }
@@ -1635,7 +2180,7 @@ mod tests {
         let resolved = config.clone().resolve()?;
         let resources = PythonResourcesState::try_from(&resolved)?;
 
-        assert!(resources.resources.contains_key("foo".into()));
+        assert!(resources.resources.get("foo").is_some());
 
         // Now let's try with relative paths.
         let relative_path =
@@ -1647,7 +2192,58 @@ mod tests {
 
         let resolved = config.resolve()?;
         let resources = PythonResourcesState::try_from(&resolved)?;
-        assert!(resources.resources.contains_key("foo".into()));
+        assert!(resources.resources.get("foo").is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_packed_resources_verification() -> Result<()> {
+        let mut good = Resource {
+            name: "good".into(),
+            is_module: true,
+            in_memory_source: Some(vec![42].into()),
+            ..Default::default()
+        };
+        good.content_sha256 = Some(
+            python_packed_resources::integrity::resource_content_sha256(&good)
+                .to_vec()
+                .into(),
+        );
+        let mut bad = Resource {
+            name: "bad".into(),
+            is_module: true,
+            in_memory_source: Some(vec![42].into()),
+            ..Default::default()
+        };
+        bad.content_sha256 = Some(vec![0; 32].into());
+
+        let mut state = PythonResourcesState::default();
+        state.add_resource(good).unwrap();
+        state.add_resource(bad).unwrap();
+        let data = state.serialize_resources(true, true)?;
+
+        let mut config = OxidizedPythonInterpreterConfig::default();
+        config.packed_resources_verification = PackedResourcesVerification::Import;
+        let resolved = config.clone().resolve()?;
+
+        let mut resources = PythonResourcesState::try_from(&resolved)?;
+        resources.index_data(&data).unwrap();
+        assert!(resources
+            .resolve_importable_module("good", OptimizeLevel::Zero)
+            .is_some());
+        assert!(resources
+            .resolve_importable_module("bad", OptimizeLevel::Zero)
+            .is_none());
+
+        config.packed_resources_verification = PackedResourcesVerification::Startup;
+        let resolved = config.resolve()?;
+
+        let mut resources = PythonResourcesState::try_from(&resolved)?;
+        assert_eq!(
+            resources.index_data(&data),
+            Err("resource content digest mismatch")
+        );
 
         Ok(())
     }
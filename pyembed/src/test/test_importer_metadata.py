@@ -18,6 +18,7 @@ import unittest
 
 from oxidized_importer import (
     OxidizedDistribution,
+    OxidizedDistributionFile,
     OxidizedFinder,
     OxidizedResourceCollector,
     find_resources_in_path,
@@ -237,14 +238,121 @@ class TestImporterMetadata(unittest.TestCase):
         self.assertIsInstance(requires, list)
         self.assertEqual(requires, ["foo"])
 
-    def test_distribution_locate_file(self):
+    def _write_record(self):
+        self._write_metadata()
+
+        package_path = self.td / "my_package"
+        package_path.mkdir()
+
+        with (package_path / "__init__.py").open("wb") as fh:
+            fh.write(b"# init\n")
+        with (package_path / "data.txt").open("wb") as fh:
+            fh.write(b"my data")
+
+        record_path = self.td / "my_package-1.0.dist-info" / "RECORD"
+
+        with record_path.open("w", encoding="utf-8") as fh:
+            fh.write("my_package/__init__.py,sha256=deadbeef,7\n")
+            fh.write("my_package/data.txt,,\n")
+            fh.write("my_package-1.0.dist-info/METADATA,,\n")
+            fh.write("my_package-1.0.dist-info/RECORD,,\n")
+
+    def test_files_missing(self):
         self._write_metadata()
         f = self._finder_from_td()
 
         dist = list(f.find_distributions())[0]
+        self.assertIsNone(dist.files)
 
-        with self.assertRaises(AttributeError):
-            dist.locate_file("METADATA")
+    def test_files_record(self):
+        self._write_record()
+        f = self._finder_from_td()
+
+        dist = list(f.find_distributions())[0]
+
+        files = dist.files
+        self.assertIsInstance(files, list)
+        self.assertEqual(len(files), 4)
+
+        for p in files:
+            self.assertIsInstance(p, importlib.metadata.PackagePath)
+            self.assertIs(p.dist, dist)
+
+        self.assertEqual(
+            [str(p) for p in files],
+            [
+                "my_package/__init__.py",
+                "my_package/data.txt",
+                "my_package-1.0.dist-info/METADATA",
+                "my_package-1.0.dist-info/RECORD",
+            ],
+        )
+
+        self.assertIsInstance(files[0].hash, importlib.metadata.FileHash)
+        self.assertEqual(files[0].hash.mode, "sha256")
+        self.assertEqual(files[0].hash.value, "deadbeef")
+        self.assertEqual(files[0].size, 7)
+        self.assertIsNone(files[1].hash)
+        self.assertIsNone(files[1].size)
+
+        self.assertEqual(files[0].read_text(), "# init\n")
+        self.assertEqual(files[1].read_text(), "my data")
+        self.assertEqual(files[1].read_binary(), b"my data")
+        self.assertEqual(files[2].read_text(), "Name: my_package\nVersion: 1.0\n")
+
+    def test_files_egg_info(self):
+        egg_info_path = self.td / "my_package-1.0.egg-info"
+        egg_info_path.mkdir()
+
+        with (egg_info_path / "PKG-INFO").open("w", encoding="utf-8") as fh:
+            fh.write("Name: my_package\n")
+            fh.write("Version: 1.0\n")
+        with (egg_info_path / "SOURCES.txt").open("w", encoding="utf-8") as fh:
+            fh.write("my_package-1.0.egg-info/PKG-INFO\n")
+            fh.write('my_package/"quoted",name.txt\n')
+
+        f = self._finder_from_td()
+
+        dist = list(f.find_distributions())[0]
+
+        files = dist.files
+        self.assertEqual(
+            [str(p) for p in files],
+            ["my_package-1.0.egg-info/PKG-INFO", 'my_package/"quoted",name.txt'],
+        )
+        self.assertIsNone(files[0].hash)
+        self.assertEqual(files[0].read_text(), "Name: my_package\nVersion: 1.0\n")
+
+    def test_distribution_locate_file(self):
+        self._write_record()
+        f = self._finder_from_td()
+
+        dist = list(f.find_distributions())[0]
+
+        p = dist.locate_file("my_package/data.txt")
+        self.assertIsInstance(p, OxidizedDistributionFile)
+        self.assertEqual(str(p), "my_package/data.txt")
+        self.assertEqual(p.name, "data.txt")
+        self.assertTrue(p.exists())
+        self.assertEqual(p.read_bytes(), b"my data")
+        self.assertEqual(p.read_text(), "my data")
+
+        with p.open("rb") as fh:
+            self.assertEqual(fh.read(), b"my data")
+
+        with p.open(encoding="utf-8") as fh:
+            self.assertEqual(fh.read(), "my data")
+
+        p = dist.locate_file(
+            pathlib.PurePosixPath("my_package-1.0.dist-info/METADATA")
+        )
+        self.assertTrue(p.exists())
+
+        p = dist.locate_file("missing.txt")
+        self.assertFalse(p.exists())
+
+        with self.assertRaises(FileNotFoundError):
+            p.read_bytes()
 
     def test_distribution_from_name(self):
         self._write_metadata()
@@ -18,6 +18,7 @@ class TestImporterModule(unittest.TestCase):
                 "pkg_resources_find_distributions",
                 "register_pkg_resources",
                 "OxidizedDistribution",
+                "OxidizedDistributionFile",
                 "OxidizedFinder",
                 "OxidizedPathEntryFinder",
                 "OxidizedPkgResourcesProvider",
@@ -419,6 +419,76 @@ class TestImporterPkgResources(unittest.TestCase):
         with self.assertRaises(NotImplementedError):
             pkg_resources.resource_filename("package0", "file0")
 
+    def test_resource_filename_relative_path(self):
+        package_path = self.td / "lib" / "package0"
+        (package_path / "subdir").mkdir(parents=True)
+
+        with (package_path / "file0").open("wb") as fh:
+            fh.write(b"foo")
+        with (package_path / "subdir" / "file1").open("wb") as fh:
+            fh.write(b"bar")
+
+        f = OxidizedFinder(relative_path_origin=self.td)
+
+        r = OxidizedResource()
+        r.is_module = True
+        r.is_package = True
+        r.name = "package0"
+        r.in_memory_source = b"pass"
+        r.relative_path_package_resources = {
+            "file0": pathlib.Path("lib") / "package0" / "file0",
+            "subdir/file1": pathlib.Path("lib") / "package0" / "subdir" / "file1",
+        }
+        f.add_resource(r)
+
+        sys.meta_path.insert(0, f)
+
+        self.assertEqual(
+            pathlib.Path(pkg_resources.resource_filename("package0", "file0")),
+            package_path / "file0",
+        )
+        self.assertEqual(
+            pathlib.Path(pkg_resources.resource_filename("package0", "subdir")),
+            package_path / "subdir",
+        )
+
+        with self.assertRaises(NotImplementedError):
+            pkg_resources.resource_filename("package0", "missing")
+
+    def test_resource_filename_extract_in_memory(self):
+        f = OxidizedFinder(pkg_resources_extract_in_memory_resources=True)
+
+        r = OxidizedResource()
+        r.is_module = True
+        r.is_package = True
+        r.name = "package0"
+        r.in_memory_source = b"pass"
+        r.in_memory_package_resources = {
+            "file0": b"foo",
+            "subdir/file1": b"bar",
+            "subdir/grandchild/file2": b"baz",
+        }
+        f.add_resource(r)
+
+        sys.meta_path.insert(0, f)
+
+        path = pkg_resources.resource_filename("package0", "file0")
+        self.assertIsInstance(path, str)
+
+        with open(path, "rb") as fh:
+            self.assertEqual(fh.read(), b"foo")
+
+        # Subsequent requests reuse the extracted file.
+        self.assertEqual(pkg_resources.resource_filename("package0", "file0"), path)
+
+        subdir = pathlib.Path(pkg_resources.resource_filename("package0", "subdir"))
+        self.assertEqual(subdir.parent, pathlib.Path(path).parent)
+        self.assertEqual(sorted(os.listdir(subdir)), ["file1", "grandchild"])
+        self.assertEqual((subdir / "grandchild" / "file2").read_bytes(), b"baz")
+
+        with self.assertRaises(NotImplementedError):
+            pkg_resources.resource_filename("package0", "missing")
+
 
 if __name__ == "__main__":
     unittest.main()
@@ -125,6 +125,123 @@ class TestImporterResourceReading(unittest.TestCase):
         self.assertEqual(r.open_resource("child0/a.txt").getvalue(), b"a")
         self.assertEqual(r.open_resource("child1/b.txt").getvalue(), b"b")
 
+    def test_files_top_level(self):
+        p = self._make_package("my_package")
+
+        with (p / "resource.txt").open("wb") as fh:
+            fh.write(b"my resource")
+
+        f = self._finder_from_td()
+        r = f.get_resource_reader("my_package")
+
+        root = r.files()
+        self.assertEqual(root.name, "my_package")
+        self.assertTrue(root.is_dir())
+        self.assertFalse(root.is_file())
+
+        with self.assertRaises(IsADirectoryError):
+            root.read_bytes()
+
+        children = list(root.iterdir())
+        self.assertEqual([c.name for c in children], ["resource.txt"])
+
+        t = root.joinpath("resource.txt")
+        self.assertEqual(t.name, "resource.txt")
+        self.assertTrue(t.is_file())
+        self.assertFalse(t.is_dir())
+        self.assertEqual(t.read_bytes(), b"my resource")
+        self.assertEqual(t.read_text(), "my resource")
+        self.assertEqual(t.read_text(encoding="ascii"), "my resource")
+
+        with self.assertRaises(NotADirectoryError):
+            list(t.iterdir())
+
+        with t.open("rb") as fh:
+            self.assertEqual(fh.read(), b"my resource")
+
+        with t.open() as fh:
+            self.assertEqual(fh.read(), "my resource")
+
+        with t.open("r", encoding="utf-8") as fh:
+            self.assertEqual(fh.read(), "my resource")
+
+        with self.assertRaises(ValueError):
+            t.open("w")
+
+        missing = root / "missing"
+        self.assertFalse(missing.is_file())
+        self.assertFalse(missing.is_dir())
+
+        with self.assertRaises(FileNotFoundError):
+            missing.read_bytes()
+
+        with self.assertRaises(FileNotFoundError):
+            missing.open("rb")
+
+        with self.assertRaises(FileNotFoundError):
+            list(missing.iterdir())
+
+    def test_files_child_directory(self):
+        p = self._make_package("my_package")
+
+        child0_path = p / "child0"
+        grandchild_path = child0_path / "grandchild"
+
+        child0_path.mkdir()
+        grandchild_path.mkdir()
+
+        with (child0_path / "a.txt").open("wb") as fh:
+            fh.write(b"a")
+        with (grandchild_path / "b.txt").open("wb") as fh:
+            fh.write(b"b")
+
+        f = self._finder_from_td()
+        root = f.get_resource_reader("my_package").files()
+
+        self.assertEqual([c.name for c in root.iterdir()], ["child0"])
+
+        child0 = root / "child0"
+        self.assertTrue(child0.is_dir())
+        self.assertFalse(child0.is_file())
+        self.assertEqual([c.name for c in child0.iterdir()], ["grandchild", "a.txt"])
+
+        self.assertEqual((child0 / "a.txt").read_bytes(), b"a")
+        self.assertEqual(
+            root.joinpath("child0", "grandchild", "b.txt").read_text(), "b"
+        )
+        self.assertEqual(root.joinpath("child0/grandchild/b.txt").read_bytes(), b"b")
+
+        grandchild = root / "child0" / "grandchild"
+        self.assertEqual(
+            [c.read_bytes() for c in grandchild.iterdir() if c.is_file()], [b"b"]
+        )
+
+        with self.assertRaises(IsADirectoryError):
+            child0.read_bytes()
+
+        with self.assertRaises(ValueError):
+            child0 / ".." / "child0" / "a.txt"
+
+        with self.assertRaises(ValueError):
+            root.joinpath("child0/../../other.txt")
+
+    def test_files_subpackage(self):
+        self._make_package("my_package.child")
+        p = self.td / "my_package"
+
+        with (p / "child" / "resource.txt").open("wb") as fh:
+            fh.write(b"child resource")
+
+        f = self._finder_from_td()
+        root = f.get_resource_reader("my_package").files()
+
+        children = list(root.iterdir())
+        self.assertEqual([c.name for c in children], ["child"])
+        self.assertTrue(children[0].is_dir())
+
+        t = root / "child" / "resource.txt"
+        self.assertEqual(t.read_bytes(), b"child resource")
+
 
 if __name__ == "__main__":
     unittest.main()
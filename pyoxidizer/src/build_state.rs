@@ -0,0 +1,226 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Record the inputs of built artifacts to determine when they are stale. */
+
+use {
+    anyhow::{Context, Result},
+    serde::{Deserialize, Serialize},
+    sha2::{Digest, Sha256},
+    std::{
+        collections::BTreeMap,
+        io::Read,
+        path::{Path, PathBuf},
+        time::UNIX_EPOCH,
+    },
+};
+
+/// Filename of the build state file written to an artifacts directory.
+pub const BUILD_STATE_FILENAME: &str = "build_state.json";
+
+/// Describes the state of an input file when artifacts were built.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct InputState {
+    /// Size of the file in bytes.
+    pub size: u64,
+
+    /// Modified time of the file, in nanoseconds since the UNIX epoch.
+    pub modified_ns: Option<u64>,
+
+    /// Hex encoded SHA-256 digest of the file's content.
+    pub sha256: String,
+}
+
+impl InputState {
+    /// Resolve the state of a file.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let (size, modified_ns) = file_metadata(path)?;
+
+        Ok(Self {
+            size,
+            modified_ns,
+            sha256: sha256_path(path)?,
+        })
+    }
+}
+
+/// Records the parameters and inputs artifacts were built from.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct BuildState {
+    /// Parameters influencing the build, such as the target triple.
+    pub parameters: BTreeMap<String, String>,
+
+    /// Input files and their state at build time.
+    pub inputs: BTreeMap<PathBuf, InputState>,
+}
+
+impl BuildState {
+    /// Construct an instance with the given build parameters.
+    pub fn new(parameters: BTreeMap<String, String>) -> Self {
+        Self {
+            parameters,
+            inputs: BTreeMap::new(),
+        }
+    }
+
+    /// Read an instance from a JSON file.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+
+        serde_json::from_slice(&data).with_context(|| format!("parsing {}", path.display()))
+    }
+
+    /// Write this instance to a JSON file.
+    pub fn write_path(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)
+            .with_context(|| format!("writing {}", path.display()))
+    }
+
+    /// Record the current state of an input file.
+    pub fn add_input(&mut self, path: &Path) -> Result<()> {
+        self.inputs
+            .insert(path.to_path_buf(), InputState::from_path(path)?);
+
+        Ok(())
+    }
+
+    /// Determine why artifacts built from this state would be stale.
+    ///
+    /// `parameters` are the parameters of the pending build. Returns a
+    /// description of every difference. An empty result means artifacts are
+    /// current.
+    ///
+    /// Input files are only hashed when their size or modified time changed, so
+    /// touching a file does not make artifacts stale.
+    pub fn stale_reasons(&self, parameters: &BTreeMap<String, String>) -> Vec<String> {
+        let mut reasons = vec![];
+
+        for (key, value) in parameters {
+            match self.parameters.get(key) {
+                Some(old) if old == value => {}
+                Some(old) => reasons.push(format!(
+                    "build parameter {} changed from {} to {}",
+                    key, old, value
+                )),
+                None => reasons.push(format!("build parameter {} is new", key)),
+            }
+        }
+
+        for key in self.parameters.keys() {
+            if !parameters.contains_key(key) {
+                reasons.push(format!("build parameter {} was removed", key));
+            }
+        }
+
+        for (path, state) in &self.inputs {
+            let (size, modified_ns) = match file_metadata(path) {
+                Ok(metadata) => metadata,
+                Err(_) => {
+                    reasons.push(format!("{} no longer exists", path.display()));
+                    continue;
+                }
+            };
+
+            if size == state.size && modified_ns.is_some() && modified_ns == state.modified_ns {
+                continue;
+            }
+
+            match sha256_path(path) {
+                Ok(digest) if digest == state.sha256 => {}
+                Ok(_) => reasons.push(format!("content of {} changed", path.display())),
+                Err(e) => reasons.push(format!("error reading {}: {}", path.display(), e)),
+            }
+        }
+
+        reasons
+    }
+}
+
+fn file_metadata(path: &Path) -> Result<(u64, Option<u64>)> {
+    let metadata = path
+        .metadata()
+        .with_context(|| format!("resolving metadata of {}", path.display()))?;
+
+    let modified_ns = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos() as u64);
+
+    Ok((metadata.len(), modified_ns))
+}
+
+fn sha256_path(path: &Path) -> Result<String> {
+    let mut fh =
+        std::fs::File::open(path).with_context(|| format!("opening {}", path.display()))?;
+
+    let mut hasher = Sha256::new();
+    let mut buffer = [0; 32768];
+
+    loop {
+        let count = fh
+            .read(&mut buffer)
+            .with_context(|| format!("reading {}", path.display()))?;
+        if count == 0 {
+            break;
+        }
+        hasher.update(&buffer[..count]);
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parameters(release: bool) -> BTreeMap<String, String> {
+        let mut parameters = BTreeMap::new();
+        parameters.insert("release".to_string(), release.to_string());
+
+        parameters
+    }
+
+    #[test]
+    fn stale_reasons() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let input = temp_dir.path().join("input.txt");
+        std::fs::write(&input, b"foo")?;
+
+        let mut state = BuildState::new(parameters(false));
+        state.add_input(&input)?;
+
+        let state_path = temp_dir.path().join(BUILD_STATE_FILENAME);
+        state.write_path(&state_path)?;
+        let state = BuildState::from_path(&state_path)?;
+
+        assert!(state.stale_reasons(&parameters(false)).is_empty());
+        assert_eq!(
+            state.stale_reasons(&parameters(true)),
+            vec!["build parameter release changed from false to true".to_string()]
+        );
+        assert_eq!(
+            state.stale_reasons(&BTreeMap::new()),
+            vec!["build parameter release was removed".to_string()]
+        );
+
+        // Rewriting identical content doesn't make the state stale.
+        std::fs::write(&input, b"foo")?;
+        assert!(state.stale_reasons(&parameters(false)).is_empty());
+
+        std::fs::write(&input, b"foobar")?;
+        assert_eq!(
+            state.stale_reasons(&parameters(false)),
+            vec![format!("content of {} changed", input.display())]
+        );
+
+        std::fs::remove_file(&input)?;
+        assert_eq!(
+            state.stale_reasons(&parameters(false)),
+            vec![format!("{} no longer exists", input.display())]
+        );
+
+        Ok(())
+    }
+}
@@ -43,6 +43,18 @@ This command will invoke Rust's build system tool (Cargo) to build
 the project.
 ";
 
+const DIFF_ABOUT: &str = "\
+Compare the Python resources embedded in two built artifacts.
+
+The OLD and NEW arguments are paths to packed resources data files or to
+executables embedding packed resources data.
+
+This command reports resources that were added, removed, or changed, and
+how the number of bytes of module source, bytecode, resource files,
+extension modules, and shared libraries changed, including per top-level
+package.
+";
+
 const INIT_RUST_PROJECT_ABOUT: &str = "\
 Create a new Rust project embedding Python.
 
@@ -62,6 +74,21 @@ they were created with.
 On success, instructions on potential next steps are printed.
 ";
 
+const INSPECT_RESOURCES_ABOUT: &str = "\
+Inspect the Python resources embedded in a built artifact.
+
+The PATH argument is a path to a packed resources data file or to an
+executable (ELF, PE, or Mach-O) embedding packed resources data.
+
+This command prints every resource along with its fields. Fields holding
+data are described by their size.
+
+If --extract is given, module source, module bytecode, package resources,
+distribution resources, and embedded files are written to that directory
+instead. Bytecode is written as a marshalled code object without a .pyc
+header.
+";
+
 const RUN_BUILD_SCRIPT_ABOUT: &str = "\
 Runs a crate build script to generate Python artifacts.
 
@@ -71,6 +98,11 @@ These files are typically generated when the crate's build script runs.
 
 This command executes the functionality to derive various artifacts and
 emits special lines that tell the Rust build system how to consume them.
+
+Artifacts are only rebuilt when the content of the config file, Python
+resources, the Python distribution, or the pyoxidizer executable changes,
+or when build settings change. Use --explain to see why artifacts were
+or weren't rebuilt.
 ";
 
 const RESOURCES_SCAN_ABOUT: &str = "\
@@ -92,6 +124,22 @@ conversion is critical for properly packaging Python applications and
 bugs can result in incorrect install layouts, missing resources, etc.
 ";
 
+const WATCH_ABOUT: &str = "\
+Build a target and rebuild it whenever its inputs change.
+
+Every file read when evaluating the config file and building the target
+is watched. This includes the config file, files it loads, and paths
+read by `glob()`, `FileContent()`, `FileManifest.add_path()`, and
+`PythonExecutable.read_package_root()`. When any of them change, the
+config file is evaluated again and the target is rebuilt.
+
+Resolved Python distributions are reused between builds and unchanged
+Python modules are not compiled to bytecode again.
+
+With --run, the built target is run after every build. Changes made while
+it runs trigger a rebuild once it exits.
+";
+
 const VAR_HELP: &str = "\
 Defines a single string key to set in the VARS global dict.
 
@@ -204,6 +252,38 @@ pub fn run_cli() -> Result<()> {
             SubCommand::with_name("analyze")
                 .about("Analyze a built binary")
                 .setting(AppSettings::ArgRequiredElseHelp)
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .help("Print the analysis report as JSON"),
+                )
+                .arg(
+                    Arg::with_name("max_glibc_version")
+                        .long("max-glibc-version")
+                        .takes_value(true)
+                        .value_name("VERSION")
+                        .help("Fail if the binary requires a newer glibc version"),
+                )
+                .arg(
+                    Arg::with_name("allowed_libraries")
+                        .long("allow-library")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .value_name("NAME")
+                        .help(
+                            "Fail if the binary depends on a non-system shared library other than this one",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("target_distros")
+                        .long("target-distro")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .value_name("DISTRO")
+                        .help("Fail if the binary is not compatible with a Linux distribution version (e.g. 'RHEL 7')"),
+                )
                 .arg(Arg::with_name("path").help("Path to executable to analyze")),
         )
         .subcommand(add_env_args(
@@ -238,7 +318,36 @@ pub fn run_cli() -> Result<()> {
                 ),
         ))
         .subcommand(
-            SubCommand::with_name("cache-clear").about("Clear PyOxidizer's user-specific cache"),
+            SubCommand::with_name("cache-clear")
+                .about("Clear PyOxidizer's user-specific cache")
+                .arg(
+                    Arg::with_name("bytecode_only")
+                        .long("bytecode-only")
+                        .help("Only clear the cache of compiled Python bytecode"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("diff")
+                .about("Compare the Python resources embedded in two built artifacts")
+                .long_about(DIFF_ABOUT)
+                .setting(AppSettings::ArgRequiredElseHelp)
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .help("Emit differences as JSON"),
+                )
+                .arg(
+                    Arg::with_name("old")
+                        .required(true)
+                        .value_name("OLD")
+                        .help("Path to packed resources data or executable to compare from"),
+                )
+                .arg(
+                    Arg::with_name("new")
+                        .required(true)
+                        .value_name("NEW")
+                        .help("Path to packed resources data or executable to compare to"),
+                ),
         )
         .subcommand(
             SubCommand::with_name("find-resources")
@@ -315,6 +424,34 @@ pub fn run_cli() -> Result<()> {
                         .help("Path of project directory to create"),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("inspect-resources")
+                .setting(AppSettings::ArgRequiredElseHelp)
+                .about("Inspect the Python resources embedded in a built artifact")
+                .long_about(INSPECT_RESOURCES_ABOUT)
+                .arg(
+                    Arg::with_name("extract")
+                        .long("extract")
+                        .takes_value(true)
+                        .value_name("DIR")
+                        .help("Directory to extract resource data to"),
+                )
+                .arg(
+                    Arg::with_name("name")
+                        .long("name")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .value_name("NAME")
+                        .help("Only operate on resources with this name"),
+                )
+                .arg(
+                    Arg::with_name("path")
+                        .required(true)
+                        .value_name("PATH")
+                        .help("Path to packed resources data or executable to inspect"),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("list-targets")
                 .setting(AppSettings::ArgRequiredElseHelp)
@@ -382,6 +519,11 @@ pub fn run_cli() -> Result<()> {
                         .long("target")
                         .takes_value(true)
                         .help("The config file target to resolve"),
+                )
+                .arg(
+                    Arg::with_name("explain")
+                        .long("explain")
+                        .help("Print why artifacts are or are not rebuilt"),
                 ),
         ))
         .subcommand(add_env_args(
@@ -414,6 +556,40 @@ pub fn run_cli() -> Result<()> {
                 )
                 .arg(Arg::with_name("extra").multiple(true)),
         ))
+        .subcommand(add_env_args(
+            SubCommand::with_name("watch")
+                .about("Rebuild a target whenever its inputs change")
+                .long_about(WATCH_ABOUT)
+                .arg(
+                    Arg::with_name("target_triple")
+                        .long("target-triple")
+                        .takes_value(true)
+                        .help("Rust target triple to build for"),
+                )
+                .arg(
+                    Arg::with_name("release")
+                        .long("release")
+                        .help("Build a release binary"),
+                )
+                .arg(
+                    Arg::with_name("path")
+                        .long("path")
+                        .default_value(".")
+                        .value_name("PATH")
+                        .help("Directory containing project to build"),
+                )
+                .arg(
+                    Arg::with_name("target")
+                        .long("target")
+                        .takes_value(true)
+                        .help("Build target to watch"),
+                )
+                .arg(
+                    Arg::with_name("run")
+                        .long("run")
+                        .help("Run the target after every build"),
+                ),
+        ))
         .get_matches();
 
     let verbose = matches.is_present("verbose");
@@ -440,9 +616,38 @@ pub fn run_cli() -> Result<()> {
         ("analyze", Some(args)) => {
             let path = args.value_of("path").unwrap();
             let path = PathBuf::from(path);
-            tugger_binary_analysis::analyze_file(path);
+            let report = tugger_binary_analysis::analyze_file(path)?;
+
+            let mut policy = tugger_binary_analysis::PortabilityPolicy::default();
+            policy.set_max_glibc_version(args.value_of("max_glibc_version").map(String::from))?;
+            policy.set_allowed_libraries(
+                args.values_of("allowed_libraries")
+                    .map(|values| values.map(String::from).collect()),
+            );
+            policy.set_target_distros(
+                args.values_of("target_distros")
+                    .unwrap_or_default()
+                    .map(tugger_binary_analysis::TargetDistro::parse)
+                    .collect::<Result<Vec<_>>>()?,
+            );
+
+            if args.is_present("json") {
+                println!("{}", report.to_json()?);
+            } else {
+                print!("{}", report);
+            }
+
+            let violations = policy.evaluate(&report);
+
+            if violations.is_empty() {
+                Ok(())
+            } else {
+                for violation in &violations {
+                    eprintln!("portability policy violation: {}", violation);
+                }
 
-            Ok(())
+                Err(anyhow!("binary violates portability policy"))
+            }
         }
 
         ("build", Some(args)) => {
@@ -466,7 +671,16 @@ pub fn run_cli() -> Result<()> {
             )
         }
 
-        ("cache-clear", Some(_)) => projectmgmt::cache_clear(&env),
+        ("cache-clear", Some(args)) => {
+            projectmgmt::cache_clear(&env, args.is_present("bytecode_only"))
+        }
+
+        ("diff", Some(args)) => {
+            let old = Path::new(args.value_of("old").unwrap());
+            let new = Path::new(args.value_of("new").unwrap());
+
+            projectmgmt::diff(old, new, args.is_present("json"))
+        }
 
         ("find-resources", Some(args)) => {
             let path = args.value_of("path").map(|value| Path::new(value));
@@ -519,6 +733,17 @@ pub fn run_cli() -> Result<()> {
             projectmgmt::init_rust_project(&env, &logger_context.logger, &project_path)
         }
 
+        ("inspect-resources", Some(args)) => {
+            let path = Path::new(args.value_of("path").unwrap());
+            let extract = args.value_of("extract").map(Path::new);
+            let names = args
+                .values_of("name")
+                .map(|values| values.collect::<Vec<_>>())
+                .unwrap_or_default();
+
+            projectmgmt::inspect_resources(path, extract, &names)
+        }
+
         ("python-distribution-extract", Some(args)) => {
             let download_default = args.is_present("download-default");
             let archive_path = args.value_of("archive-path");
@@ -558,6 +783,7 @@ pub fn run_cli() -> Result<()> {
                 build_script,
                 target,
                 starlark_vars,
+                args.is_present("explain"),
             )
         }
 
@@ -582,6 +808,27 @@ pub fn run_cli() -> Result<()> {
             )
         }
 
+        ("watch", Some(args)) => {
+            let starlark_vars = starlark_vars(args)?;
+            let target_triple = args.value_of("target_triple");
+            let release = args.is_present("release");
+            let path = args.value_of("path").unwrap();
+            let target = args.value_of("target");
+            let run = args.is_present("run");
+
+            projectmgmt::watch(
+                &env,
+                &logger_context.logger,
+                Path::new(path),
+                target_triple,
+                release,
+                target,
+                run,
+                starlark_vars,
+                verbose,
+            )
+        }
+
         _ => Err(anyhow!("invalid sub-command")),
     }
 }
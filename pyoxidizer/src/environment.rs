@@ -8,6 +8,7 @@ use {
     crate::{project_layout::PyembedLocation, py_packaging::distribution::AppleSdkInfo},
     anyhow::{anyhow, Context, Result},
     once_cell::sync::Lazy,
+    python_packaging::bytecode::BytecodeCache,
     semver::Version,
     slog::{info, warn},
     std::{
@@ -26,6 +27,9 @@ const PYOXIDIZER_CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
 /// Version string of pyembed crate from its Cargo.toml.
 const PYEMBED_CRATE_VERSION: &str = "0.17.0-pre";
 
+/// Default maximum size in bytes of the Python bytecode cache.
+const DEFAULT_BYTECODE_CACHE_MAX_SIZE: u64 = 512 * 1024 * 1024;
+
 /// URL of Git repository we were built from.
 const GIT_REPO_URL: &str = env!("GIT_REPO_URL");
 
@@ -235,6 +239,11 @@ pub struct Environment {
     /// Directory to use for caching things.
     cache_dir: PathBuf,
 
+    /// Maximum size in bytes of the Python bytecode cache.
+    ///
+    /// 0 disables the cache.
+    bytecode_cache_max_size: u64,
+
     /// Whether we should use a Rust installation we manage ourselves.
     managed_rust: bool,
 
@@ -257,11 +266,24 @@ impl Environment {
             dirs::home_dir().ok_or_else(|| anyhow!("could not resolve home dir as part of resolving PyOxidizer cache directory"))?.join(".pyoxidizer").join("cache")
         };
 
+        let bytecode_cache_max_size =
+            if let Ok(value) = std::env::var("PYOXIDIZER_BYTECODE_CACHE_MAX_SIZE") {
+                value.parse::<u64>().with_context(|| {
+                    format!(
+                        "parsing PYOXIDIZER_BYTECODE_CACHE_MAX_SIZE value {} as an integer",
+                        value
+                    )
+                })?
+            } else {
+                DEFAULT_BYTECODE_CACHE_MAX_SIZE
+            };
+
         let managed_rust = std::env::var("PYOXIDIZER_SYSTEM_RUST").is_err();
 
         Ok(Self {
             pyoxidizer_source,
             cache_dir,
+            bytecode_cache_max_size,
             managed_rust,
             rust_environment: Arc::new(RwLock::new(None)),
         })
@@ -284,6 +306,25 @@ impl Environment {
         self.cache_dir.join("rust")
     }
 
+    /// Directory to hold compiled Python bytecode.
+    pub fn bytecode_cache_dir(&self) -> PathBuf {
+        self.cache_dir.join("bytecode")
+    }
+
+    /// Obtain the cache of compiled Python bytecode.
+    ///
+    /// Returns `None` if the cache is disabled.
+    pub fn bytecode_cache(&self) -> Option<BytecodeCache> {
+        if self.bytecode_cache_max_size == 0 {
+            None
+        } else {
+            Some(BytecodeCache::new(
+                self.bytecode_cache_dir(),
+                Some(self.bytecode_cache_max_size),
+            ))
+        }
+    }
+
     /// Do not use a managed Rust.
     ///
     /// When called, [self.ensure_rust_toolchain()] will attempt to locate a
@@ -11,6 +11,7 @@ distribution and embedding it in a larger binary, oftentimes an executable.
 This library exposes that functionality to other tools.
 */
 
+pub mod build_state;
 pub mod environment;
 pub mod logging;
 pub mod project_building;
@@ -18,7 +19,9 @@ pub mod project_layout;
 pub mod projectmgmt;
 pub mod py_packaging;
 pub mod python_distributions;
+pub mod resources_diff;
 pub mod starlark;
+pub mod watch;
 
 #[cfg(test)]
 mod testutil;
@@ -27,6 +27,7 @@ is implemented in C. Rust is simply a tool to achieve an end goal, albeit
 a rather effective and powerful tool.
 */
 
+mod build_state;
 mod cli;
 mod environment;
 mod logging;
@@ -35,9 +36,11 @@ mod project_layout;
 mod projectmgmt;
 mod py_packaging;
 mod python_distributions;
+mod resources_diff;
 pub mod starlark;
 #[cfg(test)]
 mod testutil;
+mod watch;
 
 fn main() {
     std::process::exit(match cli::run_cli() {
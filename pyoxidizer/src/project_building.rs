@@ -4,6 +4,7 @@
 
 use {
     crate::{
+        build_state::{BuildState, BUILD_STATE_FILENAME},
         environment::{canonicalize_path, Environment, RustEnvironment},
         project_layout::initialize_project,
         py_packaging::{
@@ -14,15 +15,18 @@ use {
     },
     anyhow::{anyhow, Context, Result},
     duct::cmd,
+    python_packaging::resource_collection::CompiledResourcesCollection,
     slog::warn,
     starlark_dialect_build_targets::ResolvedTarget,
     std::{
-        collections::HashMap,
+        collections::{BTreeMap, HashMap},
         convert::TryInto,
         fs::create_dir_all,
         io::{BufRead, BufReader},
         path::{Path, PathBuf},
     },
+    tugger_binary_analysis::{analyze_data, PortabilityPolicy},
+    tugger_file_manifest::FileManifest,
 };
 
 /// Find a pyoxidizer.toml configuration file by walking directory ancestry.
@@ -344,6 +348,12 @@ pub fn build_executable_with_rust_project<'a>(
         features.push("global-allocator-snmalloc");
         features.push("allocator-snmalloc");
     }
+    if exe.requires_packed_resources_signatures() {
+        features.push("packed-resources-signatures");
+    }
+    if exe.requires_config_overlay() {
+        features.push("config-overlay");
+    }
 
     let features = features.join(" ");
 
@@ -389,6 +399,15 @@ pub fn build_executable_with_rust_project<'a>(
         std::fs::read(&exe_path).with_context(|| format!("reading {}", exe_path.display()))?;
     let exe_name = exe_path.file_name().unwrap().to_string_lossy().to_string();
 
+    verify_portability(
+        logger,
+        exe.portability_policy(),
+        &exe_name,
+        &exe_data,
+        &embedded_data.extra_files,
+        &embedded_data.pending_resources,
+    )?;
+
     Ok(BuiltExecutable {
         exe_path: Some(exe_path),
         exe_name,
@@ -397,6 +416,92 @@ pub fn build_executable_with_rust_project<'a>(
     })
 }
 
+/// Verify that built binaries satisfy a portability policy.
+///
+/// The executable, every binary in `extra_files` (extension modules, shared
+/// libraries, etc) and every extension module and shared library loaded from
+/// memory by `pending_resources` are analyzed. Shared libraries distributed in
+/// `extra_files` or loaded from memory are always allowed as dependencies.
+pub fn verify_portability(
+    logger: &slog::Logger,
+    policy: &PortabilityPolicy,
+    exe_name: &str,
+    exe_data: &[u8],
+    extra_files: &FileManifest,
+    pending_resources: &[(CompiledResourcesCollection, PathBuf)],
+) -> Result<()> {
+    if policy.is_empty() {
+        return Ok(());
+    }
+
+    let in_memory_resources = pending_resources
+        .iter()
+        .flat_map(|(collection, _)| collection.resources.values());
+
+    let mut policy = policy.clone();
+    if policy.allowed_libraries().is_some() {
+        for (path, _) in extra_files.iter_entries() {
+            if let Some(name) = path.file_name() {
+                policy.allow_library(name.to_string_lossy());
+            }
+        }
+
+        for resource in in_memory_resources.clone() {
+            if resource.in_memory_shared_library.is_some() {
+                policy.allow_library(&resource.name);
+            }
+        }
+    }
+
+    let mut problems = vec![];
+
+    let report = analyze_data(exe_data).with_context(|| format!("analyzing {}", exe_name))?;
+    for violation in policy.evaluate(&report) {
+        problems.push(format!("{}: {}", exe_name, violation));
+    }
+
+    for (path, entry) in extra_files.iter_entries() {
+        let data = entry
+            .resolve_content()
+            .with_context(|| format!("resolving content of {}", path.display()))?;
+
+        // Files that aren't binaries (Python source, resources, etc) are ignored.
+        let report = match analyze_data(&data) {
+            Ok(report) => report,
+            Err(_) => continue,
+        };
+
+        for violation in policy.evaluate(&report) {
+            problems.push(format!("{}: {}", path.display(), violation));
+        }
+    }
+
+    for resource in in_memory_resources {
+        for data in resource
+            .in_memory_extension_module_shared_library
+            .iter()
+            .chain(resource.in_memory_shared_library.iter())
+        {
+            let report = analyze_data(data)
+                .with_context(|| format!("analyzing in-memory library {}", resource.name))?;
+
+            for violation in policy.evaluate(&report) {
+                problems.push(format!("{} (in-memory): {}", resource.name, violation));
+            }
+        }
+    }
+
+    if problems.is_empty() {
+        warn!(logger, "built binaries satisfy portability policy");
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "built binaries violate portability policy:\n{}",
+            problems.join("\n")
+        ))
+    }
+}
+
 /// Build a Python executable using a temporary Rust project.
 ///
 /// Returns the binary data constituting the built executable.
@@ -472,15 +577,44 @@ pub fn build_pyembed_artifacts(
     target_triple: &str,
     release: bool,
     verbose: bool,
+    explain: bool,
 ) -> Result<()> {
     create_dir_all(artifacts_path)?;
 
     let artifacts_path = canonicalize_path(artifacts_path)?;
 
-    if artifacts_current(logger, config_path, &artifacts_path) {
+    let parameters = pyembed_build_parameters(
+        config_path,
+        resolve_target,
+        &extra_vars,
+        target_triple,
+        release,
+    );
+    let reasons = artifacts_stale_reasons(&artifacts_path, &parameters);
+
+    if reasons.is_empty() {
+        if explain {
+            warn!(logger, "PyOxidizer artifacts are current; not rebuilding");
+        }
+
         return Ok(());
     }
 
+    if explain {
+        for reason in &reasons {
+            warn!(logger, "building artifacts because {}", reason);
+        }
+    } else {
+        warn!(logger, "building artifacts because {}", reasons[0]);
+    }
+
+    // Existing state no longer describes the artifacts once we start replacing them.
+    let state_path = artifacts_path.join(BUILD_STATE_FILENAME);
+    if state_path.exists() {
+        std::fs::remove_file(&state_path)
+            .with_context(|| format!("removing {}", state_path.display()))?;
+    }
+
     let mut context: EvaluationContext = EvaluationContextBuilder::new(
         env,
         logger.clone(),
@@ -523,7 +657,7 @@ pub fn build_pyembed_artifacts(
         // TODO should we normalize paths to pyoxidizer build directory in cargo_metadata.txt
         // with the new artifacts directory?
 
-        return Ok(());
+        return write_artifacts_build_state(&artifacts_path, config_path, parameters);
     }
 
     Err(anyhow!("unable to find generated cargo_metadata.txt; did you specify the correct target to resolve?"))
@@ -546,7 +680,8 @@ pub fn build_pyembed_artifacts(
 ///
 /// Receives a logger for receiving log messages, the path to the Rust
 /// build script invoking us, and an optional named target in the config
-/// file to resolve.
+/// file to resolve. If `explain` is true, every reason artifacts are (or
+/// aren't) rebuilt is logged.
 ///
 /// For this to work as expected, the target resolved in the config file must
 /// return a `PythonEmbeddeResources` starlark type.
@@ -556,6 +691,7 @@ pub fn run_from_build(
     build_script: &str,
     resolve_target: Option<&str>,
     extra_vars: HashMap<String, Option<String>>,
+    explain: bool,
 ) -> Result<()> {
     // Adding our our rerun-if-changed lines will overwrite the default, so
     // we need to emit the build script name explicitly.
@@ -598,6 +734,7 @@ pub fn run_from_build(
         &target,
         profile == "release",
         false,
+        explain,
     )?;
 
     let cargo_metadata = dest_dir.join("cargo_metadata.txt");
@@ -610,99 +747,82 @@ pub fn run_from_build(
     Ok(())
 }
 
-fn dependency_current(
-    logger: &slog::Logger,
-    path: &Path,
-    built_time: std::time::SystemTime,
-) -> bool {
-    match path.metadata() {
-        Ok(md) => match md.modified() {
-            Ok(t) => {
-                if t > built_time {
-                    warn!(
-                        logger,
-                        "building artifacts because {} changed",
-                        path.display()
-                    );
-                    false
-                } else {
-                    true
-                }
-            }
-            Err(_) => {
-                warn!(logger, "error resolving mtime of {}", path.display());
-                false
-            }
-        },
-        Err(_) => {
-            warn!(logger, "error resolving metadata of {}", path.display());
-            false
-        }
-    }
-}
+/// Derive the build state parameters for building pyembed artifacts.
+fn pyembed_build_parameters(
+    config_path: &Path,
+    resolve_target: Option<&str>,
+    extra_vars: &HashMap<String, Option<String>>,
+    target_triple: &str,
+    release: bool,
+) -> BTreeMap<String, String> {
+    let mut parameters = BTreeMap::new();
 
-/// Determines whether PyOxidizer artifacts are current.
-fn artifacts_current(logger: &slog::Logger, config_path: &Path, artifacts_path: &Path) -> bool {
-    let metadata_path = artifacts_path.join("cargo_metadata.txt");
-
-    if !metadata_path.exists() {
-        warn!(logger, "no existing PyOxidizer artifacts found");
-        return false;
-    }
-
-    // We assume the mtime of the metadata file is the built time. If we
-    // encounter any modified times newer than that file, we're not up to date.
-    let built_time = match metadata_path.metadata() {
-        Ok(md) => match md.modified() {
-            Ok(t) => t,
-            Err(_) => {
-                warn!(
-                    logger,
-                    "error determining mtime of {}",
-                    metadata_path.display()
-                );
-                return false;
-            }
-        },
-        Err(_) => {
-            warn!(
-                logger,
-                "error resolving metadata of {}",
-                metadata_path.display()
-            );
-            return false;
-        }
-    };
+    parameters.insert("config_path".to_string(), config_path.display().to_string());
+    parameters.insert(
+        "pyoxidizer_version".to_string(),
+        env!("CARGO_PKG_VERSION").to_string(),
+    );
+    parameters.insert("release".to_string(), release.to_string());
+    parameters.insert(
+        "resolve_target".to_string(),
+        resolve_target.unwrap_or("<default>").to_string(),
+    );
+    parameters.insert("target_triple".to_string(), target_triple.to_string());
 
-    let metadata_data = match std::fs::read_to_string(&metadata_path) {
-        Ok(data) => data,
-        Err(_) => {
-            warn!(logger, "error reading {}", metadata_path.display());
-            return false;
-        }
-    };
+    for (name, value) in extra_vars {
+        parameters.insert(
+            format!("var.{}", name),
+            value.clone().unwrap_or_else(|| "<none>".to_string()),
+        );
+    }
+
+    parameters
+}
 
-    for line in metadata_data.split('\n') {
-        if line.starts_with("cargo:rerun-if-changed=") {
-            let path = PathBuf::from(&line[23..line.len()]);
+/// Determine why PyOxidizer artifacts need to be built.
+///
+/// Returns an empty Vec if existing artifacts are current.
+fn artifacts_stale_reasons(
+    artifacts_path: &Path,
+    parameters: &BTreeMap<String, String>,
+) -> Vec<String> {
+    if !artifacts_path.join("cargo_metadata.txt").exists() {
+        return vec!["no existing PyOxidizer artifacts found".to_string()];
+    }
 
-            if !dependency_current(logger, &path, built_time) {
-                return false;
-            }
-        }
+    let state_path = artifacts_path.join(BUILD_STATE_FILENAME);
+    if !state_path.exists() {
+        return vec![format!("{} does not exist", state_path.display())];
     }
 
-    let current_exe = std::env::current_exe().expect("unable to determine current exe");
-    if !dependency_current(logger, &current_exe, built_time) {
-        return false;
+    match BuildState::from_path(&state_path) {
+        Ok(state) => state.stale_reasons(parameters),
+        Err(e) => vec![format!("{:?}", e)],
     }
+}
 
-    if !dependency_current(logger, config_path, built_time) {
-        return false;
+/// Record the inputs of freshly built PyOxidizer artifacts.
+fn write_artifacts_build_state(
+    artifacts_path: &Path,
+    config_path: &Path,
+    parameters: BTreeMap<String, String>,
+) -> Result<()> {
+    let mut state = BuildState::new(parameters);
+
+    state.add_input(config_path)?;
+    state.add_input(&std::env::current_exe().context("resolving current executable")?)?;
+
+    let inputs_path = artifacts_path.join("build_inputs.txt");
+    if inputs_path.exists() {
+        let inputs = std::fs::read_to_string(&inputs_path)
+            .with_context(|| format!("reading {}", inputs_path.display()))?;
+
+        for line in inputs.lines().filter(|line| !line.is_empty()) {
+            state.add_input(Path::new(line))?;
+        }
     }
 
-    // TODO detect config file change.
-    true
+    state.write_path(&artifacts_path.join(BUILD_STATE_FILENAME))
 }
 
 #[cfg(test)]
@@ -715,11 +835,112 @@ mod tests {
             testutil::*,
         },
         python_packaging::interpreter::MemoryAllocatorBackend,
+        python_packed_resources::data::Resource,
+        std::borrow::Cow,
+        tugger_file_manifest::FileEntry,
     };
 
     #[cfg(target_env = "msvc")]
     use crate::py_packaging::distribution::DistributionFlavor;
 
+    #[test]
+    fn test_artifacts_build_state() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let artifacts_path = temp_dir.path().join("artifacts");
+        create_dir_all(&artifacts_path)?;
+        let config_path = temp_dir.path().join("pyoxidizer.bzl");
+        let resource_path = temp_dir.path().join("foo.py");
+
+        std::fs::write(&config_path, "# config")?;
+        std::fs::write(&resource_path, "print('hello')")?;
+
+        let parameters =
+            pyembed_build_parameters(&config_path, None, &HashMap::new(), "x86_64", false);
+
+        assert_eq!(
+            artifacts_stale_reasons(&artifacts_path, &parameters),
+            vec!["no existing PyOxidizer artifacts found".to_string()]
+        );
+
+        std::fs::write(artifacts_path.join("cargo_metadata.txt"), "")?;
+        std::fs::write(
+            artifacts_path.join("build_inputs.txt"),
+            resource_path.display().to_string(),
+        )?;
+        write_artifacts_build_state(&artifacts_path, &config_path, parameters.clone())?;
+
+        assert!(artifacts_stale_reasons(&artifacts_path, &parameters).is_empty());
+
+        let release_parameters =
+            pyembed_build_parameters(&config_path, None, &HashMap::new(), "x86_64", true);
+        assert_eq!(
+            artifacts_stale_reasons(&artifacts_path, &release_parameters),
+            vec!["build parameter release changed from false to true".to_string()]
+        );
+
+        std::fs::write(&resource_path, "print('hello world')")?;
+        assert_eq!(
+            artifacts_stale_reasons(&artifacts_path, &parameters),
+            vec![format!("content of {} changed", resource_path.display())]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_portability() -> Result<()> {
+        let logger = get_logger()?;
+        let exe_data = std::fs::read(std::env::current_exe()?)?;
+
+        let mut extra_files = FileManifest::default();
+        extra_files.add_file_entry(
+            Path::new("lib/foo.py"),
+            FileEntry::new_from_data(b"print('hello')".to_vec(), false),
+        )?;
+
+        let mut collection = CompiledResourcesCollection::default();
+        collection.resources.insert(
+            "libfoo".to_string(),
+            Resource {
+                name: Cow::Borrowed("libfoo"),
+                is_shared_library: true,
+                in_memory_shared_library: Some(Cow::Borrowed(&exe_data)),
+                ..Resource::default()
+            },
+        );
+        let pending_resources = vec![(collection, PathBuf::from("packed-resources"))];
+
+        let mut policy = PortabilityPolicy::default();
+        verify_portability(
+            &logger,
+            &policy,
+            "exe",
+            &exe_data,
+            &extra_files,
+            &pending_resources,
+        )?;
+
+        policy.set_max_glibc_version(Some("2.0".to_string()))?;
+        let res = verify_portability(
+            &logger,
+            &policy,
+            "exe",
+            &exe_data,
+            &extra_files,
+            &pending_resources,
+        );
+
+        if cfg!(all(target_os = "linux", target_env = "gnu")) {
+            let message = format!("{}", res.unwrap_err());
+            assert!(message.contains("exe: requires glibc"));
+            assert!(message.contains("libfoo (in-memory): requires glibc"));
+        } else {
+            res?;
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_empty_project() -> Result<()> {
         let env = get_env()?;
@@ -12,21 +12,29 @@ use {
         py_packaging::{
             distribution::{
                 default_distribution_location, resolve_distribution,
-                resolve_python_distribution_archive, DistributionFlavor,
+                resolve_python_distribution_archive, DistributionCache, DistributionFlavor,
             },
             standalone_distribution::StandaloneDistribution,
         },
+        resources_diff::{diff_resources, summarize_resources},
         starlark::eval::EvaluationContextBuilder,
+        watch::{InputWatcher, WatchedPaths},
     },
     anyhow::{anyhow, Context, Result},
     python_packaging::{
         filesystem_scanning::find_python_resources, resource::PythonResource, wheel::WheelArchive,
     },
+    python_packed_resources::{
+        inspect::{describe_resource_fields, find_embedded_resources, resource_extracted_files},
+        parser::load_resources,
+    },
+    slog::{error, warn},
     std::{
         collections::HashMap,
         fs::create_dir_all,
         io::{Cursor, Read},
         path::{Path, PathBuf},
+        sync::Arc,
     },
     tugger_file_manifest::FileData,
     tugger_licensing::LicenseFlavor,
@@ -165,8 +173,110 @@ pub fn run(
     context.run_target(target)
 }
 
-pub fn cache_clear(env: &Environment) -> Result<()> {
-    let cache_dir = env.cache_dir();
+/// Build a target and rebuild it whenever its inputs change.
+///
+/// If `run` is true, the built target is run after every build.
+#[allow(clippy::too_many_arguments)]
+pub fn watch(
+    env: &Environment,
+    logger: &slog::Logger,
+    project_path: &Path,
+    target_triple: Option<&str>,
+    release: bool,
+    target: Option<&str>,
+    run: bool,
+    extra_vars: HashMap<String, Option<String>>,
+    verbose: bool,
+) -> Result<()> {
+    let config_path = find_pyoxidizer_config_file_env(logger, project_path).ok_or_else(|| {
+        anyhow!(
+            "unable to find PyOxidizer config file at {}",
+            project_path.display()
+        )
+    })?;
+    let target_triple = resolve_target(target_triple)?;
+    let cwd = std::env::current_dir()?;
+
+    // Resolved Python distributions are kept in memory across builds. Compiled
+    // bytecode is reused through the persistent bytecode cache.
+    let distribution_cache = Arc::new(DistributionCache::new(Some(
+        &env.python_distributions_dir(),
+    )));
+
+    loop {
+        let mut context = EvaluationContextBuilder::new(
+            env,
+            logger.clone(),
+            config_path.clone(),
+            target_triple.clone(),
+        )
+        .extra_vars(extra_vars.clone())
+        .release(release)
+        .verbose(verbose)
+        .resolve_target_optional(target)
+        .distribution_cache(distribution_cache.clone())
+        .into_context()?;
+
+        // Errors don't stop watching, as fixing them requires changing inputs.
+        let resolved = context.evaluate_file(&config_path).and_then(|_| {
+            let target = match target {
+                Some(target) => target.to_string(),
+                None => context
+                    .default_target()?
+                    .ok_or_else(|| anyhow!("unable to determine target to build"))?,
+            };
+
+            context.build_resolved_target(&target)
+        });
+
+        let resolved = match resolved {
+            Ok(resolved) => Some(resolved),
+            Err(e) => {
+                error!(logger, "build failed: {:?}", e);
+                None
+            }
+        };
+
+        let ignored = context.build_path().ok().into_iter().collect::<Vec<_>>();
+        let paths = WatchedPaths::new(&cwd, &context.input_paths()?, &ignored);
+        if paths.is_empty() {
+            return Err(anyhow!("no build inputs were found to watch"));
+        }
+
+        let watcher = InputWatcher::new(paths)?;
+
+        // Watching starts before running so changes made while the target runs
+        // are detected.
+        if let (true, Some(resolved)) = (run, resolved) {
+            if let Err(e) = resolved.run() {
+                error!(logger, "run failed: {:?}", e);
+            }
+        }
+
+        warn!(logger, "waiting for changes to build inputs...");
+        let changed = watcher.wait()?;
+
+        if changed.is_empty() {
+            warn!(logger, "build inputs changed; rebuilding");
+        } else {
+            for path in changed {
+                warn!(logger, "{} changed", path.display());
+            }
+            warn!(logger, "rebuilding");
+        }
+    }
+}
+
+pub fn cache_clear(env: &Environment, bytecode_only: bool) -> Result<()> {
+    let cache_dir = if bytecode_only {
+        env.bytecode_cache_dir()
+    } else {
+        env.cache_dir().to_path_buf()
+    };
+
+    if !cache_dir.exists() {
+        return Ok(());
+    }
 
     println!("removing {}", cache_dir.display());
     remove_dir_all::remove_dir_all(&cache_dir)?;
@@ -174,6 +284,80 @@ pub fn cache_clear(env: &Environment) -> Result<()> {
     Ok(())
 }
 
+/// Compare the packed resources data in 2 files.
+pub fn diff(old_path: &Path, new_path: &Path, json: bool) -> Result<()> {
+    let old_data = std::fs::read(old_path)?;
+    let new_data = std::fs::read(new_path)?;
+
+    let old = summarize_resources(
+        find_embedded_resources(&old_data)
+            .with_context(|| format!("reading {}", old_path.display()))?
+            .data,
+    )
+    .with_context(|| format!("parsing resources in {}", old_path.display()))?;
+    let new = summarize_resources(
+        find_embedded_resources(&new_data)
+            .with_context(|| format!("reading {}", new_path.display()))?
+            .data,
+    )
+    .with_context(|| format!("parsing resources in {}", new_path.display()))?;
+
+    let diff = diff_resources(&old, &new);
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&diff)?);
+    } else {
+        diff.write_report(&mut std::io::stdout())?;
+    }
+
+    Ok(())
+}
+
+/// Print or extract the Python resources embedded in a file.
+///
+/// If `names` is non-empty, only resources with one of these names are
+/// considered.
+pub fn inspect_resources(path: &Path, extract: Option<&Path>, names: &[&str]) -> Result<()> {
+    let data = std::fs::read(path)?;
+    let embedded =
+        find_embedded_resources(&data).with_context(|| format!("reading {}", path.display()))?;
+
+    if extract.is_none() {
+        println!("format: {:?}", embedded.format);
+        println!("offset: {}", embedded.offset);
+        println!("length: {}", embedded.data.len());
+    }
+
+    for resource in load_resources(embedded.data).map_err(|e| anyhow!(e))? {
+        let resource = resource.map_err(|e| anyhow!(e))?;
+
+        if !names.is_empty() && !names.contains(&resource.name.as_ref()) {
+            continue;
+        }
+
+        if let Some(dest_dir) = extract {
+            for file in resource_extracted_files(&resource)
+                .with_context(|| format!("extracting {}", resource.name))?
+            {
+                let dest_path = dest_dir.join(&file.path);
+                println!("writing {}", dest_path.display());
+
+                if let Some(parent) = dest_path.parent() {
+                    create_dir_all(parent)?;
+                }
+                std::fs::write(&dest_path, &file.data)?;
+            }
+        } else {
+            println!();
+            for (field, value) in describe_resource_fields(&resource) {
+                println!("{}: {}", field, value);
+            }
+        }
+    }
+
+    Ok(())
+}
+
 /// Find resources given a source path.
 pub fn find_resources(
     logger: &slog::Logger,
@@ -11,6 +11,7 @@ use {
     crate::environment::Environment,
     anyhow::{anyhow, Context, Result},
     python_packaging::{
+        interpreter::PackedResourcesVerification,
         policy::PythonPackagingPolicy,
         resource::{
             PythonExtensionModule, PythonModuleSource, PythonPackageDistributionResource,
@@ -20,15 +21,18 @@ use {
             CompiledResourcesCollection, PrePackagedResource, PythonResourceAddCollectionContext,
         },
     },
+    python_packed_resources::data::BlobSectionCompression,
     std::{
-        collections::HashMap,
+        collections::{BTreeSet, HashMap},
         convert::TryFrom,
         io::Write,
         path::{Path, PathBuf},
         sync::Arc,
     },
+    tugger_binary_analysis::PortabilityPolicy,
     tugger_file_manifest::{File, FileManifest},
     tugger_windows::VcRedistributablePlatform,
+    x509_certificate::InMemorySigningKeyPair,
 };
 
 include!("../pyembed-license.rs");
@@ -234,6 +238,32 @@ pub trait PythonBinaryBuilder {
     /// Set how packed Python resources will be loaded by the binary.
     fn set_packed_resources_load_mode(&mut self, load_mode: PackedResourcesLoadMode);
 
+    /// Path to a PEM encoded PKCS#8 private key used to sign packed resources data.
+    fn packed_resources_signing_key_path(&self) -> &Option<String>;
+
+    /// Set the path to the private key used to sign packed resources data.
+    fn set_packed_resources_signing_key_path(&mut self, value: Option<String>);
+
+    /// Compression applied to blob sections of packed resources data.
+    fn packed_resources_compression(&self) -> Option<BlobSectionCompression>;
+
+    /// Set the compression applied to blob sections of packed resources data.
+    fn set_packed_resources_compression(&mut self, value: Option<BlobSectionCompression>);
+
+    /// Number of Python processes to use for compiling bytecode.
+    fn bytecode_compiler_processes(&self) -> usize;
+
+    /// Set the number of Python processes to use for compiling bytecode.
+    fn set_bytecode_compiler_processes(&mut self, value: usize);
+
+    /// Portability constraints built binaries must satisfy.
+    ///
+    /// Violations fail the build.
+    fn portability_policy(&self) -> &PortabilityPolicy;
+
+    /// Set the portability constraints built binaries must satisfy.
+    fn set_portability_policy(&mut self, policy: PortabilityPolicy);
+
     /// Obtain an iterator over all resource entries that will be embedded in the binary.
     ///
     /// This likely does not return extension modules that are statically linked
@@ -398,6 +428,12 @@ pub trait PythonBinaryBuilder {
     /// Whether the binary requires the Snmalloc library.
     fn requires_snmalloc(&self) -> bool;
 
+    /// Whether the binary requires support for verifying packed resources signatures.
+    fn requires_packed_resources_signatures(&self) -> bool;
+
+    /// Whether the binary requires support for runtime configuration overlays.
+    fn requires_config_overlay(&self) -> bool;
+
     /// Obtain an `EmbeddedPythonContext` instance from this one.
     fn to_embedded_python_context(
         &self,
@@ -430,6 +466,59 @@ pub struct PythonLinkingInfo {
     pub cargo_metadata: Vec<String>,
 }
 
+/// Serialize a collection of resources to packed resources data.
+///
+/// If the interpreter verifies packed resources or a signing key is
+/// defined, every resource is given a content digest and the data is
+/// followed by an integrity trailer, which is signed with the key at
+/// `signing_key_path`, if present. Blob sections are compressed with
+/// `compression`, if defined.
+pub fn serialize_packed_resources(
+    collection: &CompiledResourcesCollection,
+    config: &PyembedPythonInterpreterConfig,
+    compression: Option<BlobSectionCompression>,
+    signing_key_path: Option<&Path>,
+) -> Result<Vec<u8>> {
+    let mut buffer = vec![];
+
+    if config.packed_resources_signing_certificate.is_some() && signing_key_path.is_none() {
+        return Err(anyhow!(
+            "packed_resources_signing_certificate requires a packed resources signing key"
+        ));
+    }
+
+    if config.packed_resources_verification == PackedResourcesVerification::None
+        && signing_key_path.is_none()
+    {
+        collection.write_packed_resources(&mut buffer, compression)?;
+
+        return Ok(buffer);
+    }
+
+    let signing_key = if let Some(path) = signing_key_path {
+        let data = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+
+        Some(
+            InMemorySigningKeyPair::from_pkcs8_pem(&data)
+                .with_context(|| format!("parsing signing key {}", path.display()))?,
+        )
+    } else {
+        None
+    };
+
+    collection.write_packed_resources_with_integrity(&mut buffer, compression, |digest| {
+        if let Some(key) = &signing_key {
+            let (signature, _) = key.sign(digest).context("signing packed resources")?;
+
+            Ok(Some(signature))
+        } else {
+            Ok(None)
+        }
+    })?;
+
+    Ok(buffer)
+}
+
 /// Holds context necessary to embed Python in a binary.
 pub struct EmbeddedPythonContext<'a> {
     /// The configuration for the embedded interpreter.
@@ -441,6 +530,12 @@ pub struct EmbeddedPythonContext<'a> {
     /// Python resources that need to be serialized to a file.
     pub pending_resources: Vec<(CompiledResourcesCollection<'a>, PathBuf)>,
 
+    /// Compression applied to blob sections of packed resources data.
+    pub packed_resources_compression: Option<BlobSectionCompression>,
+
+    /// Path to private key used to sign packed resources data.
+    pub packed_resources_signing_key_path: Option<PathBuf>,
+
     /// Extra files to install next to produced binary.
     pub extra_files: FileManifest,
 
@@ -449,6 +544,11 @@ pub struct EmbeddedPythonContext<'a> {
 
     /// Rust target triple for the target we are building for.
     pub target_triple: String,
+
+    /// Paths of files used as inputs to derive this context.
+    ///
+    /// Changes to these files should result in a rebuild.
+    pub input_paths: BTreeSet<PathBuf>,
 }
 
 impl<'a> EmbeddedPythonContext<'a> {
@@ -508,13 +608,16 @@ impl<'a> EmbeddedPythonContext<'a> {
         for (collection, path) in &self.pending_resources {
             let dest_path = dest_dir.as_ref().join(path);
 
-            let mut writer = std::io::BufWriter::new(
-                std::fs::File::create(&dest_path)
-                    .with_context(|| format!("opening {} for writing", dest_path.display()))?,
-            );
-            collection
-                .write_packed_resources(&mut writer)
-                .context("writing packed resources")?;
+            let data = serialize_packed_resources(
+                collection,
+                &self.config,
+                self.packed_resources_compression,
+                self.packed_resources_signing_key_path.as_deref(),
+            )
+            .context("writing packed resources")?;
+
+            std::fs::write(&dest_path, data)
+                .with_context(|| format!("writing {}", dest_path.display()))?;
         }
 
         Ok(())
@@ -552,6 +655,24 @@ impl<'a> EmbeddedPythonContext<'a> {
         Ok(())
     }
 
+    /// Obtain the path of the file listing paths of build inputs.
+    pub fn build_inputs_path(&self, dest_dir: impl AsRef<Path>) -> PathBuf {
+        dest_dir.as_ref().join("build_inputs.txt")
+    }
+
+    /// Write file listing paths of build inputs, one per line.
+    pub fn write_build_inputs(&self, dest_dir: impl AsRef<Path>) -> Result<()> {
+        let lines = self
+            .input_paths
+            .iter()
+            .map(|p| p.display().to_string())
+            .collect::<Vec<_>>();
+
+        std::fs::write(self.build_inputs_path(&dest_dir), lines.join("\n"))?;
+
+        Ok(())
+    }
+
     /// Write out files needed to build a binary against our configuration.
     pub fn write_files(&self, dest_dir: &Path) -> Result<()> {
         self.write_packed_resources(&dest_dir)
@@ -562,6 +683,8 @@ impl<'a> EmbeddedPythonContext<'a> {
             .context("write_interpreter_config_rs()")?;
         self.write_cargo_metadata(&dest_dir)
             .context("write_cargo_metadata()")?;
+        self.write_build_inputs(&dest_dir)
+            .context("write_build_inputs()")?;
 
         Ok(())
     }
@@ -12,8 +12,8 @@ use {
     python_packaging::{
         interpreter::{
             Allocator, BytesWarning, CheckHashPycsMode, CoerceCLocale, MemoryAllocatorBackend,
-            MultiprocessingStartMethod, PythonInterpreterConfig, PythonInterpreterProfile,
-            TerminfoResolution,
+            MultiprocessingStartMethod, PackedResourcesVerification, PythonInterpreterConfig,
+            PythonInterpreterProfile, TerminfoResolution,
         },
         resource::BytecodeOptimizationLevel,
     },
@@ -127,14 +127,19 @@ pub struct PyembedPythonInterpreterConfig {
     pub oxidized_importer: bool,
     pub filesystem_importer: bool,
     pub packed_resources: Vec<PyembedPackedResourcesSource>,
+    pub packed_resources_verification: PackedResourcesVerification,
+    pub packed_resources_signing_certificate: Option<String>,
     pub argvb: bool,
     pub multiprocessing_auto_dispatch: bool,
     pub multiprocessing_start_method: MultiprocessingStartMethod,
+    pub pkg_resources_extract_in_memory_resources: bool,
     pub sys_frozen: bool,
     pub sys_meipass: bool,
     pub terminfo_resolution: TerminfoResolution,
     pub tcl_library: Option<PathBuf>,
     pub write_modules_directory_env: Option<String>,
+    pub config_overlay_path: Option<PathBuf>,
+    pub config_overlay_fields: Vec<String>,
 }
 
 impl Default for PyembedPythonInterpreterConfig {
@@ -162,14 +167,19 @@ impl Default for PyembedPythonInterpreterConfig {
             oxidized_importer: true,
             filesystem_importer: false,
             packed_resources: vec![],
+            packed_resources_verification: PackedResourcesVerification::None,
+            packed_resources_signing_certificate: None,
             argvb: false,
             multiprocessing_auto_dispatch: true,
             multiprocessing_start_method: MultiprocessingStartMethod::Auto,
+            pkg_resources_extract_in_memory_resources: false,
             sys_frozen: true,
             sys_meipass: false,
             terminfo_resolution: TerminfoResolution::None,
             tcl_library: None,
             write_modules_directory_env: None,
+            config_overlay_path: None,
+            config_overlay_fields: vec![],
         }
     }
 }
@@ -249,16 +259,21 @@ impl PyembedPythonInterpreterConfig {
             oxidized_importer: {},\n    \
             filesystem_importer: {},\n    \
             packed_resources: {},\n    \
+            packed_resources_verification: {},\n    \
+            packed_resources_signing_certificate: {},\n    \
             extra_extension_modules: None,\n    \
             argv: None,\n    \
             argvb: {},\n    \
             multiprocessing_auto_dispatch: {},\n    \
             multiprocessing_start_method: {},\n    \
+            pkg_resources_extract_in_memory_resources: {},\n    \
             sys_frozen: {},\n    \
             sys_meipass: {},\n    \
             terminfo_resolution: {},\n    \
             tcl_library: {},\n    \
             write_modules_directory_env: {},\n    \
+            config_overlay_path: {},\n    \
+            config_overlay_fields: vec![{}],\n    \
             }}\n\
             ",
             match self.config.profile {
@@ -387,6 +402,14 @@ impl PyembedPythonInterpreterConfig {
                     .map(|e| e.to_string())
                     .join(", ")
             ),
+            match self.packed_resources_verification {
+                PackedResourcesVerification::None => "pyembed::PackedResourcesVerification::None",
+                PackedResourcesVerification::Import =>
+                    "pyembed::PackedResourcesVerification::Import",
+                PackedResourcesVerification::Startup =>
+                    "pyembed::PackedResourcesVerification::Startup",
+            },
+            optional_string_to_string(&self.packed_resources_signing_certificate),
             self.argvb,
             self.multiprocessing_auto_dispatch,
             match self.multiprocessing_start_method {
@@ -401,6 +424,7 @@ impl PyembedPythonInterpreterConfig {
                 MultiprocessingStartMethod::Auto =>
                     "pyembed::MultiprocessingStartMethod::Auto".to_string(),
             },
+            self.pkg_resources_extract_in_memory_resources,
             self.sys_frozen,
             self.sys_meipass,
             match self.terminfo_resolution {
@@ -412,6 +436,12 @@ impl PyembedPythonInterpreterConfig {
             },
             optional_pathbuf_to_string(&self.tcl_library),
             optional_string_to_string(&self.write_modules_directory_env),
+            optional_pathbuf_to_string(&self.config_overlay_path),
+            self.config_overlay_fields
+                .iter()
+                .map(|x| format!("\"{}\".to_string()", x.escape_default()))
+                .collect::<Vec<_>>()
+                .join(", "),
         );
 
         Ok(code)
@@ -511,6 +541,28 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_serialize_config_overlay() -> Result<()> {
+        let mut config = PyembedPythonInterpreterConfig::default();
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+        assert_contains(&code, "config_overlay_path: None,")?;
+        assert_contains(&code, "config_overlay_fields: vec![],")?;
+
+        config.config_overlay_path = Some(PathBuf::from("$ORIGIN/overrides.toml"));
+        config.config_overlay_fields = vec!["verbose".to_string(), "tcl_library".to_string()];
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+        assert_contains(
+            &code,
+            "config_overlay_path: Some(std::path::PathBuf::from(\"$ORIGIN/overrides.toml\")),",
+        )?;
+        assert_contains(
+            &code,
+            "config_overlay_fields: vec![\"verbose\".to_string(), \"tcl_library\".to_string()],",
+        )
+    }
+
     // TODO enable once CI has a linkable Python.
     #[test]
     #[ignore]
@@ -594,14 +646,19 @@ mod tests {
                     "$ORIGIN/packed-resources",
                 )),
             ],
+            packed_resources_verification: PackedResourcesVerification::Import,
+            packed_resources_signing_certificate: None,
             argvb: true,
             sys_frozen: false,
             sys_meipass: true,
             terminfo_resolution: TerminfoResolution::Dynamic,
             tcl_library: Some("path".into()),
             write_modules_directory_env: Some("env".into()),
+            config_overlay_path: Some("$ORIGIN/overrides.toml".into()),
+            config_overlay_fields: vec!["verbose".into(), "module_search_paths".into()],
             multiprocessing_auto_dispatch: false,
             multiprocessing_start_method: MultiprocessingStartMethod::Spawn,
+            pkg_resources_extract_in_memory_resources: true,
         };
 
         let builder = dist.as_python_executable_builder(
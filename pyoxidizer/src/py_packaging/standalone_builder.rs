@@ -5,9 +5,9 @@
 use {
     super::{
         binary::{
-            pyembed_licenses, EmbeddedPythonContext, LibpythonLinkMode, PackedResourcesLoadMode,
-            PythonBinaryBuilder, PythonLinkingInfo, ResourceAddCollectionContextCallback,
-            WindowsRuntimeDllsMode,
+            pyembed_licenses, serialize_packed_resources, EmbeddedPythonContext, LibpythonLinkMode,
+            PackedResourcesLoadMode, PythonBinaryBuilder, PythonLinkingInfo,
+            ResourceAddCollectionContextCallback, WindowsRuntimeDllsMode,
         },
         config::{PyembedPackedResourcesSource, PyembedPythonInterpreterConfig},
         distribution::{AppleSdkInfo, BinaryLibpythonLinkMode, PythonDistribution},
@@ -22,7 +22,7 @@ use {
     anyhow::{anyhow, Context, Result},
     once_cell::sync::Lazy,
     python_packaging::{
-        bytecode::BytecodeCompiler,
+        bytecode::{BytecodeCompilerPool, CachingBytecodeCompiler},
         interpreter::MemoryAllocatorBackend,
         libpython::LibPythonBuildContext,
         licensing::derive_package_license_infos,
@@ -33,9 +33,11 @@ use {
             PythonPackageResource, PythonResource,
         },
         resource_collection::{
-            PrePackagedResource, PythonResourceAddCollectionContext, PythonResourceCollector,
+            PrePackagedResource, PythonModuleBytecodeProvider, PythonResourceAddCollectionContext,
+            PythonResourceCollector,
         },
     },
+    python_packed_resources::data::BlobSectionCompression,
     slog::warn,
     std::{
         collections::{BTreeMap, BTreeSet, HashMap},
@@ -43,6 +45,7 @@ use {
         path::{Path, PathBuf},
         sync::Arc,
     },
+    tugger_binary_analysis::PortabilityPolicy,
     tugger_file_manifest::{File, FileData, FileEntry, FileManifest},
     tugger_licensing::{ComponentFlavor, LicensedComponent},
     tugger_windows::{find_visual_cpp_redistributable, VcRedistributablePlatform},
@@ -120,6 +123,18 @@ pub struct StandalonePythonExecutableBuilder {
     /// Path to install tcl/tk files into.
     tcl_files_path: Option<String>,
 
+    /// Path to private key used to sign packed resources data.
+    packed_resources_signing_key_path: Option<String>,
+
+    /// Compression applied to blob sections of packed resources data.
+    packed_resources_compression: Option<BlobSectionCompression>,
+
+    /// Number of Python processes to use for compiling bytecode.
+    bytecode_compiler_processes: usize,
+
+    /// Portability constraints built binaries must satisfy.
+    portability_policy: PortabilityPolicy,
+
     /// Describes how Windows runtime DLLs should be handled during builds.
     windows_runtime_dlls_mode: WindowsRuntimeDllsMode,
 }
@@ -221,6 +236,10 @@ impl StandalonePythonExecutableBuilder {
             host_python_exe,
             windows_subsystem: "console".to_string(),
             tcl_files_path: None,
+            packed_resources_signing_key_path: None,
+            packed_resources_compression: None,
+            bytecode_compiler_processes: 1,
+            portability_policy: PortabilityPolicy::default(),
             windows_runtime_dlls_mode: WindowsRuntimeDllsMode::WhenPresent,
         });
 
@@ -512,6 +531,38 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
         self.resources_load_mode = load_mode;
     }
 
+    fn packed_resources_signing_key_path(&self) -> &Option<String> {
+        &self.packed_resources_signing_key_path
+    }
+
+    fn set_packed_resources_signing_key_path(&mut self, value: Option<String>) {
+        self.packed_resources_signing_key_path = value;
+    }
+
+    fn packed_resources_compression(&self) -> Option<BlobSectionCompression> {
+        self.packed_resources_compression
+    }
+
+    fn set_packed_resources_compression(&mut self, value: Option<BlobSectionCompression>) {
+        self.packed_resources_compression = value;
+    }
+
+    fn bytecode_compiler_processes(&self) -> usize {
+        self.bytecode_compiler_processes
+    }
+
+    fn set_bytecode_compiler_processes(&mut self, value: usize) {
+        self.bytecode_compiler_processes = value;
+    }
+
+    fn portability_policy(&self) -> &PortabilityPolicy {
+        &self.portability_policy
+    }
+
+    fn set_portability_policy(&mut self, policy: PortabilityPolicy) {
+        self.portability_policy = policy;
+    }
+
     fn iter_resources<'a>(
         &'a self,
     ) -> Box<dyn Iterator<Item = (&'a String, &'a PrePackagedResource)> + 'a> {
@@ -865,6 +916,14 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
         self.config.allocator_backend == MemoryAllocatorBackend::Snmalloc
     }
 
+    fn requires_packed_resources_signatures(&self) -> bool {
+        self.config.packed_resources_signing_certificate.is_some()
+    }
+
+    fn requires_config_overlay(&self) -> bool {
+        self.config.config_overlay_path.is_some()
+    }
+
     fn to_embedded_python_context(
         &self,
         logger: &slog::Logger,
@@ -925,8 +984,37 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
 
         let compiled_resources = {
             let temp_dir = tempfile::TempDir::new()?;
-            let mut compiler = BytecodeCompiler::new(self.host_python_exe_path(), temp_dir.path())?;
-            self.resources_collector.compile_resources(&mut compiler)?
+            let mut compiler = BytecodeCompilerPool::new(
+                self.host_python_exe_path(),
+                temp_dir.path(),
+                self.bytecode_compiler_processes,
+            )?;
+
+            if let Some(cache) = env.bytecode_cache() {
+                let mut compiler = CachingBytecodeCompiler::new(&mut compiler, cache);
+                let compiled = self.resources_collector.compile_resources(&mut compiler)?;
+
+                let stats = compiler.stats();
+                warn!(
+                    logger,
+                    "bytecode cache: {} hits, {} misses", stats.hits, stats.misses
+                );
+
+                match compiler.cache().prune() {
+                    Ok(0) => {}
+                    Ok(count) => warn!(
+                        logger,
+                        "pruned {} entries from bytecode cache {}",
+                        count,
+                        compiler.cache().root().display()
+                    ),
+                    Err(e) => warn!(logger, "error pruning bytecode cache: {:?}", e),
+                }
+
+                compiled
+            } else {
+                self.resources_collector.compile_resources(&mut compiler)?
+            }
         };
 
         let mut pending_resources = vec![];
@@ -940,6 +1028,10 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
         }
 
         let mut config = self.config.clone();
+        let signing_key_path = self
+            .packed_resources_signing_key_path
+            .as_ref()
+            .map(PathBuf::from);
 
         match &self.resources_load_mode {
             PackedResourcesLoadMode::None => {}
@@ -953,10 +1045,13 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
             }
             PackedResourcesLoadMode::BinaryRelativePathMemoryMapped(path) => {
                 // We need to materialize the file in extra_files. So compile now.
-                let mut buffer = vec![];
-                compiled_resources
-                    .write_packed_resources(&mut buffer)
-                    .context("serializing packed resources")?;
+                let buffer = serialize_packed_resources(
+                    &compiled_resources,
+                    &config,
+                    self.packed_resources_compression,
+                    signing_key_path.as_deref(),
+                )
+                .context("serializing packed resources")?;
                 extra_files.add_file_entry(Path::new(path), buffer)?;
 
                 config
@@ -1000,17 +1095,100 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
         // Install Windows runtime DLLs if told to do so.
         extra_files.add_manifest(&self.resolve_windows_runtime_dll_files()?)?;
 
+        let mut input_paths = self
+            .resources_collector
+            .iter_resources()
+            .flat_map(|(_, resource)| resource_input_paths(resource))
+            .collect::<BTreeSet<_>>();
+        if let Some(path) = &self.target_distribution.archive_path {
+            input_paths.insert(path.clone());
+        }
+        if let Some(path) = &signing_key_path {
+            input_paths.insert(path.clone());
+        }
+
         Ok(EmbeddedPythonContext {
             config,
             linking_info,
             pending_resources,
+            packed_resources_compression: self.packed_resources_compression,
+            packed_resources_signing_key_path: signing_key_path,
             extra_files,
             host_triple: self.host_triple.clone(),
             target_triple: self.target_triple.clone(),
+            input_paths,
         })
     }
 }
 
+/// Obtain the filesystem paths a resource's data is read from.
+fn resource_input_paths(resource: &PrePackagedResource) -> Vec<PathBuf> {
+    let mut data: Vec<&FileData> = vec![];
+
+    data.extend(resource.in_memory_source.iter());
+    data.extend(resource.in_memory_extension_module_shared_library.iter());
+    data.extend(resource.in_memory_shared_library.iter());
+    data.extend(resource.file_data_embedded.iter());
+
+    for resources in resource
+        .in_memory_resources
+        .iter()
+        .chain(resource.in_memory_distribution_resources.iter())
+    {
+        data.extend(resources.values());
+    }
+
+    for bytecode in resource
+        .in_memory_bytecode
+        .iter()
+        .chain(resource.in_memory_bytecode_opt1.iter())
+        .chain(resource.in_memory_bytecode_opt2.iter())
+        .chain(
+            resource
+                .relative_path_bytecode
+                .iter()
+                .chain(resource.relative_path_bytecode_opt1.iter())
+                .chain(resource.relative_path_bytecode_opt2.iter())
+                .map(|(_, _, bytecode)| bytecode),
+        )
+    {
+        match bytecode {
+            PythonModuleBytecodeProvider::Provided(location)
+            | PythonModuleBytecodeProvider::FromSource(location) => data.push(location),
+        }
+    }
+
+    data.extend(resource.relative_path_module_source.iter().map(|(_, x)| x));
+    data.extend(
+        resource
+            .relative_path_extension_module_shared_library
+            .iter()
+            .map(|(_, x)| x),
+    );
+    data.extend(
+        resource
+            .relative_path_shared_library
+            .iter()
+            .map(|(_, _, x)| x),
+    );
+    data.extend(resource.file_data_utf8_relative_path.iter().map(|(_, x)| x));
+
+    for resources in resource
+        .relative_path_package_resources
+        .iter()
+        .chain(resource.relative_path_distribution_resources.iter())
+    {
+        data.extend(resources.values().map(|(_, x)| x));
+    }
+
+    data.into_iter()
+        .filter_map(|location| match location {
+            FileData::Path(path) => Some(path.clone()),
+            FileData::Memory(_) => None,
+        })
+        .collect()
+}
+
 #[cfg(test)]
 pub mod tests {
     use {
@@ -1022,7 +1200,10 @@ pub mod tests {
             testutil::*,
         },
         once_cell::sync::Lazy,
-        python_packaging::{location::ConcreteResourceLocation, policy::ExtensionModuleFilter},
+        python_packaging::{
+            bytecode::BytecodeCompiler, location::ConcreteResourceLocation,
+            policy::ExtensionModuleFilter,
+        },
         std::ops::DerefMut,
         tugger_licensing::LicensedComponents,
     };
@@ -1577,6 +1758,45 @@ pub mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_linux_dynamic_extension_in_memory_only() -> Result<()> {
+        let options = StandalonePythonExecutableBuilderOptions {
+            target_triple: "x86_64-unknown-linux-gnu".to_string(),
+            extension_module_filter: Some(ExtensionModuleFilter::Minimal),
+            libpython_link_mode: BinaryLibpythonLinkMode::Dynamic,
+            resources_location: Some(ConcreteResourceLocation::InMemory),
+            resources_location_fallback: Some(None),
+            allow_in_memory_shared_library_loading: Some(true),
+            ..StandalonePythonExecutableBuilderOptions::default()
+        };
+
+        let mut builder = options.new_builder()?;
+
+        builder.add_python_extension_module(&EXTENSION_MODULE_SHARED_LIBRARY_ONLY, None)?;
+        assert_extension_shared_library(
+            &builder,
+            &EXTENSION_MODULE_SHARED_LIBRARY_ONLY,
+            ConcreteResourceLocation::InMemory,
+        );
+
+        let res = builder.add_python_extension_module(&EXTENSION_MODULE_OBJECT_FILES_ONLY, None);
+        assert!(res.is_err());
+        assert_eq!(
+            res.err().unwrap().to_string(),
+            "no shared library data present"
+        );
+
+        builder
+            .add_python_extension_module(&EXTENSION_MODULE_SHARED_LIBRARY_AND_OBJECT_FILES, None)?;
+        assert_extension_shared_library(
+            &builder,
+            &EXTENSION_MODULE_SHARED_LIBRARY_AND_OBJECT_FILES,
+            ConcreteResourceLocation::InMemory,
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_linux_distribution_extension_filesystem_relative_only() -> Result<()> {
         for libpython_link_mode in vec![
@@ -3125,4 +3345,28 @@ pub mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_bytecode_compiler_pool_deterministic() -> Result<()> {
+        let options = StandalonePythonExecutableBuilderOptions::default();
+        let builder = options.new_builder()?;
+
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+
+        let mut compiler = BytecodeCompiler::new(builder.host_python_exe_path(), temp_dir.path())?;
+        let serial = builder
+            .resources_collector
+            .compile_resources(&mut compiler)?;
+
+        let mut pool =
+            BytecodeCompilerPool::new(builder.host_python_exe_path(), temp_dir.path(), 4)?;
+        assert_eq!(pool.size(), 4);
+        let parallel = builder.resources_collector.compile_resources(&mut pool)?;
+
+        assert_eq!(serial.resources, parallel.resources);
+
+        Ok(())
+    }
 }
@@ -371,6 +371,9 @@ pub struct StandaloneDistribution {
     /// Directory where distribution lives in the filesystem.
     pub base_dir: PathBuf,
 
+    /// Path to the archive the distribution was extracted from, if known.
+    pub archive_path: Option<PathBuf>,
+
     /// Rust target triple that this distribution runs on.
     pub target_triple: String,
 
@@ -522,7 +525,10 @@ impl StandaloneDistribution {
         let reader = BufReader::new(fh);
         warn!(logger, "reading data from Python distribution...");
 
-        Self::from_tar_zst(reader, &extract_dir)
+        let mut dist = Self::from_tar_zst(reader, &extract_dir)?;
+        dist.archive_path = Some(path.to_path_buf());
+
+        Ok(dist)
     }
 
     /// Extract and analyze a standalone distribution from a zstd compressed tar stream.
@@ -948,6 +954,7 @@ impl StandaloneDistribution {
 
         Ok(Self {
             base_dir: dist_dir.to_path_buf(),
+            archive_path: None,
             target_triple: pi.target_triple,
             python_implementation: pi.python_implementation_name,
             python_tag: pi.python_tag,
@@ -1396,11 +1403,14 @@ impl PythonDistribution for StandaloneDistribution {
 
     /// Determines whether dynamically linked extension modules can be loaded from memory.
     fn supports_in_memory_shared_library_loading(&self) -> bool {
-        // Loading from memory is only supported on Windows where symbols are
-        // declspec(dllexport) and the distribution is capable of loading
-        // shared library extensions.
-        self.target_triple.contains("pc-windows")
-            && self.python_symbol_visibility == "dllexport"
+        // Loading from memory is supported on Windows where symbols are
+        // declspec(dllexport) and on Linux via memfd_create(). In both cases
+        // the distribution must be capable of loading shared library extensions.
+        let platform_supported = (self.target_triple.contains("pc-windows")
+            && self.python_symbol_visibility == "dllexport")
+            || self.target_triple.contains("-linux-");
+
+        platform_supported
             && self
                 .extension_module_loading
                 .contains(&"shared-library".to_string())
@@ -0,0 +1,414 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Compare the Python resources embedded in two built artifacts. */
+
+use {
+    anyhow::{anyhow, Result},
+    python_packed_resources::{
+        data::{Resource, ResourceFlavor},
+        integrity::resource_content_sha256,
+        parser::load_resources,
+    },
+    serde::Serialize,
+    std::{borrow::Cow, collections::BTreeMap, io::Write},
+};
+
+/// Describes a single resource in packed resources data.
+///
+/// Sizes only account for data embedded in the packed resources data.
+/// Resources installed to relative paths are referenced by path only.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct ResourceSummary {
+    /// The type of resource.
+    pub kind: String,
+    /// Top-level package this resource belongs to.
+    pub package: String,
+    /// Bytes of Python module source.
+    pub source_bytes: u64,
+    /// Bytes of Python module bytecode, at all optimization levels.
+    pub bytecode_bytes: u64,
+    /// Bytes of package and distribution resources.
+    pub resource_bytes: u64,
+    /// Bytes of extension module shared library data.
+    pub extension_module_bytes: u64,
+    /// Bytes of shared library data.
+    pub shared_library_bytes: u64,
+    /// Bytes of arbitrary file data.
+    pub file_bytes: u64,
+    /// Hex encoded SHA-256 digest of the resource's content.
+    #[serde(skip)]
+    pub digest: String,
+}
+
+impl ResourceSummary {
+    /// Obtain the total number of bytes this resource occupies.
+    pub fn total_bytes(&self) -> u64 {
+        self.source_bytes
+            + self.bytecode_bytes
+            + self.resource_bytes
+            + self.extension_module_bytes
+            + self.shared_library_bytes
+            + self.file_bytes
+    }
+}
+
+fn data_len(data: &Option<Cow<[u8]>>) -> u64 {
+    data.as_ref().map_or(0, |data| data.len() as u64)
+}
+
+impl<'a> From<&Resource<'a, u8>> for ResourceSummary {
+    fn from(resource: &Resource<'a, u8>) -> Self {
+        let kind = match resource.flavor {
+            ResourceFlavor::None => "file",
+            ResourceFlavor::Module => "module",
+            ResourceFlavor::BuiltinExtensionModule => "builtin-extension-module",
+            ResourceFlavor::FrozenModule => "frozen-module",
+            ResourceFlavor::Extension => "extension-module",
+            ResourceFlavor::SharedLibrary => "shared-library",
+        };
+
+        let package = match resource.flavor {
+            ResourceFlavor::None => "".to_string(),
+            ResourceFlavor::SharedLibrary => resource.name.to_string(),
+            _ => resource
+                .name
+                .split('.')
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+        };
+
+        let resource_bytes = resource
+            .in_memory_package_resources
+            .iter()
+            .chain(resource.in_memory_distribution_resources.iter())
+            .flat_map(|resources| resources.values())
+            .map(|data| data.len() as u64)
+            .sum();
+
+        Self {
+            kind: kind.to_string(),
+            package,
+            source_bytes: data_len(&resource.in_memory_source),
+            bytecode_bytes: data_len(&resource.in_memory_bytecode)
+                + data_len(&resource.in_memory_bytecode_opt1)
+                + data_len(&resource.in_memory_bytecode_opt2),
+            resource_bytes,
+            extension_module_bytes: data_len(&resource.in_memory_extension_module_shared_library),
+            shared_library_bytes: data_len(&resource.in_memory_shared_library),
+            file_bytes: data_len(&resource.file_data_embedded),
+            digest: resource_content_sha256(resource)
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect(),
+        }
+    }
+}
+
+/// Summarize all resources in packed resources data, indexed by name.
+pub fn summarize_resources(data: &[u8]) -> Result<BTreeMap<String, ResourceSummary>> {
+    let mut res = BTreeMap::new();
+
+    for resource in load_resources(data).map_err(|e| anyhow!(e))? {
+        let resource = resource.map_err(|e| anyhow!(e))?;
+
+        res.insert(resource.name.to_string(), ResourceSummary::from(&resource));
+    }
+
+    Ok(res)
+}
+
+/// Aggregate sizes of a collection of resources.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct ResourceTotals {
+    pub count: u64,
+    pub source_bytes: u64,
+    pub bytecode_bytes: u64,
+    pub resource_bytes: u64,
+    pub extension_module_bytes: u64,
+    pub shared_library_bytes: u64,
+    pub file_bytes: u64,
+    pub total_bytes: u64,
+}
+
+impl ResourceTotals {
+    fn add(&mut self, summary: &ResourceSummary) {
+        self.count += 1;
+        self.source_bytes += summary.source_bytes;
+        self.bytecode_bytes += summary.bytecode_bytes;
+        self.resource_bytes += summary.resource_bytes;
+        self.extension_module_bytes += summary.extension_module_bytes;
+        self.shared_library_bytes += summary.shared_library_bytes;
+        self.file_bytes += summary.file_bytes;
+        self.total_bytes += summary.total_bytes();
+    }
+}
+
+/// A resource present in only one of the compared artifacts.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct ResourceEntry {
+    pub name: String,
+    #[serde(flatten)]
+    pub summary: ResourceSummary,
+}
+
+/// A resource whose content differs between the compared artifacts.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct ResourceChange {
+    pub name: String,
+    pub old: ResourceSummary,
+    pub new: ResourceSummary,
+}
+
+/// Size of a package in the compared artifacts.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct PackageSizes {
+    pub old_bytes: u64,
+    pub new_bytes: u64,
+}
+
+/// Describes differences between the resources of 2 artifacts.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct ResourcesDiff {
+    pub old_totals: ResourceTotals,
+    pub new_totals: ResourceTotals,
+    pub added: Vec<ResourceEntry>,
+    pub removed: Vec<ResourceEntry>,
+    pub changed: Vec<ResourceChange>,
+    pub packages: BTreeMap<String, PackageSizes>,
+}
+
+/// Compute the differences between 2 sets of resources.
+pub fn diff_resources(
+    old: &BTreeMap<String, ResourceSummary>,
+    new: &BTreeMap<String, ResourceSummary>,
+) -> ResourcesDiff {
+    let mut diff = ResourcesDiff::default();
+
+    for (name, summary) in old {
+        diff.old_totals.add(summary);
+        diff.packages
+            .entry(summary.package.clone())
+            .or_default()
+            .old_bytes += summary.total_bytes();
+
+        match new.get(name) {
+            None => diff.removed.push(ResourceEntry {
+                name: name.clone(),
+                summary: summary.clone(),
+            }),
+            Some(new_summary) if new_summary != summary => diff.changed.push(ResourceChange {
+                name: name.clone(),
+                old: summary.clone(),
+                new: new_summary.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+
+    for (name, summary) in new {
+        diff.new_totals.add(summary);
+        diff.packages
+            .entry(summary.package.clone())
+            .or_default()
+            .new_bytes += summary.total_bytes();
+
+        if !old.contains_key(name) {
+            diff.added.push(ResourceEntry {
+                name: name.clone(),
+                summary: summary.clone(),
+            });
+        }
+    }
+
+    diff
+}
+
+fn delta(old: u64, new: u64) -> String {
+    if new >= old {
+        format!("+{}", new - old)
+    } else {
+        format!("-{}", old - new)
+    }
+}
+
+impl ResourcesDiff {
+    /// Write a human readable report of differences.
+    pub fn write_report(&self, writer: &mut impl Write) -> Result<()> {
+        let old = &self.old_totals;
+        let new = &self.new_totals;
+
+        writeln!(writer, "resources: {} -> {}", old.count, new.count)?;
+        for (label, old_value, new_value) in &[
+            ("source bytes", old.source_bytes, new.source_bytes),
+            ("bytecode bytes", old.bytecode_bytes, new.bytecode_bytes),
+            ("resource bytes", old.resource_bytes, new.resource_bytes),
+            (
+                "extension module bytes",
+                old.extension_module_bytes,
+                new.extension_module_bytes,
+            ),
+            (
+                "shared library bytes",
+                old.shared_library_bytes,
+                new.shared_library_bytes,
+            ),
+            ("file bytes", old.file_bytes, new.file_bytes),
+            ("total bytes", old.total_bytes, new.total_bytes),
+        ] {
+            writeln!(
+                writer,
+                "{}: {} -> {} ({})",
+                label,
+                old_value,
+                new_value,
+                delta(*old_value, *new_value)
+            )?;
+        }
+
+        let mut packages = self
+            .packages
+            .iter()
+            .filter(|(_, sizes)| sizes.old_bytes != sizes.new_bytes)
+            .collect::<Vec<_>>();
+        // Largest changes first.
+        packages.sort_by_key(|(name, sizes)| {
+            let change =
+                sizes.new_bytes.max(sizes.old_bytes) - sizes.new_bytes.min(sizes.old_bytes);
+
+            (std::cmp::Reverse(change), name.to_string())
+        });
+
+        if !packages.is_empty() {
+            writeln!(writer)?;
+            writeln!(writer, "packages with size changes:")?;
+            for (name, sizes) in packages {
+                writeln!(
+                    writer,
+                    "  {}: {} -> {} ({})",
+                    if name.is_empty() { "<files>" } else { name },
+                    sizes.old_bytes,
+                    sizes.new_bytes,
+                    delta(sizes.old_bytes, sizes.new_bytes)
+                )?;
+            }
+        }
+
+        for (label, prefix, entries) in
+            &[("added", "+", &self.added), ("removed", "-", &self.removed)]
+        {
+            if entries.is_empty() {
+                continue;
+            }
+
+            writeln!(writer)?;
+            writeln!(writer, "{} resources ({}):", label, entries.len())?;
+            for entry in entries.iter() {
+                writeln!(
+                    writer,
+                    "  {} {} {} ({} bytes)",
+                    prefix,
+                    entry.summary.kind,
+                    entry.name,
+                    entry.summary.total_bytes()
+                )?;
+            }
+        }
+
+        if !self.changed.is_empty() {
+            writeln!(writer)?;
+            writeln!(writer, "changed resources ({}):", self.changed.len())?;
+            for change in &self.changed {
+                writeln!(
+                    writer,
+                    "  ~ {} {}: {} -> {} ({})",
+                    change.new.kind,
+                    change.name,
+                    change.old.total_bytes(),
+                    change.new.total_bytes(),
+                    delta(change.old.total_bytes(), change.new.total_bytes())
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, python_packed_resources::writer::write_packed_resources_v3};
+
+    fn module<'a>(name: &'a str, source: &'a [u8], bytecode: &'a [u8]) -> Resource<'a, u8> {
+        Resource {
+            flavor: ResourceFlavor::Module,
+            name: Cow::from(name),
+            is_module: true,
+            in_memory_source: Some(Cow::from(source)),
+            in_memory_bytecode: Some(Cow::from(bytecode)),
+            ..Resource::default()
+        }
+    }
+
+    fn payload(resources: &[Resource<u8>]) -> Result<Vec<u8>> {
+        let mut data = vec![];
+        write_packed_resources_v3(resources, &mut data, None)?;
+
+        Ok(data)
+    }
+
+    #[test]
+    fn test_diff() -> Result<()> {
+        let old = summarize_resources(&payload(&[
+            module("foo", b"source", b"bytecode"),
+            module("foo.bar", b"a", b"b"),
+            module("removed", b"x", b"y"),
+        ])?)?;
+        let new = summarize_resources(&payload(&[
+            module("foo", b"source", b"bytecode"),
+            module("foo.bar", b"aaaa", b"bbbb"),
+            module("added", b"x", b"yy"),
+        ])?)?;
+
+        let diff = diff_resources(&old, &new);
+
+        assert_eq!(diff.old_totals.count, 3);
+        assert_eq!(diff.new_totals.count, 3);
+        assert_eq!(diff.old_totals.source_bytes, 8);
+        assert_eq!(diff.new_totals.bytecode_bytes, 14);
+        assert_eq!(
+            diff.added.iter().map(|e| &e.name).collect::<Vec<_>>(),
+            vec!["added"]
+        );
+        assert_eq!(
+            diff.removed.iter().map(|e| &e.name).collect::<Vec<_>>(),
+            vec!["removed"]
+        );
+        assert_eq!(
+            diff.changed.iter().map(|c| &c.name).collect::<Vec<_>>(),
+            vec!["foo.bar"]
+        );
+        assert_eq!(
+            diff.packages.get("foo"),
+            Some(&PackageSizes {
+                old_bytes: 16,
+                new_bytes: 22
+            })
+        );
+
+        let mut report = vec![];
+        diff.write_report(&mut report)?;
+        let report = String::from_utf8(report)?;
+        assert!(report.contains("total bytes: 18 -> 25 (+7)"));
+        assert!(report.contains("  + module added (3 bytes)"));
+        assert!(report.contains("  ~ module foo.bar: 2 -> 8 (+6)"));
+
+        let json = serde_json::to_value(&diff)?;
+        assert_eq!(json["added"][0]["name"], "added");
+        assert_eq!(json["added"][0]["kind"], "module");
+
+        Ok(())
+    }
+}
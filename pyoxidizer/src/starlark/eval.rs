@@ -16,7 +16,7 @@ use {
     codemap_diagnostic::{Diagnostic, Emitter},
     starlark::{
         environment::{Environment, EnvironmentError, TypeValues},
-        eval::call_stack::CallStack,
+        eval::{call_stack::CallStack, EvalException, FileLoader},
         syntax::dialect::Dialect,
         values::{
             error::{RuntimeError, ValueError},
@@ -27,13 +27,65 @@ use {
         build_target, run_target, EnvironmentContext, ResolvedTarget,
     },
     std::{
-        collections::HashMap,
+        collections::{BTreeSet, HashMap},
         convert::TryFrom,
         path::{Path, PathBuf},
         sync::{Arc, Mutex},
     },
 };
 
+/// Record a filesystem path as an input of a Starlark evaluation.
+fn record_input_path(type_values: &TypeValues, path: &Path) {
+    if let Ok(raw_context) = starlark_dialect_build_targets::get_context_value(type_values) {
+        if let Some(context) = raw_context.downcast_ref::<EnvironmentContext>() {
+            context.record_input_path(path);
+        }
+    }
+}
+
+/// A `FileLoader` that loads files from disk and records them as inputs.
+///
+/// This behaves like `starlark::eval::simple::SimpleFileLoader` except
+/// loaded files are recorded in the build targets context.
+#[derive(Clone)]
+struct RecordingFileLoader {
+    envs: Arc<Mutex<HashMap<String, Environment>>>,
+    parent_env: Environment,
+    map: Arc<Mutex<CodeMap>>,
+}
+
+impl RecordingFileLoader {
+    fn new(map: &Arc<Mutex<CodeMap>>, parent_env: Environment) -> Self {
+        Self {
+            envs: Arc::new(Mutex::new(HashMap::new())),
+            parent_env,
+            map: map.clone(),
+        }
+    }
+}
+
+impl FileLoader for RecordingFileLoader {
+    fn load(&self, path: &str, type_values: &TypeValues) -> Result<Environment, EvalException> {
+        if let Some(env) = self.envs.lock().unwrap().get(path) {
+            return Ok(env.clone());
+        }
+
+        record_input_path(type_values, Path::new(path));
+
+        let mut env = self.parent_env.child(path);
+        starlark::eval::eval_file(&self.map, path, Dialect::Bzl, &mut env, type_values, self)
+            .map_err(EvalException::DiagnosedError)?;
+        env.freeze();
+
+        self.envs
+            .lock()
+            .unwrap()
+            .insert(path.to_string(), env.clone());
+
+        Ok(env)
+    }
+}
+
 /// Builder type to construct `EvaluationContext` instances.
 pub struct EvaluationContextBuilder {
     env: crate::environment::Environment,
@@ -207,15 +259,17 @@ impl EvaluationContext {
     /// Evaluate a Starlark configuration file, returning a Diagnostic on error.
     pub fn evaluate_file_diagnostic(&mut self, config_path: &Path) -> Result<(), Diagnostic> {
         let map = Arc::new(Mutex::new(CodeMap::new()));
-        let file_loader_env = self.parent_env.clone();
+        let file_loader = RecordingFileLoader::new(&map, self.parent_env.clone());
+
+        record_input_path(&self.type_values, config_path);
 
-        starlark::eval::simple::eval_file(
+        starlark::eval::eval_file(
             &map,
             &config_path.display().to_string(),
             Dialect::Bzl,
             &mut self.child_env,
             &self.type_values,
-            file_loader_env,
+            &file_loader,
         )
         .map_err(|e| {
             if let Ok(raw_context) = self.build_targets_context_value() {
@@ -346,6 +400,19 @@ impl EvaluationContext {
             .collect::<Vec<_>>())
     }
 
+    /// Obtain filesystem paths read when evaluating the configuration.
+    ///
+    /// This includes evaluated Starlark files and paths read by functions
+    /// and methods, such as `glob()` and `PythonExecutable.read_package_root()`.
+    pub fn input_paths(&self) -> Result<BTreeSet<PathBuf>> {
+        let raw_context = self.build_targets_context_value()?;
+        let context = raw_context
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or_else(|| anyhow!("context has incorrect type"))?;
+
+        Ok(context.input_paths())
+    }
+
     /// Obtain targets that should be resolved.
     pub fn targets_to_resolve(&self) -> Result<Vec<String>> {
         let raw_context = self.build_targets_context_value()?;
@@ -420,6 +487,62 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_input_paths() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+        let env = get_env()?;
+        let logger = get_logger()?;
+
+        let data_dir = temp_dir.path().join("data");
+        std::fs::create_dir(&data_dir)?;
+        let data_path = data_dir.join("foo.txt");
+        std::fs::write(&data_path, b"foo")?;
+        let content_path = temp_dir.path().join("bar.txt");
+        std::fs::write(&content_path, b"bar")?;
+
+        let load_path = temp_dir.path().join("load.bzl");
+        std::fs::write(
+            &load_path,
+            format!(
+                "def make_files():\n    return glob(['data/*.txt'], strip_prefix = '{}/')\n",
+                temp_dir.path().display().to_string().escape_default()
+            )
+            .as_bytes(),
+        )?;
+
+        let main_path = temp_dir.path().join("main.bzl");
+        std::fs::write(
+            &main_path,
+            format!(
+                "load('{}', 'make_files')\nmake_files()\nFileContent(path = 'bar.txt')\n",
+                load_path.display().to_string().escape_default()
+            )
+            .as_bytes(),
+        )?;
+
+        let mut context = EvaluationContextBuilder::new(
+            &env,
+            logger,
+            main_path.clone(),
+            default_target_triple().to_string(),
+        )
+        .into_context()?;
+        context.evaluate_file(&main_path)?;
+
+        let input_paths = context.input_paths()?;
+        for path in &[main_path, load_path, data_dir, data_path, content_path] {
+            assert!(
+                input_paths.contains(path),
+                "{} not recorded as input",
+                path.display()
+            );
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_register_target() -> Result<()> {
         let temp_dir = tempfile::Builder::new()
@@ -14,6 +14,7 @@ use {
         python_package_resource::PythonPackageResourceValue,
         python_packaging_policy::PythonPackagingPolicyValue,
         python_resource::{is_resource_starlark_compatible, python_resource_to_value},
+        util::ToValue,
     },
     crate::{
         project_building::build_python_executable,
@@ -23,6 +24,7 @@ use {
     anyhow::{anyhow, Context, Result},
     linked_hash_map::LinkedHashMap,
     python_packaging::resource::PythonModuleSource,
+    python_packed_resources::data::BlobSectionCompression,
     slog::{info, warn},
     starlark::{
         environment::TypeValues,
@@ -40,8 +42,9 @@ use {
         },
     },
     starlark_dialect_build_targets::{
-        optional_dict_arg, optional_list_arg, optional_type_arg, required_list_arg, ResolvedTarget,
-        ResolvedTargetValue, RunMode, ToOptional,
+        get_context_value, optional_dict_arg, optional_list_arg, optional_type_arg,
+        required_list_arg, EnvironmentContext, ResolvedTarget, ResolvedTargetValue, RunMode,
+        ToOptional, TryToOptional,
     },
     std::{
         collections::HashMap,
@@ -57,6 +60,7 @@ use {
         wix_bundle_builder::WiXBundleBuilderValue,
         wix_msi_builder::WiXMsiBuilderValue,
     },
+    tugger_binary_analysis::TargetDistro,
     tugger_code_signing::SigningDestination,
     tugger_file_manifest::FileData,
 };
@@ -119,6 +123,18 @@ pub fn build_internal(
     ))
 }
 
+/// Record a filesystem path read by a method as an input of the evaluation.
+fn record_input_path(type_values: &TypeValues, path: &str) -> Result<(), ValueError> {
+    let context_value = get_context_value(type_values)?;
+    let context = context_value
+        .downcast_ref::<EnvironmentContext>()
+        .ok_or(ValueError::IncorrectParameterType)?;
+
+    context.record_input_path(path);
+
+    Ok(())
+}
+
 /// Represents a builder for a Python executable.
 pub struct PythonExecutableValue {
     // The non-Starlark API to construct the builder returns a Box<T> and it is
@@ -176,9 +192,38 @@ impl TypedValue for PythonExecutableValue {
         let exe = self.inner(&format!("PythonExecutable.{}", attribute))?;
 
         match attribute {
+            "bytecode_compiler_processes" => {
+                Ok(Value::from(exe.bytecode_compiler_processes() as i64))
+            }
             "packed_resources_load_mode" => {
                 Ok(Value::from(exe.packed_resources_load_mode().to_string()))
             }
+            "packed_resources_compression" => match exe.packed_resources_compression() {
+                Some(BlobSectionCompression::Zstd) => Ok(Value::from("zstd")),
+                Some(BlobSectionCompression::Lz4) => Ok(Value::from("lz4")),
+                Some(BlobSectionCompression::None) | None => Ok(Value::from(NoneType::None)),
+            },
+            "packed_resources_signing_key_path" => match exe.packed_resources_signing_key_path() {
+                Some(value) => Ok(Value::from(value.to_string())),
+                None => Ok(Value::from(NoneType::None)),
+            },
+            "portability_allowed_libraries" => Ok(exe
+                .portability_policy()
+                .allowed_libraries()
+                .map(|x| x.iter().cloned().collect::<Vec<_>>())
+                .to_value()),
+            "portability_max_glibc_version" => Ok(exe
+                .portability_policy()
+                .max_glibc_version()
+                .map(|x| x.to_string())
+                .to_value()),
+            "portability_target_distros" => Ok(Value::from(
+                exe.portability_policy()
+                    .target_distros()
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<_>>(),
+            )),
             "tcl_files_path" => match exe.tcl_files_path() {
                 Some(value) => Ok(Value::from(value.to_string())),
                 None => Ok(Value::from(NoneType::None)),
@@ -198,7 +243,13 @@ impl TypedValue for PythonExecutableValue {
     fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
         Ok(matches!(
             attribute,
-            "packed_resources_load_mode"
+            "bytecode_compiler_processes"
+                | "packed_resources_compression"
+                | "packed_resources_load_mode"
+                | "packed_resources_signing_key_path"
+                | "portability_allowed_libraries"
+                | "portability_max_glibc_version"
+                | "portability_target_distros"
                 | "tcl_files_path"
                 | "windows_runtime_dlls_mode"
                 | "windows_subsystem"
@@ -209,6 +260,21 @@ impl TypedValue for PythonExecutableValue {
         let mut exe = self.inner(&format!("PythonExecutable.{}", attribute))?;
 
         match attribute {
+            "bytecode_compiler_processes" => {
+                let value = value.to_int()?;
+
+                if value < 1 {
+                    return Err(ValueError::from(RuntimeError {
+                        code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                        message: "bytecode_compiler_processes must be at least 1".to_string(),
+                        label: format!("{}.{}", Self::TYPE, attribute),
+                    }));
+                }
+
+                exe.set_bytecode_compiler_processes(value as usize);
+
+                Ok(())
+            }
             "packed_resources_load_mode" => {
                 exe.set_packed_resources_load_mode(
                     PackedResourcesLoadMode::try_from(value.to_string().as_str()).map_err(|e| {
@@ -222,6 +288,74 @@ impl TypedValue for PythonExecutableValue {
 
                 Ok(())
             }
+            "packed_resources_compression" => {
+                let value: Option<String> = value.to_optional();
+
+                exe.set_packed_resources_compression(match value.as_deref() {
+                    None => None,
+                    Some("zstd") => Some(BlobSectionCompression::Zstd),
+                    Some("lz4") => Some(BlobSectionCompression::Lz4),
+                    Some(value) => return Err(ValueError::from(RuntimeError {
+                        code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                        message: format!(
+                            "invalid packed resources compression: {}; must be None, zstd, or lz4",
+                            value
+                        ),
+                        label: format!("{}.{}", Self::TYPE, attribute),
+                    })),
+                });
+
+                Ok(())
+            }
+            "packed_resources_signing_key_path" => {
+                exe.set_packed_resources_signing_key_path(value.to_optional());
+
+                Ok(())
+            }
+            "portability_allowed_libraries" => {
+                let mut policy = exe.portability_policy().clone();
+                policy.set_allowed_libraries(value.try_to_optional()?);
+                exe.set_portability_policy(policy);
+
+                Ok(())
+            }
+            "portability_max_glibc_version" => {
+                let mut policy = exe.portability_policy().clone();
+                policy
+                    .set_max_glibc_version(value.to_optional())
+                    .map_err(|e| {
+                        ValueError::from(RuntimeError {
+                            code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                            message: format!("{:?}", e),
+                            label: format!("{}.{}", Self::TYPE, attribute),
+                        })
+                    })?;
+                exe.set_portability_policy(policy);
+
+                Ok(())
+            }
+            "portability_target_distros" => {
+                let values: Option<Vec<String>> = value.try_to_optional()?;
+
+                let distros = values
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|x| TargetDistro::parse(x))
+                    .collect::<Result<Vec<_>>>()
+                    .map_err(|e| {
+                        ValueError::from(RuntimeError {
+                            code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                            message: format!("{:?}", e),
+                            label: format!("{}.{}", Self::TYPE, attribute),
+                        })
+                    })?;
+
+                let mut policy = exe.portability_policy().clone();
+                policy.set_target_distros(distros);
+                exe.set_portability_policy(policy);
+
+                Ok(())
+            }
             "tcl_files_path" => {
                 exe.set_tcl_files_path(value.to_optional());
 
@@ -469,6 +603,8 @@ impl PythonExecutableValue {
             .downcast_ref::<PyOxidizerEnvironmentContext>()
             .ok_or(ValueError::IncorrectParameterType)?;
 
+        record_input_path(type_values, &path)?;
+
         let python_packaging_policy = self.python_packaging_policy();
 
         let mut exe = self.inner(LABEL)?;
@@ -508,6 +644,8 @@ impl PythonExecutableValue {
             .downcast_ref::<PyOxidizerEnvironmentContext>()
             .ok_or(ValueError::IncorrectParameterType)?;
 
+        record_input_path(type_values, &path)?;
+
         let python_packaging_policy = self.python_packaging_policy();
 
         let mut exe = self.inner(LABEL)?;
@@ -1405,6 +1543,116 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_bytecode_compiler_processes() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        add_exe(&mut env)?;
+
+        let value = env.eval("exe.bytecode_compiler_processes")?;
+        assert_eq!(value.get_type(), "int");
+        assert_eq!(value.to_int().unwrap(), 1);
+
+        let value =
+            env.eval("exe.bytecode_compiler_processes = 4; exe.bytecode_compiler_processes")?;
+        assert_eq!(value.to_int().unwrap(), 4);
+
+        assert!(env.eval("exe.bytecode_compiler_processes = 0").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_portability_policy() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        add_exe(&mut env)?;
+
+        eval_assert(&mut env, "exe.portability_max_glibc_version == None")?;
+        eval_assert(&mut env, "exe.portability_allowed_libraries == None")?;
+        eval_assert(&mut env, "exe.portability_target_distros == []")?;
+
+        env.eval("exe.portability_max_glibc_version = '2.17'")?;
+        eval_assert(&mut env, "exe.portability_max_glibc_version == '2.17'")?;
+        assert!(env
+            .eval("exe.portability_max_glibc_version = 'GLIBC_2.17'")
+            .is_err());
+
+        env.eval("exe.portability_allowed_libraries = ['libfoo.so.1']")?;
+        eval_assert(
+            &mut env,
+            "exe.portability_allowed_libraries == ['libfoo.so.1']",
+        )?;
+
+        env.eval("exe.portability_target_distros = ['RHEL 7', 'Debian 9']")?;
+        eval_assert(
+            &mut env,
+            "exe.portability_target_distros == ['RHEL 7', 'Debian 9']",
+        )?;
+        assert!(env
+            .eval("exe.portability_target_distros = ['Gentoo 1']")
+            .is_err());
+
+        let exe = env.eval("exe")?;
+        let exe = exe.downcast_ref::<PythonExecutableValue>().unwrap();
+        let inner = exe.inner("ignored").unwrap();
+        let policy = inner.portability_policy();
+        assert_eq!(policy.max_glibc_version(), Some("2.17"));
+        assert_eq!(policy.target_distros().len(), 2);
+
+        env.eval("exe.portability_allowed_libraries = None")?;
+        eval_assert(&mut env, "exe.portability_allowed_libraries == None")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_packed_resources_compression() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        add_exe(&mut env)?;
+
+        let value = env.eval("exe.packed_resources_compression")?;
+        assert_eq!(value.get_type(), "NoneType");
+
+        let value = env
+            .eval("exe.packed_resources_compression = 'zstd'; exe.packed_resources_compression")?;
+        assert_eq!(value.to_string(), "zstd");
+
+        let value =
+            env.eval("exe.packed_resources_compression = 'lz4'; exe.packed_resources_compression")?;
+        assert_eq!(value.to_string(), "lz4");
+
+        assert!(env
+            .eval("exe.packed_resources_compression = 'gzip'")
+            .is_err());
+
+        let value =
+            env.eval("exe.packed_resources_compression = None; exe.packed_resources_compression")?;
+        assert_eq!(value.get_type(), "NoneType");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_packed_resources_signing_key_path() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        add_exe(&mut env)?;
+
+        let value = env.eval("exe.packed_resources_signing_key_path")?;
+        assert_eq!(value.get_type(), "NoneType");
+
+        let value = env.eval(
+            "exe.packed_resources_signing_key_path = 'key.pem'; exe.packed_resources_signing_key_path",
+        )?;
+        assert_eq!(value.get_type(), "string");
+        assert_eq!(value.to_string(), "key.pem");
+
+        let value = env.eval(
+            "exe.packed_resources_signing_key_path = None; exe.packed_resources_signing_key_path",
+        )?;
+        assert_eq!(value.get_type(), "NoneType");
+
+        Ok(())
+    }
+
     #[test]
     fn test_tcl_files_path() -> Result<()> {
         let mut env = test_evaluation_context_builder()?.into_context()?;
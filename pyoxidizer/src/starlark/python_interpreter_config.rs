@@ -8,7 +8,8 @@ use {
     python_packaging::{
         interpreter::{
             Allocator, BytesWarning, CheckHashPycsMode, CoerceCLocale, MemoryAllocatorBackend,
-            MultiprocessingStartMethod, PythonInterpreterProfile, TerminfoResolution,
+            MultiprocessingStartMethod, PackedResourcesVerification, PythonInterpreterProfile,
+            TerminfoResolution, CONFIG_OVERLAY_FIELDS,
         },
         resource::BytecodeOptimizationLevel,
     },
@@ -223,10 +224,21 @@ impl TypedValue for PythonInterpreterConfigValue {
             "multiprocessing_start_method" => {
                 Value::from(inner.multiprocessing_start_method.to_string())
             }
+            "packed_resources_verification" => {
+                Value::from(inner.packed_resources_verification.to_string())
+            }
+            "packed_resources_signing_certificate" => {
+                inner.packed_resources_signing_certificate.to_value()
+            }
+            "pkg_resources_extract_in_memory_resources" => {
+                Value::from(inner.pkg_resources_extract_in_memory_resources)
+            }
             "sys_frozen" => Value::from(inner.sys_frozen),
             "sys_meipass" => Value::from(inner.sys_meipass),
             "terminfo_resolution" => inner.terminfo_resolution.to_value(),
             "write_modules_directory_env" => inner.write_modules_directory_env.to_value(),
+            "config_overlay_path" => inner.config_overlay_path.to_value(),
+            "config_overlay_fields" => Value::from(inner.config_overlay_fields.clone()),
             attr => {
                 return Err(ValueError::OperationNotSupported {
                     op: UnsupportedOperation::GetAttr(attr.to_string()),
@@ -308,10 +320,15 @@ impl TypedValue for PythonInterpreterConfigValue {
                 | "argvb"
                 | "multiprocessing_auto_dispatch"
                 | "multiprocessing_start_method"
+                | "packed_resources_verification"
+                | "packed_resources_signing_certificate"
+                | "pkg_resources_extract_in_memory_resources"
                 | "sys_frozen"
                 | "sys_meipass"
                 | "terminfo_resolution"
                 | "write_modules_directory_env"
+                | "config_overlay_path"
+                | "config_overlay_fields"
         ))
     }
 
@@ -599,6 +616,24 @@ impl TypedValue for PythonInterpreterConfigValue {
                     })
                 })?;
             }
+            "packed_resources_verification" => {
+                inner.packed_resources_verification = PackedResourcesVerification::from_str(
+                    value.to_string().as_str(),
+                )
+                .map_err(|e| {
+                    ValueError::from(RuntimeError {
+                        code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                        message: e,
+                        label: format!("{}.{}", Self::TYPE, attribute),
+                    })
+                })?;
+            }
+            "packed_resources_signing_certificate" => {
+                inner.packed_resources_signing_certificate = value.to_optional();
+            }
+            "pkg_resources_extract_in_memory_resources" => {
+                inner.pkg_resources_extract_in_memory_resources = value.to_bool();
+            }
             "sys_frozen" => {
                 inner.sys_frozen = value.to_bool();
             }
@@ -618,6 +653,29 @@ impl TypedValue for PythonInterpreterConfigValue {
             "write_modules_directory_env" => {
                 inner.write_modules_directory_env = value.to_optional();
             }
+            "config_overlay_path" => {
+                inner.config_overlay_path = value.to_optional();
+            }
+            "config_overlay_fields" => {
+                let fields: Option<Vec<String>> = value.try_to_optional()?;
+                let fields = fields.unwrap_or_default();
+
+                if let Some(field) = fields
+                    .iter()
+                    .find(|field| !CONFIG_OVERLAY_FIELDS.contains(&field.as_str()))
+                {
+                    return Err(ValueError::from(RuntimeError {
+                        code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                        message: format!(
+                            "{} is not a configuration field that can be overridden",
+                            field
+                        ),
+                        label: format!("{}.{}", Self::TYPE, attribute),
+                    }));
+                }
+
+                inner.config_overlay_fields = fields;
+            }
             attr => {
                 return Err(ValueError::OperationNotSupported {
                     op: UnsupportedOperation::SetAttr(attr.to_string()),
@@ -1339,6 +1397,64 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_packed_resources_verification() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.packed_resources_verification == 'none'")?;
+
+        env.eval("config.packed_resources_verification = 'import'")?;
+        eval_assert(&mut env, "config.packed_resources_verification == 'import'")?;
+
+        env.eval("config.packed_resources_verification = 'startup'")?;
+        eval_assert(
+            &mut env,
+            "config.packed_resources_verification == 'startup'",
+        )?;
+
+        assert!(env
+            .eval("config.packed_resources_verification = 'invalid'")
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_packed_resources_signing_certificate() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(
+            &mut env,
+            "config.packed_resources_signing_certificate == None",
+        )?;
+
+        env.eval("config.packed_resources_signing_certificate = 'pem'")?;
+        eval_assert(
+            &mut env,
+            "config.packed_resources_signing_certificate == 'pem'",
+        )?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pkg_resources_extract_in_memory_resources() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(
+            &mut env,
+            "config.pkg_resources_extract_in_memory_resources == False",
+        )?;
+
+        env.eval("config.pkg_resources_extract_in_memory_resources = True")?;
+        eval_assert(
+            &mut env,
+            "config.pkg_resources_extract_in_memory_resources == True",
+        )?;
+
+        Ok(())
+    }
+
     #[test]
     fn test_sys_frozen() -> Result<()> {
         let mut env = get_env()?;
@@ -1380,4 +1496,38 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_config_overlay_path() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.config_overlay_path == None")?;
+
+        env.eval("config.config_overlay_path = '$ORIGIN/overrides.toml'")?;
+        eval_assert(
+            &mut env,
+            "config.config_overlay_path == '$ORIGIN/overrides.toml'",
+        )?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_overlay_fields() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.config_overlay_fields == []")?;
+
+        env.eval("config.config_overlay_fields = ['fault_handler', 'module_search_paths']")?;
+        eval_assert(
+            &mut env,
+            "config.config_overlay_fields == ['fault_handler', 'module_search_paths']",
+        )?;
+
+        assert!(env
+            .eval("config.config_overlay_fields = ['isolated']")
+            .is_err());
+
+        Ok(())
+    }
 }
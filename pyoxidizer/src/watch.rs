@@ -0,0 +1,234 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Watch the inputs of a build for changes. */
+
+use {
+    anyhow::{anyhow, Context, Result},
+    notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher},
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        path::{Path, PathBuf},
+        sync::mpsc::{channel, Receiver},
+        time::Duration,
+    },
+};
+
+/// How long filesystem events are debounced for.
+const DEBOUNCE_DELAY: Duration = Duration::from_millis(500);
+
+/// Describes which filesystem paths are inputs of a build.
+#[derive(Clone, Debug, Default)]
+pub struct WatchedPaths {
+    /// Files whose changes are relevant.
+    files: BTreeSet<PathBuf>,
+
+    /// Directories in which changes to any file are relevant.
+    directories: BTreeSet<PathBuf>,
+
+    /// Directories in which changes are never relevant.
+    ignored: Vec<PathBuf>,
+}
+
+impl WatchedPaths {
+    /// Construct an instance from recorded input paths.
+    ///
+    /// Relative paths are resolved against `cwd`. Directories are watched
+    /// recursively. Changes under `ignored` directories, such as the build
+    /// directory, are ignored.
+    pub fn new(cwd: &Path, input_paths: &BTreeSet<PathBuf>, ignored: &[PathBuf]) -> Self {
+        let mut res = Self {
+            ignored: ignored.iter().map(|p| cwd.join(p)).collect(),
+            ..Self::default()
+        };
+
+        for path in input_paths {
+            let path = cwd.join(path);
+
+            if path.is_dir() {
+                res.directories.insert(path);
+            } else {
+                res.files.insert(path);
+            }
+        }
+
+        res
+    }
+
+    /// Whether there are no paths to watch.
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty() && self.directories.is_empty()
+    }
+
+    /// Whether a change to a filesystem path is relevant.
+    pub fn is_relevant(&self, path: &Path) -> bool {
+        if self.ignored.iter().any(|p| path.starts_with(p)) {
+            return false;
+        }
+
+        self.files.contains(path) || self.directories.iter().any(|p| path.starts_with(p))
+    }
+
+    /// Resolve the directories to register with the filesystem watcher.
+    ///
+    /// Files are watched through their parent directory so changes are
+    /// detected when editors replace a file instead of writing to it.
+    fn watch_roots(&self) -> BTreeMap<PathBuf, RecursiveMode> {
+        let mut roots = BTreeMap::new();
+
+        for path in &self.directories {
+            roots.insert(path.clone(), RecursiveMode::Recursive);
+        }
+
+        for path in &self.files {
+            if let Some(parent) = path.parent() {
+                if parent.is_dir() {
+                    roots
+                        .entry(parent.to_path_buf())
+                        .or_insert(RecursiveMode::NonRecursive);
+                }
+            }
+        }
+
+        roots
+    }
+}
+
+/// Watches the filesystem for changes to build inputs.
+pub struct InputWatcher {
+    paths: WatchedPaths,
+    // Watching stops when the watcher is dropped.
+    _watcher: RecommendedWatcher,
+    rx: Receiver<DebouncedEvent>,
+}
+
+impl InputWatcher {
+    /// Start watching for changes to the given paths.
+    pub fn new(paths: WatchedPaths) -> Result<Self> {
+        let (tx, rx) = channel();
+        let mut watcher: RecommendedWatcher =
+            Watcher::new(tx, DEBOUNCE_DELAY).context("creating filesystem watcher")?;
+
+        for (path, mode) in paths.watch_roots() {
+            watcher
+                .watch(&path, mode)
+                .with_context(|| format!("watching {}", path.display()))?;
+        }
+
+        Ok(Self {
+            paths,
+            _watcher: watcher,
+            rx,
+        })
+    }
+
+    /// Block until a relevant change occurs.
+    ///
+    /// Returns the changed paths. An empty result means changes occurred but
+    /// the affected paths are unknown.
+    pub fn wait(&self) -> Result<BTreeSet<PathBuf>> {
+        let mut changed = BTreeSet::new();
+
+        loop {
+            let event = self
+                .rx
+                .recv()
+                .map_err(|_| anyhow!("filesystem watcher stopped unexpectedly"))?;
+
+            let rescan = self.record_event(event, &mut changed)?;
+
+            if rescan || !changed.is_empty() {
+                // Coalesce events that were delivered alongside this one.
+                while let Ok(event) = self.rx.try_recv() {
+                    self.record_event(event, &mut changed)?;
+                }
+
+                return Ok(changed);
+            }
+        }
+    }
+
+    /// Record relevant paths from an event, returning whether a rescan is required.
+    fn record_event(&self, event: DebouncedEvent, changed: &mut BTreeSet<PathBuf>) -> Result<bool> {
+        let paths = match event {
+            DebouncedEvent::Create(path)
+            | DebouncedEvent::Write(path)
+            | DebouncedEvent::Chmod(path)
+            | DebouncedEvent::Remove(path) => vec![path],
+            DebouncedEvent::Rename(from, to) => vec![from, to],
+            DebouncedEvent::Rescan => return Ok(true),
+            DebouncedEvent::Error(e, path) => {
+                return Err(match path {
+                    Some(path) => anyhow!("error watching {}: {}", path.display(), e),
+                    None => anyhow!("error watching files: {}", e),
+                });
+            }
+            DebouncedEvent::NoticeWrite(_) | DebouncedEvent::NoticeRemove(_) => vec![],
+        };
+
+        for path in paths {
+            if self.paths.is_relevant(&path) {
+                changed.insert(path);
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watched_paths() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let cwd = temp_dir.path();
+
+        let package_dir = cwd.join("package");
+        let build_dir = package_dir.join("build");
+        std::fs::create_dir_all(&build_dir)?;
+        std::fs::write(cwd.join("pyoxidizer.bzl"), b"")?;
+
+        let mut inputs = BTreeSet::new();
+        inputs.insert(PathBuf::from("pyoxidizer.bzl"));
+        inputs.insert(package_dir.clone());
+
+        let paths = WatchedPaths::new(cwd, &inputs, &[build_dir.clone()]);
+        assert!(!paths.is_empty());
+
+        assert!(paths.is_relevant(&cwd.join("pyoxidizer.bzl")));
+        assert!(paths.is_relevant(&package_dir.join("foo.py")));
+        assert!(paths.is_relevant(&package_dir.join("sub").join("bar.py")));
+        assert!(!paths.is_relevant(&cwd.join("other.txt")));
+        assert!(!paths.is_relevant(&build_dir.join("foo.o")));
+
+        let roots = paths.watch_roots();
+        assert_eq!(roots.len(), 2);
+        assert_eq!(roots.get(cwd), Some(&RecursiveMode::NonRecursive));
+        assert_eq!(roots.get(&package_dir), Some(&RecursiveMode::Recursive));
+
+        Ok(())
+    }
+
+    #[test]
+    fn input_watcher() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let input = temp_dir.path().join("input.txt");
+        std::fs::write(&input, b"foo")?;
+
+        let mut inputs = BTreeSet::new();
+        inputs.insert(input.clone());
+
+        let watcher = InputWatcher::new(WatchedPaths::new(temp_dir.path(), &inputs, &[]))?;
+
+        // Changes to unrelated files are ignored.
+        std::fs::write(temp_dir.path().join("other.txt"), b"bar")?;
+        std::fs::write(&input, b"foobar")?;
+
+        assert_eq!(watcher.wait()?, vec![input].into_iter().collect());
+
+        Ok(())
+    }
+}
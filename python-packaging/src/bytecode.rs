@@ -8,9 +8,10 @@ use {
     super::resource::BytecodeOptimizationLevel,
     anyhow::{anyhow, Context, Result},
     byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt},
+    sha2::{Digest, Sha256},
     std::{
         io::{BufRead, BufReader, Read, Write},
-        path::Path,
+        path::{Path, PathBuf},
         process,
     },
 };
@@ -30,6 +31,38 @@ pub trait PythonBytecodeCompiler {
         optimize: BytecodeOptimizationLevel,
         output_mode: CompileMode,
     ) -> Result<Vec<u8>>;
+
+    /// Compile multiple Python sources.
+    ///
+    /// Results are returned in the same order as the requests. The default
+    /// implementation compiles each request serially. Implementations may
+    /// override this to compile requests concurrently.
+    fn compile_many(&mut self, requests: &[BytecodeCompileRequest]) -> Result<Vec<Vec<u8>>> {
+        requests
+            .iter()
+            .map(|request| {
+                self.compile(
+                    &request.source,
+                    &request.filename,
+                    request.optimize,
+                    request.output_mode,
+                )
+            })
+            .collect()
+    }
+}
+
+/// A request to compile Python source into bytecode.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BytecodeCompileRequest {
+    /// Python source code to compile.
+    pub source: Vec<u8>,
+    /// Filename to record in the compiled code object.
+    pub filename: String,
+    /// Optimization level to compile with.
+    pub optimize: BytecodeOptimizationLevel,
+    /// Output format of compiled bytecode.
+    pub output_mode: CompileMode,
 }
 
 /// An entity to perform Python bytecode compilation.
@@ -42,6 +75,7 @@ pub struct BytecodeCompiler {
 }
 
 /// Output mode for BytecodeCompiler.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum CompileMode {
     /// Emit just Python bytecode.
     Bytecode,
@@ -221,6 +255,466 @@ impl Drop for BytecodeCompiler {
     }
 }
 
+/// A bytecode compiler distributing work across multiple Python processes.
+///
+/// [PythonBytecodeCompiler::compile_many] splits requests across a pool of
+/// [BytecodeCompiler] instances, each running in its own thread. Results are
+/// reassembled in request order, so output is identical to serial compilation.
+#[derive(Debug)]
+pub struct BytecodeCompilerPool {
+    compilers: Vec<BytecodeCompiler>,
+
+    /// Magic number for bytecode header.
+    magic_number: u32,
+}
+
+impl BytecodeCompilerPool {
+    /// Create a pool of `size` bytecode compilers using a Python executable.
+    ///
+    /// See [BytecodeCompiler::new] for the semantics of `script_dir`. A pool
+    /// always has at least one compiler.
+    pub fn new(python: &Path, script_dir: impl AsRef<Path>, size: usize) -> Result<Self> {
+        let compilers = (0..size.max(1))
+            .map(|_| BytecodeCompiler::new(python, script_dir.as_ref()))
+            .collect::<Result<Vec<_>>>()?;
+
+        let magic_number = compilers[0].get_magic_number();
+
+        Ok(Self {
+            compilers,
+            magic_number,
+        })
+    }
+
+    /// The number of compiler processes in this pool.
+    pub fn size(&self) -> usize {
+        self.compilers.len()
+    }
+}
+
+impl PythonBytecodeCompiler for BytecodeCompilerPool {
+    fn get_magic_number(&self) -> u32 {
+        self.magic_number
+    }
+
+    fn compile(
+        &mut self,
+        source: &[u8],
+        filename: &str,
+        optimize: BytecodeOptimizationLevel,
+        output_mode: CompileMode,
+    ) -> Result<Vec<u8>> {
+        self.compilers
+            .first_mut()
+            .ok_or_else(|| anyhow!("bytecode compiler pool is empty"))?
+            .compile(source, filename, optimize, output_mode)
+    }
+
+    fn compile_many(&mut self, requests: &[BytecodeCompileRequest]) -> Result<Vec<Vec<u8>>> {
+        let worker_count = self.compilers.len().min(requests.len());
+
+        if worker_count <= 1 {
+            return requests
+                .iter()
+                .map(|request| {
+                    self.compile(
+                        &request.source,
+                        &request.filename,
+                        request.optimize,
+                        request.output_mode,
+                    )
+                })
+                .collect();
+        }
+
+        let mut chunks = vec![vec![]; worker_count];
+        for (index, request) in requests.iter().enumerate() {
+            chunks[index % worker_count].push((index, request.clone()));
+        }
+
+        let handles = self
+            .compilers
+            .drain(0..worker_count)
+            .zip(chunks)
+            .map(|(mut compiler, chunk)| {
+                std::thread::spawn(move || {
+                    let mut results = vec![];
+
+                    for (index, request) in chunk {
+                        match compiler
+                            .compile(
+                                &request.source,
+                                &request.filename,
+                                request.optimize,
+                                request.output_mode,
+                            )
+                            .with_context(|| format!("compiling {}", request.filename))
+                        {
+                            Ok(bytecode) => results.push((index, bytecode)),
+                            Err(e) => return (compiler, Err((index, e))),
+                        }
+                    }
+
+                    (compiler, Ok(results))
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut results = vec![None; requests.len()];
+        let mut first_error: Option<(usize, anyhow::Error)> = None;
+        let mut panicked = false;
+
+        for handle in handles {
+            match handle.join() {
+                Ok((compiler, result)) => {
+                    self.compilers.push(compiler);
+
+                    match result {
+                        Ok(compiled) => {
+                            for (index, bytecode) in compiled {
+                                results[index] = Some(bytecode);
+                            }
+                        }
+                        Err((index, e)) => {
+                            let earliest = match &first_error {
+                                Some((i, _)) => index < *i,
+                                None => true,
+                            };
+                            if earliest {
+                                first_error = Some((index, e));
+                            }
+                        }
+                    }
+                }
+                Err(_) => {
+                    panicked = true;
+                }
+            }
+        }
+
+        // Report the error of the earliest request for deterministic output.
+        if let Some((_, e)) = first_error {
+            return Err(e);
+        }
+        if panicked {
+            return Err(anyhow!("bytecode compiler thread panicked"));
+        }
+
+        results
+            .into_iter()
+            .map(|bytecode| bytecode.ok_or_else(|| anyhow!("bytecode was not compiled")))
+            .collect()
+    }
+}
+
+/// A persistent, content-addressed store of compiled Python bytecode.
+///
+/// Entries are keyed by a digest of the source code, filename, bytecode magic
+/// number, optimization level, and compile mode. So an entry can only be
+/// reused if compiling would produce identical output. The cache is safe to
+/// share between processes: entries are written to a temporary file and
+/// atomically renamed into place.
+#[derive(Clone, Debug)]
+pub struct BytecodeCache {
+    /// Directory holding cache entries.
+    root: PathBuf,
+
+    /// Maximum size in bytes of all cache entries.
+    ///
+    /// Enforced by [BytecodeCache::prune].
+    max_size: Option<u64>,
+}
+
+impl BytecodeCache {
+    /// Construct an instance storing entries in a directory.
+    pub fn new(root: impl AsRef<Path>, max_size: Option<u64>) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            max_size,
+        }
+    }
+
+    /// The directory holding cache entries.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The maximum size in bytes of all cache entries.
+    pub fn max_size(&self) -> Option<u64> {
+        self.max_size
+    }
+
+    /// Compute the cache key for a compilation request.
+    pub fn cache_key(
+        source: &[u8],
+        filename: &str,
+        magic_number: u32,
+        optimize: BytecodeOptimizationLevel,
+        mode: CompileMode,
+    ) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(magic_number.to_le_bytes());
+        hasher.update(i32::from(optimize).to_le_bytes());
+        hasher.update(match mode {
+            CompileMode::Bytecode => b"bytecode\0" as &[u8],
+            CompileMode::PycCheckedHash => b"pyc-checked-hash\0",
+            CompileMode::PycUncheckedHash => b"pyc-unchecked-hash\0",
+        });
+        hasher.update((filename.len() as u64).to_le_bytes());
+        hasher.update(filename.as_bytes());
+        hasher.update(source);
+
+        hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>()
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.root.join(&key[0..2]).join(key)
+    }
+
+    /// Obtain the bytecode stored for a cache key, if present.
+    ///
+    /// The modification time of a found entry is updated so [BytecodeCache::prune]
+    /// evicts the least recently used entries.
+    pub fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let path = self.entry_path(key);
+
+        match std::fs::read(&path) {
+            Ok(data) => {
+                // Failing to record the access only affects eviction order.
+                let _ = filetime::set_file_mtime(&path, filetime::FileTime::now());
+
+                Ok(Some(data))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("reading {}", path.display())),
+        }
+    }
+
+    /// Store bytecode for a cache key.
+    pub fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        let path = self.entry_path(key);
+        let parent = path.parent().expect("entry path should have a parent");
+
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("creating directory {}", parent.display()))?;
+
+        let temp_path = parent.join(format!("{}.{}.tmp", key, process::id()));
+        std::fs::write(&temp_path, data)
+            .with_context(|| format!("writing {}", temp_path.display()))?;
+        std::fs::rename(&temp_path, &path)
+            .with_context(|| format!("renaming {} to {}", temp_path.display(), path.display()))?;
+
+        Ok(())
+    }
+
+    /// Obtain the total size in bytes of all cache entries.
+    pub fn size(&self) -> Result<u64> {
+        Ok(self.entries()?.iter().map(|(_, size, _)| size).sum())
+    }
+
+    fn entries(&self) -> Result<Vec<(PathBuf, u64, std::time::SystemTime)>> {
+        let mut res = vec![];
+
+        if !self.root.exists() {
+            return Ok(res);
+        }
+
+        for entry in walkdir::WalkDir::new(&self.root) {
+            let entry = entry?;
+
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            let metadata = entry.metadata()?;
+            res.push((
+                entry.path().to_path_buf(),
+                metadata.len(),
+                metadata.modified()?,
+            ));
+        }
+
+        Ok(res)
+    }
+
+    /// Delete the least recently used entries until the cache fits its size limit.
+    ///
+    /// Returns the number of entries deleted.
+    pub fn prune(&self) -> Result<usize> {
+        let max_size = if let Some(max_size) = self.max_size {
+            max_size
+        } else {
+            return Ok(0);
+        };
+
+        let mut entries = self.entries()?;
+        let mut size = entries.iter().map(|(_, size, _)| size).sum::<u64>();
+
+        entries.sort_by(|a, b| a.2.cmp(&b.2).then_with(|| a.0.cmp(&b.0)));
+
+        let mut removed = 0;
+        for (path, entry_size, _) in entries {
+            if size <= max_size {
+                break;
+            }
+
+            std::fs::remove_file(&path).with_context(|| format!("removing {}", path.display()))?;
+            size -= entry_size;
+            removed += 1;
+        }
+
+        Ok(removed)
+    }
+
+    /// Delete all cache entries.
+    pub fn clear(&self) -> Result<()> {
+        if self.root.exists() {
+            std::fs::remove_dir_all(&self.root)
+                .with_context(|| format!("removing {}", self.root.display()))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Statistics of [CachingBytecodeCompiler] cache lookups.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct BytecodeCacheStats {
+    /// Number of compilations satisfied from the cache.
+    pub hits: usize,
+    /// Number of compilations that had to invoke the underlying compiler.
+    pub misses: usize,
+}
+
+/// A [PythonBytecodeCompiler] that consults a [BytecodeCache] before compiling.
+///
+/// Compilation requests not in the cache are proxied to another compiler and
+/// its output is stored in the cache. Failures to write the cache are not
+/// fatal: the compiled bytecode is returned regardless.
+pub struct CachingBytecodeCompiler<'a> {
+    inner: &'a mut dyn PythonBytecodeCompiler,
+    cache: BytecodeCache,
+    stats: BytecodeCacheStats,
+}
+
+impl<'a> CachingBytecodeCompiler<'a> {
+    /// Construct an instance wrapping another compiler.
+    pub fn new(inner: &'a mut dyn PythonBytecodeCompiler, cache: BytecodeCache) -> Self {
+        Self {
+            inner,
+            cache,
+            stats: BytecodeCacheStats::default(),
+        }
+    }
+
+    /// The cache used by this compiler.
+    pub fn cache(&self) -> &BytecodeCache {
+        &self.cache
+    }
+
+    /// Obtain statistics of cache lookups performed so far.
+    pub fn stats(&self) -> BytecodeCacheStats {
+        self.stats
+    }
+}
+
+impl<'a> PythonBytecodeCompiler for CachingBytecodeCompiler<'a> {
+    fn get_magic_number(&self) -> u32 {
+        self.inner.get_magic_number()
+    }
+
+    fn compile(
+        &mut self,
+        source: &[u8],
+        filename: &str,
+        optimize: BytecodeOptimizationLevel,
+        output_mode: CompileMode,
+    ) -> Result<Vec<u8>> {
+        let key = BytecodeCache::cache_key(
+            source,
+            filename,
+            self.inner.get_magic_number(),
+            optimize,
+            output_mode,
+        );
+
+        // An unreadable entry is treated like a missing one.
+        if let Ok(Some(bytecode)) = self.cache.get(&key) {
+            self.stats.hits += 1;
+            return Ok(bytecode);
+        }
+
+        self.stats.misses += 1;
+
+        let bytecode = self
+            .inner
+            .compile(source, filename, optimize, output_mode)?;
+        let _ = self.cache.put(&key, &bytecode);
+
+        Ok(bytecode)
+    }
+
+    fn compile_many(&mut self, requests: &[BytecodeCompileRequest]) -> Result<Vec<Vec<u8>>> {
+        let magic_number = self.inner.get_magic_number();
+
+        let keys = requests
+            .iter()
+            .map(|request| {
+                BytecodeCache::cache_key(
+                    &request.source,
+                    &request.filename,
+                    magic_number,
+                    request.optimize,
+                    request.output_mode,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let mut results = keys
+            .iter()
+            .map(|key| self.cache.get(key).unwrap_or(None))
+            .collect::<Vec<_>>();
+
+        let missing = results
+            .iter()
+            .enumerate()
+            .filter_map(|(index, bytecode)| {
+                if bytecode.is_none() {
+                    Some(index)
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        self.stats.hits += requests.len() - missing.len();
+        self.stats.misses += missing.len();
+
+        if !missing.is_empty() {
+            let missing_requests = missing
+                .iter()
+                .map(|index| requests[*index].clone())
+                .collect::<Vec<_>>();
+
+            let compiled = self.inner.compile_many(&missing_requests)?;
+
+            for (index, bytecode) in missing.into_iter().zip(compiled) {
+                let _ = self.cache.put(&keys[index], &bytecode);
+                results[index] = Some(bytecode);
+            }
+        }
+
+        results
+            .into_iter()
+            .map(|bytecode| bytecode.ok_or_else(|| anyhow!("bytecode was not compiled")))
+            .collect()
+    }
+}
+
 /// How to write out a .pyc bytecode header.
 #[derive(Debug, Clone, Copy)]
 pub enum BytecodeHeaderMode {
@@ -263,6 +757,205 @@ pub fn compute_bytecode_header(magic_number: u32, mode: BytecodeHeaderMode) -> R
 mod tests {
     use super::*;
 
+    struct CountingCompiler {
+        count: usize,
+    }
+
+    impl PythonBytecodeCompiler for CountingCompiler {
+        fn get_magic_number(&self) -> u32 {
+            42
+        }
+
+        fn compile(
+            &mut self,
+            source: &[u8],
+            filename: &str,
+            _optimize: BytecodeOptimizationLevel,
+            _output_mode: CompileMode,
+        ) -> Result<Vec<u8>> {
+            self.count += 1;
+
+            let mut res = filename.as_bytes().to_vec();
+            res.extend(source);
+
+            Ok(res)
+        }
+    }
+
+    #[test]
+    fn test_cache_key() {
+        let key = BytecodeCache::cache_key(
+            b"foo",
+            "foo.py",
+            42,
+            BytecodeOptimizationLevel::Zero,
+            CompileMode::Bytecode,
+        );
+        assert_eq!(key.len(), 64);
+
+        for other in &[
+            BytecodeCache::cache_key(
+                b"bar",
+                "foo.py",
+                42,
+                BytecodeOptimizationLevel::Zero,
+                CompileMode::Bytecode,
+            ),
+            BytecodeCache::cache_key(
+                b"foo",
+                "bar.py",
+                42,
+                BytecodeOptimizationLevel::Zero,
+                CompileMode::Bytecode,
+            ),
+            BytecodeCache::cache_key(
+                b"foo",
+                "foo.py",
+                43,
+                BytecodeOptimizationLevel::Zero,
+                CompileMode::Bytecode,
+            ),
+            BytecodeCache::cache_key(
+                b"foo",
+                "foo.py",
+                42,
+                BytecodeOptimizationLevel::One,
+                CompileMode::Bytecode,
+            ),
+            BytecodeCache::cache_key(
+                b"foo",
+                "foo.py",
+                42,
+                BytecodeOptimizationLevel::Zero,
+                CompileMode::PycCheckedHash,
+            ),
+        ] {
+            assert_ne!(&key, other);
+        }
+    }
+
+    #[test]
+    fn test_caching_compiler() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let cache = BytecodeCache::new(temp_dir.path(), None);
+
+        let mut inner = CountingCompiler { count: 0 };
+        {
+            let mut compiler = CachingBytecodeCompiler::new(&mut inner, cache.clone());
+            assert_eq!(compiler.get_magic_number(), 42);
+
+            let bytecode = compiler.compile(
+                b"foo",
+                "foo.py",
+                BytecodeOptimizationLevel::Zero,
+                CompileMode::Bytecode,
+            )?;
+            assert_eq!(bytecode, b"foo.pyfoo");
+            assert_eq!(compiler.stats(), BytecodeCacheStats { hits: 0, misses: 1 });
+        }
+        assert_eq!(inner.count, 1);
+
+        let mut compiler = CachingBytecodeCompiler::new(&mut inner, cache.clone());
+        let bytecode = compiler.compile(
+            b"foo",
+            "foo.py",
+            BytecodeOptimizationLevel::Zero,
+            CompileMode::Bytecode,
+        )?;
+        assert_eq!(bytecode, b"foo.pyfoo");
+        compiler.compile(
+            b"foo",
+            "foo.py",
+            BytecodeOptimizationLevel::Two,
+            CompileMode::Bytecode,
+        )?;
+        assert_eq!(compiler.stats(), BytecodeCacheStats { hits: 1, misses: 1 });
+        assert_eq!(inner.count, 2);
+
+        assert_eq!(cache.size()?, 18);
+        cache.clear()?;
+        assert_eq!(cache.size()?, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_caching_compiler_compile_many() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let cache = BytecodeCache::new(temp_dir.path(), None);
+
+        let request = |name: &str| BytecodeCompileRequest {
+            source: b"source".to_vec(),
+            filename: name.to_string(),
+            optimize: BytecodeOptimizationLevel::Zero,
+            output_mode: CompileMode::Bytecode,
+        };
+
+        let mut inner = CountingCompiler { count: 0 };
+        let mut compiler = CachingBytecodeCompiler::new(&mut inner, cache);
+
+        compiler.compile_many(&[request("b.py")])?;
+
+        let results =
+            compiler.compile_many(&[request("a.py"), request("b.py"), request("c.py")])?;
+        assert_eq!(
+            results,
+            vec![
+                b"a.pysource".to_vec(),
+                b"b.pysource".to_vec(),
+                b"c.pysource".to_vec()
+            ]
+        );
+        assert_eq!(compiler.stats(), BytecodeCacheStats { hits: 1, misses: 3 });
+        assert_eq!(inner.count, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache_prune() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let cache = BytecodeCache::new(temp_dir.path(), Some(10));
+
+        cache.put(&"a".repeat(64), b"123456")?;
+        assert_eq!(cache.prune()?, 0);
+
+        cache.put(&"b".repeat(64), b"123456")?;
+        assert_eq!(cache.size()?, 12);
+        assert_eq!(cache.prune()?, 1);
+        assert_eq!(cache.size()?, 6);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache_prune_lru() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let cache = BytecodeCache::new(temp_dir.path(), Some(10));
+
+        let a = "a".repeat(64);
+        let b = "b".repeat(64);
+
+        cache.put(&a, b"123456")?;
+        cache.put(&b, b"123456")?;
+        filetime::set_file_mtime(
+            cache.entry_path(&a),
+            filetime::FileTime::from_unix_time(1000, 0),
+        )?;
+        filetime::set_file_mtime(
+            cache.entry_path(&b),
+            filetime::FileTime::from_unix_time(2000, 0),
+        )?;
+
+        // Reading the older entry makes it the most recently used.
+        assert_eq!(cache.get(&a)?, Some(b"123456".to_vec()));
+        assert_eq!(cache.prune()?, 1);
+        assert_eq!(cache.get(&a)?, Some(b"123456".to_vec()));
+        assert_eq!(cache.get(&b)?, None);
+
+        Ok(())
+    }
+
     #[test]
     fn test_header() -> Result<()> {
         assert_eq!(
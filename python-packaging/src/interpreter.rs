@@ -345,6 +345,98 @@ impl FromStr for MultiprocessingStartMethod {
     }
 }
 
+/// Defines when integrity digests of packed resources are verified.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PackedResourcesVerification {
+    /// Do not verify packed resources.
+    None,
+    /// Verify each resource's content digest when it is first loaded.
+    ///
+    /// Resources failing verification can't be imported or loaded.
+    Import,
+    /// Verify all packed resources before the interpreter is started.
+    ///
+    /// Any verification failure prevents the interpreter from starting.
+    Startup,
+}
+
+impl Default for PackedResourcesVerification {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+impl ToString for PackedResourcesVerification {
+    fn to_string(&self) -> String {
+        match self {
+            Self::None => "none",
+            Self::Import => "import",
+            Self::Startup => "startup",
+        }
+        .to_string()
+    }
+}
+
+impl FromStr for PackedResourcesVerification {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "import" => Ok(Self::Import),
+            "startup" => Ok(Self::Startup),
+            _ => Err(format!(
+                "{} is not a valid packed resources verification mode",
+                s
+            )),
+        }
+    }
+}
+
+/// Configuration fields that can be overridden at run-time by a configuration overlay file.
+///
+/// Names match the attributes of the Starlark `PythonInterpreterConfig` type.
+/// Fields influencing how the binary was built, such as the memory allocator
+/// backend or the packed resources, can't be overridden.
+pub const CONFIG_OVERLAY_FIELDS: &[&str] = &[
+    "allocator",
+    "allocator_debug",
+    "allocator_mem",
+    "allocator_obj",
+    "allocator_pymalloc_arena",
+    "allocator_raw",
+    "argvb",
+    "buffered_stdio",
+    "development_mode",
+    "fault_handler",
+    "filesystem_importer",
+    "import_time",
+    "inspect",
+    "malloc_stats",
+    "module_search_paths",
+    "multiprocessing_auto_dispatch",
+    "multiprocessing_start_method",
+    "parser_debug",
+    "pkg_resources_extract_in_memory_resources",
+    "pycache_prefix",
+    "quiet",
+    "show_ref_count",
+    "site_import",
+    "sys_frozen",
+    "sys_meipass",
+    "tcl_library",
+    "terminfo_resolution",
+    "tracemalloc",
+    "use_environment",
+    "user_site_directory",
+    "utf8_mode",
+    "verbose",
+    "warn_options",
+    "write_bytecode",
+    "write_modules_directory_env",
+    "x_options",
+];
+
 /// Holds configuration of a Python interpreter.
 ///
 /// This struct holds fields that are exposed by `PyPreConfig` and
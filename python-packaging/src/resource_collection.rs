@@ -7,7 +7,8 @@
 use {
     crate::{
         bytecode::{
-            compute_bytecode_header, BytecodeHeaderMode, CompileMode, PythonBytecodeCompiler,
+            compute_bytecode_header, BytecodeCache, BytecodeCompileRequest, BytecodeHeaderMode,
+            CompileMode, PythonBytecodeCompiler,
         },
         libpython::LibPythonBuildContext,
         location::{AbstractResourceLocation, ConcreteResourceLocation},
@@ -20,10 +21,13 @@ use {
         },
     },
     anyhow::{anyhow, Context, Result},
-    python_packed_resources::data::{Resource, ResourceFlavor},
+    python_packed_resources::{
+        data::{BlobSectionCompression, Resource, ResourceFlavor},
+        integrity::{append_integrity_trailer, resource_content_sha256},
+    },
     std::{
         borrow::Cow,
-        collections::{BTreeMap, BTreeSet, HashMap},
+        collections::{BTreeMap, BTreeSet, HashMap, HashSet},
         convert::TryFrom,
         path::PathBuf,
     },
@@ -400,6 +404,7 @@ impl PrePackagedResource {
             } else {
                 None
             },
+            content_sha256: None,
         };
 
         if let Some((prefix, filename, location)) = &self.relative_path_shared_library {
@@ -624,17 +629,69 @@ pub struct CompiledResourcesCollection<'a> {
 }
 
 impl<'a> CompiledResourcesCollection<'a> {
-    /// Write resources to packed resources data, version 1.
-    pub fn write_packed_resources<W: std::io::Write>(&self, writer: &mut W) -> Result<()> {
-        python_packed_resources::writer::write_packed_resources_v3(
-            &self
-                .resources
-                .values()
-                .cloned()
-                .collect::<Vec<Resource<'a, u8>>>(),
-            writer,
+    /// Write resources to packed resources data.
+    ///
+    /// Version 3 of the format is written unless `compression` is defined,
+    /// in which case blob sections are compressed and version 4 is written.
+    pub fn write_packed_resources<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+        compression: Option<BlobSectionCompression>,
+    ) -> Result<()> {
+        let resources = self
+            .resources
+            .values()
+            .cloned()
+            .collect::<Vec<Resource<'a, u8>>>();
+
+        if compression.is_some() {
+            python_packed_resources::writer::write_packed_resources_v4(
+                &resources,
+                writer,
+                None,
+                compression,
+            )
+        } else {
+            python_packed_resources::writer::write_packed_resources_v3(&resources, writer, None)
+        }
+    }
+
+    /// Write resources to packed resources data with integrity metadata.
+    ///
+    /// Every resource is given a content digest and the data is followed by
+    /// an integrity trailer. `sign` receives the digest of the packed
+    /// resources data and returns an optional detached signature over it.
+    /// Blob sections are compressed with `compression`, if defined.
+    pub fn write_packed_resources_with_integrity<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+        compression: Option<BlobSectionCompression>,
+        sign: impl FnOnce(&[u8]) -> Result<Option<Vec<u8>>>,
+    ) -> Result<()> {
+        let resources = self
+            .resources
+            .values()
+            .map(|resource| {
+                let mut resource = resource.clone();
+                resource.content_sha256 =
+                    Some(Cow::Owned(resource_content_sha256(&resource).to_vec()));
+
+                resource
+            })
+            .collect::<Vec<Resource<'a, u8>>>();
+
+        let mut data = vec![];
+        python_packed_resources::writer::write_packed_resources_v4(
+            &resources,
+            &mut data,
             None,
-        )
+            compression,
+        )?;
+        append_integrity_trailer(&mut data, sign)?;
+
+        writer
+            .write_all(&data)
+            .context("writing packed resources data")
     }
 }
 
@@ -1808,12 +1865,32 @@ impl PythonResourceCollector {
         let mut input_resources = self.resources.clone();
         populate_parent_packages(&mut input_resources).context("populating parent packages")?;
 
+        // Bytecode is compiled in a single batch so the compiler can process
+        // requests concurrently. So we convert resources once to collect
+        // compilation requests and again to consume the compiled bytecode.
+        let mut recorder = RecordingBytecodeCompiler::new(compiler.get_magic_number());
+
+        for (name, resource) in &input_resources {
+            resource
+                .to_resource(&mut recorder)
+                .with_context(|| format!("converting {} to resource", name))?;
+        }
+
+        let compiled = compiler
+            .compile_many(&recorder.requests)
+            .context("compiling bytecode")?;
+
+        let mut precompiled = PrecompiledBytecodeCompiler {
+            magic_number: recorder.magic_number,
+            bytecode: recorder.keys.into_iter().zip(compiled).collect(),
+        };
+
         let mut resources = BTreeMap::new();
         let mut extra_files = Vec::new();
 
         for (name, resource) in &input_resources {
             let (entry, installs) = resource
-                .to_resource(compiler)
+                .to_resource(&mut precompiled)
                 .with_context(|| format!("converting {} to resource", name))?;
 
             for install in installs {
@@ -1830,6 +1907,84 @@ impl PythonResourceCollector {
     }
 }
 
+/// A [PythonBytecodeCompiler] that records compilation requests without compiling.
+struct RecordingBytecodeCompiler {
+    magic_number: u32,
+    /// Unique compilation requests, in the order they were first seen.
+    requests: Vec<BytecodeCompileRequest>,
+    /// Cache keys of `requests`.
+    keys: Vec<String>,
+    seen: HashSet<String>,
+}
+
+impl RecordingBytecodeCompiler {
+    fn new(magic_number: u32) -> Self {
+        Self {
+            magic_number,
+            requests: vec![],
+            keys: vec![],
+            seen: HashSet::new(),
+        }
+    }
+}
+
+impl PythonBytecodeCompiler for RecordingBytecodeCompiler {
+    fn get_magic_number(&self) -> u32 {
+        self.magic_number
+    }
+
+    fn compile(
+        &mut self,
+        source: &[u8],
+        filename: &str,
+        optimize: BytecodeOptimizationLevel,
+        output_mode: CompileMode,
+    ) -> Result<Vec<u8>> {
+        let key =
+            BytecodeCache::cache_key(source, filename, self.magic_number, optimize, output_mode);
+
+        if self.seen.insert(key.clone()) {
+            self.keys.push(key);
+            self.requests.push(BytecodeCompileRequest {
+                source: source.to_vec(),
+                filename: filename.to_string(),
+                optimize,
+                output_mode,
+            });
+        }
+
+        Ok(vec![])
+    }
+}
+
+/// A [PythonBytecodeCompiler] returning bytecode that was compiled ahead of time.
+struct PrecompiledBytecodeCompiler {
+    magic_number: u32,
+    bytecode: HashMap<String, Vec<u8>>,
+}
+
+impl PythonBytecodeCompiler for PrecompiledBytecodeCompiler {
+    fn get_magic_number(&self) -> u32 {
+        self.magic_number
+    }
+
+    fn compile(
+        &mut self,
+        source: &[u8],
+        filename: &str,
+        optimize: BytecodeOptimizationLevel,
+        output_mode: CompileMode,
+    ) -> Result<Vec<u8>> {
+        let key =
+            BytecodeCache::cache_key(source, filename, self.magic_number, optimize, output_mode);
+
+        self.bytecode
+            .get(&key)
+            .cloned()
+            .ok_or_else(|| anyhow!("bytecode for {} was not compiled", filename))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use {
@@ -4524,6 +4679,84 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_write_packed_resources_with_integrity() -> Result<()> {
+        let mut r = PythonResourceCollector::new(
+            vec![AbstractResourceLocation::InMemory],
+            vec![],
+            false,
+            true,
+            DEFAULT_CACHE_TAG,
+        );
+        r.add_file_data(
+            &File::new("foo/bar.py", vec![42]),
+            &ConcreteResourceLocation::InMemory,
+        )?;
+
+        let mut compiler = FakeBytecodeCompiler { magic_number: 42 };
+        let resources = r.compile_resources(&mut compiler)?;
+
+        let mut data = vec![];
+        resources.write_packed_resources_with_integrity(&mut data, None, |_| {
+            Ok(Some(b"signature".to_vec()))
+        })?;
+
+        let trailer = python_packed_resources::integrity::find_integrity_trailer(&data)
+            .map_err(|e| anyhow!(e))?
+            .unwrap();
+        assert_eq!(trailer.verify_payload_digest(), Ok(()));
+        assert_eq!(trailer.signature, Some(&b"signature"[..]));
+
+        let parsed = python_packed_resources::parser::load_resources(&data)
+            .map_err(|e| anyhow!(e))?
+            .collect::<Result<Vec<_>, &'static str>>()
+            .map_err(|e| anyhow!(e))?;
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(
+            python_packed_resources::integrity::verify_resource_content_sha256(&parsed[0]),
+            Ok(true)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_packed_resources_compression() -> Result<()> {
+        let mut r = PythonResourceCollector::new(
+            vec![AbstractResourceLocation::InMemory],
+            vec![],
+            false,
+            true,
+            DEFAULT_CACHE_TAG,
+        );
+        r.add_file_data(
+            &File::new("foo/bar.py", vec![42; 64]),
+            &ConcreteResourceLocation::InMemory,
+        )?;
+
+        let mut compiler = FakeBytecodeCompiler { magic_number: 42 };
+        let resources = r.compile_resources(&mut compiler)?;
+
+        let mut uncompressed = vec![];
+        resources.write_packed_resources(&mut uncompressed, None)?;
+        assert_eq!(&uncompressed[0..8], b"pyembed\x03");
+
+        let mut compressed = vec![];
+        resources.write_packed_resources(&mut compressed, Some(BlobSectionCompression::Zstd))?;
+        assert_eq!(&compressed[0..8], b"pyembed\x04");
+        assert!(compressed.len() < uncompressed.len());
+
+        let parse = |data| -> Result<Vec<_>> {
+            python_packed_resources::parser::load_resources(data)
+                .map_err(|e| anyhow!(e))?
+                .collect::<Result<Vec<_>, &'static str>>()
+                .map_err(|e| anyhow!(e))
+        };
+        assert_eq!(parse(&compressed)?, parse(&uncompressed)?);
+
+        Ok(())
+    }
+
     #[test]
     fn test_add_relative_path_file_data() -> Result<()> {
         let mut r = PythonResourceCollector::new(
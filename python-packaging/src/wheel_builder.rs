@@ -13,9 +13,12 @@ use {
         io::{Seek, Write},
         path::{Path, PathBuf},
     },
-    tugger_file_manifest::{FileEntry, FileManifest},
+    tugger_file_manifest::{FileEntry, FileManifest, Reproducibility},
 };
 
+/// Earliest time representable in zip files (1980-01-01T00:00:00Z).
+const ZIP_EPOCH: i64 = 315_532_800;
+
 /// Wheel filename component escape regular expression.
 static RE_FILENAME_ESCAPE: Lazy<regex::Regex> =
     Lazy::new(|| regex::Regex::new(r"[^\w\d.]+").unwrap());
@@ -108,9 +111,10 @@ static RE_FILENAME_ESCAPE: Lazy<regex::Regex> =
 ///
 /// Wheel zip archive content is deterministic for the same builder instance.
 /// For separate builder instances, content can be made identical by calling
-/// [Self::set_modified_time] to set the modified time and using identical input
-/// settings/files. (The modified time of files in zip files defaults to the time
-/// when the builder instance was created, which is obviously not deterministic.)
+/// [Self::set_modified_time] or [Self::set_reproducibility] to set the modified
+/// time and using identical input settings/files. (The modified time of files in
+/// zip files defaults to the time when the builder instance was created, which is
+/// obviously not deterministic.)
 ///
 /// # Validation
 ///
@@ -270,6 +274,17 @@ impl WheelBuilder {
         self.modified_time = v;
     }
 
+    /// Write the wheel archive reproducibly.
+    ///
+    /// Archive members always have normalized permissions and ordering. So this
+    /// sets the modified time. Times before 1980 can't be represented in zip
+    /// files and are clamped to 1980-01-01.
+    pub fn set_reproducibility(&mut self, reproducibility: Reproducibility) {
+        let seconds = std::cmp::max(reproducibility.mtime() as i64, ZIP_EPOCH);
+
+        self.modified_time = time::at_utc(time::Timespec::new(seconds, 0));
+    }
+
     fn normalized_distribution(&self) -> String {
         self.distribution.to_lowercase().replace('-', "_")
     }
@@ -6,9 +6,14 @@
 
 use std::{borrow::Cow, collections::HashMap, convert::TryFrom, path::Path};
 
-/// Header value for version 2 of resources payload.
+/// Header value for version 3 of resources payload.
 pub const HEADER_V3: &[u8] = b"pyembed\x03";
 
+/// Header value for version 4 of resources payload.
+///
+/// Version 4 is identical to version 3 except blob sections may be compressed.
+pub const HEADER_V4: &[u8] = b"pyembed\x04";
+
 /// Defines the type of a resource.
 ///
 /// This is deprecated in favor of individual boolean fields on resources
@@ -81,6 +86,54 @@ impl From<&BlobInteriorPadding> for u8 {
     }
 }
 
+/// Defines the compression codec applied to a blob section.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BlobSectionCompression {
+    /// No compression.
+    ///
+    /// The blob section data is stored as-is.
+    None = 0x01,
+
+    /// zstandard compression.
+    ///
+    /// The entire blob section is a single zstandard frame.
+    Zstd = 0x02,
+
+    /// LZ4 compression.
+    ///
+    /// The entire blob section is a single LZ4 block.
+    Lz4 = 0x03,
+}
+
+impl Default for BlobSectionCompression {
+    fn default() -> Self {
+        BlobSectionCompression::None
+    }
+}
+
+impl From<&BlobSectionCompression> for u8 {
+    fn from(source: &BlobSectionCompression) -> Self {
+        match source {
+            BlobSectionCompression::None => 0x01,
+            BlobSectionCompression::Zstd => 0x02,
+            BlobSectionCompression::Lz4 => 0x03,
+        }
+    }
+}
+
+impl TryFrom<u8> for BlobSectionCompression {
+    type Error = &'static str;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(BlobSectionCompression::None),
+            0x02 => Ok(BlobSectionCompression::Zstd),
+            0x03 => Ok(BlobSectionCompression::Lz4),
+            _ => Err("invalid value for blob section compression"),
+        }
+    }
+}
+
 /// Describes a blob section field type in the blob index.
 #[derive(Debug, PartialEq, PartialOrd)]
 pub enum BlobSectionField {
@@ -90,6 +143,8 @@ pub enum BlobSectionField {
     ResourceFieldType = 0x03,
     RawPayloadLength = 0x04,
     InteriorPadding = 0x05,
+    Compression = 0x06,
+    UncompressedPayloadLength = 0x07,
 }
 
 impl From<BlobSectionField> for u8 {
@@ -100,6 +155,8 @@ impl From<BlobSectionField> for u8 {
             BlobSectionField::ResourceFieldType => 0x02,
             BlobSectionField::RawPayloadLength => 0x03,
             BlobSectionField::InteriorPadding => 0x04,
+            BlobSectionField::Compression => 0x05,
+            BlobSectionField::UncompressedPayloadLength => 0x06,
             BlobSectionField::EndOfEntry => 0xff,
         }
     }
@@ -115,6 +172,8 @@ impl TryFrom<u8> for BlobSectionField {
             0x02 => Ok(BlobSectionField::ResourceFieldType),
             0x03 => Ok(BlobSectionField::RawPayloadLength),
             0x04 => Ok(BlobSectionField::InteriorPadding),
+            0x05 => Ok(BlobSectionField::Compression),
+            0x06 => Ok(BlobSectionField::UncompressedPayloadLength),
             0xff => Ok(BlobSectionField::EndOfEntry),
             _ => Err("invalid blob index field type"),
         }
@@ -156,6 +215,7 @@ pub enum ResourceField {
     FileExecutable = 0x1c,
     FileDataEmbedded = 0x1d,
     FileDataUtf8RelativePath = 0x1e,
+    ContentSha256 = 0x1f,
 }
 
 impl From<ResourceField> for u8 {
@@ -192,6 +252,7 @@ impl From<ResourceField> for u8 {
             ResourceField::FileExecutable => 0x1c,
             ResourceField::FileDataEmbedded => 0x1d,
             ResourceField::FileDataUtf8RelativePath => 0x1e,
+            ResourceField::ContentSha256 => 0x1f,
             ResourceField::EndOfEntry => 0xff,
         }
     }
@@ -233,6 +294,7 @@ impl TryFrom<u8> for ResourceField {
             0x1c => Ok(ResourceField::FileExecutable),
             0x1d => Ok(ResourceField::FileDataEmbedded),
             0x1e => Ok(ResourceField::FileDataUtf8RelativePath),
+            0x1f => Ok(ResourceField::ContentSha256),
             0xff => Ok(ResourceField::EndOfEntry),
             _ => Err("invalid field type"),
         }
@@ -351,6 +413,11 @@ where
 
     /// Holds arbitrary file data in a relative path encoded in UTF-8.
     pub file_data_utf8_relative_path: Option<Cow<'a, str>>,
+
+    /// SHA-256 digest of the resource's content.
+    ///
+    /// See `crate::integrity::resource_content_sha256()` for how this is computed.
+    pub content_sha256: Option<Cow<'a, [X]>>,
 }
 
 impl<'a, X> Default for Resource<'a, X>
@@ -388,6 +455,7 @@ where
             file_executable: false,
             file_data_embedded: None,
             file_data_utf8_relative_path: None,
+            content_sha256: None,
         }
     }
 }
@@ -480,6 +548,9 @@ where
         if let Some(value) = other.file_data_utf8_relative_path {
             self.file_data_utf8_relative_path.replace(value);
         }
+        if let Some(value) = other.content_sha256 {
+            self.content_sha256.replace(value);
+        }
 
         Ok(())
     }
@@ -608,6 +679,10 @@ where
                 .file_data_utf8_relative_path
                 .as_ref()
                 .map(|value| Cow::Owned(value.clone().into_owned())),
+            content_sha256: self
+                .content_sha256
+                .as_ref()
+                .map(|value| Cow::Owned(value.clone().into_owned())),
         }
     }
 }
@@ -0,0 +1,678 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Inspect packed resources data embedded in other files.
+
+Executables produced by PyOxidizer embed packed resources data verbatim
+(e.g. in the read-only data section of an ELF, PE, or Mach-O binary).
+The functionality in this module locates that data so it can be parsed
+and allows resource data to be extracted back to files.
+*/
+
+use {
+    crate::{
+        data::{Resource, ResourceFlavor, HEADER_V3, HEADER_V4},
+        parser::load_resources,
+    },
+    anyhow::{anyhow, Result},
+    std::{
+        borrow::Cow,
+        collections::HashMap,
+        path::{Path, PathBuf},
+    },
+};
+
+/// Describes the format of a file holding packed resources data.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ContainerFormat {
+    /// The file is standalone packed resources data.
+    PackedResources,
+    /// An ELF binary.
+    Elf,
+    /// A PE binary.
+    Pe,
+    /// A Mach-O binary, including universal binaries.
+    MachO,
+    /// Some other format.
+    Unknown,
+}
+
+impl ContainerFormat {
+    /// Detect the container format from the leading bytes of a file.
+    pub fn from_data(data: &[u8]) -> Self {
+        if data.starts_with(HEADER_V3) || data.starts_with(HEADER_V4) {
+            Self::PackedResources
+        } else if data.starts_with(b"\x7fELF") {
+            Self::Elf
+        } else if data.starts_with(b"MZ") {
+            Self::Pe
+        } else if data.len() >= 4
+            && matches!(
+                &data[0..4],
+                b"\xfe\xed\xfa\xce"
+                    | b"\xce\xfa\xed\xfe"
+                    | b"\xfe\xed\xfa\xcf"
+                    | b"\xcf\xfa\xed\xfe"
+                    | b"\xca\xfe\xba\xbe"
+            )
+        {
+            Self::MachO
+        } else {
+            Self::Unknown
+        }
+    }
+}
+
+/// Packed resources data found within a file.
+#[derive(Clone, Debug)]
+pub struct EmbeddedResources<'a> {
+    /// Format of the file holding the packed resources data.
+    pub format: ContainerFormat,
+    /// Offset of the packed resources data within the file.
+    pub offset: usize,
+    /// The packed resources data, from its header to the end of its blob data.
+    pub data: &'a [u8],
+}
+
+/// Attempt to parse packed resources data at the start of `data`.
+///
+/// Returns the length of the packed resources data if it parses without
+/// error and contains at least 1 resource.
+fn validate_candidate(data: &[u8]) -> Option<usize> {
+    let mut iter = load_resources(data).ok()?;
+    let length = iter.packed_length();
+
+    let mut count = 0;
+    for resource in &mut iter {
+        resource.ok()?;
+        count += 1;
+    }
+
+    if count > 0 {
+        Some(length)
+    } else {
+        None
+    }
+}
+
+/// Locate packed resources data within the content of a file.
+///
+/// If `data` begins with a packed resources header, it is parsed as-is.
+/// Otherwise the entire file is scanned for packed resources headers and
+/// the first location holding parseable resources data with at least 1
+/// resource is returned. Matching on full parses avoids false positives
+/// from e.g. string constants containing the header bytes.
+///
+/// The returned data is truncated to the length of the packed resources
+/// data. Data following it, such as an integrity trailer, isn't included.
+pub fn find_embedded_resources(data: &[u8]) -> Result<EmbeddedResources<'_>> {
+    let format = ContainerFormat::from_data(data);
+
+    if format == ContainerFormat::PackedResources {
+        let length = load_resources(data)
+            .map_err(|e| anyhow!("error parsing packed resources data: {}", e))?
+            .packed_length();
+
+        return Ok(EmbeddedResources {
+            format,
+            offset: 0,
+            data: &data[0..length],
+        });
+    }
+
+    let mut offset = 0;
+    while let Some(position) = data[offset..]
+        .windows(HEADER_V3.len())
+        .position(|window| window == HEADER_V3 || window == HEADER_V4)
+    {
+        let start = offset + position;
+
+        if let Some(length) = validate_candidate(&data[start..]) {
+            return Ok(EmbeddedResources {
+                format,
+                offset: start,
+                data: &data[start..start + length],
+            });
+        }
+
+        offset = start + 1;
+    }
+
+    Err(anyhow!("unable to find packed resources data"))
+}
+
+fn describe_data<X>(data: &Option<Cow<[X]>>) -> Option<String>
+where
+    [X]: ToOwned<Owned = Vec<X>>,
+{
+    data.as_ref().map(|data| format!("{} bytes", data.len()))
+}
+
+fn describe_data_map<X>(map: &Option<HashMap<Cow<str>, Cow<[X]>>>) -> Option<String>
+where
+    [X]: ToOwned<Owned = Vec<X>>,
+{
+    map.as_ref().map(|map| {
+        let mut keys = map.keys().collect::<Vec<_>>();
+        keys.sort();
+
+        format!(
+            "{} entries, {} bytes ({})",
+            map.len(),
+            map.values().map(|data| data.len()).sum::<usize>(),
+            keys.into_iter()
+                .map(|key| format!("{}: {} bytes", key, map[key].len()))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    })
+}
+
+fn describe_path_map(map: &Option<HashMap<Cow<str>, Cow<Path>>>) -> Option<String> {
+    map.as_ref().map(|map| {
+        let mut keys = map.keys().collect::<Vec<_>>();
+        keys.sort();
+
+        keys.into_iter()
+            .map(|key| format!("{}: {}", key, map[key].display()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    })
+}
+
+/// Describe the fields set on a resource.
+///
+/// Returns pairs of field name and a human readable description of the
+/// field's value, in field declaration order. Fields holding data are
+/// described by their size. Unset fields are omitted.
+pub fn describe_resource_fields(resource: &Resource<u8>) -> Vec<(&'static str, String)> {
+    let mut fields = vec![
+        ("name", resource.name.to_string()),
+        ("flavor", format!("{:?}", resource.flavor)),
+    ];
+
+    for (name, value) in &[
+        ("is_module", resource.is_module),
+        (
+            "is_builtin_extension_module",
+            resource.is_builtin_extension_module,
+        ),
+        ("is_frozen_module", resource.is_frozen_module),
+        ("is_extension_module", resource.is_extension_module),
+        ("is_shared_library", resource.is_shared_library),
+        ("is_utf8_filename_data", resource.is_utf8_filename_data),
+        ("is_package", resource.is_package),
+        ("is_namespace_package", resource.is_namespace_package),
+    ] {
+        if *value {
+            fields.push((name, "true".to_string()));
+        }
+    }
+
+    let optional = vec![
+        (
+            "in_memory_source",
+            describe_data(&resource.in_memory_source),
+        ),
+        (
+            "in_memory_bytecode",
+            describe_data(&resource.in_memory_bytecode),
+        ),
+        (
+            "in_memory_bytecode_opt1",
+            describe_data(&resource.in_memory_bytecode_opt1),
+        ),
+        (
+            "in_memory_bytecode_opt2",
+            describe_data(&resource.in_memory_bytecode_opt2),
+        ),
+        (
+            "in_memory_extension_module_shared_library",
+            describe_data(&resource.in_memory_extension_module_shared_library),
+        ),
+        (
+            "in_memory_package_resources",
+            describe_data_map(&resource.in_memory_package_resources),
+        ),
+        (
+            "in_memory_distribution_resources",
+            describe_data_map(&resource.in_memory_distribution_resources),
+        ),
+        (
+            "in_memory_shared_library",
+            describe_data(&resource.in_memory_shared_library),
+        ),
+        (
+            "shared_library_dependency_names",
+            resource
+                .shared_library_dependency_names
+                .as_ref()
+                .map(|names| names.join(", ")),
+        ),
+        (
+            "relative_path_module_source",
+            resource
+                .relative_path_module_source
+                .as_ref()
+                .map(|p| p.display().to_string()),
+        ),
+        (
+            "relative_path_module_bytecode",
+            resource
+                .relative_path_module_bytecode
+                .as_ref()
+                .map(|p| p.display().to_string()),
+        ),
+        (
+            "relative_path_module_bytecode_opt1",
+            resource
+                .relative_path_module_bytecode_opt1
+                .as_ref()
+                .map(|p| p.display().to_string()),
+        ),
+        (
+            "relative_path_module_bytecode_opt2",
+            resource
+                .relative_path_module_bytecode_opt2
+                .as_ref()
+                .map(|p| p.display().to_string()),
+        ),
+        (
+            "relative_path_extension_module_shared_library",
+            resource
+                .relative_path_extension_module_shared_library
+                .as_ref()
+                .map(|p| p.display().to_string()),
+        ),
+        (
+            "relative_path_package_resources",
+            describe_path_map(&resource.relative_path_package_resources),
+        ),
+        (
+            "relative_path_distribution_resources",
+            describe_path_map(&resource.relative_path_distribution_resources),
+        ),
+        (
+            "file_executable",
+            if resource.file_executable {
+                Some("true".to_string())
+            } else {
+                None
+            },
+        ),
+        (
+            "file_data_embedded",
+            describe_data(&resource.file_data_embedded),
+        ),
+        (
+            "file_data_utf8_relative_path",
+            resource
+                .file_data_utf8_relative_path
+                .as_ref()
+                .map(|p| p.to_string()),
+        ),
+        (
+            "content_sha256",
+            resource
+                .content_sha256
+                .as_ref()
+                .map(|digest| digest.iter().map(|b| format!("{:02x}", b)).collect()),
+        ),
+    ];
+
+    fields.extend(
+        optional
+            .into_iter()
+            .filter_map(|(name, value)| value.map(|value| (name, value))),
+    );
+
+    fields
+}
+
+/// A file that can be materialized from data embedded in a resource.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ExtractedFile<'a> {
+    /// Name of the resource field the data came from.
+    pub field: &'static str,
+    /// Relative path the data should be written to.
+    pub path: PathBuf,
+    /// The file content.
+    pub data: Cow<'a, [u8]>,
+}
+
+/// Convert a `/` delimited name to a relative path, rejecting unsafe paths.
+fn safe_relative_path(name: &str) -> Result<PathBuf> {
+    let mut path = PathBuf::new();
+
+    for component in name.split('/') {
+        if component.is_empty()
+            || component == "."
+            || component == ".."
+            || component.contains(&['\\', ':'][..])
+        {
+            return Err(anyhow!("refusing to extract unsafe path: {}", name));
+        }
+
+        path.push(component);
+    }
+
+    Ok(path)
+}
+
+/// Obtain files that can be extracted from a resource's embedded data.
+///
+/// Paths are relative and derived from the resource name:
+///
+/// * Module source is written to `<package>/<module>.py`, or
+///   `<package>/__init__.py` for packages.
+/// * Module bytecode is written next to the source with a `.bytecode`,
+///   `.opt-1.bytecode`, or `.opt-2.bytecode` extension. Bytecode is the
+///   marshalled code object without a `.pyc` header.
+/// * Package resources are written to the package's directory.
+/// * Distribution resources are written to a `<package>.dist-info`
+///   directory.
+/// * Embedded file data is written to the resource's name.
+///
+/// Names that would escape the destination directory are rejected.
+pub fn resource_extracted_files<'a>(resource: &Resource<'a, u8>) -> Result<Vec<ExtractedFile<'a>>> {
+    let mut files = vec![];
+
+    if resource.file_data_embedded.is_some() {
+        files.push(ExtractedFile {
+            field: "file_data_embedded",
+            path: safe_relative_path(&resource.name)?,
+            data: resource.file_data_embedded.clone().unwrap(),
+        });
+    }
+
+    if !resource.is_module && resource.flavor != ResourceFlavor::Module {
+        return Ok(files);
+    }
+
+    let package_path = safe_relative_path(&resource.name.replace('.', "/"))?;
+    let module_stem = if resource.is_package {
+        package_path.join("__init__")
+    } else {
+        package_path.clone()
+    };
+
+    for (field, data, extension) in &[
+        ("in_memory_source", &resource.in_memory_source, "py"),
+        (
+            "in_memory_bytecode",
+            &resource.in_memory_bytecode,
+            "bytecode",
+        ),
+        (
+            "in_memory_bytecode_opt1",
+            &resource.in_memory_bytecode_opt1,
+            "opt-1.bytecode",
+        ),
+        (
+            "in_memory_bytecode_opt2",
+            &resource.in_memory_bytecode_opt2,
+            "opt-2.bytecode",
+        ),
+    ] {
+        if let Some(data) = data {
+            files.push(ExtractedFile {
+                field,
+                path: module_stem.with_extension(extension),
+                data: data.clone(),
+            });
+        }
+    }
+
+    let dist_path = PathBuf::from(format!("{}.dist-info", resource.name));
+
+    for (field, base, resources) in &[
+        (
+            "in_memory_package_resources",
+            &package_path,
+            &resource.in_memory_package_resources,
+        ),
+        (
+            "in_memory_distribution_resources",
+            &dist_path,
+            &resource.in_memory_distribution_resources,
+        ),
+    ] {
+        if let Some(resources) = resources {
+            let mut names = resources.keys().collect::<Vec<_>>();
+            names.sort();
+
+            for name in names {
+                files.push(ExtractedFile {
+                    field,
+                    path: base.join(safe_relative_path(name)?),
+                    data: resources[name].clone(),
+                });
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            data::BlobSectionCompression,
+            integrity::{append_integrity_trailer, resource_content_sha256},
+            writer::{write_packed_resources_v3, write_packed_resources_v4},
+        },
+    };
+
+    fn package() -> Resource<'static, u8> {
+        let mut package_resources = HashMap::new();
+        package_resources.insert(Cow::from("data/foo.txt"), Cow::from(b"foo".to_vec()));
+        let mut distribution_resources = HashMap::new();
+        distribution_resources.insert(Cow::from("METADATA"), Cow::from(b"Name: foo".to_vec()));
+
+        Resource {
+            flavor: ResourceFlavor::Module,
+            name: Cow::from("foo.bar"),
+            is_module: true,
+            is_package: true,
+            in_memory_source: Some(Cow::from(b"import os".to_vec())),
+            in_memory_bytecode_opt2: Some(Cow::from(b"bytecode".to_vec())),
+            in_memory_package_resources: Some(package_resources),
+            in_memory_distribution_resources: Some(distribution_resources),
+            ..Resource::default()
+        }
+    }
+
+    #[test]
+    fn test_container_format() {
+        assert_eq!(
+            ContainerFormat::from_data(b"pyembed\x03"),
+            ContainerFormat::PackedResources
+        );
+        assert_eq!(
+            ContainerFormat::from_data(b"\x7fELF\x02"),
+            ContainerFormat::Elf
+        );
+        assert_eq!(
+            ContainerFormat::from_data(b"MZ\x90\x00"),
+            ContainerFormat::Pe
+        );
+        assert_eq!(
+            ContainerFormat::from_data(b"\xcf\xfa\xed\xfe"),
+            ContainerFormat::MachO
+        );
+        assert_eq!(ContainerFormat::from_data(b"foo"), ContainerFormat::Unknown);
+    }
+
+    #[test]
+    fn test_find_embedded_resources() -> Result<()> {
+        let mut data = vec![];
+        write_packed_resources_v3(&[package()], &mut data, None)?;
+
+        let found = find_embedded_resources(&data)?;
+        assert_eq!(found.format, ContainerFormat::PackedResources);
+        assert_eq!(found.offset, 0);
+        assert_eq!(found.data, &data[..]);
+
+        // An integrity trailer isn't part of the packed resources data.
+        let mut with_trailer = data.clone();
+        append_integrity_trailer(&mut with_trailer, |_| Ok(None))?;
+        assert_eq!(find_embedded_resources(&with_trailer)?.data, &data[..]);
+
+        // Embedded in an executable after a bogus header.
+        let mut exe = b"\x7fELF garbage".to_vec();
+        exe.extend_from_slice(HEADER_V3);
+        exe.extend_from_slice(b"more garbage");
+        let offset = exe.len();
+        exe.extend_from_slice(&data);
+        exe.extend_from_slice(b"trailing data");
+
+        let found = find_embedded_resources(&exe)?;
+        assert_eq!(found.format, ContainerFormat::Elf);
+        assert_eq!(found.offset, offset);
+        assert_eq!(found.data, &data[..]);
+
+        assert!(find_embedded_resources(b"MZ no resources here").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_embedded_resources_garbage() -> Result<()> {
+        // A xorshift generator keeps the inputs reproducible.
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        let mut valid = vec![];
+        write_packed_resources_v3(&[package()], &mut valid, None)?;
+        let mut compressed = vec![];
+        write_packed_resources_v4(
+            &[package()],
+            &mut compressed,
+            None,
+            if cfg!(feature = "lz4") {
+                Some(BlobSectionCompression::Lz4)
+            } else {
+                None
+            },
+        )?;
+
+        for i in 0..20000 {
+            let mut data = b"\x7fELF".to_vec();
+
+            match i % 3 {
+                // Random data following a header.
+                0 => {
+                    data.extend_from_slice(if i % 2 == 0 { HEADER_V3 } else { HEADER_V4 });
+                    let length = next() % 256;
+                    data.extend((0..length).map(|_| next() as u8));
+                }
+                // Valid data with random bytes changed.
+                _ => {
+                    data.extend_from_slice(if i % 3 == 1 { &valid } else { &compressed });
+                    for _ in 0..(1 + next() % 4) {
+                        let offset = 4 + HEADER_V3.len() + (next() as usize % (data.len() - 12));
+                        data[offset] = next() as u8;
+                    }
+                }
+            }
+
+            // Errors are fine. Panics aren't.
+            if let Ok(found) = find_embedded_resources(&data) {
+                for resource in load_resources(found.data).unwrap() {
+                    resource.unwrap();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_describe_resource_fields() {
+        let mut resource = package();
+        resource.content_sha256 = Some(Cow::from(resource_content_sha256(&resource).to_vec()));
+
+        let fields = describe_resource_fields(&resource);
+        let names = fields.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+
+        assert_eq!(
+            names,
+            vec![
+                "name",
+                "flavor",
+                "is_module",
+                "is_package",
+                "in_memory_source",
+                "in_memory_bytecode_opt2",
+                "in_memory_package_resources",
+                "in_memory_distribution_resources",
+                "content_sha256"
+            ]
+        );
+        assert_eq!(fields[4].1, "9 bytes");
+        assert_eq!(fields[6].1, "1 entries, 3 bytes (data/foo.txt: 3 bytes)");
+    }
+
+    #[test]
+    fn test_resource_extracted_files() -> Result<()> {
+        let files = resource_extracted_files(&package())?;
+
+        assert_eq!(
+            files
+                .iter()
+                .map(|f| (f.field, f.path.as_path(), f.data.as_ref()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "in_memory_source",
+                    Path::new("foo/bar/__init__.py"),
+                    b"import os".as_ref()
+                ),
+                (
+                    "in_memory_bytecode_opt2",
+                    Path::new("foo/bar/__init__.opt-2.bytecode"),
+                    b"bytecode".as_ref()
+                ),
+                (
+                    "in_memory_package_resources",
+                    Path::new("foo/bar/data/foo.txt"),
+                    b"foo".as_ref()
+                ),
+                (
+                    "in_memory_distribution_resources",
+                    Path::new("foo.bar.dist-info/METADATA"),
+                    b"Name: foo".as_ref()
+                ),
+            ]
+        );
+
+        let module = Resource {
+            flavor: ResourceFlavor::Module,
+            name: Cow::from("foo"),
+            is_module: true,
+            in_memory_source: Some(Cow::from(b"".to_vec())),
+            ..Resource::default()
+        };
+        assert_eq!(
+            resource_extracted_files(&module)?[0].path,
+            Path::new("foo.py")
+        );
+
+        let mut evil = package();
+        evil.in_memory_package_resources
+            .as_mut()
+            .unwrap()
+            .insert(Cow::from("../../etc/passwd"), Cow::from(b"".to_vec()));
+        assert!(resource_extracted_files(&evil).is_err());
+
+        Ok(())
+    }
+}
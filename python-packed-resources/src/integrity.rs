@@ -0,0 +1,482 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Integrity verification of packed resources data.
+
+Resources can carry a SHA-256 digest of their content in the
+`ContentSha256` field. The digest is computed over a canonical
+serialization of the resource (see [resource_content_sha256]) so it
+doesn't depend on the layout of the packed resources data.
+
+Packed resources data can also be followed by an *integrity trailer*
+holding a SHA-256 digest of all bytes preceding it and an optional
+detached signature over that digest. The trailer is located by reading
+from the end of the data, so parsers that are unaware of it simply
+ignore it.
+*/
+
+use {
+    crate::data::{Resource, ResourceField},
+    anyhow::{anyhow, Context, Result},
+    byteorder::{ByteOrder, LittleEndian, WriteBytesExt},
+    sha2::{Digest, Sha256},
+    std::{borrow::Cow, collections::HashMap, convert::TryFrom, io::Write, path::Path},
+};
+
+/// Magic bytes terminating an integrity trailer.
+pub const INTEGRITY_TRAILER_MAGIC: &[u8] = b"pyembedI";
+
+/// Length in bytes of SHA-256 digests.
+pub const SHA256_DIGEST_LENGTH: usize = 32;
+
+/// Length of the fixed size portion of the integrity trailer.
+///
+/// Signature length + payload digest + magic.
+const TRAILER_FIXED_LENGTH: usize = 4 + SHA256_DIGEST_LENGTH + 8;
+
+fn update_field(hasher: &mut Sha256, field: ResourceField) {
+    hasher.update([u8::from(field)]);
+}
+
+fn update_bytes(hasher: &mut Sha256, field: ResourceField, value: &[u8]) {
+    update_field(hasher, field);
+    hasher.update((value.len() as u64).to_le_bytes());
+    hasher.update(value);
+}
+
+fn update_path(hasher: &mut Sha256, field: ResourceField, value: &Path) {
+    update_bytes(hasher, field, value.to_string_lossy().as_bytes());
+}
+
+fn update_map<V>(
+    hasher: &mut Sha256,
+    field: ResourceField,
+    value: &HashMap<Cow<str>, V>,
+    value_bytes: impl Fn(&V) -> Cow<[u8]>,
+) {
+    let mut keys = value.keys().collect::<Vec<_>>();
+    keys.sort();
+
+    update_field(hasher, field);
+    hasher.update((keys.len() as u64).to_le_bytes());
+
+    for key in keys {
+        let data = value_bytes(&value[key]);
+
+        hasher.update((key.len() as u64).to_le_bytes());
+        hasher.update(key.as_bytes());
+        hasher.update((data.len() as u64).to_le_bytes());
+        hasher.update(&data);
+    }
+}
+
+/// Compute the SHA-256 content digest of a resource.
+///
+/// The digest covers the resource name, its flags, and all of its data
+/// fields. Each present field is fed into the hasher as its
+/// `ResourceField` identifier followed by its length-prefixed value.
+/// Mappings are fed in sorted key order and paths are normalized to
+/// UTF-8 so the digest is stable across platforms. The `content_sha256`
+/// field itself is not part of the digest.
+pub fn resource_content_sha256(resource: &Resource<u8>) -> [u8; SHA256_DIGEST_LENGTH] {
+    let mut h = Sha256::new();
+
+    update_field(&mut h, ResourceField::Flavor);
+    h.update([u8::from(resource.flavor)]);
+    update_bytes(&mut h, ResourceField::ModuleName, resource.name.as_bytes());
+
+    let flags = [
+        (resource.is_package, ResourceField::IsPackage),
+        (
+            resource.is_namespace_package,
+            ResourceField::IsNamespacePackage,
+        ),
+        (resource.is_module, ResourceField::IsModule),
+        (
+            resource.is_builtin_extension_module,
+            ResourceField::IsBuiltinExtensionModule,
+        ),
+        (resource.is_frozen_module, ResourceField::IsFrozenModule),
+        (
+            resource.is_extension_module,
+            ResourceField::IsExtensionModule,
+        ),
+        (resource.is_shared_library, ResourceField::IsSharedLibrary),
+        (
+            resource.is_utf8_filename_data,
+            ResourceField::IsUtf8FilenameData,
+        ),
+        (resource.file_executable, ResourceField::FileExecutable),
+    ];
+    for (value, field) in flags.iter() {
+        if *value {
+            update_field(&mut h, *field);
+        }
+    }
+
+    let data = [
+        (&resource.in_memory_source, ResourceField::InMemorySource),
+        (
+            &resource.in_memory_bytecode,
+            ResourceField::InMemoryBytecode,
+        ),
+        (
+            &resource.in_memory_bytecode_opt1,
+            ResourceField::InMemoryBytecodeOpt1,
+        ),
+        (
+            &resource.in_memory_bytecode_opt2,
+            ResourceField::InMemoryBytecodeOpt2,
+        ),
+        (
+            &resource.in_memory_extension_module_shared_library,
+            ResourceField::InMemoryExtensionModuleSharedLibrary,
+        ),
+        (
+            &resource.in_memory_shared_library,
+            ResourceField::InMemorySharedLibrary,
+        ),
+        (
+            &resource.file_data_embedded,
+            ResourceField::FileDataEmbedded,
+        ),
+    ];
+    for (value, field) in data.iter() {
+        if let Some(value) = value {
+            update_bytes(&mut h, *field, value);
+        }
+    }
+
+    if let Some(value) = &resource.in_memory_package_resources {
+        update_map(
+            &mut h,
+            ResourceField::InMemoryResourcesData,
+            value,
+            |v: &Cow<[u8]>| Cow::Borrowed(v.as_ref()),
+        );
+    }
+    if let Some(value) = &resource.in_memory_distribution_resources {
+        update_map(
+            &mut h,
+            ResourceField::InMemoryDistributionResource,
+            value,
+            |v: &Cow<[u8]>| Cow::Borrowed(v.as_ref()),
+        );
+    }
+
+    if let Some(names) = &resource.shared_library_dependency_names {
+        update_field(&mut h, ResourceField::SharedLibraryDependencyNames);
+        h.update((names.len() as u64).to_le_bytes());
+        for name in names {
+            h.update((name.len() as u64).to_le_bytes());
+            h.update(name.as_bytes());
+        }
+    }
+
+    let paths = [
+        (
+            &resource.relative_path_module_source,
+            ResourceField::RelativeFilesystemModuleSource,
+        ),
+        (
+            &resource.relative_path_module_bytecode,
+            ResourceField::RelativeFilesystemModuleBytecode,
+        ),
+        (
+            &resource.relative_path_module_bytecode_opt1,
+            ResourceField::RelativeFilesystemModuleBytecodeOpt1,
+        ),
+        (
+            &resource.relative_path_module_bytecode_opt2,
+            ResourceField::RelativeFilesystemModuleBytecodeOpt2,
+        ),
+        (
+            &resource.relative_path_extension_module_shared_library,
+            ResourceField::RelativeFilesystemExtensionModuleSharedLibrary,
+        ),
+    ];
+    for (value, field) in paths.iter() {
+        if let Some(value) = value {
+            update_path(&mut h, *field, value);
+        }
+    }
+
+    if let Some(value) = &resource.relative_path_package_resources {
+        update_map(
+            &mut h,
+            ResourceField::RelativeFilesystemPackageResources,
+            value,
+            |v: &Cow<Path>| Cow::Owned(v.to_string_lossy().as_bytes().to_vec()),
+        );
+    }
+    if let Some(value) = &resource.relative_path_distribution_resources {
+        update_map(
+            &mut h,
+            ResourceField::RelativeFilesystemDistributionResource,
+            value,
+            |v: &Cow<Path>| Cow::Owned(v.to_string_lossy().as_bytes().to_vec()),
+        );
+    }
+
+    if let Some(value) = &resource.file_data_utf8_relative_path {
+        update_bytes(
+            &mut h,
+            ResourceField::FileDataUtf8RelativePath,
+            value.as_bytes(),
+        );
+    }
+
+    let mut digest = [0u8; SHA256_DIGEST_LENGTH];
+    digest.copy_from_slice(&h.finalize());
+    digest
+}
+
+/// Verify that a resource's content matches its recorded content digest.
+///
+/// Returns `Ok(false)` if the resource doesn't have a content digest.
+pub fn verify_resource_content_sha256(resource: &Resource<u8>) -> Result<bool, &'static str> {
+    if let Some(expected) = &resource.content_sha256 {
+        if resource_content_sha256(resource)[..] == expected[..] {
+            Ok(true)
+        } else {
+            Err("resource content digest mismatch")
+        }
+    } else {
+        Ok(false)
+    }
+}
+
+/// Compute the SHA-256 digest of packed resources data.
+pub fn payload_sha256(data: &[u8]) -> [u8; SHA256_DIGEST_LENGTH] {
+    let mut digest = [0u8; SHA256_DIGEST_LENGTH];
+    digest.copy_from_slice(&Sha256::digest(data));
+    digest
+}
+
+/// Write an integrity trailer for packed resources data.
+///
+/// `payload` is the complete packed resources data, which must be written
+/// to `dest` before the trailer. `signature` is an optional detached signature
+/// over the SHA-256 digest of `payload`.
+pub fn write_integrity_trailer<W: Write>(
+    dest: &mut W,
+    payload: &[u8],
+    signature: Option<&[u8]>,
+) -> Result<()> {
+    let signature = signature.unwrap_or(&[]);
+    let signature_length =
+        u32::try_from(signature.len()).context("converting signature length to u32")?;
+
+    dest.write_all(signature)
+        .context("writing integrity trailer signature")?;
+    dest.write_u32::<LittleEndian>(signature_length)
+        .context("writing integrity trailer signature length")?;
+    dest.write_all(&payload_sha256(payload))
+        .context("writing integrity trailer payload digest")?;
+    dest.write_all(INTEGRITY_TRAILER_MAGIC)
+        .context("writing integrity trailer magic")?;
+
+    Ok(())
+}
+
+/// Append an integrity trailer to packed resources data held in memory.
+///
+/// `sign` receives the payload digest and returns the detached signature to
+/// record, if any.
+pub fn append_integrity_trailer(
+    data: &mut Vec<u8>,
+    sign: impl FnOnce(&[u8]) -> Result<Option<Vec<u8>>>,
+) -> Result<()> {
+    if find_integrity_trailer(data)
+        .map_err(|e| anyhow!(e))?
+        .is_some()
+    {
+        return Err(anyhow!(
+            "packed resources data already has an integrity trailer"
+        ));
+    }
+
+    let signature = sign(&payload_sha256(data))?;
+    let mut trailer = Vec::new();
+    write_integrity_trailer(&mut trailer, data, signature.as_deref())?;
+    data.extend(trailer);
+
+    Ok(())
+}
+
+/// An integrity trailer parsed from packed resources data.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IntegrityTrailer<'a> {
+    /// Packed resources data covered by the trailer.
+    pub payload: &'a [u8],
+
+    /// Recorded SHA-256 digest of `payload`.
+    pub payload_sha256: &'a [u8],
+
+    /// Detached signature over `payload_sha256`.
+    pub signature: Option<&'a [u8]>,
+}
+
+impl<'a> IntegrityTrailer<'a> {
+    /// Verify that the payload matches its recorded digest.
+    pub fn verify_payload_digest(&self) -> Result<(), &'static str> {
+        if payload_sha256(self.payload)[..] == self.payload_sha256[..] {
+            Ok(())
+        } else {
+            Err("packed resources payload digest mismatch")
+        }
+    }
+}
+
+/// Find the integrity trailer at the end of packed resources data.
+///
+/// Returns `Ok(None)` if the data doesn't end with an integrity trailer.
+pub fn find_integrity_trailer(data: &[u8]) -> Result<Option<IntegrityTrailer<'_>>, &'static str> {
+    if data.len() < TRAILER_FIXED_LENGTH || !data.ends_with(INTEGRITY_TRAILER_MAGIC) {
+        return Ok(None);
+    }
+
+    let digest_end = data.len() - INTEGRITY_TRAILER_MAGIC.len();
+    let digest_start = digest_end - SHA256_DIGEST_LENGTH;
+    let signature_end = digest_start - 4;
+
+    let signature_length = LittleEndian::read_u32(&data[signature_end..digest_start]) as usize;
+
+    if signature_length > signature_end {
+        return Err("integrity trailer signature extends beyond start of data");
+    }
+
+    let signature_start = signature_end - signature_length;
+
+    Ok(Some(IntegrityTrailer {
+        payload: &data[0..signature_start],
+        payload_sha256: &data[digest_start..digest_end],
+        signature: if signature_length > 0 {
+            Some(&data[signature_start..signature_end])
+        } else {
+            None
+        },
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{parser::load_resources, writer::write_packed_resources_v4},
+    };
+
+    fn resource_with_digest() -> Resource<'static, u8> {
+        let mut resource = Resource {
+            name: Cow::from("foo"),
+            is_module: true,
+            in_memory_source: Some(Cow::from(b"import bar".to_vec())),
+            relative_path_module_bytecode: Some(Cow::from(Path::new("foo.pyc"))),
+            ..Resource::default()
+        };
+        resource.content_sha256 = Some(Cow::from(resource_content_sha256(&resource).to_vec()));
+
+        resource
+    }
+
+    #[test]
+    fn test_content_digest_stable() {
+        let a = resource_with_digest();
+        let mut b = a.clone();
+        b.content_sha256 = None;
+
+        assert_eq!(resource_content_sha256(&a), resource_content_sha256(&b));
+        assert_eq!(verify_resource_content_sha256(&a), Ok(true));
+        assert_eq!(verify_resource_content_sha256(&b), Ok(false));
+
+        b.in_memory_source = Some(Cow::from(b"import baz".to_vec()));
+        b.content_sha256 = a.content_sha256.clone();
+        assert!(verify_resource_content_sha256(&b).is_err());
+    }
+
+    #[test]
+    fn test_content_digest_roundtrip() -> Result<()> {
+        let resource = resource_with_digest();
+
+        let mut data = Vec::new();
+        write_packed_resources_v4(std::slice::from_ref(&resource), &mut data, None, None)?;
+
+        let resources = load_resources(&data)
+            .map_err(|e| anyhow!(e))?
+            .collect::<Result<Vec<_>, &'static str>>()
+            .map_err(|e| anyhow!(e))?;
+
+        assert_eq!(resources.len(), 1);
+        assert_eq!(resources[0], resource);
+        assert_eq!(verify_resource_content_sha256(&resources[0]), Ok(true));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_v3_rejects_content_digest() {
+        let mut data = Vec::new();
+        assert!(crate::writer::write_packed_resources_v3(
+            &[resource_with_digest()],
+            &mut data,
+            None
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_no_trailer() -> Result<()> {
+        let mut data = Vec::new();
+        write_packed_resources_v4(&[resource_with_digest()], &mut data, None, None)?;
+
+        assert_eq!(find_integrity_trailer(&data), Ok(None));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_trailer_roundtrip() -> Result<()> {
+        let mut data = Vec::new();
+        write_packed_resources_v4(&[resource_with_digest()], &mut data, None, None)?;
+        let payload = data.clone();
+
+        append_integrity_trailer(&mut data, |digest| {
+            assert_eq!(digest, &payload_sha256(&payload)[..]);
+            Ok(Some(b"signature".to_vec()))
+        })?;
+
+        let trailer = find_integrity_trailer(&data)
+            .map_err(|e| anyhow!(e))?
+            .unwrap();
+        assert_eq!(trailer.payload, &payload[..]);
+        assert_eq!(trailer.signature, Some(&b"signature"[..]));
+        assert_eq!(trailer.verify_payload_digest(), Ok(()));
+
+        // The trailer is ignored by the parser.
+        assert_eq!(load_resources(&data).map_err(|e| anyhow!(e))?.count(), 1);
+
+        // Appending a 2nd trailer is refused.
+        assert!(append_integrity_trailer(&mut data, |_| Ok(None)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_trailer_detects_tampering() -> Result<()> {
+        let mut data = Vec::new();
+        write_packed_resources_v4(&[resource_with_digest()], &mut data, None, None)?;
+        append_integrity_trailer(&mut data, |_| Ok(None))?;
+
+        let offset = data.len() / 2;
+        data[offset] ^= 0xff;
+
+        let trailer = find_integrity_trailer(&data)
+            .map_err(|e| anyhow!(e))?
+            .unwrap();
+        assert_eq!(trailer.signature, None);
+        assert!(trailer.verify_payload_digest().is_err());
+
+        Ok(())
+    }
+}
@@ -26,6 +26,8 @@ for the canonical specification of this format.
 */
 
 pub mod data;
+pub mod inspect;
+pub mod integrity;
 #[allow(unused)]
 pub mod parser;
 pub mod writer;
@@ -6,9 +6,11 @@
 
 use {
     super::data::{
-        BlobInteriorPadding, BlobSectionField, Resource, ResourceField, ResourceFlavor, HEADER_V3,
+        BlobInteriorPadding, BlobSectionCompression, BlobSectionField, Resource, ResourceField,
+        ResourceFlavor, HEADER_V3, HEADER_V4,
     },
     byteorder::{LittleEndian, ReadBytesExt},
+    once_cell::unsync::OnceCell,
     std::{
         borrow::Cow,
         collections::{HashMap, HashSet},
@@ -20,7 +22,11 @@ use {
 };
 
 #[cfg(unix)]
-use std::os::unix::ffi::OsStrExt;
+use std::{
+    ffi::OsString,
+    os::unix::ffi::{OsStrExt, OsStringExt},
+    path::PathBuf,
+};
 #[cfg(windows)]
 use {std::ffi::OsString, std::os::windows::ffi::OsStringExt, std::path::PathBuf};
 
@@ -30,13 +36,171 @@ struct BlobSection {
     resource_field: u8,
     raw_payload_length: usize,
     interior_padding: Option<BlobInteriorPadding>,
+    compression: Option<BlobSectionCompression>,
+    uncompressed_payload_length: Option<usize>,
 }
 
 /// Holds state used to read an individual blob section.
 #[derive(Clone, Copy, Debug)]
 struct BlobSectionReadState {
+    /// Offset of the next blob to read.
+    ///
+    /// For uncompressed sections, this is relative to the start of the payload.
+    /// For compressed sections, this is relative to the start of the
+    /// decompressed section data.
     offset: usize,
     interior_padding: BlobInteriorPadding,
+    compression: BlobSectionCompression,
+    /// Offset of the raw section data relative to the start of the payload.
+    raw_offset: usize,
+    raw_length: usize,
+    uncompressed_length: usize,
+}
+
+/// Decompress the raw data of a blob section.
+///
+/// `uncompressed_length` comes from untrusted data. So no more than the
+/// decompressed data actually produced is ever allocated.
+fn decompress_blob_section(
+    compression: BlobSectionCompression,
+    data: &[u8],
+    uncompressed_length: usize,
+) -> Result<Vec<u8>, &'static str> {
+    let decompressed = match compression {
+        BlobSectionCompression::None => return Err("blob section is not compressed"),
+        #[cfg(feature = "zstd")]
+        BlobSectionCompression::Zstd => {
+            let mut decompressed = vec![];
+            zstd::stream::read::Decoder::with_buffer(data)
+                .map_err(|_| "error decompressing zstd blob section")?
+                .take((uncompressed_length as u64).saturating_add(1))
+                .read_to_end(&mut decompressed)
+                .map_err(|_| "error decompressing zstd blob section")?;
+
+            decompressed
+        }
+        #[cfg(not(feature = "zstd"))]
+        BlobSectionCompression::Zstd => {
+            return Err("zstd compressed blob sections are not supported");
+        }
+        #[cfg(feature = "lz4")]
+        BlobSectionCompression::Lz4 => {
+            // Each LZ4 sequence of at least 1 byte yields at most 255 bytes per input byte.
+            if uncompressed_length / 255 > data.len() {
+                return Err("invalid uncompressed length of lz4 blob section");
+            }
+
+            lz4_flex::block::decompress(data, uncompressed_length)
+                .map_err(|_| "error decompressing lz4 blob section")?
+        }
+        #[cfg(not(feature = "lz4"))]
+        BlobSectionCompression::Lz4 => {
+            return Err("lz4 compressed blob sections are not supported");
+        }
+    };
+
+    if decompressed.len() != uncompressed_length {
+        return Err("decompressed blob section length mismatch");
+    }
+
+    Ok(decompressed)
+}
+
+/// Holds the decompressed data of compressed blob sections.
+///
+/// Resources parsed via [load_resources_with_sections] borrow data in
+/// compressed blob sections from this instance instead of copying it. Each
+/// blob section is decompressed the first time data is read from it.
+///
+/// An instance must only be used to parse a single packed resources data.
+pub struct DecompressedBlobSections {
+    sections: Vec<OnceCell<Vec<u8>>>,
+}
+
+impl Default for DecompressedBlobSections {
+    fn default() -> Self {
+        Self {
+            sections: (0..256).map(|_| OnceCell::new()).collect(),
+        }
+    }
+}
+
+impl std::fmt::Debug for DecompressedBlobSections {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DecompressedBlobSections")
+            .field(
+                "decompressed_count",
+                &self.sections.iter().filter(|s| s.get().is_some()).count(),
+            )
+            .finish()
+    }
+}
+
+impl DecompressedBlobSections {
+    /// Whether the blob section for a resource field has been decompressed.
+    pub fn is_decompressed(&self, resource_field: ResourceField) -> bool {
+        self.sections[resource_field as usize].get().is_some()
+    }
+
+    /// Obtain the decompressed data of a blob section, decompressing it if needed.
+    fn get_or_decompress(
+        &self,
+        resource_field: ResourceField,
+        state: &BlobSectionReadState,
+        data: &[u8],
+    ) -> Result<&[u8], &'static str> {
+        self.sections[resource_field as usize]
+            .get_or_try_init(|| {
+                decompress_blob_section(
+                    state.compression,
+                    &data[state.raw_offset..state.raw_offset + state.raw_length],
+                    state.uncompressed_length,
+                )
+            })
+            .map(|section| section.as_slice())
+    }
+}
+
+/// Where a parser stores decompressed blob sections.
+enum SectionStorage<'a> {
+    /// Sections outlive the parser and blobs are borrowed from them.
+    Borrowed(&'a DecompressedBlobSections),
+    /// Sections are owned by the parser and blobs are copied out of them.
+    Owned(DecompressedBlobSections),
+}
+
+/// A resource field whose value is in a compressed blob section and hasn't been read.
+#[derive(Clone, Copy, Debug)]
+struct DeferredResourceField {
+    field: ResourceField,
+    /// Offset of the field's value in the index data.
+    index_offset: usize,
+    /// Offset of the field's first blob in the decompressed blob section.
+    blob_offset: usize,
+}
+
+/// A resource whose fields in compressed blob sections haven't been read.
+///
+/// Fields stored in compressed blob sections are `None` until the resource is
+/// passed to [resolve_deferred_resource]. The resource name and fields not
+/// stored in blob sections, such as flags, are always set.
+#[derive(Clone, Debug)]
+pub struct DeferredResource<'a> {
+    /// The resource without values of deferred fields.
+    pub resource: Resource<'a, u8>,
+    deferred_fields: Vec<DeferredResourceField>,
+}
+
+impl<'a> DeferredResource<'a> {
+    /// Whether the resource doesn't have any deferred fields.
+    pub fn is_resolved(&self) -> bool {
+        self.deferred_fields.is_empty()
+    }
+
+    /// Whether the value of a field is deferred.
+    pub fn is_deferred(&self, field: ResourceField) -> bool {
+        self.deferred_fields.iter().any(|f| f.field == field)
+    }
 }
 
 pub type PythonPackageResources<'a> = HashMap<&'a str, &'a [u8]>;
@@ -46,8 +210,16 @@ pub struct ResourceParserIterator<'a> {
     data: &'a [u8],
     reader: Cursor<&'a [u8]>,
     blob_sections: [Option<BlobSectionReadState>; 256],
+    /// Decompressed data for compressed blob sections.
+    decompressed_sections: SectionStorage<'a>,
+    /// Whether to defer reading fields in compressed blob sections.
+    defer_compressed: bool,
+    /// Whether blob data in compressed sections is being skipped over.
+    skip_compressed_data: bool,
     claimed_resources_count: usize,
     read_resources_count: usize,
+    /// Length of the packed resources data, including the header.
+    packed_length: usize,
 }
 
 impl<'a> ResourceParserIterator<'a> {
@@ -56,49 +228,147 @@ impl<'a> ResourceParserIterator<'a> {
         self.claimed_resources_count
     }
 
-    /// Resolve a slice to an individual blob's data.
+    /// The length in bytes of the packed resources data being parsed.
+    ///
+    /// This includes the header and all blob sections. Any data following
+    /// the packed resources data in the source buffer is not counted.
+    pub fn packed_length(&self) -> usize {
+        self.packed_length
+    }
+
+    /// Bound a capacity read from the index by the remaining index data.
+    ///
+    /// Each entry occupies at least 1 byte of index data, so this avoids
+    /// huge allocations when parsing malformed data.
+    fn bounded_capacity(&self, count: usize) -> usize {
+        let remaining = self
+            .data
+            .len()
+            .saturating_sub(self.reader.position() as usize);
+
+        count.min(remaining)
+    }
+
+    /// Resolve an individual blob's data.
     ///
-    /// This accepts a reference to the original blobs payload, an array of
-    /// current blob section offsets, the resource field being accessed, and the
-    /// length of the blob and returns a slice to that blob.
-    fn resolve_blob_data(&mut self, resource_field: ResourceField, length: usize) -> &'a [u8] {
-        let mut state = self.blob_sections[resource_field as usize]
+    /// This accepts the resource field being accessed and the length of the
+    /// blob and returns the data for that blob. Data from uncompressed blob
+    /// sections is borrowed from the original payload. Data from compressed
+    /// blob sections is borrowed from the decompressed section if sections
+    /// outlive the parser and copied out of it otherwise.
+    fn resolve_blob_data(
+        &mut self,
+        resource_field: ResourceField,
+        length: usize,
+    ) -> Result<Cow<'a, [u8]>, &'static str> {
+        let state = self.blob_sections[resource_field as usize]
             .as_mut()
-            .expect("blob state not found");
+            .ok_or("blob section not found for resource field")?;
 
-        let blob = &self.data[state.offset..state.offset + length];
+        let start = state.offset;
+        let end = start
+            .checked_add(length)
+            .ok_or("blob data length overflow")?;
 
         let increment = match &state.interior_padding {
             BlobInteriorPadding::None => length,
-            BlobInteriorPadding::Null => length + 1,
+            BlobInteriorPadding::Null => {
+                length.checked_add(1).ok_or("blob data length overflow")?
+            }
         };
 
-        state.offset += increment;
+        state.offset = start
+            .checked_add(increment)
+            .ok_or("blob data length overflow")?;
+
+        if state.compression == BlobSectionCompression::None {
+            if end > state.raw_offset + state.raw_length {
+                return Err("blob data exceeds blob section");
+            }
+
+            return Ok(Cow::Borrowed(&self.data[start..end]));
+        }
+
+        if self.skip_compressed_data {
+            if end > state.uncompressed_length {
+                return Err("blob data exceeds decompressed blob section");
+            }
+
+            return Ok(Cow::Borrowed(&[]));
+        }
+
+        let state = *state;
+        let data = self.data;
+
+        match &self.decompressed_sections {
+            SectionStorage::Borrowed(sections) => {
+                let sections: &'a DecompressedBlobSections = sections;
+
+                Ok(Cow::Borrowed(
+                    sections
+                        .get_or_decompress(resource_field, &state, data)?
+                        .get(start..end)
+                        .ok_or("blob data exceeds decompressed blob section")?,
+                ))
+            }
+            SectionStorage::Owned(sections) => Ok(Cow::Owned(
+                sections
+                    .get_or_decompress(resource_field, &state, data)?
+                    .get(start..end)
+                    .ok_or("blob data exceeds decompressed blob section")?
+                    .to_vec(),
+            )),
+        }
+    }
 
-        blob
+    /// Resolve a blob holding a UTF-8 string.
+    fn resolve_str(
+        &mut self,
+        resource_field: ResourceField,
+        length: usize,
+    ) -> Result<Cow<'a, str>, &'static str> {
+        Ok(match self.resolve_blob_data(resource_field, length)? {
+            Cow::Borrowed(data) => {
+                Cow::Borrowed(std::str::from_utf8(data).map_err(|_| "string data is not UTF-8")?)
+            }
+            Cow::Owned(data) => {
+                Cow::Owned(String::from_utf8(data).map_err(|_| "string data is not UTF-8")?)
+            }
+        })
     }
 
     #[cfg(unix)]
-    fn resolve_path(&mut self, resource_field: ResourceField, length: usize) -> Cow<'a, Path> {
-        let path_str = OsStr::from_bytes(self.resolve_blob_data(resource_field, length));
-        Cow::Borrowed(Path::new(path_str))
+    fn resolve_path(
+        &mut self,
+        resource_field: ResourceField,
+        length: usize,
+    ) -> Result<Cow<'a, Path>, &'static str> {
+        Ok(match self.resolve_blob_data(resource_field, length)? {
+            Cow::Borrowed(data) => Cow::Borrowed(Path::new(OsStr::from_bytes(data))),
+            Cow::Owned(data) => Cow::Owned(PathBuf::from(OsString::from_vec(data))),
+        })
     }
 
     #[cfg(windows)]
-    fn resolve_path(&mut self, resource_field: ResourceField, length: usize) -> Cow<'a, Path> {
-        let raw = self.resolve_blob_data(resource_field, length);
+    fn resolve_path(
+        &mut self,
+        resource_field: ResourceField,
+        length: usize,
+    ) -> Result<Cow<'a, Path>, &'static str> {
+        let raw = self.resolve_blob_data(resource_field, length)?;
         let raw = unsafe { std::slice::from_raw_parts(raw.as_ptr() as *const u16, raw.len() / 2) };
 
         // There isn't an API that lets us get a OsStr from &[u16]. So we need to use
         // owned types.
         let path_string = OsString::from_wide(raw);
 
-        Cow::Owned(PathBuf::from(path_string))
+        Ok(Cow::Owned(PathBuf::from(path_string)))
     }
 
-    fn parse_next(&mut self) -> Result<Option<Resource<'a, u8>>, &'static str> {
+    fn parse_next(&mut self) -> Result<Option<DeferredResource<'a>>, &'static str> {
         let mut current_resource = Resource::default();
-        let mut current_resource_name = None;
+        let mut current_resource_has_name = false;
+        let mut deferred_fields = Vec::new();
 
         loop {
             let field_type = self
@@ -121,385 +391,422 @@ impl<'a> ResourceParserIterator<'a> {
                 ResourceField::StartOfEntry => {
                     self.read_resources_count += 1;
                     current_resource = Resource::default();
-                    current_resource_name = None;
+                    current_resource_has_name = false;
+                    deferred_fields.clear();
                 }
 
                 ResourceField::EndOfEntry => {
-                    let res = if let Some(name) = current_resource_name {
-                        Ok(Some(current_resource))
+                    return if current_resource_has_name {
+                        Ok(Some(DeferredResource {
+                            resource: current_resource,
+                            deferred_fields,
+                        }))
                     } else {
                         Err("resource name field is required")
                     };
-
-                    current_resource = Resource::default();
-                    current_resource_name = None;
-
-                    return res;
                 }
-                ResourceField::Flavor => {
-                    let flavor = self
-                        .reader
-                        .read_u8()
-                        .map_err(|_| "failed reading flavor value")?;
-
-                    current_resource.flavor = ResourceFlavor::try_from(flavor)?;
+                ResourceField::ModuleName => {
+                    self.parse_field(field_type, &mut current_resource)?;
+                    current_resource_has_name = true;
                 }
+                _ => match self.blob_sections[field_type as usize] {
+                    Some(state)
+                        if self.defer_compressed
+                            && state.compression != BlobSectionCompression::None =>
+                    {
+                        deferred_fields.push(DeferredResourceField {
+                            field: field_type,
+                            index_offset: self.reader.position() as usize,
+                            blob_offset: state.offset,
+                        });
+
+                        // Parse into a throwaway resource to advance past the
+                        // field without decompressing its blob section.
+                        self.skip_compressed_data = true;
+                        let skipped = self.parse_field(field_type, &mut Resource::default());
+                        self.skip_compressed_data = false;
+                        skipped?;
+                    }
+                    _ => {
+                        self.parse_field(field_type, &mut current_resource)?;
+                    }
+                },
+            }
+        }
+    }
 
-                ResourceField::ModuleName => {
-                    let l = self
-                        .reader
-                        .read_u16::<LittleEndian>()
-                        .map_err(|_| "failed reading resource name length")?
-                        as usize;
+    /// Parse the value of a field into a resource.
+    ///
+    /// The reader must be positioned just after the field type.
+    fn parse_field(
+        &mut self,
+        field_type: ResourceField,
+        resource: &mut Resource<'a, u8>,
+    ) -> Result<(), &'static str> {
+        match field_type {
+            ResourceField::EndOfIndex | ResourceField::StartOfEntry | ResourceField::EndOfEntry => {
+                return Err("resource field does not have a value");
+            }
+            ResourceField::Flavor => {
+                let flavor = self
+                    .reader
+                    .read_u8()
+                    .map_err(|_| "failed reading flavor value")?;
 
-                    let name = unsafe {
-                        std::str::from_utf8_unchecked(self.resolve_blob_data(field_type, l))
-                    };
+                resource.flavor = ResourceFlavor::try_from(flavor)?;
+            }
 
-                    current_resource_name = Some(name);
-                    current_resource.name = Cow::Borrowed(name);
-                }
-                ResourceField::IsPackage => {
-                    current_resource.is_package = true;
-                }
-                ResourceField::IsNamespacePackage => {
-                    current_resource.is_namespace_package = true;
-                }
-                ResourceField::InMemorySource => {
-                    let l = self
-                        .reader
-                        .read_u32::<LittleEndian>()
-                        .map_err(|_| "failed reading source length")?
-                        as usize;
+            ResourceField::ModuleName => {
+                let l = self
+                    .reader
+                    .read_u16::<LittleEndian>()
+                    .map_err(|_| "failed reading resource name length")?
+                    as usize;
 
-                    current_resource.in_memory_source =
-                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)));
-                }
-                ResourceField::InMemoryBytecode => {
-                    let l = self
-                        .reader
-                        .read_u32::<LittleEndian>()
-                        .map_err(|_| "failed reading bytecode length")?
-                        as usize;
+                resource.name = self.resolve_str(field_type, l)?;
+            }
+            ResourceField::IsPackage => {
+                resource.is_package = true;
+            }
+            ResourceField::IsNamespacePackage => {
+                resource.is_namespace_package = true;
+            }
+            ResourceField::InMemorySource => {
+                let l = self
+                    .reader
+                    .read_u32::<LittleEndian>()
+                    .map_err(|_| "failed reading source length")? as usize;
 
-                    current_resource.in_memory_bytecode =
-                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)));
-                }
-                ResourceField::InMemoryBytecodeOpt1 => {
-                    let l = self
-                        .reader
-                        .read_u32::<LittleEndian>()
-                        .map_err(|_| "failed reading bytecode length")?
-                        as usize;
+                resource.in_memory_source = Some(self.resolve_blob_data(field_type, l)?);
+            }
+            ResourceField::InMemoryBytecode => {
+                let l = self
+                    .reader
+                    .read_u32::<LittleEndian>()
+                    .map_err(|_| "failed reading bytecode length")?
+                    as usize;
+
+                resource.in_memory_bytecode = Some(self.resolve_blob_data(field_type, l)?);
+            }
+            ResourceField::InMemoryBytecodeOpt1 => {
+                let l = self
+                    .reader
+                    .read_u32::<LittleEndian>()
+                    .map_err(|_| "failed reading bytecode length")?
+                    as usize;
+
+                resource.in_memory_bytecode_opt1 = Some(self.resolve_blob_data(field_type, l)?);
+            }
+            ResourceField::InMemoryBytecodeOpt2 => {
+                let l = self
+                    .reader
+                    .read_u32::<LittleEndian>()
+                    .map_err(|_| "failed reading bytecode length")?
+                    as usize;
+
+                resource.in_memory_bytecode_opt2 = Some(self.resolve_blob_data(field_type, l)?);
+            }
+            ResourceField::InMemoryExtensionModuleSharedLibrary => {
+                let l = self
+                    .reader
+                    .read_u32::<LittleEndian>()
+                    .map_err(|_| "failed reading extension module length")?
+                    as usize;
+
+                resource.in_memory_extension_module_shared_library =
+                    Some(self.resolve_blob_data(field_type, l)?);
+            }
 
-                    current_resource.in_memory_bytecode_opt1 =
-                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)));
-                }
-                ResourceField::InMemoryBytecodeOpt2 => {
-                    let l = self
-                        .reader
-                        .read_u32::<LittleEndian>()
-                        .map_err(|_| "failed reading bytecode length")?
-                        as usize;
+            ResourceField::InMemoryResourcesData => {
+                let resource_count = self
+                    .reader
+                    .read_u32::<LittleEndian>()
+                    .map_err(|_| "failed reading resources length")?
+                    as usize;
 
-                    current_resource.in_memory_bytecode_opt2 =
-                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)));
-                }
-                ResourceField::InMemoryExtensionModuleSharedLibrary => {
-                    let l = self
+                let mut resources = HashMap::with_capacity(self.bounded_capacity(resource_count));
+
+                for _ in 0..resource_count {
+                    let resource_name_length = self
                         .reader
-                        .read_u32::<LittleEndian>()
-                        .map_err(|_| "failed reading extension module length")?
+                        .read_u16::<LittleEndian>()
+                        .map_err(|_| "failed reading resource name")?
                         as usize;
 
-                    current_resource.in_memory_extension_module_shared_library =
-                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)));
-                }
+                    let resource_name = self.resolve_str(field_type, resource_name_length)?;
 
-                ResourceField::InMemoryResourcesData => {
-                    let resource_count = self
+                    let resource_length = self
                         .reader
-                        .read_u32::<LittleEndian>()
-                        .map_err(|_| "failed reading resources length")?
+                        .read_u64::<LittleEndian>()
+                        .map_err(|_| "failed reading resource length")?
                         as usize;
 
-                    let mut resources = HashMap::with_capacity(resource_count);
-
-                    for _ in 0..resource_count {
-                        let resource_name_length = self
-                            .reader
-                            .read_u16::<LittleEndian>()
-                            .map_err(|_| "failed reading resource name")?
-                            as usize;
+                    let resource_data = self.resolve_blob_data(field_type, resource_length)?;
 
-                        let resource_name = unsafe {
-                            std::str::from_utf8_unchecked(
-                                self.resolve_blob_data(field_type, resource_name_length),
-                            )
-                        };
-
-                        let resource_length = self
-                            .reader
-                            .read_u64::<LittleEndian>()
-                            .map_err(|_| "failed reading resource length")?
-                            as usize;
+                    resources.insert(resource_name, resource_data);
+                }
 
-                        let resource_data = self.resolve_blob_data(field_type, resource_length);
+                resource.in_memory_package_resources = Some(resources);
+            }
 
-                        resources
-                            .insert(Cow::Borrowed(resource_name), Cow::Borrowed(resource_data));
-                    }
+            ResourceField::InMemoryDistributionResource => {
+                let resource_count = self
+                    .reader
+                    .read_u32::<LittleEndian>()
+                    .map_err(|_| "failed reading package distribution length")?
+                    as usize;
 
-                    current_resource.in_memory_package_resources = Some(resources);
-                }
+                let mut resources = HashMap::with_capacity(self.bounded_capacity(resource_count));
 
-                ResourceField::InMemoryDistributionResource => {
-                    let resource_count = self
+                for _ in 0..resource_count {
+                    let name_length = self
                         .reader
-                        .read_u32::<LittleEndian>()
-                        .map_err(|_| "failed reading package distribution length")?
+                        .read_u16::<LittleEndian>()
+                        .map_err(|_| "failed reading distribution metadata name")?
                         as usize;
 
-                    let mut resources = HashMap::with_capacity(resource_count);
+                    let name = self.resolve_str(field_type, name_length)?;
 
-                    for _ in 0..resource_count {
-                        let name_length = self
-                            .reader
-                            .read_u16::<LittleEndian>()
-                            .map_err(|_| "failed reading distribution metadata name")?
-                            as usize;
+                    let resource_length = self
+                        .reader
+                        .read_u64::<LittleEndian>()
+                        .map_err(|_| "failed reading package distribution resource length")?
+                        as usize;
 
-                        let name = unsafe {
-                            std::str::from_utf8_unchecked(
-                                self.resolve_blob_data(field_type, name_length),
-                            )
-                        };
+                    let resource_data = self.resolve_blob_data(field_type, resource_length)?;
 
-                        let resource_length =
-                            self.reader.read_u64::<LittleEndian>().map_err(|_| {
-                                "failed reading package distribution resource length"
-                            })? as usize;
+                    resources.insert(name, resource_data);
+                }
 
-                        let resource_data = self.resolve_blob_data(field_type, resource_length);
+                resource.in_memory_distribution_resources = Some(resources);
+            }
 
-                        resources.insert(Cow::Borrowed(name), Cow::Borrowed(resource_data));
-                    }
+            ResourceField::InMemorySharedLibrary => {
+                let l = self
+                    .reader
+                    .read_u64::<LittleEndian>()
+                    .map_err(|_| "failed reading in-memory shared library length")?
+                    as usize;
 
-                    current_resource.in_memory_distribution_resources = Some(resources);
-                }
+                resource.in_memory_shared_library = Some(self.resolve_blob_data(field_type, l)?);
+            }
 
-                ResourceField::InMemorySharedLibrary => {
-                    let l = self
-                        .reader
-                        .read_u64::<LittleEndian>()
-                        .map_err(|_| "failed reading in-memory shared library length")?
-                        as usize;
+            ResourceField::SharedLibraryDependencyNames => {
+                let names_count = self
+                    .reader
+                    .read_u16::<LittleEndian>()
+                    .map_err(|_| "failed reading shared library dependency names length")?
+                    as usize;
 
-                    current_resource.in_memory_shared_library =
-                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)));
-                }
+                let mut names = Vec::new();
 
-                ResourceField::SharedLibraryDependencyNames => {
-                    let names_count = self
+                for _ in 0..names_count {
+                    let name_length = self
                         .reader
                         .read_u16::<LittleEndian>()
-                        .map_err(|_| "failed reading shared library dependency names length")?
+                        .map_err(|_| "failed reading shared library dependency name length")?
                         as usize;
 
-                    let mut names = Vec::new();
-
-                    for _ in 0..names_count {
-                        let name_length =
-                            self.reader.read_u16::<LittleEndian>().map_err(|_| {
-                                "failed reading shared library dependency name length"
-                            })? as usize;
-
-                        let name = unsafe {
-                            std::str::from_utf8_unchecked(
-                                self.resolve_blob_data(field_type, name_length),
-                            )
-                        };
+                    let name = self.resolve_str(field_type, name_length)?;
 
-                        names.push(Cow::Borrowed(name));
-                    }
-
-                    current_resource.shared_library_dependency_names = Some(names);
+                    names.push(name);
                 }
 
-                ResourceField::RelativeFilesystemModuleSource => {
-                    let path_length = self
-                        .reader
-                        .read_u32::<LittleEndian>()
-                        .map_err(|_| "failed reading Python module relative path length")?
-                        as usize;
+                resource.shared_library_dependency_names = Some(names);
+            }
 
-                    let path = self.resolve_path(field_type, path_length);
+            ResourceField::RelativeFilesystemModuleSource => {
+                let path_length = self
+                    .reader
+                    .read_u32::<LittleEndian>()
+                    .map_err(|_| "failed reading Python module relative path length")?
+                    as usize;
 
-                    current_resource.relative_path_module_source = Some(path);
-                }
+                let path = self.resolve_path(field_type, path_length)?;
 
-                ResourceField::RelativeFilesystemModuleBytecode => {
-                    let path_length =
-                        self.reader.read_u32::<LittleEndian>().map_err(|_| {
-                            "failed reading Python module bytecode relative path length"
-                        })? as usize;
+                resource.relative_path_module_source = Some(path);
+            }
 
-                    let path = self.resolve_path(field_type, path_length);
+            ResourceField::RelativeFilesystemModuleBytecode => {
+                let path_length = self
+                    .reader
+                    .read_u32::<LittleEndian>()
+                    .map_err(|_| "failed reading Python module bytecode relative path length")?
+                    as usize;
 
-                    current_resource.relative_path_module_bytecode = Some(path);
-                }
+                let path = self.resolve_path(field_type, path_length)?;
 
-                ResourceField::RelativeFilesystemModuleBytecodeOpt1 => {
-                    let path_length = self.reader.read_u32::<LittleEndian>().map_err(|_| {
-                        "failed reading Python module bytecode opt 1 relative path length"
-                    })? as usize;
+                resource.relative_path_module_bytecode = Some(path);
+            }
 
-                    let path = self.resolve_path(field_type, path_length);
+            ResourceField::RelativeFilesystemModuleBytecodeOpt1 => {
+                let path_length = self.reader.read_u32::<LittleEndian>().map_err(|_| {
+                    "failed reading Python module bytecode opt 1 relative path length"
+                })? as usize;
 
-                    current_resource.relative_path_module_bytecode_opt1 = Some(path);
-                }
+                let path = self.resolve_path(field_type, path_length)?;
 
-                ResourceField::RelativeFilesystemModuleBytecodeOpt2 => {
-                    let path_length = self.reader.read_u32::<LittleEndian>().map_err(|_| {
-                        "failed reading Python module bytecode opt 2 relative path length"
-                    })? as usize;
+                resource.relative_path_module_bytecode_opt1 = Some(path);
+            }
 
-                    let path = self.resolve_path(field_type, path_length);
+            ResourceField::RelativeFilesystemModuleBytecodeOpt2 => {
+                let path_length = self.reader.read_u32::<LittleEndian>().map_err(|_| {
+                    "failed reading Python module bytecode opt 2 relative path length"
+                })? as usize;
 
-                    current_resource.relative_path_module_bytecode_opt2 = Some(path);
-                }
+                let path = self.resolve_path(field_type, path_length)?;
 
-                ResourceField::RelativeFilesystemExtensionModuleSharedLibrary => {
-                    let path_length = self.reader.read_u32::<LittleEndian>().map_err(|_| {
-                        "failed reading Python extension module shared library relative path length"
-                    })? as usize;
+                resource.relative_path_module_bytecode_opt2 = Some(path);
+            }
 
-                    let path = self.resolve_path(field_type, path_length);
+            ResourceField::RelativeFilesystemExtensionModuleSharedLibrary => {
+                let path_length = self.reader.read_u32::<LittleEndian>().map_err(|_| {
+                    "failed reading Python extension module shared library relative path length"
+                })? as usize;
 
-                    current_resource.relative_path_extension_module_shared_library = Some(path);
-                }
+                let path = self.resolve_path(field_type, path_length)?;
 
-                ResourceField::RelativeFilesystemPackageResources => {
-                    let resource_count =
-                        self.reader.read_u32::<LittleEndian>().map_err(|_| {
-                            "failed reading package resources relative path item count"
-                        })? as usize;
+                resource.relative_path_extension_module_shared_library = Some(path);
+            }
 
-                    let mut resources = HashMap::with_capacity(resource_count);
+            ResourceField::RelativeFilesystemPackageResources => {
+                let resource_count = self
+                    .reader
+                    .read_u32::<LittleEndian>()
+                    .map_err(|_| "failed reading package resources relative path item count")?
+                    as usize;
 
-                    for _ in 0..resource_count {
-                        let resource_name_length = self
-                            .reader
-                            .read_u16::<LittleEndian>()
-                            .map_err(|_| "failed reading resource name")?
-                            as usize;
+                let mut resources = HashMap::with_capacity(self.bounded_capacity(resource_count));
 
-                        let resource_name = unsafe {
-                            std::str::from_utf8_unchecked(
-                                self.resolve_blob_data(field_type, resource_name_length),
-                            )
-                        };
+                for _ in 0..resource_count {
+                    let resource_name_length = self
+                        .reader
+                        .read_u16::<LittleEndian>()
+                        .map_err(|_| "failed reading resource name")?
+                        as usize;
 
-                        let path_length = self
-                            .reader
-                            .read_u32::<LittleEndian>()
-                            .map_err(|_| "failed reading resource path length")?
-                            as usize;
+                    let resource_name = self.resolve_str(field_type, resource_name_length)?;
 
-                        let path = self.resolve_path(field_type, path_length);
+                    let path_length = self
+                        .reader
+                        .read_u32::<LittleEndian>()
+                        .map_err(|_| "failed reading resource path length")?
+                        as usize;
 
-                        resources.insert(Cow::Borrowed(resource_name), path);
-                    }
+                    let path = self.resolve_path(field_type, path_length)?;
 
-                    current_resource.relative_path_package_resources = Some(resources);
+                    resources.insert(resource_name, path);
                 }
 
-                ResourceField::RelativeFilesystemDistributionResource => {
-                    let resource_count = self.reader.read_u32::<LittleEndian>().map_err(|_| {
+                resource.relative_path_package_resources = Some(resources);
+            }
+
+            ResourceField::RelativeFilesystemDistributionResource => {
+                let resource_count =
+                    self.reader.read_u32::<LittleEndian>().map_err(|_| {
                         "failed reading package distribution relative path item count"
                     })? as usize;
 
-                    let mut resources = HashMap::with_capacity(resource_count);
+                let mut resources = HashMap::with_capacity(self.bounded_capacity(resource_count));
 
-                    for _ in 0..resource_count {
-                        let name_length = self
-                            .reader
-                            .read_u16::<LittleEndian>()
-                            .map_err(|_| "failed reading package distribution metadata name")?
-                            as usize;
-
-                        let name = unsafe {
-                            std::str::from_utf8_unchecked(
-                                self.resolve_blob_data(field_type, name_length),
-                            )
-                        };
+                for _ in 0..resource_count {
+                    let name_length = self
+                        .reader
+                        .read_u16::<LittleEndian>()
+                        .map_err(|_| "failed reading package distribution metadata name")?
+                        as usize;
 
-                        let path_length = self
-                            .reader
-                            .read_u32::<LittleEndian>()
-                            .map_err(|_| "failed reading package distribution path length")?
-                            as usize;
+                    let name = self.resolve_str(field_type, name_length)?;
 
-                        let path = self.resolve_path(field_type, path_length);
+                    let path_length = self
+                        .reader
+                        .read_u32::<LittleEndian>()
+                        .map_err(|_| "failed reading package distribution path length")?
+                        as usize;
 
-                        resources.insert(Cow::Borrowed(name), path);
-                    }
+                    let path = self.resolve_path(field_type, path_length)?;
 
-                    current_resource.relative_path_distribution_resources = Some(resources);
+                    resources.insert(name, path);
                 }
 
-                ResourceField::IsModule => {
-                    current_resource.is_module = true;
-                }
+                resource.relative_path_distribution_resources = Some(resources);
+            }
 
-                ResourceField::IsBuiltinExtensionModule => {
-                    current_resource.is_builtin_extension_module = true;
-                }
+            ResourceField::IsModule => {
+                resource.is_module = true;
+            }
 
-                ResourceField::IsFrozenModule => {
-                    current_resource.is_frozen_module = true;
-                }
+            ResourceField::IsBuiltinExtensionModule => {
+                resource.is_builtin_extension_module = true;
+            }
 
-                ResourceField::IsExtensionModule => {
-                    current_resource.is_extension_module = true;
-                }
+            ResourceField::IsFrozenModule => {
+                resource.is_frozen_module = true;
+            }
 
-                ResourceField::IsSharedLibrary => {
-                    current_resource.is_shared_library = true;
-                }
+            ResourceField::IsExtensionModule => {
+                resource.is_extension_module = true;
+            }
 
-                ResourceField::IsUtf8FilenameData => {
-                    current_resource.is_utf8_filename_data = true;
-                }
+            ResourceField::IsSharedLibrary => {
+                resource.is_shared_library = true;
+            }
 
-                ResourceField::FileExecutable => {
-                    current_resource.file_executable = true;
-                }
+            ResourceField::IsUtf8FilenameData => {
+                resource.is_utf8_filename_data = true;
+            }
 
-                ResourceField::FileDataEmbedded => {
-                    let l = self
-                        .reader
-                        .read_u64::<LittleEndian>()
-                        .map_err(|_| "failed reading embedded file data length")?
-                        as usize;
+            ResourceField::FileExecutable => {
+                resource.file_executable = true;
+            }
 
-                    current_resource.file_data_embedded =
-                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)));
-                }
+            ResourceField::FileDataEmbedded => {
+                let l = self
+                    .reader
+                    .read_u64::<LittleEndian>()
+                    .map_err(|_| "failed reading embedded file data length")?
+                    as usize;
 
-                ResourceField::FileDataUtf8RelativePath => {
-                    let l = self
-                        .reader
-                        .read_u32::<LittleEndian>()
-                        .map_err(|_| "failed reading file data relative path length")?
-                        as usize;
+                resource.file_data_embedded = Some(self.resolve_blob_data(field_type, l)?);
+            }
 
-                    current_resource.file_data_utf8_relative_path = Some(Cow::Borrowed(unsafe {
-                        std::str::from_utf8_unchecked(self.resolve_blob_data(field_type, l))
-                    }));
-                }
+            ResourceField::FileDataUtf8RelativePath => {
+                let l = self
+                    .reader
+                    .read_u32::<LittleEndian>()
+                    .map_err(|_| "failed reading file data relative path length")?
+                    as usize;
+
+                resource.file_data_utf8_relative_path = Some(self.resolve_str(field_type, l)?);
+            }
+
+            ResourceField::ContentSha256 => {
+                let l = self
+                    .reader
+                    .read_u8()
+                    .map_err(|_| "failed reading content digest length")?
+                    as usize;
+
+                resource.content_sha256 = Some(self.resolve_blob_data(field_type, l)?);
             }
         }
+
+        Ok(())
+    }
+
+    /// Resolve a field that was deferred when the resource was parsed.
+    fn resolve_deferred_field(
+        &mut self,
+        field: &DeferredResourceField,
+        resource: &mut Resource<'a, u8>,
+    ) -> Result<(), &'static str> {
+        self.reader.set_position(field.index_offset as u64);
+        self.blob_sections[field.field as usize]
+            .as_mut()
+            .ok_or("blob section not found for resource field")?
+            .offset = field.blob_offset;
+
+        self.parse_field(field.field, resource)
     }
 }
 
@@ -512,13 +819,111 @@ impl<'a> Iterator for ResourceParserIterator<'a> {
         }
 
         match self.parse_next() {
+            Ok(res) => res.map(|deferred| Ok(deferred.resource)),
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+/// Iterates over resources without reading data in compressed blob sections.
+///
+/// Obtained from [load_resources_deferred].
+pub struct DeferredResourceParserIterator<'a> {
+    parser: ResourceParserIterator<'a>,
+}
+
+impl<'a> DeferredResourceParserIterator<'a> {
+    /// The expected number of resources we will emit.
+    pub fn expected_resources_count(&self) -> usize {
+        self.parser.expected_resources_count()
+    }
+}
+
+impl<'a> Iterator for DeferredResourceParserIterator<'a> {
+    type Item = Result<DeferredResource<'a>, &'static str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.parser.done {
+            return None;
+        }
+
+        match self.parser.parse_next() {
             Ok(res) => res.map(Ok),
             Err(e) => Some(Err(e)),
         }
     }
 }
 
+/// Parse packed resources data.
+///
+/// Data in compressed blob sections is copied out of the decompressed
+/// sections. Use [load_resources_with_sections] to avoid the copy.
 pub fn load_resources<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>, &'static str> {
+    load_resources_with_storage(
+        data,
+        SectionStorage::Owned(DecompressedBlobSections::default()),
+    )
+}
+
+/// Parse packed resources data, decompressing blob sections into `sections`.
+///
+/// Resources borrow data in compressed blob sections from `sections`. So
+/// no resource data is copied.
+pub fn load_resources_with_sections<'a>(
+    data: &'a [u8],
+    sections: &'a DecompressedBlobSections,
+) -> Result<ResourceParserIterator<'a>, &'static str> {
+    load_resources_with_storage(data, SectionStorage::Borrowed(sections))
+}
+
+/// Parse packed resources data without reading data in compressed blob sections.
+///
+/// Resource names are always read. Other fields stored in compressed blob
+/// sections are deferred until the resource is passed to
+/// [resolve_deferred_resource]. So blob sections are only decompressed once
+/// a resource in them is resolved.
+///
+/// Because deferred fields aren't read, errors in their data, such as invalid
+/// UTF-8 or corrupt compressed data, are only reported when resolving them.
+pub fn load_resources_deferred<'a>(
+    data: &'a [u8],
+    sections: &'a DecompressedBlobSections,
+) -> Result<DeferredResourceParserIterator<'a>, &'static str> {
+    let mut parser = load_resources_with_sections(data, sections)?;
+    parser.defer_compressed = true;
+
+    Ok(DeferredResourceParserIterator { parser })
+}
+
+/// Read the deferred fields of a resource.
+///
+/// `data` and `sections` must be the same values the resource was parsed
+/// with by [load_resources_deferred]. Blob sections holding deferred fields
+/// are decompressed into `sections` if they haven't been already.
+pub fn resolve_deferred_resource<'a>(
+    data: &'a [u8],
+    sections: &'a DecompressedBlobSections,
+    deferred: DeferredResource<'a>,
+) -> Result<Resource<'a, u8>, &'static str> {
+    let mut resource = deferred.resource;
+
+    if deferred.deferred_fields.is_empty() {
+        return Ok(resource);
+    }
+
+    let mut parser = load_resources_with_sections(data, sections)?;
+
+    for field in &deferred.deferred_fields {
+        parser.resolve_deferred_field(field, &mut resource)?;
+    }
+
+    Ok(resource)
+}
+
+fn load_resources_with_storage<'a>(
+    data: &'a [u8],
+    storage: SectionStorage<'a>,
+) -> Result<ResourceParserIterator<'a>, &'static str> {
     if data.len() < HEADER_V3.len() {
         return Err("error reading 8 byte header");
     }
@@ -526,13 +931,23 @@ pub fn load_resources<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>,
     let header = &data[0..8];
 
     if header == HEADER_V3 {
-        load_resources_v3(&data[8..])
+        load_resources_v3(&data[8..], false, storage)
+    } else if header == HEADER_V4 {
+        load_resources_v3(&data[8..], true, storage)
     } else {
         Err("unrecognized file format")
     }
 }
 
-fn load_resources_v3<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>, &'static str> {
+/// Parse the payload following the header.
+///
+/// Versions 3 and 4 share the same layout. Version 4 additionally allows blob
+/// sections to be compressed, which is indicated by `allow_compression`.
+fn load_resources_v3<'a>(
+    data: &'a [u8],
+    allow_compression: bool,
+    storage: SectionStorage<'a>,
+) -> Result<ResourceParserIterator<'a>, &'static str> {
     let mut reader = Cursor::new(data);
 
     let blob_section_count = reader
@@ -552,6 +967,8 @@ fn load_resources_v3<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>, &
     let mut current_blob_field = None;
     let mut current_blob_raw_payload_length = None;
     let mut current_blob_interior_padding = None;
+    let mut current_blob_compression = None;
+    let mut current_blob_uncompressed_payload_length = None;
     let mut blob_entry_count = 0;
     let mut blob_sections = Vec::with_capacity(blob_section_count as usize);
 
@@ -570,6 +987,8 @@ fn load_resources_v3<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>, &
                     current_blob_field = None;
                     current_blob_raw_payload_length = None;
                     current_blob_interior_padding = None;
+                    current_blob_compression = None;
+                    current_blob_uncompressed_payload_length = None;
                 }
                 BlobSectionField::EndOfEntry => {
                     if current_blob_field.is_none() {
@@ -578,16 +997,27 @@ fn load_resources_v3<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>, &
                     if current_blob_raw_payload_length.is_none() {
                         return Err("blob raw payload length is required");
                     }
+                    if matches!(
+                        current_blob_compression,
+                        Some(BlobSectionCompression::Zstd) | Some(BlobSectionCompression::Lz4)
+                    ) && current_blob_uncompressed_payload_length.is_none()
+                    {
+                        return Err("blob uncompressed payload length is required");
+                    }
 
                     blob_sections.push(BlobSection {
                         resource_field: current_blob_field.unwrap(),
                         raw_payload_length: current_blob_raw_payload_length.unwrap(),
                         interior_padding: current_blob_interior_padding,
+                        compression: current_blob_compression,
+                        uncompressed_payload_length: current_blob_uncompressed_payload_length,
                     });
 
                     current_blob_field = None;
                     current_blob_raw_payload_length = None;
                     current_blob_interior_padding = None;
+                    current_blob_compression = None;
+                    current_blob_uncompressed_payload_length = None;
                 }
                 BlobSectionField::ResourceFieldType => {
                     let field = reader
@@ -612,6 +1042,27 @@ fn load_resources_v3<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>, &
                         _ => return Err("invalid value for interior padding field"),
                     });
                 }
+                BlobSectionField::Compression => {
+                    if !allow_compression {
+                        return Err("blob section compression requires format version 4");
+                    }
+
+                    let compression = reader
+                        .read_u8()
+                        .map_err(|_| "failed reading compression field value")?;
+
+                    current_blob_compression = Some(BlobSectionCompression::try_from(compression)?);
+                }
+                BlobSectionField::UncompressedPayloadLength => {
+                    if !allow_compression {
+                        return Err("blob section compression requires format version 4");
+                    }
+
+                    let l = reader
+                        .read_u64::<LittleEndian>()
+                        .map_err(|_| "failed reading uncompressed payload length")?;
+                    current_blob_uncompressed_payload_length = Some(l as usize);
+                }
             }
         }
     }
@@ -630,17 +1081,39 @@ fn load_resources_v3<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>, &
             + blob_index_length
             + resources_index_length
         ;
+    if blob_start_offset > data.len() {
+        return Err("index data extends beyond end of data");
+    }
+
     // Current offset from start of blobs data.
     let mut current_blob_offset = 0;
 
     for section in &blob_sections {
         let section_start_offset = blob_start_offset + current_blob_offset;
+        let compression = section.compression.unwrap_or(BlobSectionCompression::None);
+
+        match section_start_offset.checked_add(section.raw_payload_length) {
+            Some(end) if end <= data.len() => {}
+            _ => return Err("blob section extends beyond end of data"),
+        }
+
         blob_offsets[section.resource_field as usize] = Some(BlobSectionReadState {
-            offset: section_start_offset,
+            // Reads from compressed sections are relative to the decompressed data.
+            offset: if compression == BlobSectionCompression::None {
+                section_start_offset
+            } else {
+                0
+            },
             interior_padding: match section.interior_padding {
                 Some(padding) => padding,
                 None => BlobInteriorPadding::None,
             },
+            compression,
+            raw_offset: section_start_offset,
+            raw_length: section.raw_payload_length,
+            uncompressed_length: section
+                .uncompressed_payload_length
+                .unwrap_or(section.raw_payload_length),
         });
         current_blob_offset += section.raw_payload_length;
     }
@@ -650,8 +1123,12 @@ fn load_resources_v3<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>, &
         data,
         reader,
         blob_sections: blob_offsets,
+        decompressed_sections: storage,
+        defer_compressed: false,
+        skip_compressed_data: false,
         claimed_resources_count: resources_count,
         read_resources_count: 0,
+        packed_length: HEADER_V3.len() + blob_start_offset + current_blob_offset,
     })
 }
 
@@ -660,7 +1137,7 @@ mod tests {
     use {
         super::*,
         crate::data::{BlobInteriorPadding, Resource},
-        crate::writer::write_packed_resources_v3,
+        crate::writer::{write_packed_resources_v3, write_packed_resources_v4},
         std::collections::BTreeMap,
     };
 
@@ -678,7 +1155,7 @@ mod tests {
         let res = load_resources(data);
         assert_eq!(res.err(), Some("unrecognized file format"));
 
-        let data = b"pyembed\x04";
+        let data = b"pyembed\x05";
         let res = load_resources(data);
         assert_eq!(res.err(), Some("unrecognized file format"));
     }
@@ -1410,6 +1887,7 @@ mod tests {
             file_executable: true,
             file_data_embedded: Some(Cow::from(b"file_data_embedded".to_vec())),
             file_data_utf8_relative_path: Some(Cow::from("file_data_utf8_relative_path")),
+            content_sha256: None,
         };
 
         let mut data = Vec::new();
@@ -1553,4 +2031,247 @@ mod tests {
 
         assert_eq!(resources, loaded);
     }
+
+    fn compressible_resources() -> Vec<Resource<'static, u8>> {
+        let mut package_resources = HashMap::new();
+        package_resources.insert(Cow::from("data.txt"), Cow::from(b"data ".repeat(64)));
+        package_resources.insert(Cow::from("other.txt"), Cow::from(b"other".to_vec()));
+
+        (0..32)
+            .map(|i| Resource {
+                name: Cow::Owned(format!("module{}", i)),
+                is_module: true,
+                in_memory_source: Some(Cow::Owned(
+                    format!("import io\nimport os\nvalue = {}\n", i).into_bytes(),
+                )),
+                in_memory_bytecode: Some(Cow::Owned(b"fake bytecode ".repeat(i + 1))),
+                in_memory_package_resources: Some(package_resources.clone()),
+                relative_path_module_source: Some(Cow::Owned(
+                    Path::new(&format!("lib/module{}.py", i)).to_path_buf(),
+                )),
+                ..Resource::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_v4_uncompressed() {
+        let resources = compressible_resources();
+
+        let mut data = Vec::new();
+        write_packed_resources_v4(&resources, &mut data, None, None).unwrap();
+        assert_eq!(&data[0..8], b"pyembed\x04");
+
+        let loaded = load_resources(&data)
+            .unwrap()
+            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .unwrap();
+
+        assert_eq!(resources, loaded);
+        assert!(matches!(loaded[0].in_memory_source, Some(Cow::Borrowed(_))));
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn test_v4_zstd() {
+        let resources = compressible_resources();
+
+        let mut uncompressed = Vec::new();
+        write_packed_resources_v4(&resources, &mut uncompressed, None, None).unwrap();
+
+        let mut data = Vec::new();
+        write_packed_resources_v4(
+            &resources,
+            &mut data,
+            None,
+            Some(BlobSectionCompression::Zstd),
+        )
+        .unwrap();
+        assert!(data.len() < uncompressed.len());
+
+        let loaded = load_resources(&data)
+            .unwrap()
+            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .unwrap();
+
+        assert_eq!(resources, loaded);
+    }
+
+    #[test]
+    #[cfg(feature = "lz4")]
+    fn test_v4_lz4() {
+        let resources = compressible_resources();
+
+        let mut uncompressed = Vec::new();
+        write_packed_resources_v4(&resources, &mut uncompressed, None, None).unwrap();
+
+        let mut data = Vec::new();
+        write_packed_resources_v4(
+            &resources,
+            &mut data,
+            None,
+            Some(BlobSectionCompression::Lz4),
+        )
+        .unwrap();
+        assert!(data.len() < uncompressed.len());
+
+        let loaded = load_resources(&data)
+            .unwrap()
+            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .unwrap();
+
+        assert_eq!(resources, loaded);
+    }
+
+    #[test]
+    fn test_v4_uncompressed_borrowed() {
+        let resources = compressible_resources();
+
+        let mut data = Vec::new();
+        write_packed_resources_v4(&resources, &mut data, None, None).unwrap();
+
+        for resource in load_resources(&data).unwrap() {
+            let resource = resource.unwrap();
+            assert!(matches!(resource.name, Cow::Borrowed(_)));
+            assert!(matches!(
+                resource.in_memory_bytecode,
+                Some(Cow::Borrowed(_))
+            ));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn test_v4_compressed_borrowed_sections() {
+        let resources = compressible_resources();
+
+        let mut data = Vec::new();
+        write_packed_resources_v4(
+            &resources,
+            &mut data,
+            None,
+            Some(BlobSectionCompression::Zstd),
+        )
+        .unwrap();
+
+        let sections = DecompressedBlobSections::default();
+        let mut iter = load_resources_with_sections(&data, &sections).unwrap();
+
+        // Nothing is decompressed until data is read.
+        assert!(sections.sections.iter().all(|s| s.get().is_none()));
+
+        let first = iter.next().unwrap().unwrap();
+        assert!(matches!(first.name, Cow::Borrowed(_)));
+        assert!(matches!(first.in_memory_bytecode, Some(Cow::Borrowed(_))));
+        assert!(sections.sections[ResourceField::InMemoryBytecode as usize]
+            .get()
+            .is_some());
+
+        let mut loaded = vec![first];
+        for resource in iter {
+            loaded.push(resource.unwrap());
+        }
+
+        assert_eq!(resources, loaded);
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn test_v4_deferred() {
+        let resources = compressible_resources();
+
+        let mut data = Vec::new();
+        write_packed_resources_v4(
+            &resources,
+            &mut data,
+            Some(BlobInteriorPadding::Null),
+            Some(BlobSectionCompression::Zstd),
+        )
+        .unwrap();
+
+        let sections = DecompressedBlobSections::default();
+        let deferred = load_resources_deferred(&data, &sections)
+            .unwrap()
+            .collect::<Result<Vec<DeferredResource>, &'static str>>()
+            .unwrap();
+
+        // Only resource names are read when indexing.
+        for (i, section) in sections.sections.iter().enumerate() {
+            assert_eq!(
+                section.get().is_some(),
+                i == ResourceField::ModuleName as usize
+            );
+        }
+
+        assert_eq!(deferred.len(), resources.len());
+        assert_eq!(deferred[0].resource.name, "module0");
+        assert!(deferred[0].resource.is_module);
+        assert!(deferred[0].resource.in_memory_bytecode.is_none());
+        assert!(deferred[0].is_deferred(ResourceField::InMemoryBytecode));
+        assert!(!deferred[0].is_resolved());
+
+        // Resolving resources out of order yields the original data.
+        for (i, resource) in deferred.into_iter().enumerate().rev() {
+            let resolved = resolve_deferred_resource(&data, &sections, resource).unwrap();
+            assert_eq!(resolved, resources[i]);
+            assert!(matches!(
+                resolved.in_memory_bytecode,
+                Some(Cow::Borrowed(_))
+            ));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn test_v4_compressed_null_padding() {
+        let resources = compressible_resources();
+
+        let mut data = Vec::new();
+        write_packed_resources_v4(
+            &resources,
+            &mut data,
+            Some(BlobInteriorPadding::Null),
+            Some(BlobSectionCompression::Zstd),
+        )
+        .unwrap();
+
+        let loaded = load_resources(&data)
+            .unwrap()
+            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .unwrap();
+
+        assert_eq!(resources, loaded);
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn test_decompress_huge_uncompressed_length() {
+        let data = zstd::encode_all(&b"foo"[..], 3).unwrap();
+
+        assert_eq!(
+            decompress_blob_section(BlobSectionCompression::Zstd, &data, usize::MAX),
+            Err("decompressed blob section length mismatch")
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn test_v3_rejects_compression() {
+        let resources = compressible_resources();
+
+        let mut data = Vec::new();
+        write_packed_resources_v4(
+            &resources,
+            &mut data,
+            None,
+            Some(BlobSectionCompression::Zstd),
+        )
+        .unwrap();
+        data[7] = 0x03;
+
+        assert_eq!(
+            load_resources(&data).err(),
+            Some("blob section compression requires format version 4")
+        );
+    }
 }
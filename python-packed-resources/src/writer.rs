@@ -6,7 +6,8 @@
 
 use {
     super::data::{
-        BlobInteriorPadding, BlobSectionField, Resource, ResourceField, ResourceFlavor, HEADER_V3,
+        BlobInteriorPadding, BlobSectionCompression, BlobSectionField, Resource, ResourceField,
+        ResourceFlavor, HEADER_V3, HEADER_V4,
     },
     anyhow::{anyhow, Context, Result},
     byteorder::{LittleEndian, WriteBytesExt},
@@ -46,11 +47,43 @@ fn path_to_bytes(p: &Path) -> Vec<u8> {
     }
 }
 
+/// zstandard compression level to use for compressed blob sections.
+#[cfg(feature = "zstd")]
+const ZSTD_COMPRESSION_LEVEL: i32 = 19;
+
+/// Resource fields that have data stored in blob sections.
+///
+/// Order is significant: blob sections are written in this order.
+const BLOB_FIELDS: &[ResourceField] = &[
+    ResourceField::ModuleName,
+    ResourceField::InMemorySource,
+    ResourceField::InMemoryBytecode,
+    ResourceField::InMemoryBytecodeOpt1,
+    ResourceField::InMemoryBytecodeOpt2,
+    ResourceField::InMemoryExtensionModuleSharedLibrary,
+    ResourceField::InMemoryResourcesData,
+    ResourceField::InMemoryDistributionResource,
+    ResourceField::InMemorySharedLibrary,
+    ResourceField::SharedLibraryDependencyNames,
+    ResourceField::RelativeFilesystemModuleSource,
+    ResourceField::RelativeFilesystemModuleBytecode,
+    ResourceField::RelativeFilesystemModuleBytecodeOpt1,
+    ResourceField::RelativeFilesystemModuleBytecodeOpt2,
+    ResourceField::RelativeFilesystemExtensionModuleSharedLibrary,
+    ResourceField::RelativeFilesystemPackageResources,
+    ResourceField::RelativeFilesystemDistributionResource,
+    ResourceField::FileDataEmbedded,
+    ResourceField::FileDataUtf8RelativePath,
+    ResourceField::ContentSha256,
+];
+
 #[derive(Debug)]
 struct BlobSection {
     resource_field: ResourceField,
     raw_payload_length: usize,
     interior_padding: Option<BlobInteriorPadding>,
+    compression: Option<BlobSectionCompression>,
+    uncompressed_payload_length: Option<usize>,
 }
 
 impl BlobSection {
@@ -70,6 +103,16 @@ impl BlobSection {
             index += 2;
         }
 
+        if self.compression.is_some() {
+            // Field + value.
+            index += 2;
+        }
+
+        if self.uncompressed_payload_length.is_some() {
+            // Field + value.
+            index += 9;
+        }
+
         // End of index entry.
         index += 1;
 
@@ -97,6 +140,20 @@ impl BlobSection {
                 .context("writing interior padding value")?;
         }
 
+        if let Some(compression) = &self.compression {
+            dest.write_u8(BlobSectionField::Compression.into())
+                .context("writing compression field")?;
+            dest.write_u8(compression.into())
+                .context("writing compression value")?;
+        }
+
+        if let Some(length) = self.uncompressed_payload_length {
+            dest.write_u8(BlobSectionField::UncompressedPayloadLength.into())
+                .context("writing uncompressed payload length field")?;
+            dest.write_u64::<LittleEndian>(length as u64)
+                .context("writing uncompressed payload length")?;
+        }
+
         dest.write_u8(BlobSectionField::EndOfEntry.into())
             .context("writing end of index entry")?;
 
@@ -264,6 +321,10 @@ where
             index += 5;
         }
 
+        if self.content_sha256.is_some() {
+            index += 2;
+        }
+
         // End of index entry.
         index += 1;
 
@@ -427,6 +488,13 @@ where
                     0
                 }
             }
+            ResourceField::ContentSha256 => {
+                if let Some(digest) = &self.content_sha256 {
+                    digest.len()
+                } else {
+                    0
+                }
+            }
         }
     }
 
@@ -577,6 +645,13 @@ where
                     0
                 }
             }
+            ResourceField::ContentSha256 => {
+                if self.content_sha256.is_some() {
+                    1
+                } else {
+                    0
+                }
+            }
         };
 
         let overhead = match padding {
@@ -862,6 +937,13 @@ where
                 .context("writing file_data_utf_relative_path field")?;
         }
 
+        if let Some(digest) = &self.content_sha256 {
+            let l = u8::try_from(digest.len()).context("converting content digest length to u8")?;
+            dest.write_u8(ResourceField::ContentSha256.into())
+                .context("writing content_sha256 field")?;
+            dest.write_u8(l).context("writing content_sha256 length")?;
+        }
+
         dest.write_u8(ResourceField::EndOfEntry.into())
             .map_err(|_| anyhow!("error writing end of index entry"))?;
 
@@ -869,13 +951,224 @@ where
     }
 }
 
+/// Write blob data for a single resource field.
+///
+/// Data for the field is written for every resource, in order, with interior
+/// padding applied between elements.
+fn write_blob_section_data<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
+    modules: &[T],
+    field: ResourceField,
+    dest: &mut W,
+    interior_padding: Option<BlobInteriorPadding>,
+) -> Result<()> {
+    let add_interior_padding = |dest: &mut W| -> Result<()> {
+        if interior_padding == Some(BlobInteriorPadding::Null) {
+            dest.write_all(b"\0")?;
+        }
+
+        Ok(())
+    };
+
+    for module in modules {
+        let module = module.as_ref();
+
+        match field {
+            ResourceField::ModuleName => {
+                dest.write_all(module.name.as_bytes())?;
+                add_interior_padding(dest)?;
+            }
+            ResourceField::InMemorySource => {
+                if let Some(data) = &module.in_memory_source {
+                    dest.write_all(data)?;
+                    add_interior_padding(dest)?;
+                }
+            }
+            ResourceField::InMemoryBytecode => {
+                if let Some(data) = &module.in_memory_bytecode {
+                    dest.write_all(data)?;
+                    add_interior_padding(dest)?;
+                }
+            }
+            ResourceField::InMemoryBytecodeOpt1 => {
+                if let Some(data) = &module.in_memory_bytecode_opt1 {
+                    dest.write_all(data)?;
+                    add_interior_padding(dest)?;
+                }
+            }
+            ResourceField::InMemoryBytecodeOpt2 => {
+                if let Some(data) = &module.in_memory_bytecode_opt2 {
+                    dest.write_all(data)?;
+                    add_interior_padding(dest)?;
+                }
+            }
+            ResourceField::InMemoryExtensionModuleSharedLibrary => {
+                if let Some(data) = &module.in_memory_extension_module_shared_library {
+                    dest.write_all(data)?;
+                    add_interior_padding(dest)?;
+                }
+            }
+            ResourceField::InMemoryResourcesData => {
+                if let Some(resources) = &module.in_memory_package_resources {
+                    for (key, value) in resources.iter() {
+                        dest.write_all(key.as_bytes())?;
+                        add_interior_padding(dest)?;
+                        dest.write_all(value)?;
+                        add_interior_padding(dest)?;
+                    }
+                }
+            }
+            ResourceField::InMemoryDistributionResource => {
+                if let Some(resources) = &module.in_memory_distribution_resources {
+                    for (key, value) in resources {
+                        dest.write_all(key.as_bytes())?;
+                        add_interior_padding(dest)?;
+                        dest.write_all(value)?;
+                        add_interior_padding(dest)?;
+                    }
+                }
+            }
+            ResourceField::InMemorySharedLibrary => {
+                if let Some(data) = &module.in_memory_shared_library {
+                    dest.write_all(data)?;
+                    add_interior_padding(dest)?;
+                }
+            }
+            ResourceField::SharedLibraryDependencyNames => {
+                if let Some(names) = &module.shared_library_dependency_names {
+                    for name in names {
+                        dest.write_all(name.as_bytes())?;
+                        add_interior_padding(dest)?;
+                    }
+                }
+            }
+            ResourceField::RelativeFilesystemModuleSource => {
+                if let Some(path) = &module.relative_path_module_source {
+                    dest.write_all(&path_to_bytes(path))?;
+                    add_interior_padding(dest)?;
+                }
+            }
+            ResourceField::RelativeFilesystemModuleBytecode => {
+                if let Some(path) = &module.relative_path_module_bytecode {
+                    dest.write_all(&path_to_bytes(path))?;
+                    add_interior_padding(dest)?;
+                }
+            }
+            ResourceField::RelativeFilesystemModuleBytecodeOpt1 => {
+                if let Some(path) = &module.relative_path_module_bytecode_opt1 {
+                    dest.write_all(&path_to_bytes(path))?;
+                    add_interior_padding(dest)?;
+                }
+            }
+            ResourceField::RelativeFilesystemModuleBytecodeOpt2 => {
+                if let Some(path) = &module.relative_path_module_bytecode_opt2 {
+                    dest.write_all(&path_to_bytes(path))?;
+                    add_interior_padding(dest)?;
+                }
+            }
+            ResourceField::RelativeFilesystemExtensionModuleSharedLibrary => {
+                if let Some(path) = &module.relative_path_extension_module_shared_library {
+                    dest.write_all(&path_to_bytes(path))?;
+                    add_interior_padding(dest)?;
+                }
+            }
+            ResourceField::RelativeFilesystemPackageResources => {
+                if let Some(resources) = &module.relative_path_package_resources {
+                    for (key, path) in resources.iter() {
+                        dest.write_all(key.as_bytes())?;
+                        add_interior_padding(dest)?;
+                        dest.write_all(&path_to_bytes(path))?;
+                        add_interior_padding(dest)?;
+                    }
+                }
+            }
+            ResourceField::RelativeFilesystemDistributionResource => {
+                if let Some(resources) = &module.relative_path_distribution_resources {
+                    for (key, path) in resources {
+                        dest.write_all(key.as_bytes())?;
+                        add_interior_padding(dest)?;
+                        dest.write_all(&path_to_bytes(path))?;
+                        add_interior_padding(dest)?;
+                    }
+                }
+            }
+            ResourceField::FileDataEmbedded => {
+                if let Some(data) = &module.file_data_embedded {
+                    dest.write_all(data)?;
+                    add_interior_padding(dest)?;
+                }
+            }
+            ResourceField::FileDataUtf8RelativePath => {
+                if let Some(path) = &module.file_data_utf8_relative_path {
+                    dest.write_all(path.as_bytes())?;
+                    add_interior_padding(dest)?;
+                }
+            }
+            ResourceField::ContentSha256 => {
+                if let Some(digest) = &module.content_sha256 {
+                    dest.write_all(digest)?;
+                    add_interior_padding(dest)?;
+                }
+            }
+            // Remaining fields don't have blob data.
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Compress blob section data with the given codec.
+fn compress_blob_section(compression: BlobSectionCompression, data: &[u8]) -> Result<Vec<u8>> {
+    match compression {
+        BlobSectionCompression::None => Ok(data.to_vec()),
+        #[cfg(feature = "zstd")]
+        BlobSectionCompression::Zstd => {
+            zstd::block::compress(data, ZSTD_COMPRESSION_LEVEL).context("zstd compressing blob")
+        }
+        #[cfg(not(feature = "zstd"))]
+        BlobSectionCompression::Zstd => Err(anyhow!("zstd support not available")),
+        #[cfg(feature = "lz4")]
+        BlobSectionCompression::Lz4 => Ok(lz4_flex::block::compress(data)),
+        #[cfg(not(feature = "lz4"))]
+        BlobSectionCompression::Lz4 => Err(anyhow!("lz4 support not available")),
+    }
+}
+
 /// Write packed resources data, version 3.
-#[allow(clippy::cognitive_complexity)]
 pub fn write_packed_resources_v3<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
     modules: &[T],
     dest: &mut W,
     interior_padding: Option<BlobInteriorPadding>,
 ) -> Result<()> {
+    write_packed_resources(modules, dest, HEADER_V3, interior_padding, None)
+}
+
+/// Write packed resources data, version 4.
+///
+/// Version 4 allows blob sections to be compressed. When `compression` is
+/// defined, every blob section is compressed with that codec. Sections that
+/// don't get smaller when compressed are stored uncompressed.
+pub fn write_packed_resources_v4<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
+    modules: &[T],
+    dest: &mut W,
+    interior_padding: Option<BlobInteriorPadding>,
+    compression: Option<BlobSectionCompression>,
+) -> Result<()> {
+    write_packed_resources(modules, dest, HEADER_V4, interior_padding, compression)
+}
+
+#[allow(clippy::cognitive_complexity)]
+fn write_packed_resources<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
+    modules: &[T],
+    dest: &mut W,
+    header: &[u8],
+    interior_padding: Option<BlobInteriorPadding>,
+    compression: Option<BlobSectionCompression>,
+) -> Result<()> {
+    if header == HEADER_V3 && modules.iter().any(|m| m.as_ref().content_sha256.is_some()) {
+        return Err(anyhow!("content digests require format version 4"));
+    }
+
     let mut blob_sections = BTreeMap::new();
 
     let mut blob_section_count = 0;
@@ -902,102 +1195,48 @@ pub fn write_packed_resources_v3<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
                     resource_field: field,
                     raw_payload_length: 0,
                     interior_padding,
+                    compression: None,
+                    uncompressed_payload_length: None,
                 })
                 .raw_payload_length += l;
         }
     };
 
-    let add_interior_padding = |dest: &mut W| -> Result<()> {
-        if interior_padding == Some(BlobInteriorPadding::Null) {
-            dest.write_all(b"\0")?;
-        }
-
-        Ok(())
-    };
-
     for module in modules {
         let module = module.as_ref();
         module_index_length += module.index_v1_length();
 
-        process_field(&mut blob_sections, module, ResourceField::ModuleName);
-        process_field(&mut blob_sections, module, ResourceField::InMemorySource);
-        process_field(&mut blob_sections, module, ResourceField::InMemoryBytecode);
-        process_field(
-            &mut blob_sections,
-            module,
-            ResourceField::InMemoryBytecodeOpt1,
-        );
-        process_field(
-            &mut blob_sections,
-            module,
-            ResourceField::InMemoryBytecodeOpt2,
-        );
-        process_field(
-            &mut blob_sections,
-            module,
-            ResourceField::InMemoryExtensionModuleSharedLibrary,
-        );
-        process_field(
-            &mut blob_sections,
-            module,
-            ResourceField::InMemoryResourcesData,
-        );
-        process_field(
-            &mut blob_sections,
-            module,
-            ResourceField::InMemoryDistributionResource,
-        );
-        process_field(
-            &mut blob_sections,
-            module,
-            ResourceField::InMemorySharedLibrary,
-        );
-        process_field(
-            &mut blob_sections,
-            module,
-            ResourceField::SharedLibraryDependencyNames,
-        );
-        process_field(
-            &mut blob_sections,
-            module,
-            ResourceField::RelativeFilesystemModuleSource,
-        );
-        process_field(
-            &mut blob_sections,
-            module,
-            ResourceField::RelativeFilesystemModuleBytecode,
-        );
-        process_field(
-            &mut blob_sections,
-            module,
-            ResourceField::RelativeFilesystemModuleBytecodeOpt1,
-        );
-        process_field(
-            &mut blob_sections,
-            module,
-            ResourceField::RelativeFilesystemModuleBytecodeOpt2,
-        );
-        process_field(
-            &mut blob_sections,
-            module,
-            ResourceField::RelativeFilesystemExtensionModuleSharedLibrary,
-        );
-        process_field(
-            &mut blob_sections,
-            module,
-            ResourceField::RelativeFilesystemPackageResources,
-        );
-        process_field(
-            &mut blob_sections,
-            module,
-            ResourceField::RelativeFilesystemDistributionResource,
-        );
-        process_field(&mut blob_sections, module, ResourceField::FileDataEmbedded);
-        process_field(
-            &mut blob_sections,
-            module,
-            ResourceField::FileDataUtf8RelativePath,
-        );
+        for field in BLOB_FIELDS {
+            process_field(&mut blob_sections, module, *field);
+        }
+    }
+
+    // Compressed sections need to be materialized up front so the index
+    // can record their stored lengths.
+    let mut compressed_sections = BTreeMap::new();
+
+    if let Some(compression) = compression {
+        if compression != BlobSectionCompression::None {
+            for section in blob_sections.values_mut() {
+                let mut data = Vec::with_capacity(section.raw_payload_length);
+                write_blob_section_data(
+                    modules,
+                    section.resource_field,
+                    &mut data,
+                    interior_padding,
+                )?;
+
+                let compressed = compress_blob_section(compression, &data)
+                    .with_context(|| format!("compressing {:?}", section.resource_field))?;
+
+                if compressed.len() < data.len() {
+                    section.compression = Some(compression);
+                    section.uncompressed_payload_length = Some(data.len());
+                    section.raw_payload_length = compressed.len();
+                    compressed_sections.insert(section.resource_field, compressed);
+                }
+            }
+        }
     }
 
     for section in blob_sections.values() {
@@ -1005,7 +1244,7 @@ pub fn write_packed_resources_v3<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
         blob_index_length += section.index_v1_length();
     }
 
-    dest.write_all(HEADER_V3)?;
+    dest.write_all(header)?;
 
     dest.write_u8(blob_section_count)?;
     dest.write_u32::<LittleEndian>(blob_index_length as u32)?;
@@ -1025,155 +1264,11 @@ pub fn write_packed_resources_v3<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
     dest.write_u8(ResourceField::EndOfIndex.into())?;
 
     // Write blob data, one field at a time.
-    for module in modules {
-        dest.write_all(module.as_ref().name.as_bytes())?;
-        add_interior_padding(dest)?;
-    }
-
-    for module in modules {
-        if let Some(data) = &module.as_ref().in_memory_source {
-            dest.write_all(data)?;
-            add_interior_padding(dest)?;
-        }
-    }
-
-    for module in modules {
-        if let Some(data) = &module.as_ref().in_memory_bytecode {
-            dest.write_all(data)?;
-            add_interior_padding(dest)?;
-        }
-    }
-
-    for module in modules {
-        if let Some(data) = &module.as_ref().in_memory_bytecode_opt1 {
-            dest.write_all(data)?;
-            add_interior_padding(dest)?;
-        }
-    }
-
-    for module in modules {
-        if let Some(data) = &module.as_ref().in_memory_bytecode_opt2 {
-            dest.write_all(data)?;
-            add_interior_padding(dest)?;
-        }
-    }
-
-    for module in modules {
-        if let Some(data) = &module.as_ref().in_memory_extension_module_shared_library {
-            dest.write_all(data)?;
-            add_interior_padding(dest)?;
-        }
-    }
-
-    for module in modules {
-        if let Some(resources) = &module.as_ref().in_memory_package_resources {
-            for (key, value) in resources.iter() {
-                dest.write_all(key.as_bytes())?;
-                add_interior_padding(dest)?;
-                dest.write_all(value)?;
-                add_interior_padding(dest)?;
-            }
-        }
-    }
-
-    for module in modules {
-        if let Some(resources) = &module.as_ref().in_memory_distribution_resources {
-            for (key, value) in resources {
-                dest.write_all(key.as_bytes())?;
-                add_interior_padding(dest)?;
-                dest.write_all(value)?;
-                add_interior_padding(dest)?;
-            }
-        }
-    }
-
-    for module in modules {
-        if let Some(data) = &module.as_ref().in_memory_shared_library {
+    for field in blob_sections.keys() {
+        if let Some(data) = compressed_sections.get(field) {
             dest.write_all(data)?;
-            add_interior_padding(dest)?;
-        }
-    }
-
-    for module in modules {
-        if let Some(names) = &module.as_ref().shared_library_dependency_names {
-            for name in names {
-                dest.write_all(name.as_bytes())?;
-                add_interior_padding(dest)?;
-            }
-        }
-    }
-
-    for module in modules {
-        if let Some(path) = &module.as_ref().relative_path_module_source {
-            dest.write_all(&path_to_bytes(path))?;
-            add_interior_padding(dest)?;
-        }
-    }
-
-    for module in modules {
-        if let Some(path) = &module.as_ref().relative_path_module_bytecode {
-            dest.write_all(&path_to_bytes(path))?;
-            add_interior_padding(dest)?;
-        }
-    }
-
-    for module in modules {
-        if let Some(path) = &module.as_ref().relative_path_module_bytecode_opt1 {
-            dest.write_all(&path_to_bytes(path))?;
-            add_interior_padding(dest)?;
-        }
-    }
-
-    for module in modules {
-        if let Some(path) = &module.as_ref().relative_path_module_bytecode_opt2 {
-            dest.write_all(&path_to_bytes(path))?;
-            add_interior_padding(dest)?;
-        }
-    }
-
-    for module in modules {
-        if let Some(path) = &module
-            .as_ref()
-            .relative_path_extension_module_shared_library
-        {
-            dest.write_all(&path_to_bytes(path))?;
-            add_interior_padding(dest)?;
-        }
-    }
-
-    for module in modules {
-        if let Some(resources) = &module.as_ref().relative_path_package_resources {
-            for (key, path) in resources.iter() {
-                dest.write_all(key.as_bytes())?;
-                add_interior_padding(dest)?;
-                dest.write_all(&path_to_bytes(path))?;
-                add_interior_padding(dest)?;
-            }
-        }
-    }
-
-    for module in modules {
-        if let Some(resources) = &module.as_ref().relative_path_distribution_resources {
-            for (key, path) in resources {
-                dest.write_all(key.as_bytes())?;
-                add_interior_padding(dest)?;
-                dest.write_all(&path_to_bytes(path))?;
-                add_interior_padding(dest)?;
-            }
-        }
-    }
-
-    for module in modules {
-        if let Some(data) = &module.as_ref().file_data_embedded {
-            dest.write_all(data)?;
-            add_interior_padding(dest)?;
-        }
-    }
-
-    for module in modules {
-        if let Some(path) = &module.as_ref().file_data_utf8_relative_path {
-            dest.write_all(path.as_bytes())?;
-            add_interior_padding(dest)?;
+        } else {
+            write_blob_section_data(modules, *field, dest, interior_padding)?;
         }
     }
 
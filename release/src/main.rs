@@ -43,6 +43,7 @@ static RELEASE_ORDER: Lazy<Vec<&'static str>> = Lazy::new(|| {
         "tugger-licensing",
         "tugger-licensing-net",
         "tugger-rpm",
+        "tugger-appimage",
         "tugger-snapcraft",
         "tugger-apple",
         "tugger-apple-codesign",
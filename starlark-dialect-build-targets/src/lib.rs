@@ -24,7 +24,8 @@ use {
     },
     std::{
         borrow::Cow,
-        collections::{BTreeMap, HashMap},
+        cell::RefCell,
+        collections::{BTreeMap, BTreeSet, HashMap},
         os::raw::c_ulong,
         path::{Path, PathBuf},
     },
@@ -141,6 +142,12 @@ pub struct EnvironmentContext {
     ///
     /// This will change the default target to resolve.
     pub build_script_mode: bool,
+
+    /// Filesystem paths read when evaluating the environment.
+    ///
+    /// Uses interior mutability so paths can be recorded while other
+    /// references to the context are held.
+    input_paths: RefCell<BTreeSet<PathBuf>>,
 }
 
 impl EnvironmentContext {
@@ -158,6 +165,7 @@ impl EnvironmentContext {
             resolve_targets: None,
             default_build_script_target: None,
             build_script_mode: false,
+            input_paths: RefCell::new(BTreeSet::new()),
         }
     }
 
@@ -284,6 +292,21 @@ impl EnvironmentContext {
         }
     }
 
+    /// Record a filesystem path that was read as part of evaluation.
+    ///
+    /// Recorded paths describe the inputs of the evaluated configuration
+    /// and can be used to detect when it needs to be evaluated again.
+    pub fn record_input_path(&self, path: impl AsRef<Path>) {
+        self.input_paths
+            .borrow_mut()
+            .insert(path.as_ref().to_path_buf());
+    }
+
+    /// Obtain all filesystem paths recorded as inputs.
+    pub fn input_paths(&self) -> BTreeSet<PathBuf> {
+        self.input_paths.borrow().clone()
+    }
+
     /// Determine what targets should be resolved.
     ///
     /// This isn't the full list of targets that will be resolved, only the main
@@ -1129,6 +1152,28 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_record_input_path() -> Result<()> {
+        let env = StarlarkEnvironment::new()?;
+
+        let context_value = get_context_value(&env.type_values).unwrap();
+        let context = context_value
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)
+            .unwrap();
+
+        assert!(context.input_paths().is_empty());
+        context.record_input_path("foo.txt");
+        context.record_input_path(PathBuf::from("foo.txt"));
+        context.record_input_path("bar.txt");
+        assert_eq!(
+            context.input_paths().into_iter().collect::<Vec<_>>(),
+            vec![PathBuf::from("bar.txt"), PathBuf::from("foo.txt")]
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_register_target_multiple() -> Result<()> {
         let mut env = StarlarkEnvironment::new()?;
@@ -0,0 +1,379 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::{desktop::DesktopEntry, squashfs::SquashfsBuilder},
+    anyhow::{anyhow, Context, Result},
+    std::{
+        convert::TryFrom,
+        io::Write,
+        path::{Path, PathBuf},
+        time::SystemTime,
+    },
+    tugger_file_manifest::{FileEntry, FileManifest, Reproducibility},
+};
+
+/// File extensions of icons supported by AppImage.
+const ICON_EXTENSIONS: &[&str] = &["png", "svg", "xpm"];
+
+/// Magic bytes identifying a type 2 AppImage runtime, at offset 8 of the ELF header.
+const RUNTIME_MAGIC: &[u8] = b"AI\x02";
+
+/// Quote a string for use within double quotes in a POSIX shell script.
+fn shell_quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        if matches!(c, '\\' | '"' | '$' | '`') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+
+    quoted
+}
+
+/// Build AppImages.
+///
+/// An AppImage consists of an AppImage runtime executable followed by a
+/// squashfs filesystem image holding the application's files (the
+/// *AppDir*). When executed, the runtime mounts the filesystem and runs
+/// the `AppRun` program at its root.
+///
+/// Instances assemble the AppDir from a [FileManifest], generating the
+/// `AppRun` launcher, `.desktop` file, and icon files at its root. The
+/// runtime is not provided by this crate and must be obtained separately,
+/// e.g. from <https://github.com/AppImage/AppImageKit/releases>.
+#[derive(Clone, Debug)]
+pub struct AppImageBuilder {
+    name: String,
+    exec: PathBuf,
+    files: FileManifest,
+    icon: Option<(String, FileEntry)>,
+    desktop_entry: DesktopEntry,
+    reproducibility: Option<Reproducibility>,
+}
+
+impl AppImageBuilder {
+    /// Construct a new instance.
+    ///
+    /// `name` is the name of the application. It is used to name files
+    /// in the AppDir and should be a simple identifier (e.g. `myapp`).
+    ///
+    /// `exec` is the path of the program to run, relative to the root of
+    /// the AppDir. e.g. `usr/bin/myapp`.
+    pub fn new(name: impl ToString, exec: impl AsRef<Path>) -> Self {
+        let name = name.to_string();
+        let exec = exec.as_ref().to_path_buf();
+
+        let exec_name = exec
+            .file_name()
+            .map(|x| x.to_string_lossy().to_string())
+            .unwrap_or_else(|| name.clone());
+
+        Self {
+            desktop_entry: DesktopEntry::new(&name, exec_name, &name),
+            name,
+            exec,
+            files: FileManifest::new_with_links(),
+            icon: None,
+            reproducibility: None,
+        }
+    }
+
+    /// The name of the application.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The filename of the `.AppImage` produced by this builder.
+    pub fn appimage_file_name(&self) -> String {
+        format!("{}.AppImage", self.name)
+    }
+
+    /// Add files to the AppDir.
+    ///
+    /// Paths in the manifest are relative to the root of the AppDir.
+    pub fn add_file_manifest(mut self, manifest: &FileManifest) -> Result<Self> {
+        self.files.add_manifest(manifest)?;
+
+        Ok(self)
+    }
+
+    /// Set the application icon.
+    ///
+    /// `filename` is used to derive the icon format. Its extension must be
+    /// `png`, `svg`, or `xpm`.
+    pub fn set_icon(mut self, filename: impl AsRef<Path>, entry: FileEntry) -> Result<Self> {
+        let filename = filename.as_ref();
+
+        let extension = filename
+            .extension()
+            .and_then(|x| x.to_str())
+            .map(|x| x.to_lowercase())
+            .filter(|x| ICON_EXTENSIONS.contains(&x.as_str()))
+            .ok_or_else(|| {
+                anyhow!(
+                    "icon {} must have one of the following extensions: {}",
+                    filename.display(),
+                    ICON_EXTENSIONS.join(", ")
+                )
+            })?;
+
+        self.icon = Some((extension, entry));
+
+        Ok(self)
+    }
+
+    /// Obtain the desktop entry describing the application.
+    pub fn desktop_entry(&self) -> &DesktopEntry {
+        &self.desktop_entry
+    }
+
+    /// Set the desktop entry describing the application.
+    ///
+    /// The `Icon` field should be the application name, as that is the name
+    /// the icon is installed under.
+    pub fn set_desktop_entry(mut self, entry: DesktopEntry) -> Self {
+        self.desktop_entry = entry;
+        self
+    }
+
+    /// Normalize metadata of files in the AppImage so output is reproducible.
+    pub fn set_reproducibility(mut self, reproducibility: Reproducibility) -> Self {
+        self.reproducibility = Some(reproducibility);
+        self
+    }
+
+    /// Generate the content of the `AppRun` launcher.
+    fn app_run(&self) -> Result<String> {
+        let exec = self
+            .exec
+            .to_str()
+            .ok_or_else(|| anyhow!("exec path is not valid UTF-8"))?;
+
+        Ok(format!(
+            "#!/bin/sh\n\
+            HERE=\"$(dirname \"$(readlink -f \"$0\")\")\"\n\
+            exec \"$HERE/{}\" \"$@\"\n",
+            shell_quote(exec)
+        ))
+    }
+
+    /// Resolve the files constituting the AppDir.
+    ///
+    /// An `AppRun` in the added files takes precedence over the generated one.
+    pub fn app_dir_manifest(&self) -> Result<FileManifest> {
+        let mut manifest = FileManifest::new_with_links();
+        manifest.add_manifest(&self.files)?;
+
+        if !manifest.has_path(&self.exec) {
+            return Err(anyhow!(
+                "exec path {} is not in the file manifest",
+                self.exec.display()
+            ));
+        }
+
+        if !manifest.has_path("AppRun") {
+            manifest.add_file_entry(
+                "AppRun",
+                FileEntry::new_from_data(self.app_run()?.into_bytes(), true),
+            )?;
+        }
+
+        manifest.add_file_entry(
+            format!("{}.desktop", self.name),
+            FileEntry::new_from_data(self.desktop_entry.to_desktop_file().into_bytes(), false),
+        )?;
+
+        let (extension, icon) = self
+            .icon
+            .as_ref()
+            .ok_or_else(|| anyhow!("an icon is required to build an AppImage"))?;
+
+        let icon_filename = format!("{}.{}", self.name, extension);
+        manifest.add_file_entry(&icon_filename, icon.clone())?;
+        manifest.add_symlink(".DirIcon", &icon_filename)?;
+
+        Ok(manifest)
+    }
+
+    /// Produce the squashfs image holding the AppDir.
+    pub fn squashfs_image(&self) -> Result<Vec<u8>> {
+        let mtime = if let Some(reproducibility) = &self.reproducibility {
+            reproducibility.mtime()
+        } else {
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .context("resolving current time")?
+                .as_secs()
+        };
+
+        let mut builder = SquashfsBuilder::default();
+        builder.set_mtime(u32::try_from(mtime).context("modified time out of range")?);
+        builder.add_file_manifest(&self.app_dir_manifest()?)?;
+
+        builder.to_vec()
+    }
+
+    /// Write the AppImage to a writer.
+    ///
+    /// `runtime` is the content of the AppImage runtime executable.
+    pub fn write(&self, runtime: &[u8], writer: &mut impl Write) -> Result<()> {
+        if runtime.get(0..4) != Some(b"\x7fELF") || runtime.get(8..11) != Some(RUNTIME_MAGIC) {
+            return Err(anyhow!("runtime is not an AppImage type 2 runtime"));
+        }
+
+        let image = self.squashfs_image()?;
+
+        writer.write_all(runtime)?;
+        writer.write_all(&image)?;
+
+        Ok(())
+    }
+
+    /// Write the AppImage to a path, using a runtime stored at a path.
+    ///
+    /// The written file is marked as executable.
+    pub fn write_to_path(
+        &self,
+        runtime_path: impl AsRef<Path>,
+        dest_path: impl AsRef<Path>,
+    ) -> Result<()> {
+        let runtime_path = runtime_path.as_ref();
+        let dest_path = dest_path.as_ref();
+
+        let runtime = std::fs::read(runtime_path)
+            .with_context(|| format!("reading runtime {}", runtime_path.display()))?;
+
+        if let Some(parent) = dest_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut fh = std::fs::File::create(dest_path)?;
+        self.write(&runtime, &mut fh)
+            .with_context(|| format!("writing {}", dest_path.display()))?;
+        tugger_file_manifest::set_executable(&mut fh)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fake_runtime() -> Vec<u8> {
+        let mut runtime = b"\x7fELF\x02\x01\x01\x00AI\x02".to_vec();
+        runtime.resize(64, 0);
+        runtime
+    }
+
+    fn builder() -> Result<AppImageBuilder> {
+        let mut manifest = FileManifest::default();
+        manifest.add_file_entry(
+            "usr/bin/myapp",
+            FileEntry::new_from_data(b"#!/bin/sh\necho hello\n".to_vec(), true),
+        )?;
+
+        AppImageBuilder::new("myapp", "usr/bin/myapp")
+            .add_file_manifest(&manifest)?
+            .set_icon(
+                "icon.svg",
+                FileEntry::new_from_data(b"<svg/>".to_vec(), false),
+            )
+    }
+
+    #[test]
+    fn app_dir() -> Result<()> {
+        let manifest = builder()?.app_dir_manifest()?;
+
+        assert_eq!(
+            manifest
+                .iter_entries()
+                .map(|(path, _)| path.display().to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                ".DirIcon",
+                "AppRun",
+                "myapp.desktop",
+                "myapp.svg",
+                "usr/bin/myapp"
+            ]
+        );
+
+        let app_run = manifest.get("AppRun").unwrap();
+        assert!(app_run.is_executable());
+        assert_eq!(
+            String::from_utf8(app_run.resolve_content()?)?,
+            "#!/bin/sh\nHERE=\"$(dirname \"$(readlink -f \"$0\")\")\"\nexec \"$HERE/usr/bin/myapp\" \"$@\"\n"
+        );
+
+        let desktop = manifest.get("myapp.desktop").unwrap().resolve_content()?;
+        assert!(String::from_utf8(desktop)?.contains("\nExec=myapp\nIcon=myapp\n"));
+
+        assert_eq!(
+            manifest.get(".DirIcon").unwrap().link_target(),
+            Some(Path::new("myapp.svg"))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn validation() -> Result<()> {
+        assert!(AppImageBuilder::new("myapp", "usr/bin/myapp")
+            .set_icon("icon.bmp", FileEntry::new_from_data(vec![], false))
+            .is_err());
+
+        // No icon.
+        let manifest = builder()?.app_dir_manifest()?;
+        assert!(AppImageBuilder::new("myapp", "usr/bin/myapp")
+            .add_file_manifest(&manifest)?
+            .app_dir_manifest()
+            .is_err());
+
+        // Exec not in manifest.
+        assert!(AppImageBuilder::new("myapp", "usr/bin/other")
+            .add_file_manifest(&manifest)?
+            .set_icon("icon.png", FileEntry::new_from_data(vec![], false))?
+            .app_dir_manifest()
+            .is_err());
+
+        // Runtime isn't an AppImage runtime.
+        let mut data = vec![];
+        assert!(builder()?.write(b"\x7fELF", &mut data).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn write() -> Result<()> {
+        let builder = builder()?.set_reproducibility(Reproducibility::new(1_600_000_000));
+        let runtime = fake_runtime();
+
+        let mut data = vec![];
+        builder.write(&runtime, &mut data)?;
+
+        assert_eq!(&data[0..runtime.len()], runtime.as_slice());
+        // squashfs magic follows the runtime.
+        assert_eq!(&data[runtime.len()..runtime.len() + 4], b"hsqs");
+
+        let mut again = vec![];
+        builder.write(&runtime, &mut again)?;
+        assert_eq!(data, again);
+
+        let temp_dir = tempfile::Builder::new()
+            .prefix("tugger-appimage-test")
+            .tempdir()?;
+        let runtime_path = temp_dir.path().join("runtime");
+        std::fs::write(&runtime_path, &runtime)?;
+
+        let dest_path = temp_dir.path().join(builder.appimage_file_name());
+        builder.write_to_path(&runtime_path, &dest_path)?;
+        assert_eq!(std::fs::read(&dest_path)?, data);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Desktop entry files.
+
+See <https://specifications.freedesktop.org/desktop-entry-spec/latest/>.
+*/
+
+/// Escape a value per the desktop entry specification.
+fn escape_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Represents an application in a `.desktop` file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DesktopEntry {
+    /// Name of the application.
+    pub name: String,
+    /// Program to execute.
+    pub exec: String,
+    /// Name of the icon, without file extension.
+    pub icon: String,
+    /// Tooltip describing the application.
+    pub comment: Option<String>,
+    /// Categories the application should be shown in.
+    ///
+    /// If empty, `Utility` is used.
+    pub categories: Vec<String>,
+    /// Whether the program runs in a terminal window.
+    pub terminal: bool,
+}
+
+impl DesktopEntry {
+    /// Construct a new instance with required fields.
+    pub fn new(name: impl ToString, exec: impl ToString, icon: impl ToString) -> Self {
+        Self {
+            name: name.to_string(),
+            exec: exec.to_string(),
+            icon: icon.to_string(),
+            comment: None,
+            categories: vec![],
+            terminal: false,
+        }
+    }
+
+    /// Serialize to the content of a `.desktop` file.
+    pub fn to_desktop_file(&self) -> String {
+        let mut lines = vec![
+            "[Desktop Entry]".to_string(),
+            "Type=Application".to_string(),
+            format!("Name={}", escape_value(&self.name)),
+            format!("Exec={}", escape_value(&self.exec)),
+            format!("Icon={}", escape_value(&self.icon)),
+        ];
+
+        if let Some(comment) = &self.comment {
+            lines.push(format!("Comment={}", escape_value(comment)));
+        }
+
+        let categories = if self.categories.is_empty() {
+            vec!["Utility".to_string()]
+        } else {
+            self.categories.clone()
+        };
+        lines.push(format!(
+            "Categories={};",
+            categories
+                .iter()
+                .map(|c| escape_value(c))
+                .collect::<Vec<_>>()
+                .join(";")
+        ));
+
+        lines.push(format!("Terminal={}", self.terminal));
+        lines.push("".to_string());
+
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn desktop_file() {
+        let mut entry = DesktopEntry::new("My App", "myapp", "myapp");
+        assert_eq!(
+            entry.to_desktop_file(),
+            "[Desktop Entry]\nType=Application\nName=My App\nExec=myapp\nIcon=myapp\n\
+            Categories=Utility;\nTerminal=false\n"
+        );
+
+        entry.comment = Some("Does\nthings".to_string());
+        entry.categories = vec!["Development".to_string(), "Utility".to_string()];
+        entry.terminal = true;
+        assert_eq!(
+            entry.to_desktop_file(),
+            "[Desktop Entry]\nType=Application\nName=My App\nExec=myapp\nIcon=myapp\n\
+            Comment=Does\\nthings\nCategories=Development;Utility;\nTerminal=true\n"
+        );
+    }
+}
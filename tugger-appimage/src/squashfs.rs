@@ -0,0 +1,775 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Writing of squashfs filesystem images.
+
+This module implements a minimal writer of version 4.0 squashfs images,
+as consumed by the Linux kernel and by `squashfuse` (which AppImage
+runtimes use to mount their payload).
+
+Data and metadata are compressed with zlib (the `gzip` squashfs
+compressor). Fragments, extended attributes, and the NFS export table
+are not written. All entries are owned by `root`.
+*/
+
+use {
+    anyhow::{anyhow, Context, Result},
+    flate2::{write::ZlibEncoder, Compression},
+    std::{collections::BTreeMap, convert::TryFrom, io::Write, path::Path},
+    tugger_file_manifest::FileManifest,
+};
+
+const MAGIC: u32 = 0x7371_7368;
+
+/// Size of data blocks.
+const BLOCK_SIZE: u32 = 131_072;
+const BLOCK_LOG: u16 = 17;
+
+/// Maximum size of uncompressed metadata blocks.
+const METADATA_BLOCK_SIZE: usize = 8192;
+
+const COMPRESSION_GZIP: u16 = 1;
+
+const FLAG_NO_FRAGMENTS: u16 = 0x0010;
+const FLAG_NO_XATTRS: u16 = 0x0200;
+
+/// Flag set on data block sizes when the block is stored uncompressed.
+const DATA_BLOCK_UNCOMPRESSED: u32 = 1 << 24;
+/// Flag set on metadata block headers when the block is stored uncompressed.
+const METADATA_BLOCK_UNCOMPRESSED: u16 = 0x8000;
+
+const INVALID_FRAGMENT: u32 = 0xffff_ffff;
+const INVALID_TABLE: u64 = 0xffff_ffff_ffff_ffff;
+
+const INODE_DIRECTORY: u16 = 1;
+const INODE_FILE: u16 = 2;
+const INODE_SYMLINK: u16 = 3;
+
+/// Maximum number of entries following a directory header.
+const DIRECTORY_HEADER_MAX_ENTRIES: usize = 256;
+
+/// Images are padded to a multiple of this size.
+const PADDING_SIZE: usize = 4096;
+
+/// Compress data using zlib, returning `None` if compression doesn't save space.
+fn compress(data: &[u8]) -> Result<Option<Vec<u8>>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(data)?;
+    let compressed = encoder.finish()?;
+
+    Ok(if compressed.len() < data.len() {
+        Some(compressed)
+    } else {
+        None
+    })
+}
+
+/// Writes a stream of metadata as a series of metadata blocks.
+#[derive(Default)]
+struct MetadataWriter {
+    /// Serialized metadata blocks.
+    blocks: Vec<u8>,
+    /// Uncompressed metadata not yet written to a block.
+    pending: Vec<u8>,
+}
+
+impl MetadataWriter {
+    /// Obtain the offset of the current block and the offset within it.
+    fn position(&self) -> (u32, u16) {
+        (self.blocks.len() as u32, self.pending.len() as u16)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.pending.extend_from_slice(data);
+
+        while self.pending.len() >= METADATA_BLOCK_SIZE {
+            let remaining = self.pending.split_off(METADATA_BLOCK_SIZE);
+            let block = std::mem::replace(&mut self.pending, remaining);
+            self.write_block(&block)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_block(&mut self, data: &[u8]) -> Result<()> {
+        if let Some(compressed) = compress(data)? {
+            self.blocks
+                .extend_from_slice(&(compressed.len() as u16).to_le_bytes());
+            self.blocks.extend_from_slice(&compressed);
+        } else {
+            self.blocks.extend_from_slice(
+                &(data.len() as u16 | METADATA_BLOCK_UNCOMPRESSED).to_le_bytes(),
+            );
+            self.blocks.extend_from_slice(data);
+        }
+
+        Ok(())
+    }
+
+    /// Flush pending data and return serialized metadata blocks.
+    fn finish(mut self) -> Result<Vec<u8>> {
+        if !self.pending.is_empty() {
+            let block = std::mem::take(&mut self.pending);
+            self.write_block(&block)?;
+        }
+
+        Ok(self.blocks)
+    }
+}
+
+enum EntryKind {
+    Directory { children: BTreeMap<String, usize> },
+    File { data: Vec<u8>, executable: bool },
+    Symlink { target: String },
+}
+
+impl EntryKind {
+    fn inode_type(&self) -> u16 {
+        match self {
+            Self::Directory { .. } => INODE_DIRECTORY,
+            Self::File { .. } => INODE_FILE,
+            Self::Symlink { .. } => INODE_SYMLINK,
+        }
+    }
+
+    fn permissions(&self) -> u16 {
+        match self {
+            Self::Directory { .. } => 0o755,
+            Self::File { executable, .. } => {
+                if *executable {
+                    0o755
+                } else {
+                    0o644
+                }
+            }
+            Self::Symlink { .. } => 0o777,
+        }
+    }
+}
+
+/// An entry in the filesystem.
+///
+/// The inode number of an entry is its index in the entry list plus 1.
+struct Entry {
+    kind: EntryKind,
+    parent: usize,
+}
+
+/// Where an entry's inode was written.
+#[derive(Clone, Copy)]
+struct InodeLocation {
+    block: u32,
+    offset: u16,
+}
+
+/// Builds squashfs filesystem images.
+///
+/// Files are added from [FileManifest] instances. Links in manifests
+/// are stored as symbolic links. Directories are created implicitly.
+pub struct SquashfsBuilder {
+    manifest: FileManifest,
+    mtime: u32,
+}
+
+impl Default for SquashfsBuilder {
+    fn default() -> Self {
+        Self {
+            manifest: FileManifest::new_with_links(),
+            mtime: 0,
+        }
+    }
+}
+
+impl SquashfsBuilder {
+    /// Set the modified time recorded on all entries, in seconds since UNIX epoch.
+    pub fn set_mtime(&mut self, mtime: u32) {
+        self.mtime = mtime;
+    }
+
+    /// Add files in a [FileManifest] to the image.
+    pub fn add_file_manifest(&mut self, manifest: &FileManifest) -> Result<()> {
+        self.manifest.add_manifest(manifest)?;
+
+        Ok(())
+    }
+
+    /// Resolve entries in the filesystem.
+    ///
+    /// Entries are ordered such that children precede their parent directory
+    /// and the root directory is last.
+    fn resolve_entries(&self) -> Result<Vec<Entry>> {
+        let mut entries = vec![];
+
+        // Build an intermediate tree of path components to entries.
+        #[derive(Default)]
+        struct Node {
+            children: BTreeMap<String, Node>,
+            leaf: Option<EntryKind>,
+        }
+
+        let mut root = Node::default();
+
+        for (path, entry) in self.manifest.iter_entries() {
+            let components = path
+                .iter()
+                .map(|c| {
+                    c.to_str()
+                        .map(|s| s.to_string())
+                        .ok_or_else(|| anyhow!("path is not valid UTF-8: {}", path.display()))
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            let mut node = &mut root;
+            for component in &components {
+                if node.leaf.is_some() {
+                    return Err(anyhow!(
+                        "{} is in a directory that conflicts with a file",
+                        path.display()
+                    ));
+                }
+                node = node.children.entry(component.clone()).or_default();
+            }
+
+            if !node.children.is_empty() {
+                return Err(anyhow!("{} conflicts with a directory", path.display()));
+            }
+
+            node.leaf = Some(if let Some(target) = entry.link_target() {
+                EntryKind::Symlink {
+                    target: path_to_string(target)?,
+                }
+            } else {
+                EntryKind::File {
+                    data: entry
+                        .resolve_content()
+                        .with_context(|| format!("resolving content of {}", path.display()))?,
+                    executable: entry.is_executable(),
+                }
+            });
+        }
+
+        fn flatten(node: Node, entries: &mut Vec<Entry>) -> usize {
+            let kind = if let Some(kind) = node.leaf {
+                kind
+            } else {
+                let children = node
+                    .children
+                    .into_iter()
+                    .map(|(name, child)| (name, flatten(child, entries)))
+                    .collect::<BTreeMap<_, _>>();
+
+                EntryKind::Directory { children }
+            };
+
+            let index = entries.len();
+
+            if let EntryKind::Directory { children } = &kind {
+                for child in children.values() {
+                    entries[*child].parent = index;
+                }
+            }
+
+            entries.push(Entry { kind, parent: 0 });
+
+            index
+        }
+
+        let root_index = flatten(root, &mut entries);
+        // The parent of the root directory is conventionally one past the last inode.
+        entries[root_index].parent = root_index + 1;
+
+        Ok(entries)
+    }
+
+    /// Write the squashfs image to a writer.
+    pub fn write(&self, writer: &mut impl Write) -> Result<u64> {
+        let image = self.to_vec()?;
+        writer.write_all(&image)?;
+
+        Ok(image.len() as u64)
+    }
+
+    /// Produce the squashfs image as a byte vector.
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        let entries = self.resolve_entries()?;
+        let inode_count = u32::try_from(entries.len()).context("too many files")?;
+
+        // The superblock is filled in last.
+        let mut image = vec![0u8; 96];
+
+        // Data blocks come first.
+        let mut file_blocks = BTreeMap::new();
+        for (index, entry) in entries.iter().enumerate() {
+            if let EntryKind::File { data, .. } = &entry.kind {
+                let start = u32::try_from(image.len()).context("image too large")?;
+                let mut sizes = vec![];
+
+                for chunk in data.chunks(BLOCK_SIZE as usize) {
+                    if let Some(compressed) = compress(chunk)? {
+                        sizes.push(compressed.len() as u32);
+                        image.extend_from_slice(&compressed);
+                    } else {
+                        sizes.push(chunk.len() as u32 | DATA_BLOCK_UNCOMPRESSED);
+                        image.extend_from_slice(chunk);
+                    }
+                }
+
+                file_blocks.insert(index, (start, sizes));
+            }
+        }
+
+        let mut inodes = MetadataWriter::default();
+        let mut directories = MetadataWriter::default();
+        let mut locations: Vec<InodeLocation> = Vec::with_capacity(entries.len());
+
+        for (index, entry) in entries.iter().enumerate() {
+            let mut inode = vec![];
+            inode.extend_from_slice(&entry.kind.inode_type().to_le_bytes());
+            inode.extend_from_slice(&entry.kind.permissions().to_le_bytes());
+            // uid and gid are indices into the id table.
+            inode.extend_from_slice(&0u16.to_le_bytes());
+            inode.extend_from_slice(&0u16.to_le_bytes());
+            inode.extend_from_slice(&self.mtime.to_le_bytes());
+            inode.extend_from_slice(&(index as u32 + 1).to_le_bytes());
+
+            match &entry.kind {
+                EntryKind::Directory { children } => {
+                    let (block, offset) = directories.position();
+                    let listing = directory_listing(children, &entries, &locations)?;
+                    directories.write(&listing)?;
+
+                    let subdirectories = children
+                        .values()
+                        .filter(|child| {
+                            matches!(entries[**child].kind, EntryKind::Directory { .. })
+                        })
+                        .count();
+
+                    // The size includes the implicit `.` and `..` entries.
+                    let size = u16::try_from(listing.len() + 3)
+                        .map_err(|_| anyhow!("too many entries in directory"))?;
+
+                    inode.extend_from_slice(&block.to_le_bytes());
+                    inode.extend_from_slice(&(2 + subdirectories as u32).to_le_bytes());
+                    inode.extend_from_slice(&size.to_le_bytes());
+                    inode.extend_from_slice(&offset.to_le_bytes());
+                    inode.extend_from_slice(&(entry.parent as u32 + 1).to_le_bytes());
+                }
+                EntryKind::File { data, .. } => {
+                    let (start, sizes) = &file_blocks[&index];
+                    let size = u32::try_from(data.len()).context("file too large")?;
+
+                    inode.extend_from_slice(&start.to_le_bytes());
+                    inode.extend_from_slice(&INVALID_FRAGMENT.to_le_bytes());
+                    inode.extend_from_slice(&0u32.to_le_bytes());
+                    inode.extend_from_slice(&size.to_le_bytes());
+                    for size in sizes {
+                        inode.extend_from_slice(&size.to_le_bytes());
+                    }
+                }
+                EntryKind::Symlink { target } => {
+                    inode.extend_from_slice(&1u32.to_le_bytes());
+                    inode.extend_from_slice(&(target.len() as u32).to_le_bytes());
+                    inode.extend_from_slice(target.as_bytes());
+                }
+            }
+
+            let (block, offset) = inodes.position();
+            locations.push(InodeLocation { block, offset });
+            inodes.write(&inode)?;
+        }
+
+        let root_inode = {
+            let root = locations.last().expect("root directory always present");
+            ((root.block as u64) << 16) | root.offset as u64
+        };
+
+        let inode_table_start = image.len() as u64;
+        image.extend_from_slice(&inodes.finish()?);
+
+        let directory_table_start = image.len() as u64;
+        image.extend_from_slice(&directories.finish()?);
+
+        // There are no fragments. So the fragment table is empty.
+        let fragment_table_start = image.len() as u64;
+
+        // The id table consists of metadata blocks holding ids followed by
+        // an index of the locations of these blocks.
+        let id_block_start = image.len() as u64;
+        let mut ids = MetadataWriter::default();
+        ids.write(&0u32.to_le_bytes())?;
+        image.extend_from_slice(&ids.finish()?);
+
+        let id_table_start = image.len() as u64;
+        image.extend_from_slice(&id_block_start.to_le_bytes());
+
+        let bytes_used = image.len() as u64;
+
+        let mut superblock = Vec::with_capacity(96);
+        superblock.extend_from_slice(&MAGIC.to_le_bytes());
+        superblock.extend_from_slice(&inode_count.to_le_bytes());
+        superblock.extend_from_slice(&self.mtime.to_le_bytes());
+        superblock.extend_from_slice(&BLOCK_SIZE.to_le_bytes());
+        superblock.extend_from_slice(&0u32.to_le_bytes());
+        superblock.extend_from_slice(&COMPRESSION_GZIP.to_le_bytes());
+        superblock.extend_from_slice(&BLOCK_LOG.to_le_bytes());
+        superblock.extend_from_slice(&(FLAG_NO_FRAGMENTS | FLAG_NO_XATTRS).to_le_bytes());
+        // Number of ids in the id table.
+        superblock.extend_from_slice(&1u16.to_le_bytes());
+        superblock.extend_from_slice(&4u16.to_le_bytes());
+        superblock.extend_from_slice(&0u16.to_le_bytes());
+        superblock.extend_from_slice(&root_inode.to_le_bytes());
+        superblock.extend_from_slice(&bytes_used.to_le_bytes());
+        superblock.extend_from_slice(&id_table_start.to_le_bytes());
+        superblock.extend_from_slice(&INVALID_TABLE.to_le_bytes());
+        superblock.extend_from_slice(&inode_table_start.to_le_bytes());
+        superblock.extend_from_slice(&directory_table_start.to_le_bytes());
+        superblock.extend_from_slice(&fragment_table_start.to_le_bytes());
+        superblock.extend_from_slice(&INVALID_TABLE.to_le_bytes());
+        image[0..96].copy_from_slice(&superblock);
+
+        let padding = (PADDING_SIZE - image.len() % PADDING_SIZE) % PADDING_SIZE;
+        image.resize(image.len() + padding, 0);
+
+        Ok(image)
+    }
+}
+
+fn path_to_string(path: &Path) -> Result<String> {
+    path.to_str()
+        .map(|s| s.to_string())
+        .ok_or_else(|| anyhow!("path is not valid UTF-8: {}", path.display()))
+}
+
+/// Serialize the listing of a directory.
+///
+/// All children must already have their inodes written.
+fn directory_listing(
+    children: &BTreeMap<String, usize>,
+    entries: &[Entry],
+    locations: &[InodeLocation],
+) -> Result<Vec<u8>> {
+    let mut listing = vec![];
+
+    let children = children.iter().collect::<Vec<_>>();
+    let mut remaining = &children[..];
+
+    while !remaining.is_empty() {
+        // Entries following a header must have inodes in the same metadata
+        // block and inode numbers within range of the header's.
+        let first = *remaining[0].1;
+        let block = locations[first].block;
+
+        let run = remaining
+            .iter()
+            .take(DIRECTORY_HEADER_MAX_ENTRIES)
+            .take_while(|(_, index)| {
+                locations[**index].block == block
+                    && i16::try_from(**index as i64 - first as i64).is_ok()
+            })
+            .count();
+
+        listing.extend_from_slice(&(run as u32 - 1).to_le_bytes());
+        listing.extend_from_slice(&block.to_le_bytes());
+        listing.extend_from_slice(&(first as u32 + 1).to_le_bytes());
+
+        for (name, index) in &remaining[0..run] {
+            if name.is_empty() || name.len() > 256 {
+                return Err(anyhow!("invalid file name length: {}", name));
+            }
+
+            listing.extend_from_slice(&locations[**index].offset.to_le_bytes());
+            listing.extend_from_slice(&((**index as i64 - first as i64) as i16).to_le_bytes());
+            listing.extend_from_slice(&entries[**index].kind.inode_type().to_le_bytes());
+            listing.extend_from_slice(&(name.len() as u16 - 1).to_le_bytes());
+            listing.extend_from_slice(name.as_bytes());
+        }
+
+        remaining = &remaining[run..];
+    }
+
+    Ok(listing)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        flate2::read::ZlibDecoder,
+        std::{collections::HashMap, io::Read},
+        tugger_file_manifest::FileEntry,
+    };
+
+    #[derive(Debug, PartialEq)]
+    enum Node {
+        Directory(u16),
+        File(u16, Vec<u8>),
+        Symlink(String),
+    }
+
+    fn u16_at(data: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes([data[offset], data[offset + 1]])
+    }
+
+    fn u32_at(data: &[u8], offset: usize) -> u32 {
+        let mut b = [0u8; 4];
+        b.copy_from_slice(&data[offset..offset + 4]);
+        u32::from_le_bytes(b)
+    }
+
+    fn u64_at(data: &[u8], offset: usize) -> u64 {
+        let mut b = [0u8; 8];
+        b.copy_from_slice(&data[offset..offset + 8]);
+        u64::from_le_bytes(b)
+    }
+
+    fn decompress(data: &[u8]) -> Vec<u8> {
+        let mut res = vec![];
+        ZlibDecoder::new(data).read_to_end(&mut res).unwrap();
+        res
+    }
+
+    /// Decode a metadata table, returning its content and a mapping of block
+    /// offsets to offsets in the content.
+    fn read_metadata(data: &[u8]) -> (Vec<u8>, HashMap<u32, usize>) {
+        let mut content = vec![];
+        let mut blocks = HashMap::new();
+
+        let mut offset = 0;
+        while offset < data.len() {
+            blocks.insert(offset as u32, content.len());
+
+            let header = u16_at(data, offset);
+            let size = (header & !METADATA_BLOCK_UNCOMPRESSED) as usize;
+            let block = &data[offset + 2..offset + 2 + size];
+
+            if header & METADATA_BLOCK_UNCOMPRESSED != 0 {
+                content.extend_from_slice(block);
+            } else {
+                content.extend_from_slice(&decompress(block));
+            }
+
+            offset += 2 + size;
+        }
+
+        (content, blocks)
+    }
+
+    /// A minimal squashfs reader used to validate written images.
+    fn read_image(image: &[u8]) -> BTreeMap<String, Node> {
+        assert_eq!(u32_at(image, 0), MAGIC);
+        let inode_count = u32_at(image, 4);
+        let root_ref = u64_at(image, 32);
+        let bytes_used = u64_at(image, 40) as usize;
+        let id_table = u64_at(image, 48) as usize;
+        let inode_table = u64_at(image, 64) as usize;
+        let directory_table = u64_at(image, 72) as usize;
+        let fragment_table = u64_at(image, 80) as usize;
+
+        assert_eq!(image.len() % PADDING_SIZE, 0);
+        assert_eq!(id_table + 8, bytes_used);
+        let (ids, _) = read_metadata(&image[u64_at(image, id_table) as usize..id_table]);
+        assert_eq!(ids, vec![0, 0, 0, 0]);
+
+        let (inodes, inode_blocks) = read_metadata(&image[inode_table..directory_table]);
+        let (dirs, dir_blocks) = read_metadata(&image[directory_table..fragment_table]);
+
+        let mut res = BTreeMap::new();
+        let mut pending = vec![(
+            "".to_string(),
+            (root_ref >> 16) as u32,
+            root_ref as u16,
+            None,
+        )];
+
+        while let Some((path, block, offset, expected_number)) = pending.pop() {
+            let inode = &inodes[inode_blocks[&block] + offset as usize..];
+            let inode_type = u16_at(inode, 0);
+            let mode = u16_at(inode, 2);
+            let number = u32_at(inode, 12);
+            assert!(number >= 1 && number <= inode_count);
+            if let Some(expected) = expected_number {
+                assert_eq!(number, expected);
+            }
+
+            let node = match inode_type {
+                INODE_DIRECTORY => {
+                    let size = u16_at(inode, 24) as usize;
+                    // Listings of empty directories have no content.
+                    let listing = if size > 3 {
+                        let start = dir_blocks[&u32_at(inode, 16)] + u16_at(inode, 26) as usize;
+                        &dirs[start..start + size - 3]
+                    } else {
+                        &[]
+                    };
+
+                    let mut pos = 0;
+                    while pos < listing.len() {
+                        let count = u32_at(listing, pos) + 1;
+                        let start = u32_at(listing, pos + 4);
+                        let base = u32_at(listing, pos + 8);
+                        pos += 12;
+
+                        for _ in 0..count {
+                            let offset = u16_at(listing, pos);
+                            let delta = u16_at(listing, pos + 2) as i16;
+                            let name_size = u16_at(listing, pos + 6) as usize + 1;
+                            let name =
+                                String::from_utf8(listing[pos + 8..pos + 8 + name_size].to_vec())
+                                    .unwrap();
+                            pos += 8 + name_size;
+
+                            let child_path = if path.is_empty() {
+                                name
+                            } else {
+                                format!("{}/{}", path, name)
+                            };
+                            pending.push((
+                                child_path,
+                                start,
+                                offset,
+                                Some((base as i64 + delta as i64) as u32),
+                            ));
+                        }
+                    }
+
+                    Node::Directory(mode)
+                }
+                INODE_FILE => {
+                    let mut data_offset = u32_at(inode, 16) as usize;
+                    assert_eq!(u32_at(inode, 20), INVALID_FRAGMENT);
+                    let size = u32_at(inode, 28) as usize;
+                    let block_count = (0..size).step_by(BLOCK_SIZE as usize).count();
+
+                    let mut data = vec![];
+                    for i in 0..block_count {
+                        let block_size = u32_at(inode, 32 + i * 4);
+                        let length = (block_size & !DATA_BLOCK_UNCOMPRESSED) as usize;
+                        let block = &image[data_offset..data_offset + length];
+
+                        if block_size & DATA_BLOCK_UNCOMPRESSED != 0 {
+                            data.extend_from_slice(block);
+                        } else {
+                            data.extend_from_slice(&decompress(block));
+                        }
+
+                        data_offset += length;
+                    }
+                    assert_eq!(data.len(), size);
+
+                    Node::File(mode, data)
+                }
+                INODE_SYMLINK => {
+                    let size = u32_at(inode, 20) as usize;
+                    Node::Symlink(String::from_utf8(inode[24..24 + size].to_vec()).unwrap())
+                }
+                _ => panic!("unexpected inode type {}", inode_type),
+            };
+
+            res.insert(path, node);
+        }
+
+        res
+    }
+
+    #[test]
+    fn empty() -> Result<()> {
+        let image = SquashfsBuilder::default().to_vec()?;
+
+        let entries = read_image(&image);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[""], Node::Directory(0o755));
+
+        Ok(())
+    }
+
+    #[test]
+    fn files_and_links() -> Result<()> {
+        // Large enough to span multiple data blocks, with an incompressible tail.
+        let mut large = vec![42u8; BLOCK_SIZE as usize * 2];
+        large.extend((0..1000u32).map(|x| (x.wrapping_mul(2_654_435_761) >> 24) as u8));
+
+        let mut manifest = FileManifest::new_with_links();
+        manifest.add_file_entry(
+            "AppRun",
+            FileEntry::new_from_data(b"#!/bin/sh\n".to_vec(), true),
+        )?;
+        manifest.add_file_entry(
+            "usr/lib/large",
+            FileEntry::new_from_data(large.clone(), false),
+        )?;
+        manifest.add_file_entry("usr/share/empty", FileEntry::new_from_data(vec![], false))?;
+        manifest.add_symlink("link", "usr/lib/large")?;
+
+        let mut builder = SquashfsBuilder::default();
+        builder.set_mtime(1_600_000_000);
+        builder.add_file_manifest(&manifest)?;
+        let image = builder.to_vec()?;
+
+        assert_eq!(u32_at(&image, 8), 1_600_000_000);
+
+        let entries = read_image(&image);
+        assert_eq!(
+            entries.keys().collect::<Vec<_>>(),
+            vec![
+                "",
+                "AppRun",
+                "link",
+                "usr",
+                "usr/lib",
+                "usr/lib/large",
+                "usr/share",
+                "usr/share/empty"
+            ]
+        );
+        assert_eq!(
+            entries["AppRun"],
+            Node::File(0o755, b"#!/bin/sh\n".to_vec())
+        );
+        assert_eq!(entries["link"], Node::Symlink("usr/lib/large".to_string()));
+        assert_eq!(entries["usr"], Node::Directory(0o755));
+        assert_eq!(entries["usr/lib/large"], Node::File(0o644, large));
+        assert_eq!(entries["usr/share/empty"], Node::File(0o644, vec![]));
+
+        // Output is deterministic.
+        assert_eq!(builder.to_vec()?, image);
+
+        Ok(())
+    }
+
+    #[test]
+    fn many_entries() -> Result<()> {
+        // Enough entries to require multiple metadata blocks and directory headers.
+        let mut manifest = FileManifest::default();
+        for i in 0..2000 {
+            manifest.add_file_entry(
+                format!("dir/file{:04}", i),
+                FileEntry::new_from_data(format!("{}", i).into_bytes(), false),
+            )?;
+        }
+
+        let mut builder = SquashfsBuilder::default();
+        builder.add_file_manifest(&manifest)?;
+        let image = builder.to_vec()?;
+
+        let entries = read_image(&image);
+        assert_eq!(entries.len(), 2002);
+        assert_eq!(entries["dir/file1234"], Node::File(0o644, b"1234".to_vec()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn conflicting_paths() -> Result<()> {
+        let mut manifest = FileManifest::default();
+        manifest.add_file_entry("foo", FileEntry::new_from_data(vec![], false))?;
+        manifest.add_file_entry("foo/bar", FileEntry::new_from_data(vec![], false))?;
+
+        let mut builder = SquashfsBuilder::default();
+        builder.add_file_manifest(&manifest)?;
+        assert!(builder.to_vec().is_err());
+
+        Ok(())
+    }
+}
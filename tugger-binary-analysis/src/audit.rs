@@ -6,144 +6,560 @@
 
 use {
     crate::{
-        find_minimum_distro_version, find_undefined_elf_symbols, UndefinedSymbol,
-        GCC_VERSIONS_BY_DISTRO, GLIBC_VERSIONS_BY_DISTRO, LSB_SHARED_LIBRARIES,
+        find_pe_dependencies, find_undefined_elf_symbols, resolve_minimum_distro_versions,
+        MinimumDistroVersion, UndefinedSymbol, GCC_VERSIONS_BY_DISTRO, GLIBC_VERSIONS_BY_DISTRO,
+        LSB_SHARED_LIBRARIES,
     },
-    std::{collections::BTreeMap, fs::File, io::Read, path::PathBuf},
+    anyhow::{anyhow, Context, Result},
+    goblin::mach::{
+        load_command::{CommandVariant, LC_BUILD_VERSION},
+        Mach, MachO,
+    },
+    serde::Serialize,
+    std::{collections::BTreeMap, fmt::Display, path::Path},
 };
 
-pub fn analyze_file(path: PathBuf) {
-    let mut fd = File::open(path).unwrap();
-    let mut buffer = Vec::new();
-    fd.read_to_end(&mut buffer).unwrap();
-    analyze_data(&buffer);
+/// DLLs that are part of every Windows installation.
+///
+/// Matched case-insensitively. DLLs implementing API sets (`api-ms-win-*`)
+/// are also considered system libraries.
+pub const WINDOWS_SYSTEM_LIBRARIES: &[&str] = &[
+    "advapi32.dll",
+    "bcrypt.dll",
+    "comctl32.dll",
+    "comdlg32.dll",
+    "crypt32.dll",
+    "gdi32.dll",
+    "imm32.dll",
+    "iphlpapi.dll",
+    "kernel32.dll",
+    "msvcrt.dll",
+    "ntdll.dll",
+    "ole32.dll",
+    "oleaut32.dll",
+    "rpcrt4.dll",
+    "secur32.dll",
+    "setupapi.dll",
+    "shell32.dll",
+    "shlwapi.dll",
+    "user32.dll",
+    "userenv.dll",
+    "version.dll",
+    "winmm.dll",
+    "ws2_32.dll",
+];
+
+/// Path prefixes of Mach-O libraries that are part of the operating system.
+const MACOS_SYSTEM_LIBRARY_PREFIXES: &[&str] = &["/usr/lib/", "/System/Library/"];
+
+/// The file format of an analyzed binary.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BinaryFormat {
+    Elf,
+    Pe,
+    MachO,
+}
+
+impl Display for BinaryFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Elf => "ELF",
+            Self::Pe => "PE",
+            Self::MachO => "Mach-O",
+        })
+    }
+}
+
+/// A shared library a binary depends on.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct LibraryDependency {
+    /// Name or path of the library, as recorded in the binary.
+    pub name: String,
+
+    /// Whether the library is provided by the operating system.
+    ///
+    /// For ELF binaries, this means it is part of the Linux Standard Base.
+    /// Other libraries may not be present on all machines.
+    pub system: bool,
+}
+
+/// The minimum version of a symbol versioning namespace required by a binary.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct SymbolVersionRequirement {
+    /// The namespace of the symbol versions. e.g. `GLIBC`.
+    pub name: String,
+
+    /// The highest version of a symbol referenced in the namespace.
+    pub version: String,
+
+    /// Minimum Linux distribution versions providing this version.
+    ///
+    /// Only known for `GLIBC` and `GCC`.
+    pub minimum_distro_versions: Option<Vec<MinimumDistroVersion>>,
+}
+
+/// The minimum version of an operating system a binary targets.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct PlatformVersion {
+    /// The name of the platform. e.g. `macOS`.
+    pub platform: String,
+
+    /// The minimum version of the platform.
+    pub version: String,
+}
+
+/// Describes the portability of a binary.
+#[derive(Clone, Debug, Serialize)]
+pub struct AuditReport {
+    /// The file format of the binary.
+    pub format: BinaryFormat,
+
+    /// Shared libraries the binary depends on.
+    pub libraries: Vec<LibraryDependency>,
+
+    /// Undefined dynamic symbols having a version. Only populated for ELF.
+    pub undefined_versioned_symbols: Vec<UndefinedSymbol>,
+
+    /// Minimum versions of symbol versioning namespaces. Only populated for ELF.
+    pub symbol_versions: Vec<SymbolVersionRequirement>,
+
+    /// Minimum operating system versions. Only populated for Mach-O.
+    ///
+    /// Fat binaries may have an entry for each architecture.
+    pub minimum_os_versions: Vec<PlatformVersion>,
 }
 
-pub fn analyze_data(buffer: &[u8]) {
-    match goblin::Object::parse(buffer).unwrap() {
+impl AuditReport {
+    fn new(format: BinaryFormat) -> Self {
+        Self {
+            format,
+            libraries: vec![],
+            undefined_versioned_symbols: vec![],
+            symbol_versions: vec![],
+            minimum_os_versions: vec![],
+        }
+    }
+
+    /// Obtain the minimum glibc version required, if known.
+    pub fn minimum_glibc_version(&self) -> Option<&str> {
+        self.symbol_versions
+            .iter()
+            .find(|x| x.name == "GLIBC")
+            .map(|x| x.version.as_str())
+    }
+
+    /// Obtain libraries that are not provided by the operating system.
+    pub fn non_system_libraries(&self) -> impl Iterator<Item = &LibraryDependency> {
+        self.libraries.iter().filter(|lib| !lib.system)
+    }
+
+    /// Serialize the report to JSON.
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+impl Display for AuditReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Binary Format: {}", self.format)?;
+        writeln!(f)?;
+        writeln!(f, "Shared Library Dependencies")?;
+        writeln!(f, "===========================")?;
+
+        for lib in &self.libraries {
+            writeln!(f, "{}", lib.name)?;
+
+            if !lib.system {
+                writeln!(
+                    f,
+                    "  PROBLEMATIC - Shared library dependency may not be on all machines"
+                )?;
+            } else if self.format == BinaryFormat::Elf {
+                writeln!(
+                    f,
+                    "  OK - Library part of Linux Standard Base and present on most distros"
+                )?;
+            } else {
+                writeln!(f, "  OK - Library provided by the operating system")?;
+            }
+
+            writeln!(f)?;
+        }
+
+        if self.format == BinaryFormat::Elf {
+            writeln!(f, "Symbol Versioning")?;
+            writeln!(f, "=================")?;
+
+            for requirement in &self.symbol_versions {
+                let name = match requirement.name.as_str() {
+                    "GLIBC" => "glibc".to_string(),
+                    "GCC" => "gcc".to_string(),
+                    other => other.to_string(),
+                };
+
+                writeln!(f)?;
+                writeln!(f, "{}", name)?;
+                writeln!(f, "-----")?;
+                writeln!(f)?;
+                writeln!(f, "Minimum Version: {}", requirement.version)?;
+
+                if let Some(distros) = &requirement.minimum_distro_versions {
+                    writeln!(f, "Minimum Distro Versions:")?;
+
+                    for distro in distros {
+                        writeln!(f, "  {}", distro)?;
+                    }
+                } else {
+                    writeln!(f, "Minimum Distro Versions: Unknown")?;
+                }
+            }
+        }
+
+        if self.format == BinaryFormat::MachO {
+            writeln!(f, "Minimum OS Versions")?;
+            writeln!(f, "===================")?;
+
+            for version in &self.minimum_os_versions {
+                writeln!(f, "{} {}", version.platform, version.version)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Analyze a binary file for portability.
+pub fn analyze_file(path: impl AsRef<Path>) -> Result<AuditReport> {
+    let path = path.as_ref();
+    let data = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+
+    analyze_data(&data)
+}
+
+/// Analyze binary data for portability.
+///
+/// Supports ELF, PE, and Mach-O binaries. Other data results in an error.
+pub fn analyze_data(data: &[u8]) -> Result<AuditReport> {
+    match goblin::Object::parse(data)? {
         goblin::Object::Elf(elf) => {
-            let mut undefined_symbols = find_undefined_elf_symbols(&buffer, &elf);
+            let mut undefined_symbols = find_undefined_elf_symbols(data, &elf);
             undefined_symbols.sort();
 
-            analyze_elf_libraries(&elf.libraries, &undefined_symbols);
+            Ok(audit_elf(&elf.libraries, undefined_symbols))
         }
-        goblin::Object::PE(_pe) => {
-            panic!("PE not yet supported");
+        goblin::Object::PE(_) => {
+            let mut report = AuditReport::new(BinaryFormat::Pe);
+
+            let mut libraries = find_pe_dependencies(data)?;
+            libraries.sort_unstable();
+            libraries.dedup();
+
+            report.libraries = libraries
+                .into_iter()
+                .map(|name| LibraryDependency {
+                    system: is_windows_system_library(&name),
+                    name,
+                })
+                .collect();
+
+            Ok(report)
         }
-        goblin::Object::Mach(_mach) => {
-            panic!("mach not yet supported");
+        goblin::Object::Mach(Mach::Binary(macho)) => {
+            let mut report = AuditReport::new(BinaryFormat::MachO);
+            audit_macho(&mut report, data, &macho);
+            finish_macho_report(&mut report);
+
+            Ok(report)
         }
-        goblin::Object::Archive(_archive) => {
-            panic!("archive not yet supported");
+        goblin::Object::Mach(Mach::Fat(multiarch)) => {
+            let mut report = AuditReport::new(BinaryFormat::MachO);
+
+            for arch in multiarch.iter_arches() {
+                let arch = arch?;
+
+                // FatArch::slice() panics on out of bounds data.
+                let start = arch.offset as usize;
+                let end = start + arch.size as usize;
+                let arch_data = data
+                    .get(start..end)
+                    .ok_or_else(|| anyhow!("Mach-O architecture extends past end of data"))?;
+
+                let macho = MachO::parse(arch_data, 0)?;
+                audit_macho(&mut report, arch_data, &macho);
+            }
+
+            finish_macho_report(&mut report);
+
+            Ok(report)
         }
-        goblin::Object::Unknown(magic) => panic!("unknown magic: {:#x}", magic),
+        goblin::Object::Archive(_) => Err(anyhow!("archives are not supported")),
+        goblin::Object::Unknown(magic) => Err(anyhow!("unknown binary magic: {:#x}", magic)),
     }
 }
 
-pub fn analyze_elf_libraries(libs: &[&str], undefined_symbols: &[UndefinedSymbol]) {
-    let mut latest_symbols: BTreeMap<String, version_compare::Version> = BTreeMap::new();
-
-    println!("Shared Library Dependencies");
-    println!("===========================");
+/// Produce an [AuditReport] for an ELF binary.
+fn audit_elf(libs: &[&str], undefined_symbols: Vec<UndefinedSymbol>) -> AuditReport {
+    let mut report = AuditReport::new(BinaryFormat::Elf);
 
     let mut libs = libs.to_vec();
     libs.sort_unstable();
-    for lib in libs {
-        println!("{}", lib);
+    report.libraries = libs
+        .into_iter()
+        .map(|lib| LibraryDependency {
+            name: lib.to_string(),
+            system: LSB_SHARED_LIBRARIES.contains(&lib),
+        })
+        .collect();
+
+    let mut latest_symbols: BTreeMap<String, String> = BTreeMap::new();
 
-        if LSB_SHARED_LIBRARIES.contains(&lib) {
-            println!("  OK - Library part of Linux Standard Base and present on most distros");
-        } else {
-            println!("  PROBLEMATIC - Shared library dependency may not be on all machines");
+    for symbol in &undefined_symbols {
+        let version = match &symbol.version {
+            Some(version) => version,
+            None => continue,
+        };
+
+        let parts: Vec<&str> = version.splitn(2, '_').collect();
+        if parts.len() != 2 {
+            continue;
         }
 
-        let mut symbols: Vec<&UndefinedSymbol> = Vec::new();
+        // Versions like GLIBC_PRIVATE aren't comparable.
+        let v = match version_compare::Version::from(parts[1]) {
+            Some(v) => v,
+            None => continue,
+        };
 
-        for symbol in undefined_symbols {
-            if symbol.filename == Some((*lib).to_string()) {
-                symbols.push(symbol);
-            }
+        let newer = match latest_symbols.get(parts[0]) {
+            Some(existing) => version_compare::Version::from(existing)
+                .map(|existing| v > existing)
+                .unwrap_or(true),
+            None => true,
+        };
+
+        if newer {
+            latest_symbols.insert(parts[0].to_string(), parts[1].to_string());
         }
+    }
 
-        /*
-        println!("");
-        println!("  Symbols");
-        println!("  -------");
-        */
-
-        for symbol in symbols {
-            match &symbol.version {
-                Some(version) => {
-                    let parts: Vec<&str> = version.splitn(2, '_').collect();
-
-                    match parts.len() {
-                        1 => { /* TODO this is weird. Do something? */ }
-                        2 => {
-                            let v = version_compare::Version::from(parts[1])
-                                .expect("unable to parse version");
-
-                            match latest_symbols.get(parts[0]) {
-                                Some(existing) => {
-                                    if &v > existing {
-                                        latest_symbols.insert(parts[0].to_string(), v);
-                                    }
-                                }
-                                None => {
-                                    latest_symbols.insert(parts[0].to_string(), v);
-                                }
-                            }
-                        }
-                        _ => {}
-                    }
+    report.symbol_versions = latest_symbols
+        .into_iter()
+        .map(|(name, version)| {
+            let distro_versions = match name.as_str() {
+                "GLIBC" => Some(&*GLIBC_VERSIONS_BY_DISTRO),
+                "GCC" => Some(&*GCC_VERSIONS_BY_DISTRO),
+                _ => None,
+            };
 
-                    //println!("  {}@{}", &symbol.symbol, version)
-                }
-                None => {
-                    //println!("  {}", &symbol.symbol)
-                }
+            let minimum_distro_versions = distro_versions.and_then(|distro_versions| {
+                version_compare::Version::from(&version)
+                    .map(|v| resolve_minimum_distro_versions(&v, distro_versions))
+            });
+
+            SymbolVersionRequirement {
+                name,
+                version,
+                minimum_distro_versions,
             }
-        }
+        })
+        .collect();
+
+    report.undefined_versioned_symbols = undefined_symbols
+        .into_iter()
+        .filter(|symbol| symbol.version.is_some())
+        .collect();
+
+    report
+}
+
+fn is_windows_system_library(name: &str) -> bool {
+    let name = name.to_lowercase();
+
+    name.starts_with("api-ms-win-")
+        || name.starts_with("ext-ms-")
+        || WINDOWS_SYSTEM_LIBRARIES.contains(&name.as_str())
+}
 
-        println!();
+/// Render a Mach-O version number, which is encoded in nibbles `xxxx.yy.zz`.
+fn macho_version(version: u32) -> String {
+    format!(
+        "{}.{}.{}",
+        version >> 16,
+        (version >> 8) & 0xff,
+        version & 0xff
+    )
+}
+
+/// Add the libraries and minimum OS version of a Mach-O binary to a report.
+fn audit_macho(report: &mut AuditReport, data: &[u8], macho: &MachO) {
+    // The first entry is the binary itself.
+    for lib in macho.libs.iter().skip(1) {
+        report.libraries.push(LibraryDependency {
+            name: lib.to_string(),
+            system: MACOS_SYSTEM_LIBRARY_PREFIXES
+                .iter()
+                .any(|prefix| lib.starts_with(prefix)),
+        });
     }
 
-    println!("Symbol Versioning");
-    println!("=================");
+    for command in &macho.load_commands {
+        let version = match &command.command {
+            CommandVariant::VersionMinMacosx(c) => Some(("macOS".to_string(), c.version)),
+            CommandVariant::VersionMinIphoneos(c) => Some(("iOS".to_string(), c.version)),
+            CommandVariant::VersionMinTvos(c) => Some(("tvOS".to_string(), c.version)),
+            CommandVariant::VersionMinWatchos(c) => Some(("watchOS".to_string(), c.version)),
+            // goblin doesn't parse LC_BUILD_VERSION. So read its platform and minos fields
+            // ourselves.
+            CommandVariant::Unimplemented(header) if header.cmd == LC_BUILD_VERSION => {
+                let read = |offset: usize| {
+                    data.get(command.offset + offset..command.offset + offset + 4)
+                        .map(|b| {
+                            let b = [b[0], b[1], b[2], b[3]];
+                            if macho.little_endian {
+                                u32::from_le_bytes(b)
+                            } else {
+                                u32::from_be_bytes(b)
+                            }
+                        })
+                };
 
-    for (name, version) in &latest_symbols {
-        match name.as_str() {
-            "GLIBC" => {
-                println!();
-                println!("glibc");
-                println!("-----");
-                println!();
-                println!("Minimum Version: {}", version);
-                println!("Minimum Distro Versions:");
+                match (read(8), read(12)) {
+                    (Some(platform), Some(minos)) => {
+                        let platform = match platform {
+                            1 => "macOS".to_string(),
+                            2 => "iOS".to_string(),
+                            3 => "tvOS".to_string(),
+                            4 => "watchOS".to_string(),
+                            6 => "Mac Catalyst".to_string(),
+                            other => format!("platform {}", other),
+                        };
 
-                for s in find_minimum_distro_version(&version, &GLIBC_VERSIONS_BY_DISTRO) {
-                    println!("  {}", s);
+                        Some((platform, minos))
+                    }
+                    _ => None,
                 }
             }
-            "GCC" => {
-                println!();
-                println!("gcc");
-                println!("-----");
-                println!();
-                println!("Minimum Version: {}", version);
-                println!("Minimum Distro Versions:");
-
-                for s in find_minimum_distro_version(&version, &GCC_VERSIONS_BY_DISTRO) {
-                    println!("  {}", s);
+            _ => None,
+        };
+
+        if let Some((platform, version)) = version {
+            report.minimum_os_versions.push(PlatformVersion {
+                platform,
+                version: macho_version(version),
+            });
+        }
+    }
+}
+
+fn finish_macho_report(report: &mut AuditReport) {
+    report.libraries.sort_by(|a, b| a.name.cmp(&b.name));
+    report.libraries.dedup();
+    report.minimum_os_versions.sort();
+    report.minimum_os_versions.dedup();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn undefined_symbol(symbol: &str, filename: &str, version: Option<&str>) -> UndefinedSymbol {
+        UndefinedSymbol {
+            symbol: symbol.to_string(),
+            filename: Some(filename.to_string()),
+            version: version.map(|x| x.to_string()),
+        }
+    }
+
+    #[test]
+    fn elf_report() -> Result<()> {
+        let report = audit_elf(
+            &["libc.so.6", "libfoo.so.1"],
+            vec![
+                undefined_symbol("memcpy", "libc.so.6", Some("GLIBC_2.14")),
+                undefined_symbol("malloc", "libc.so.6", Some("GLIBC_2.2.5")),
+                undefined_symbol("secret", "libc.so.6", Some("GLIBC_PRIVATE")),
+                undefined_symbol("foo", "libfoo.so.1", None),
+            ],
+        );
+
+        assert_eq!(
+            report.libraries,
+            vec![
+                LibraryDependency {
+                    name: "libc.so.6".to_string(),
+                    system: true,
+                },
+                LibraryDependency {
+                    name: "libfoo.so.1".to_string(),
+                    system: false,
                 }
+            ]
+        );
+        assert_eq!(report.non_system_libraries().count(), 1);
+        assert_eq!(report.undefined_versioned_symbols.len(), 3);
+        assert_eq!(report.minimum_glibc_version(), Some("2.14"));
+
+        let distros = report.symbol_versions[0]
+            .minimum_distro_versions
+            .as_ref()
+            .unwrap();
+        assert!(distros.contains(&MinimumDistroVersion {
+            distro: "RHEL".to_string(),
+            version: Some("7".to_string()),
+        }));
+
+        let json = serde_json::from_str::<serde_json::Value>(&report.to_json()?)?;
+        assert_eq!(json["format"], "elf");
+        assert_eq!(json["symbol_versions"][0]["version"], "2.14");
+
+        let text = report.to_string();
+        assert!(text.contains("libfoo.so.1\n  PROBLEMATIC"));
+        assert!(text.contains("Minimum Version: 2.14\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn unsupported_data() {
+        assert!(analyze_data(b"not a binary").is_err());
+        assert!(analyze_data(b"!<arch>\n").is_err());
+    }
+
+    #[test]
+    fn windows_system_libraries() {
+        assert!(is_windows_system_library("KERNEL32.dll"));
+        assert!(is_windows_system_library(
+            "api-ms-win-crt-runtime-l1-1-0.dll"
+        ));
+        assert!(!is_windows_system_library("VCRUNTIME140.dll"));
+        assert!(!is_windows_system_library("python39.dll"));
+    }
+
+    #[test]
+    fn macho_versions() {
+        assert_eq!(macho_version(0x000a_0900), "10.9.0");
+        assert_eq!(macho_version(0x000b_0001), "11.0.1");
+    }
+
+    #[test]
+    fn current_executable() -> Result<()> {
+        let report = analyze_file(std::env::current_exe()?)?;
+
+        if cfg!(target_os = "linux") {
+            assert_eq!(report.format, BinaryFormat::Elf);
+
+            if cfg!(target_env = "gnu") {
+                assert!(report.minimum_glibc_version().is_some());
             }
-            other => {
-                println!();
-                println!("{}", other);
-                println!("-----");
-                println!();
-                println!("Minimum Version: {}", version);
-                println!("Minimum Distro Versions: Unknown");
-            }
+        } else if cfg!(target_os = "macos") {
+            assert_eq!(report.format, BinaryFormat::MachO);
+            assert!(!report.minimum_os_versions.is_empty());
+        } else if cfg!(target_family = "windows") {
+            assert_eq!(report.format, BinaryFormat::Pe);
+            assert!(report.libraries.iter().any(|lib| lib.system));
         }
+
+        Ok(())
     }
 }
@@ -4,8 +4,7 @@
 
 use {
     crate::UndefinedSymbol,
-    byteorder::ReadBytesExt,
-    std::{ffi::CStr, os::raw::c_char},
+    byteorder::{NativeEndian, ReadBytesExt},
 };
 
 #[repr(C)]
@@ -40,6 +39,75 @@ struct Elf64_Vernaux {
     vna_next: u32,
 }
 
+/// Read a NUL terminated string at an offset in a buffer.
+fn read_c_string(data: &[u8], offset: usize) -> Option<String> {
+    let data = data.get(offset..)?;
+    let end = data.iter().position(|b| *b == 0)?;
+
+    Some(String::from_utf8_lossy(&data[..end]).into_owned())
+}
+
+fn read_verneed(data: &[u8], offset: usize) -> Option<Elf64_Verneed> {
+    let mut reader = std::io::Cursor::new(data.get(offset..)?);
+
+    Some(Elf64_Verneed {
+        vn_version: reader.read_u16::<NativeEndian>().ok()?,
+        vn_cnt: reader.read_u16::<NativeEndian>().ok()?,
+        vn_file: reader.read_u32::<NativeEndian>().ok()?,
+        vn_aux: reader.read_u32::<NativeEndian>().ok()?,
+        vn_next: reader.read_u32::<NativeEndian>().ok()?,
+    })
+}
+
+fn read_vernaux(data: &[u8], offset: usize) -> Option<Elf64_Vernaux> {
+    let mut reader = std::io::Cursor::new(data.get(offset..)?);
+
+    Some(Elf64_Vernaux {
+        vna_hash: reader.read_u32::<NativeEndian>().ok()?,
+        vna_flags: reader.read_u16::<NativeEndian>().ok()?,
+        vna_other: reader.read_u16::<NativeEndian>().ok()?,
+        vna_name: reader.read_u32::<NativeEndian>().ok()?,
+        vna_next: reader.read_u32::<NativeEndian>().ok()?,
+    })
+}
+
+/// Parse `SHT_GNU_VERNEED` section data.
+///
+/// Parsing stops at the first record that doesn't fit in the data.
+fn parse_verneed_entries(data: &[u8], count: u64) -> Vec<(Elf64_Verneed, Vec<Elf64_Vernaux>)> {
+    let mut entries = Vec::new();
+    let mut offset = 0usize;
+
+    for _ in 0..count {
+        let record = match read_verneed(data, offset) {
+            Some(record) => record,
+            None => break,
+        };
+
+        let mut vernaux: Vec<Elf64_Vernaux> = Vec::new();
+        let mut aux_offset = offset.saturating_add(record.vn_aux as usize);
+
+        for _ in 0..record.vn_cnt {
+            let aux = match read_vernaux(data, aux_offset) {
+                Some(aux) => aux,
+                None => break,
+            };
+            aux_offset = aux_offset.saturating_add(aux.vna_next as usize);
+            vernaux.push(aux);
+        }
+
+        let next = record.vn_next as usize;
+        entries.push((record, vernaux));
+
+        if next == 0 {
+            break;
+        }
+        offset = offset.saturating_add(next);
+    }
+
+    entries
+}
+
 fn resolve_verneed(
     verneed_entries: &[(Elf64_Verneed, Vec<Elf64_Vernaux>)],
     names_data: &[u8],
@@ -52,15 +120,9 @@ fn resolve_verneed(
                 continue;
             }
 
-            let filename_ptr = unsafe { names_data.as_ptr().add(verneed.vn_file as usize) };
-            let filename = unsafe { CStr::from_ptr(filename_ptr as *const c_char) };
-
-            let depend_ptr = unsafe { names_data.as_ptr().add(vernaux.vna_name as usize) };
-            let depend = unsafe { CStr::from_ptr(depend_ptr as *const c_char) };
-
             return (
-                Some(filename.to_string_lossy().into_owned()),
-                Some(depend.to_string_lossy().into_owned()),
+                read_c_string(names_data, verneed.vn_file as usize),
+                read_c_string(names_data, vernaux.vna_name as usize),
             );
         }
     }
@@ -70,78 +132,67 @@ fn resolve_verneed(
 
 /// Find undefined dynamic symbols in an ELF binary.
 ///
-/// Will also resolve the filename and symbol version, if available.
-#[allow(clippy::cast_ptr_alignment)]
+/// Will also resolve the filename and symbol version, if available. Malformed
+/// version information is ignored and symbols whose names cannot be resolved
+/// are skipped.
 pub fn find_undefined_elf_symbols(buffer: &[u8], elf: &goblin::elf::Elf) -> Vec<UndefinedSymbol> {
     let mut verneed_entries: Vec<(Elf64_Verneed, Vec<Elf64_Vernaux>)> = Vec::new();
     let mut versym: Vec<u16> = Vec::new();
-    let mut verneed_names_section: u32 = 0;
+    let mut verneed_names_section: Option<usize> = None;
+
+    let section_data = |section_header: &goblin::elf::SectionHeader| -> &[u8] {
+        section_header
+            .file_range()
+            .and_then(|range| buffer.get(range))
+            .unwrap_or(&[])
+    };
 
     for section_header in &elf.section_headers {
         match section_header.sh_type {
             goblin::elf::section_header::SHT_GNU_VERSYM => {
-                let data: &[u8] = &buffer[section_header
-                    .file_range()
-                    .expect("SHT_GNU_VERSYM missing file range")];
+                let mut reader = std::io::Cursor::new(section_data(section_header));
 
-                let mut reader = std::io::Cursor::new(data);
-
-                while let Ok(value) = reader.read_u16::<byteorder::NativeEndian>() {
+                while let Ok(value) = reader.read_u16::<NativeEndian>() {
                     versym.push(value);
                 }
             }
             goblin::elf::section_header::SHT_GNU_VERNEED => {
-                verneed_names_section = section_header.sh_link;
-
-                let data: &[u8] = &buffer[section_header
-                    .file_range()
-                    .expect("SHT_GNU_VERNEED missing file range")];
-
-                let mut ptr = data.as_ptr();
-
-                for _ in 0..elf.dynamic.as_ref().unwrap().info.verneednum {
-                    let record: Elf64_Verneed = unsafe { std::ptr::read(ptr as *const _) };
+                verneed_names_section = Some(section_header.sh_link as usize);
 
-                    // Stash pointer to next Verneed record.
-                    let next_record = unsafe { ptr.add(record.vn_next as usize) };
+                let count = elf
+                    .dynamic
+                    .as_ref()
+                    .map(|dynamic| dynamic.info.verneednum)
+                    .unwrap_or(0);
 
-                    let mut vernaux: Vec<Elf64_Vernaux> = Vec::new();
-
-                    ptr = unsafe { ptr.add(record.vn_aux as usize) };
-
-                    for _ in 0..record.vn_cnt {
-                        let aux: Elf64_Vernaux = unsafe { std::ptr::read(ptr as *const _) };
-                        vernaux.push(aux.clone());
-                        ptr = unsafe { ptr.add(aux.vna_next as usize) };
-                    }
-
-                    verneed_entries.push((record.clone(), vernaux));
-
-                    ptr = next_record;
-                }
+                verneed_entries = parse_verneed_entries(section_data(section_header), count);
             }
             _ => {}
         }
     }
 
     let dynstrtab = &elf.dynstrtab;
-    let verneed_names_data: &[u8] = &buffer[elf.section_headers[verneed_names_section as usize]
-        .file_range()
-        .expect("verneed names section missing file range")];
+    let verneed_names_data: &[u8] = verneed_names_section
+        .and_then(|index| elf.section_headers.get(index))
+        .map(section_data)
+        .unwrap_or(&[]);
 
     let mut res: Vec<UndefinedSymbol> = Vec::new();
 
     let mut versym_iter = versym.iter();
 
     for sym in elf.dynsyms.iter() {
-        let versym = *versym_iter.next().unwrap();
+        let versym = versym_iter.next().copied().unwrap_or(0);
 
         if sym.is_import() {
-            let name = dynstrtab.get(sym.st_name).unwrap().unwrap();
+            let name = match dynstrtab.get(sym.st_name) {
+                Some(Ok(name)) => name,
+                _ => continue,
+            };
 
             res.push(if versym > 1 {
                 let (filename, version) =
-                    resolve_verneed(&verneed_entries, &verneed_names_data, versym);
+                    resolve_verneed(&verneed_entries, verneed_names_data, versym);
 
                 UndefinedSymbol {
                     symbol: String::from(name),
@@ -160,3 +211,17 @@ pub fn find_undefined_elf_symbols(buffer: &[u8], elf: &goblin::elf::Elf) -> Vec<
 
     res
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn malformed_verneed() {
+        assert!(parse_verneed_entries(&[], 1).is_empty());
+        assert!(parse_verneed_entries(&[0u8; 8], 1).is_empty());
+        assert_eq!(read_c_string(b"foo\0bar", 0), Some("foo".to_string()));
+        assert_eq!(read_c_string(b"foo", 0), None);
+        assert_eq!(read_c_string(b"foo\0", 10), None);
+    }
+}
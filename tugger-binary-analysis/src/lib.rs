@@ -4,16 +4,24 @@
 
 /*! Functionality for analyzing the content of platform binaries. */
 
+use serde::Serialize;
+
 mod audit;
-pub use audit::{analyze_data, analyze_elf_libraries, analyze_file};
+pub use audit::{
+    analyze_data, analyze_file, AuditReport, BinaryFormat, LibraryDependency, PlatformVersion,
+    SymbolVersionRequirement, WINDOWS_SYSTEM_LIBRARIES,
+};
 mod elf;
 pub use elf::find_undefined_elf_symbols;
 mod linux_distro_versions;
 pub use linux_distro_versions::{
-    find_minimum_distro_version, GCC_VERSIONS_BY_DISTRO, GLIBC_VERSIONS_BY_DISTRO,
+    find_minimum_distro_version, resolve_minimum_distro_versions, MinimumDistroVersion,
+    GCC_VERSIONS_BY_DISTRO, GLIBC_VERSIONS_BY_DISTRO,
 };
 mod pe;
 pub use pe::{find_pe_dependencies, find_pe_dependencies_path};
+mod policy;
+pub use policy::{PolicyViolation, PortabilityPolicy, TargetDistro};
 
 /// Shared libraries defined as part of the Linux Shared Base specification.
 pub const LSB_SHARED_LIBRARIES: &[&str] = &[
@@ -27,7 +35,8 @@ pub const LSB_SHARED_LIBRARIES: &[&str] = &[
     "libutil.so.1",
 ];
 
-#[derive(Debug, Eq, PartialEq, PartialOrd, Ord)]
+/// An undefined dynamic symbol in an ELF binary.
+#[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord, Serialize)]
 pub struct UndefinedSymbol {
     pub symbol: String,
     pub filename: Option<String>,
@@ -2,7 +2,11 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use {once_cell::sync::Lazy, std::collections::BTreeMap};
+use {
+    once_cell::sync::Lazy,
+    serde::Serialize,
+    std::{collections::BTreeMap, fmt::Display},
+};
 
 type DistroVersion = Vec<(&'static str, &'static str)>;
 
@@ -150,31 +154,59 @@ pub static GCC_VERSIONS_BY_DISTRO: Lazy<BTreeMap<&'static str, DistroVersion>> =
     res
 });
 
-/// Find the minimum Linux distribution version supporting a given version of something.
-pub fn find_minimum_distro_version(
-    version: &version_compare::Version,
-    distro_versions: &BTreeMap<&'static str, DistroVersion>,
-) -> Vec<String> {
-    let mut res: Vec<String> = Vec::new();
-
-    for (distro, dv) in distro_versions {
-        let mut found = false;
+/// The first version of a Linux distribution supporting a given version of something.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct MinimumDistroVersion {
+    /// Name of the Linux distribution.
+    pub distro: String,
 
-        for (distro_version, version_version) in dv {
-            let version_version = version_compare::Version::from(version_version)
-                .expect("unable to parse distro version");
-
-            if &version_version >= version {
-                found = true;
-                res.push(format!("{} {}", distro, distro_version));
-                break;
-            }
-        }
+    /// The distribution version. `None` if no known version is supported.
+    pub version: Option<String>,
+}
 
-        if !found {
-            res.push(format!("No known {} versions supported", distro));
+impl Display for MinimumDistroVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(version) = &self.version {
+            write!(f, "{} {}", self.distro, version)
+        } else {
+            write!(f, "No known {} versions supported", self.distro)
         }
     }
+}
 
-    res
+/// Resolve the minimum version of each Linux distribution supporting a given version of something.
+pub fn resolve_minimum_distro_versions(
+    version: &version_compare::Version,
+    distro_versions: &BTreeMap<&'static str, DistroVersion>,
+) -> Vec<MinimumDistroVersion> {
+    distro_versions
+        .iter()
+        .map(|(distro, dv)| {
+            let version = dv
+                .iter()
+                .find(|(_, version_version)| {
+                    let version_version = version_compare::Version::from(version_version)
+                        .expect("unable to parse distro version");
+
+                    &version_version >= version
+                })
+                .map(|(distro_version, _)| distro_version.to_string());
+
+            MinimumDistroVersion {
+                distro: distro.to_string(),
+                version,
+            }
+        })
+        .collect()
+}
+
+/// Find the minimum Linux distribution version supporting a given version of something.
+pub fn find_minimum_distro_version(
+    version: &version_compare::Version,
+    distro_versions: &BTreeMap<&'static str, DistroVersion>,
+) -> Vec<String> {
+    resolve_minimum_distro_versions(version, distro_versions)
+        .iter()
+        .map(|x| x.to_string())
+        .collect()
 }
@@ -0,0 +1,352 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Enforce portability requirements on binaries.
+
+use {
+    crate::{AuditReport, GCC_VERSIONS_BY_DISTRO, GLIBC_VERSIONS_BY_DISTRO},
+    anyhow::{anyhow, Result},
+    std::{collections::BTreeSet, fmt::Display},
+};
+
+/// A Linux distribution version binaries must run on.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TargetDistro {
+    /// Name of the distribution. e.g. `RHEL`.
+    pub distro: String,
+
+    /// Version of the distribution. e.g. `7`.
+    pub version: String,
+}
+
+impl TargetDistro {
+    /// Parse a `<distro> <version>` string. e.g. `RHEL 7`.
+    ///
+    /// Only distributions and versions having known glibc versions are accepted.
+    pub fn parse(s: &str) -> Result<Self> {
+        let parts = s.trim().rsplitn(2, ' ').collect::<Vec<_>>();
+        if parts.len() != 2 {
+            return Err(anyhow!(
+                "target distro must be of the form '<distro> <version>'; got '{}'",
+                s
+            ));
+        }
+
+        let (distro, version) = (parts[1].trim(), parts[0]);
+
+        let versions = GLIBC_VERSIONS_BY_DISTRO
+            .get(distro)
+            .ok_or_else(|| anyhow!("unknown Linux distribution: {}", distro))?;
+
+        if !versions.iter().any(|(v, _)| *v == version) {
+            return Err(anyhow!("unknown {} version: {}", distro, version));
+        }
+
+        Ok(Self {
+            distro: distro.to_string(),
+            version: version.to_string(),
+        })
+    }
+}
+
+impl Display for TargetDistro {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.distro, self.version)
+    }
+}
+
+/// Describes how a binary violates a [PortabilityPolicy].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PolicyViolation {
+    /// The binary requires a newer glibc than allowed.
+    GlibcVersion { required: String, maximum: String },
+
+    /// The binary depends on a shared library that isn't allowed.
+    Library(String),
+
+    /// The binary requires a symbol version not available on a target distribution.
+    Distro {
+        target: TargetDistro,
+        namespace: String,
+        required: String,
+        available: String,
+    },
+}
+
+impl Display for PolicyViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::GlibcVersion { required, maximum } => write!(
+                f,
+                "requires glibc {}; maximum allowed version is {}",
+                required, maximum
+            ),
+            Self::Library(name) => write!(
+                f,
+                "depends on shared library {}, which is not part of the operating system or allowed",
+                name
+            ),
+            Self::Distro {
+                target,
+                namespace,
+                required,
+                available,
+            } => write!(
+                f,
+                "requires {} {}; {} only provides {}",
+                namespace, required, target, available
+            ),
+        }
+    }
+}
+
+/// Constraints on the portability of binaries.
+///
+/// An empty policy allows everything.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PortabilityPolicy {
+    max_glibc_version: Option<String>,
+    allowed_libraries: Option<BTreeSet<String>>,
+    target_distros: Vec<TargetDistro>,
+}
+
+impl PortabilityPolicy {
+    /// Whether the policy defines no constraints.
+    pub fn is_empty(&self) -> bool {
+        self.max_glibc_version.is_none()
+            && self.allowed_libraries.is_none()
+            && self.target_distros.is_empty()
+    }
+
+    /// The maximum glibc version binaries may require.
+    pub fn max_glibc_version(&self) -> Option<&str> {
+        self.max_glibc_version.as_deref()
+    }
+
+    /// Set the maximum glibc version binaries may require.
+    pub fn set_max_glibc_version(&mut self, version: Option<String>) -> Result<()> {
+        if let Some(version) = &version {
+            if !version
+                .split('.')
+                .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+            {
+                return Err(anyhow!("invalid glibc version: {}", version));
+            }
+        }
+
+        self.max_glibc_version = version;
+
+        Ok(())
+    }
+
+    /// Shared libraries binaries may depend on in addition to operating system libraries.
+    ///
+    /// `None` means binaries may depend on any shared library.
+    pub fn allowed_libraries(&self) -> Option<&BTreeSet<String>> {
+        self.allowed_libraries.as_ref()
+    }
+
+    /// Allow binaries to depend on a shared library.
+    ///
+    /// This constrains shared library dependencies if they weren't already.
+    pub fn allow_library(&mut self, name: impl ToString) {
+        self.allowed_libraries
+            .get_or_insert_with(BTreeSet::new)
+            .insert(name.to_string());
+    }
+
+    /// Set the shared libraries binaries may depend on.
+    pub fn set_allowed_libraries(&mut self, names: Option<Vec<String>>) {
+        self.allowed_libraries = names.map(|names| names.into_iter().collect());
+    }
+
+    /// Linux distribution versions binaries must run on.
+    pub fn target_distros(&self) -> &[TargetDistro] {
+        &self.target_distros
+    }
+
+    /// Set the Linux distribution versions binaries must run on.
+    pub fn set_target_distros(&mut self, distros: Vec<TargetDistro>) {
+        self.target_distros = distros;
+    }
+
+    /// Evaluate an [AuditReport] against this policy.
+    ///
+    /// Returns all violations of the policy.
+    pub fn evaluate(&self, report: &AuditReport) -> Vec<PolicyViolation> {
+        let mut violations = vec![];
+
+        if let (Some(maximum), Some(required)) =
+            (&self.max_glibc_version, report.minimum_glibc_version())
+        {
+            if let (Some(max_version), Some(required_version)) = (
+                version_compare::Version::from(maximum),
+                version_compare::Version::from(required),
+            ) {
+                if required_version > max_version {
+                    violations.push(PolicyViolation::GlibcVersion {
+                        required: required.to_string(),
+                        maximum: maximum.to_string(),
+                    });
+                }
+            }
+        }
+
+        if let Some(allowed) = &self.allowed_libraries {
+            for lib in report.non_system_libraries() {
+                if !allowed.contains(&lib.name) {
+                    violations.push(PolicyViolation::Library(lib.name.clone()));
+                }
+            }
+        }
+
+        for target in &self.target_distros {
+            for requirement in &report.symbol_versions {
+                let table = match requirement.name.as_str() {
+                    "GLIBC" => &*GLIBC_VERSIONS_BY_DISTRO,
+                    "GCC" => &*GCC_VERSIONS_BY_DISTRO,
+                    _ => continue,
+                };
+
+                let available = match table.get(target.distro.as_str()).and_then(|versions| {
+                    versions
+                        .iter()
+                        .find(|(version, _)| *version == target.version)
+                        .map(|(_, available)| *available)
+                }) {
+                    Some(available) => available,
+                    None => continue,
+                };
+
+                if let (Some(available_version), Some(required_version)) = (
+                    version_compare::Version::from(available),
+                    version_compare::Version::from(&requirement.version),
+                ) {
+                    if required_version > available_version {
+                        violations.push(PolicyViolation::Distro {
+                            target: target.clone(),
+                            namespace: requirement.name.clone(),
+                            required: requirement.version.clone(),
+                            available: available.to_string(),
+                        });
+                    }
+                }
+            }
+        }
+
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{BinaryFormat, LibraryDependency, SymbolVersionRequirement},
+    };
+
+    fn report() -> AuditReport {
+        AuditReport {
+            format: BinaryFormat::Elf,
+            libraries: vec![
+                LibraryDependency {
+                    name: "libc.so.6".to_string(),
+                    system: true,
+                },
+                LibraryDependency {
+                    name: "libssl.so.1.1".to_string(),
+                    system: false,
+                },
+            ],
+            undefined_versioned_symbols: vec![],
+            symbol_versions: vec![SymbolVersionRequirement {
+                name: "GLIBC".to_string(),
+                version: "2.18".to_string(),
+                minimum_distro_versions: None,
+            }],
+            minimum_os_versions: vec![],
+        }
+    }
+
+    #[test]
+    fn target_distro_parse() -> Result<()> {
+        assert_eq!(
+            TargetDistro::parse("RHEL 7")?,
+            TargetDistro {
+                distro: "RHEL".to_string(),
+                version: "7".to_string()
+            }
+        );
+        assert!(TargetDistro::parse("RHEL").is_err());
+        assert!(TargetDistro::parse("RHEL 42").is_err());
+        assert!(TargetDistro::parse("Gentoo 1").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn empty_policy() {
+        let policy = PortabilityPolicy::default();
+        assert!(policy.is_empty());
+        assert!(policy.evaluate(&report()).is_empty());
+    }
+
+    #[test]
+    fn allowed_libraries() {
+        let mut policy = PortabilityPolicy::default();
+        policy.set_allowed_libraries(Some(vec![]));
+        assert!(!policy.is_empty());
+        assert_eq!(
+            policy.evaluate(&report()),
+            vec![PolicyViolation::Library("libssl.so.1.1".to_string())]
+        );
+
+        policy.allow_library("libssl.so.1.1");
+        assert!(policy.evaluate(&report()).is_empty());
+    }
+
+    #[test]
+    fn evaluate() -> Result<()> {
+        let mut policy = PortabilityPolicy::default();
+        policy.set_max_glibc_version(Some("2.17".to_string()))?;
+        policy.allow_library("libssl.so.1.1");
+        policy.set_target_distros(vec![
+            TargetDistro::parse("RHEL 7")?,
+            TargetDistro::parse("Debian 9")?,
+        ]);
+
+        assert!(policy.set_max_glibc_version(Some("".to_string())).is_err());
+        assert!(policy
+            .set_max_glibc_version(Some("GLIBC_2.17".to_string()))
+            .is_err());
+        assert_eq!(policy.max_glibc_version(), Some("2.17"));
+
+        let violations = policy.evaluate(&report());
+        assert_eq!(
+            violations,
+            vec![
+                PolicyViolation::GlibcVersion {
+                    required: "2.18".to_string(),
+                    maximum: "2.17".to_string(),
+                },
+                PolicyViolation::Distro {
+                    target: TargetDistro::parse("RHEL 7")?,
+                    namespace: "GLIBC".to_string(),
+                    required: "2.18".to_string(),
+                    available: "2.17".to_string(),
+                }
+            ]
+        );
+        assert_eq!(
+            violations[1].to_string(),
+            "requires GLIBC 2.18; RHEL 7 only provides 2.17"
+        );
+
+        policy.set_max_glibc_version(None)?;
+        policy.set_target_distros(vec![TargetDistro::parse("Debian 9")?]);
+        assert!(policy.evaluate(&report()).is_empty());
+
+        Ok(())
+    }
+}
@@ -77,7 +77,8 @@ use {
     tugger_apple_codesign::{AppleCodesignError, MachOSigner},
     tugger_file_manifest::{File, FileData, FileEntry},
     tugger_windows_codesign::{
-        CodeSigningCertificate, FileBasedCodeSigningCertificate, SystemStore,
+        AuthenticodeSigner, CodeSigningCertificate, FileBasedCodeSigningCertificate, PeLayout,
+        SystemStore,
     },
     x509_certificate::{CapturedX509Certificate, InMemorySigningKeyPair, X509CertificateError},
     yasna::ASN1Error,
@@ -144,6 +145,9 @@ pub enum SigningError {
     #[error("error running signtool: {0}")]
     SigntoolError(anyhow::Error),
 
+    #[error("error signing PE binary: {0}")]
+    AuthenticodeSigningError(anyhow::Error),
+
     #[error("incompatible signing destination: {0}")]
     IncompatibleSigningDestination(&'static str),
 
@@ -334,6 +338,20 @@ pub enum Signable {
     /// TODO store a Cow.
     WindowsData(Vec<u8>),
 
+    /// A PE file that is signable with our Authenticode implementation.
+    ///
+    /// [Signer] converts this to [Self::WindowsFile] when signtool.exe is
+    /// available and can use the signing certificate.
+    ///
+    /// Like [Self::MachOFile], we keep a reference to the file data read
+    /// when evaluating signability.
+    PeFile(PathBuf, Vec<u8>),
+
+    /// PE data that is signable with our Authenticode implementation.
+    ///
+    /// Like [Self::PeFile], this may be signed with signtool.exe instead.
+    PeData(Vec<u8>),
+
     /// A signable Mach-O file.
     ///
     /// We have to obtain the Mach-O data as part of evaluating whether it is
@@ -369,6 +387,15 @@ impl Signable {
                     SigningMethod::Memory,
                 ]
             }
+            Self::PeFile(_, _) => {
+                // Authenticode signing happens in memory, so we can write anywhere.
+                vec![
+                    SigningMethod::InPlaceFile,
+                    SigningMethod::NewFile,
+                    SigningMethod::Memory,
+                ]
+            }
+            Self::PeData(_) => vec![SigningMethod::NewFile, SigningMethod::Memory],
             Self::MachOFile(_, _) => {
                 // tugger-apple-codesign does all of these easily.
                 vec![
@@ -397,8 +424,11 @@ impl Signable {
     pub fn source_file(&self) -> Option<&Path> {
         match self {
             Self::WindowsFile(p) => Some(p.as_path()),
+            Self::PeFile(p, _) => Some(p.as_path()),
             Self::MachOFile(p, _) => Some(p.as_path()),
-            Self::WindowsData(_) | Self::MachOData(_) | Self::AppleBundle(_) => None,
+            Self::WindowsData(_) | Self::PeData(_) | Self::MachOData(_) | Self::AppleBundle(_) => {
+                None
+            }
         }
     }
 
@@ -408,6 +438,8 @@ impl Signable {
             Self::AppleBundle(p) => Some(p.as_path()),
             Self::WindowsFile(_)
             | Self::WindowsData(_)
+            | Self::PeFile(_, _)
+            | Self::PeData(_)
             | Self::MachOFile(_, _)
             | Self::MachOData(_) => None,
         }
@@ -513,6 +545,17 @@ pub fn path_signable(path: impl AsRef<Path>) -> Result<Signability, SigningError
     let path = path.as_ref();
 
     if path.is_file() {
+        let data = std::fs::read(path)?;
+
+        // PE binaries can be signed anywhere using our Authenticode implementation.
+        // [Signer] still prefers signtool.exe for them when it is available.
+        if PeLayout::parse(&data).is_ok() {
+            return Ok(Signability::Signable(Signable::PeFile(
+                path.to_path_buf(),
+                data,
+            )));
+        }
+
         match tugger_windows_codesign::is_file_signable(path) {
             Ok(true) => {
                 // But we can only sign Windows binaries on Windows since we call out to
@@ -531,8 +574,6 @@ pub fn path_signable(path: impl AsRef<Path>) -> Result<Signability, SigningError
             Err(e) => return Err(SigningError::SignableTestError(format!("{:?}", e))),
         }
 
-        let data = std::fs::read(path)?;
-
         if goblin::mach::Mach::parse(&data).is_ok() {
             // Try to construct a signer to see if the binary is compatible.
             return Ok(match MachOSigner::new(&data) {
@@ -551,6 +592,10 @@ pub fn path_signable(path: impl AsRef<Path>) -> Result<Signability, SigningError
 
 /// Resolve signability information given a data slice.
 pub fn data_signable(data: &[u8]) -> Result<Signability, SigningError> {
+    if PeLayout::parse(data).is_ok() {
+        return Ok(Signability::Signable(Signable::PeData(data.to_vec())));
+    }
+
     if tugger_windows_codesign::is_signable_binary_header(data) {
         // But we can only sign Windows binaries on Windows since we call out to
         // signtool.exe.
@@ -693,6 +738,16 @@ impl SigningCertificate {
         ))
     }
 
+    /// Obtain the certificate and signing key, if they are available in memory.
+    pub fn signing_key(&self) -> Option<(&CapturedX509Certificate, &InMemorySigningKeyPair)> {
+        match self {
+            Self::Memory(cert, key) | Self::PfxFile(_, _, cert, key) => Some((cert, key)),
+            Self::WindowsStoreAuto
+            | Self::WindowsStoreSubject(_, _)
+            | Self::WindowsStoreSha1Thumbprint(_, _) => None,
+        }
+    }
+
     /// Attempt to convert this instance to a [CodeSigningCertificate] for use signing on Windows.
     pub fn to_windows_code_signing_certificate(
         &self,
@@ -924,6 +979,18 @@ impl Signer {
             SignableCandidate::Forced(signable) => Ok(Signability::Signable(signable.clone())),
         }?;
 
+        // PE binaries are signed with signtool.exe when possible. Otherwise we fall
+        // back to our Authenticode implementation.
+        let signability = match signability {
+            Signability::Signable(Signable::PeFile(path, _)) if self.prefer_signtool() => {
+                Self::windows_store_signability(Signable::WindowsFile(path))
+            }
+            Signability::Signable(Signable::PeData(data)) if self.prefer_signtool() => {
+                Self::windows_store_signability(Signable::WindowsData(data))
+            }
+            signability => signability,
+        };
+
         // We don't yet support exporting the key back to PFX for Windows signing.
         if matches!(
             signability,
@@ -939,6 +1006,35 @@ impl Signer {
         }
     }
 
+    /// Whether PE binaries should be signed with signtool.exe instead of our Authenticode implementation.
+    ///
+    /// Certificates in the Windows store can only be used through signtool.exe.
+    /// In-memory certificates can only be used by our Authenticode implementation,
+    /// since we can't re-export them to PFX. For everything else, we use
+    /// signtool.exe if it is available.
+    fn prefer_signtool(&self) -> bool {
+        match &self.signing_certificate {
+            SigningCertificate::Memory(_, _) => false,
+            SigningCertificate::PfxFile(_, _, _, _) => {
+                tugger_windows_codesign::find_signtool().is_ok()
+            }
+            SigningCertificate::WindowsStoreAuto
+            | SigningCertificate::WindowsStoreSubject(_, _)
+            | SigningCertificate::WindowsStoreSha1Thumbprint(_, _) => true,
+        }
+    }
+
+    /// Resolve the signability of an entity that must be signed with signtool.exe.
+    fn windows_store_signability(signable: Signable) -> Signability {
+        if cfg!(target_family = "windows") {
+            Signability::Signable(signable)
+        } else {
+            Signability::PlatformUnsupported(
+                "signing with Windows store certificates requires running on Windows",
+            )
+        }
+    }
+
     /// Attempt to resolve a [SignableSigner] for the [SignableCandidate].
     ///
     /// This will determine if a given entity can be signed by us. If so, we will
@@ -1015,9 +1111,12 @@ impl<'a> SignableSigner<'a> {
     pub fn in_place_destination(&self) -> SigningDestination {
         match &self.signable {
             Signable::WindowsFile(path) => SigningDestination::File(path.clone()),
+            Signable::PeFile(path, _) => SigningDestination::File(path.clone()),
             Signable::MachOFile(path, _) => SigningDestination::File(path.clone()),
             Signable::AppleBundle(path) => SigningDestination::Directory(path.clone()),
-            Signable::WindowsData(_) | Signable::MachOData(_) => SigningDestination::Memory,
+            Signable::WindowsData(_) | Signable::PeData(_) | Signable::MachOData(_) => {
+                SigningDestination::Memory
+            }
         }
     }
 
@@ -1089,6 +1188,27 @@ impl<'a> SignableSigner<'a> {
         Ok(signer)
     }
 
+    /// Obtain a [tugger_windows_codesign::AuthenticodeSigner] from this instance.
+    pub fn as_authenticode_signer(&self) -> Result<AuthenticodeSigner<'_>, SigningError> {
+        let (cert, key) = self.signing_certificate.signing_key().ok_or_else(|| {
+            SigningError::CertificateNotUsable("certificates in the Windows store are not supported for Authenticode signing without signtool.exe; try using a PFX file-based certificate instead".to_string())
+        })?;
+
+        let mut signer = AuthenticodeSigner::new(key, cert.clone());
+
+        for cert in &self.certificate_chain {
+            signer.chain_certificate(cert.clone());
+        }
+
+        if let Some(url) = &self.time_stamp_url {
+            signer
+                .time_stamp_url(url.clone())
+                .expect("shouldn't have failed for already parsed URL");
+        }
+
+        Ok(signer)
+    }
+
     /// Compute [SigningDestinationCompatibility] with a given [SigningDestination].
     ///
     /// This takes the current to-be-signed entity into account.
@@ -1234,6 +1354,20 @@ impl<'a> SignableSigner<'a> {
                     }
                 }
             }
+            Signable::PeData(pe_data) => {
+                warn!(
+                    logger,
+                    "signing PE binary from in-memory data of size {} bytes",
+                    pe_data.len()
+                );
+
+                self.sign_pe_data(logger, pe_data, destination)
+            }
+            Signable::PeFile(source_file, pe_data) => {
+                warn!(logger, "signing {}", source_file.display());
+
+                self.sign_pe_data(logger, pe_data, destination)
+            }
             Signable::MachOData(macho_data) => {
                 warn!(
                     logger,
@@ -1329,6 +1463,38 @@ impl<'a> SignableSigner<'a> {
         }
     }
 
+    /// Sign PE data with our Authenticode implementation and write it to a destination.
+    fn sign_pe_data(
+        &self,
+        logger: &slog::Logger,
+        pe_data: &[u8],
+        destination: &SigningDestination,
+    ) -> Result<SignedOutput, SigningError> {
+        let dest = self
+            .as_authenticode_signer()?
+            .sign(pe_data)
+            .map_err(SigningError::AuthenticodeSigningError)?;
+
+        match destination {
+            SigningDestination::Memory => {
+                warn!(logger, "PE signing success; new size {}", dest.len());
+                Ok(SignedOutput::Memory(dest))
+            }
+            SigningDestination::File(dest_file) => {
+                warn!(
+                    logger,
+                    "PE signing success; writing to {}",
+                    dest_file.display()
+                );
+                std::fs::write(dest_file, &dest)?;
+                Ok(SignedOutput::File(dest_file.clone()))
+            }
+            SigningDestination::Directory(_) => {
+                panic!("illegal signing combination: SignablePe -> Directory");
+            }
+        }
+    }
+
     /// Whether signing to the specified [SigningDestination] will require temporary files.
     ///
     /// Temporary files are used when:
@@ -1353,8 +1519,12 @@ impl<'a> SignableSigner<'a> {
                 // Signing to a directory isn't supported.
                 SigningDestination::Directory(_) => false,
             },
-            // tugger-apple-codesign does everything in memory and doesn't need files.
-            Signable::MachOData(_) | Signable::MachOFile(_, _) => false,
+            // Authenticode and tugger-apple-codesign do everything in memory and don't
+            // need files.
+            Signable::PeData(_)
+            | Signable::PeFile(_, _)
+            | Signable::MachOData(_)
+            | Signable::MachOFile(_, _) => false,
             // But, when we are sending output to the filesystem and the output isn't
             // the input, we go through a temporary directory to prevent writing
             // bad results to the output directory.
@@ -1411,6 +1581,49 @@ mod tests {
         SigningCertificate::from_pfx_data(&pfx_data, "password").unwrap();
     }
 
+    #[test]
+    fn pe_signability() {
+        let candidate = SignableCandidate::Forced(Signable::PeData(vec![]));
+
+        let signer = Signer::from(
+            SigningCertificate::from_pfx_data(WINDOWS_PFX_DEFAULT_DATA, "password123").unwrap(),
+        );
+        assert!(matches!(
+            signer.resolve_signability(&candidate).unwrap(),
+            Signability::Signable(Signable::PeData(_))
+        ));
+
+        // PFX files are given to signtool.exe if it is available.
+        let td = tempfile::tempdir().unwrap();
+        let pfx_path = td.path().join("cert.pfx");
+        std::fs::write(&pfx_path, WINDOWS_PFX_DEFAULT_DATA).unwrap();
+        let signer =
+            Signer::from(SigningCertificate::from_pfx_file(&pfx_path, "password123").unwrap());
+        let signability = signer.resolve_signability(&candidate).unwrap();
+        if tugger_windows_codesign::find_signtool().is_ok() {
+            assert!(matches!(
+                signability,
+                Signability::Signable(Signable::WindowsData(_))
+            ));
+        } else {
+            assert!(matches!(
+                signability,
+                Signability::Signable(Signable::PeData(_))
+            ));
+        }
+
+        let signer = Signer::from(SigningCertificate::WindowsStoreAuto);
+        let signability = signer.resolve_signability(&candidate).unwrap();
+        if cfg!(target_family = "windows") {
+            assert!(matches!(
+                signability,
+                Signability::Signable(Signable::WindowsData(_))
+            ));
+        } else {
+            assert!(matches!(signability, Signability::PlatformUnsupported(_)));
+        }
+    }
+
     #[test]
     fn windows_store_with_subject() {
         let cert = SigningCertificate::windows_store_with_subject("my", "test user").unwrap();
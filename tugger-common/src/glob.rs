@@ -32,3 +32,60 @@ where
 
     Ok(res)
 }
+
+/// Resolve the directory a glob pattern searches under.
+///
+/// This is the longest leading portion of the pattern not containing glob
+/// metacharacters, resolved relative to the given directory. Files matching
+/// the pattern can only be created under this directory.
+pub fn glob_base_directory<P>(cwd: P, pattern: &str) -> PathBuf
+where
+    P: AsRef<Path>,
+{
+    let pattern_path = PathBuf::from(pattern);
+
+    let mut base = if pattern.starts_with('/') || pattern_path.is_absolute() {
+        PathBuf::new()
+    } else {
+        cwd.as_ref().to_path_buf()
+    };
+
+    let components = pattern_path.components().collect::<Vec<_>>();
+
+    // The final component is a filename, even if it has no metacharacters.
+    for component in components.iter().take(components.len().saturating_sub(1)) {
+        let s = component.as_os_str().to_string_lossy();
+
+        if s.contains(&['*', '?', '[', ']'][..]) {
+            break;
+        }
+
+        base.push(component);
+    }
+
+    base
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_base_directory() {
+        let cwd = Path::new("/project");
+
+        assert_eq!(glob_base_directory(cwd, "*.py"), PathBuf::from("/project"));
+        assert_eq!(
+            glob_base_directory(cwd, "src/**/*.py"),
+            PathBuf::from("/project/src")
+        );
+        assert_eq!(
+            glob_base_directory(cwd, "src/pkg/foo.txt"),
+            PathBuf::from("/project/src/pkg")
+        );
+        assert_eq!(
+            glob_base_directory(cwd, "/other/d?r/*.py"),
+            PathBuf::from("/other")
+        );
+    }
+}
@@ -10,9 +10,35 @@ for the specification.
 
 use {
     chrono::{DateTime, Local},
-    std::{borrow::Cow, io::Write},
+    std::{
+        borrow::Cow,
+        io::{BufRead, Write},
+    },
 };
 
+#[derive(Debug)]
+pub enum ChangelogError {
+    IoError(std::io::Error),
+    ParseError(String),
+}
+
+impl From<std::io::Error> for ChangelogError {
+    fn from(e: std::io::Error) -> Self {
+        Self::IoError(e)
+    }
+}
+
+impl std::fmt::Display for ChangelogError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IoError(inner) => write!(f, "I/O error: {}", inner),
+            Self::ParseError(msg) => write!(f, "parse error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ChangelogError {}
+
 #[derive(Clone, Debug)]
 pub struct ChangelogEntry<'a> {
     pub package: Cow<'a, str>,
@@ -26,6 +52,79 @@ pub struct ChangelogEntry<'a> {
 }
 
 impl<'a> ChangelogEntry<'a> {
+    /// Parse the header line of an entry.
+    ///
+    /// Returns the package, version, distributions, and urgency.
+    fn parse_header(line: &str) -> Result<(String, String, Vec<String>, String), ChangelogError> {
+        let err = || ChangelogError::ParseError(format!("malformed entry header: {}", line));
+
+        let open = line.find(" (").ok_or_else(err)?;
+        let close = line[open..].find(')').ok_or_else(err)? + open;
+
+        let package = line[..open].trim();
+        let version = line[open + 2..close].trim();
+
+        let mut parts = line[close + 1..].splitn(2, ';');
+        let distributions = parts
+            .next()
+            .ok_or_else(err)?
+            .split_whitespace()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>();
+
+        // Metadata is a comma delimited list of key=value pairs. We only
+        // care about urgency.
+        let urgency = parts
+            .next()
+            .ok_or_else(err)?
+            .split(',')
+            .filter_map(|kv| {
+                let mut kv = kv.trim().splitn(2, '=');
+
+                match (kv.next(), kv.next()) {
+                    (Some(key), Some(value)) if key.eq_ignore_ascii_case("urgency") => {
+                        Some(value.to_string())
+                    }
+                    _ => None,
+                }
+            })
+            .next()
+            .ok_or_else(|| ChangelogError::ParseError(format!("no urgency in {}", line)))?;
+
+        if package.is_empty() || version.is_empty() || distributions.is_empty() {
+            return Err(err());
+        }
+
+        Ok((
+            package.to_string(),
+            version.to_string(),
+            distributions,
+            urgency,
+        ))
+    }
+
+    /// Parse the trailer line of an entry, without its leading ` -- `.
+    ///
+    /// Returns the maintainer name, email, and date.
+    fn parse_trailer(line: &str) -> Result<(String, String, DateTime<Local>), ChangelogError> {
+        let err = || ChangelogError::ParseError(format!("malformed entry trailer: {}", line));
+
+        let open = line.find('<').ok_or_else(err)?;
+        let close = line[open..].find('>').ok_or_else(err)? + open;
+
+        let name = line[..open].trim();
+        let email = &line[open + 1..close];
+
+        let date = DateTime::parse_from_rfc2822(line[close + 1..].trim()).map_err(|e| {
+            ChangelogError::ParseError(format!("invalid date in trailer {}: {}", line, e))
+        })?;
+
+        // Preserve the original offset so serialization round-trips.
+        let date = DateTime::<Local>::from_utc(date.naive_utc(), *date.offset());
+
+        Ok((name.to_string(), email.to_string(), date))
+    }
+
     /// Serialize the changelog entry to a writer.
     ///
     /// This incurs multiple `.write()` calls. So a buffered writer is
@@ -50,8 +149,8 @@ impl<'a> ChangelogEntry<'a> {
         writer.write_all(self.urgency.as_bytes())?;
         writer.write_all(b"\n\n")?;
         writer.write_all(self.details.as_bytes())?;
-        writer.write_all(b"\n")?;
-        writer.write_all(b"-- ")?;
+        writer.write_all(b"\n\n")?;
+        writer.write_all(b" -- ")?;
         writer.write_all(self.maintainer_name.as_bytes())?;
         writer.write_all(b" <")?;
         writer.write_all(self.maintainer_email.as_bytes())?;
@@ -71,7 +170,84 @@ pub struct Changelog<'a> {
     entries: Vec<ChangelogEntry<'a>>,
 }
 
+impl Changelog<'static> {
+    /// Construct an instance by parsing a `debian/changelog` file from a reader.
+    ///
+    /// Blank lines surrounding the details of each entry are stripped.
+    /// Details are otherwise preserved verbatim, including indentation.
+    pub fn parse_reader<R: BufRead>(reader: &mut R) -> Result<Self, ChangelogError> {
+        let mut entries = vec![];
+        let mut header: Option<(String, String, Vec<String>, String)> = None;
+        let mut details: Vec<String> = vec![];
+
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim_end();
+
+            if let Some((package, version, distributions, urgency)) = header.take() {
+                if let Some(trailer) = line.strip_prefix(" -- ") {
+                    let (maintainer_name, maintainer_email, date) =
+                        ChangelogEntry::parse_trailer(trailer)?;
+
+                    let first = details.iter().position(|l| !l.is_empty());
+                    let last = details.iter().rposition(|l| !l.is_empty());
+                    let details = match (first, last) {
+                        (Some(first), Some(last)) => details[first..=last].join("\n"),
+                        _ => String::new(),
+                    };
+
+                    entries.push(ChangelogEntry {
+                        package: Cow::Owned(package),
+                        version: Cow::Owned(version),
+                        distributions: distributions.into_iter().map(Cow::Owned).collect(),
+                        urgency: Cow::Owned(urgency),
+                        details: Cow::Owned(details),
+                        maintainer_name: Cow::Owned(maintainer_name),
+                        maintainer_email: Cow::Owned(maintainer_email),
+                        date,
+                    });
+                } else {
+                    details.push(line.to_string());
+                    header = Some((package, version, distributions, urgency));
+                }
+            } else if line.is_empty() {
+                continue;
+            } else if line.starts_with(' ') || line.starts_with('\t') {
+                return Err(ChangelogError::ParseError(format!(
+                    "expected entry header; got {}",
+                    line
+                )));
+            } else {
+                header = Some(ChangelogEntry::parse_header(line)?);
+                details.clear();
+            }
+        }
+
+        if let Some((package, version, _, _)) = header {
+            return Err(ChangelogError::ParseError(format!(
+                "entry {} ({}) has no trailer line",
+                package, version
+            )));
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// Parse a changelog from a string.
+    pub fn parse_str(s: &str) -> Result<Self, ChangelogError> {
+        let mut reader = std::io::BufReader::new(s.as_bytes());
+        Self::parse_reader(&mut reader)
+    }
+}
+
 impl<'a> Changelog<'a> {
+    /// Obtain entries in this changelog.
+    ///
+    /// Entries are in file order, which is typically newest first.
+    pub fn entries(&self) -> impl Iterator<Item = &ChangelogEntry<'a>> {
+        self.entries.iter()
+    }
+
     /// Add an entry to this changelog.
     pub fn add_entry<'b: 'a>(&mut self, entry: ChangelogEntry<'b>) {
         self.entries.push(entry)
@@ -114,8 +290,56 @@ mod tests {
         changelog.write(&mut buf)?;
 
         let s = String::from_utf8(buf)?;
-        assert_eq!(s, "mypackage (0.1) mydist; urgency=low\n\ndetails\n-- maintainer <me@example.com>  Tue, 30 Dec 2014 21:26:40 -0700\n\n");
+        assert_eq!(s, "mypackage (0.1) mydist; urgency=low\n\ndetails\n\n -- maintainer <me@example.com>  Tue, 30 Dec 2014 21:26:40 -0700\n\n");
 
         Ok(())
     }
+
+    const CHANGELOG: &str = "mypackage (0.2-1) unstable experimental; urgency=medium\n\n  * Second release.\n    - With nested detail.\n\n  * Another change.\n\n -- Jane Doe <jane@example.com>  Wed, 31 Dec 2014 10:00:00 +0100\n\nmypackage (0.1-1) unstable; urgency=low\n\n  * Initial release.\n\n -- John Doe <john@example.com>  Tue, 30 Dec 2014 21:26:40 -0700\n\n";
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        let changelog = Changelog::parse_str(CHANGELOG)?;
+
+        let entries = changelog.entries().collect::<Vec<_>>();
+        assert_eq!(entries.len(), 2);
+
+        assert_eq!(entries[0].package, "mypackage");
+        assert_eq!(entries[0].version, "0.2-1");
+        assert_eq!(entries[0].distributions, vec!["unstable", "experimental"]);
+        assert_eq!(entries[0].urgency, "medium");
+        assert_eq!(
+            entries[0].details,
+            "  * Second release.\n    - With nested detail.\n\n  * Another change."
+        );
+        assert_eq!(entries[0].maintainer_name, "Jane Doe");
+        assert_eq!(entries[0].maintainer_email, "jane@example.com");
+        assert_eq!(entries[0].date.timestamp(), 1420016400);
+        assert_eq!(entries[1].version, "0.1-1");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let changelog = Changelog::parse_str(CHANGELOG)?;
+
+        let mut buf = vec![];
+        changelog.write(&mut buf)?;
+        assert_eq!(String::from_utf8(buf)?, CHANGELOG);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Changelog::parse_str("mypackage 0.1 unstable; urgency=low\n").is_err());
+        assert!(
+            Changelog::parse_str("mypackage (0.1) unstable; urgency=low\n\n  * Change.\n").is_err()
+        );
+        assert!(Changelog::parse_str(
+            "mypackage (0.1) unstable; urgency=low\n\n -- Me <me@example.com>  not a date\n"
+        )
+        .is_err());
+    }
 }
@@ -45,15 +45,18 @@ pub enum ControlFieldValue<'a> {
 }
 
 impl<'a> ControlFieldValue<'a> {
-    /// Write this value to a writer.
-    pub fn write<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
-        let data = match self {
+    /// Obtain the raw string value.
+    pub fn as_str(&self) -> &str {
+        match self {
             Self::Simple(v) => v,
             Self::Folded(v) => v,
             Self::Multiline(v) => v,
-        };
+        }
+    }
 
-        writer.write_all(data.as_bytes())
+    /// Write this value to a writer.
+    pub fn write<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writer.write_all(self.as_str().as_bytes())
     }
 }
 
@@ -95,10 +98,27 @@ impl<'a> ControlField<'a> {
         Ok(Self { name: key, value })
     }
 
+    /// The name of this field.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The value of this field.
+    pub fn value(&self) -> &ControlFieldValue<'a> {
+        &self.value
+    }
+
     /// Write the contents of this field to a writer.
     pub fn write<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
         writer.write_all(self.name.as_bytes())?;
-        writer.write_all(b": ")?;
+
+        // Multiline values whose first line is empty (e.g. `Files` in `.dsc`
+        // files) shouldn't have trailing whitespace after the colon.
+        if self.value.as_str().starts_with('\n') {
+            writer.write_all(b":")?;
+        } else {
+            writer.write_all(b": ")?;
+        }
         self.value.write(writer)?;
         writer.write_all(b"\n")
     }
@@ -134,6 +154,16 @@ impl<'a> ControlParagraph<'a> {
         self.fields.iter().any(|f| f.name == name)
     }
 
+    /// Obtain fields in this paragraph.
+    pub fn fields(&self) -> impl Iterator<Item = &ControlField<'a>> {
+        self.fields.iter()
+    }
+
+    /// Obtain the value of the first field with a given name in this paragraph.
+    pub fn get_field_str(&self, name: &str) -> Option<&str> {
+        self.get_field(name).map(|f| f.value.as_str())
+    }
+
     /// Obtain the first field with a given name in this paragraph.
     pub fn get_field(&self, name: &str) -> Option<&ControlField> {
         self.fields.iter().find(|f| f.name == name)
@@ -204,6 +234,10 @@ impl<'a> ControlFile<'a> {
                     )?;
 
                     if is_empty_line {
+                        // An empty line also terminates the paragraph.
+                        paragraphs.push(current_paragraph);
+                        current_paragraph = ControlParagraph::default();
+
                         None
                     } else {
                         Some(line)
@@ -236,6 +270,10 @@ impl<'a> ControlFile<'a> {
             }
         }
 
+        if !current_paragraph.fields.is_empty() {
+            paragraphs.push(current_paragraph);
+        }
+
         Ok(Self { paragraphs })
     }
 
@@ -316,6 +354,20 @@ impl<'a> SourceControl<'a> {
 mod tests {
     use {super::*, anyhow::Result};
 
+    #[test]
+    fn test_parse_paragraphs() -> Result<()> {
+        let control =
+            ControlFile::parse_str("Source: foo\nBuild-Depends: a,\n b\n\nPackage: foo\n")?;
+
+        let paragraphs = control.paragraphs().collect::<Vec<_>>();
+        assert_eq!(paragraphs.len(), 2);
+        assert_eq!(paragraphs[0].get_field_str("Source"), Some("foo"));
+        assert_eq!(paragraphs[0].get_field_str("Build-Depends"), Some("a,\n b"));
+        assert_eq!(paragraphs[1].get_field_str("Package"), Some("foo"));
+
+        Ok(())
+    }
+
     #[test]
     fn test_parse_system_lists() -> Result<()> {
         let paths = glob::glob("/var/lib/apt/lists/*_Packages")?
@@ -15,7 +15,7 @@ use {
         path::Path,
         time::SystemTime,
     },
-    tugger_file_manifest::{FileEntry, FileManifest, FileManifestError},
+    tugger_file_manifest::{FileEntry, FileManifest, FileManifestError, Reproducibility},
 };
 
 /// Represents an error related to .deb file handling.
@@ -88,7 +88,11 @@ impl DebCompression {
                 std::io::copy(reader, &mut buffer)?;
             }
             Self::Gzip => {
-                let header = libflate::gzip::HeaderBuilder::new().finish();
+                // The default header records the current time, which would
+                // make output non-deterministic.
+                let header = libflate::gzip::HeaderBuilder::new()
+                    .modification_time(0)
+                    .finish();
 
                 let mut encoder = libflate::gzip::Encoder::with_options(
                     &mut buffer,
@@ -164,6 +168,14 @@ impl<'control> DebBuilder<'control> {
         self
     }
 
+    /// Write the package reproducibly.
+    ///
+    /// Archive members always have normalized ownership, permissions, and
+    /// ordering. So this is equivalent to setting the modified time.
+    pub fn set_reproducibility(self, reproducibility: Reproducibility) -> Self {
+        self.set_mtime(Some(reproducibility.system_time()))
+    }
+
     /// Add an extra file to the `control.tar` archive.
     pub fn extra_control_tar_file(
         mut self,
@@ -254,7 +266,7 @@ impl<'control> DebBuilder<'control> {
     }
 }
 
-fn new_tar_header(mtime: u64) -> Result<tar::Header, DebError> {
+pub(crate) fn new_tar_header(mtime: u64) -> Result<tar::Header, DebError> {
     let mut header = tar::Header::new_gnu();
     header.set_uid(0);
     header.set_gid(0);
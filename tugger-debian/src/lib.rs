@@ -5,11 +5,16 @@
 mod changelog;
 mod control;
 mod deb;
+mod source_package;
 
 pub use {
-    changelog::{Changelog, ChangelogEntry},
+    changelog::{Changelog, ChangelogEntry, ChangelogError},
     control::{
         ControlError, ControlField, ControlFieldValue, ControlFile, ControlParagraph, SourceControl,
     },
-    deb::{write_deb_tar, ControlTarBuilder, DebBuilder, DebError},
+    deb::{write_deb_tar, ControlTarBuilder, DebBuilder, DebCompression, DebError},
+    source_package::{
+        split_version, DscFile, DscFileEntry, SourcePackageBuilder, SourcePackageError,
+        SourcePackageFile,
+    },
 };
@@ -0,0 +1,616 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Interfaces for Debian source packages.
+
+A source package consists of a `.dsc` file describing the package and
+one or more tarballs holding the source code. See
+https://manpages.debian.org/unstable/dpkg-dev/dsc.5.en.html and
+https://manpages.debian.org/unstable/dpkg-dev/dpkg-source.1.en.html
+for the specification.
+*/
+
+use {
+    crate::{
+        deb::new_tar_header, Changelog, ChangelogError, ControlError, ControlField,
+        ControlFieldValue, ControlParagraph, DebCompression, DebError, SourceControl,
+    },
+    sha1::Sha1,
+    sha2::{Digest, Sha256},
+    std::{
+        borrow::Cow,
+        io::Write,
+        path::{Path, PathBuf},
+        time::SystemTime,
+    },
+    tugger_file_manifest::{FileEntry, FileManifest, FileManifestError, Reproducibility},
+};
+
+/// Represents an error related to source package handling.
+#[derive(Debug)]
+pub enum SourcePackageError {
+    IoError(std::io::Error),
+    ControlError(ControlError),
+    ChangelogError(ChangelogError),
+    DebError(DebError),
+    FileManifestError(FileManifestError),
+    Invalid(String),
+}
+
+impl std::fmt::Display for SourcePackageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IoError(inner) => write!(f, "I/O error: {}", inner),
+            Self::ControlError(inner) => write!(f, "control file error: {}", inner),
+            Self::ChangelogError(inner) => write!(f, "changelog error: {}", inner),
+            Self::DebError(inner) => write!(f, "{}", inner),
+            Self::FileManifestError(inner) => write!(f, "file manifest error: {}", inner),
+            Self::Invalid(msg) => write!(f, "invalid source package: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for SourcePackageError {}
+
+impl From<std::io::Error> for SourcePackageError {
+    fn from(e: std::io::Error) -> Self {
+        Self::IoError(e)
+    }
+}
+
+impl From<ControlError> for SourcePackageError {
+    fn from(e: ControlError) -> Self {
+        Self::ControlError(e)
+    }
+}
+
+impl From<ChangelogError> for SourcePackageError {
+    fn from(e: ChangelogError) -> Self {
+        Self::ChangelogError(e)
+    }
+}
+
+impl From<DebError> for SourcePackageError {
+    fn from(e: DebError) -> Self {
+        Self::DebError(e)
+    }
+}
+
+impl From<FileManifestError> for SourcePackageError {
+    fn from(e: FileManifestError) -> Self {
+        Self::FileManifestError(e)
+    }
+}
+
+/// Split a Debian version string into its epoch, upstream version, and Debian revision.
+///
+/// e.g. `1:2.0-3` becomes `(Some("1"), "2.0", Some("3"))`.
+pub fn split_version(version: &str) -> (Option<&str>, &str, Option<&str>) {
+    let (epoch, rest) = match version.find(':') {
+        Some(pos) => (Some(&version[..pos]), &version[pos + 1..]),
+        None => (None, version),
+    };
+
+    match rest.rfind('-') {
+        Some(pos) => (epoch, &rest[..pos], Some(&rest[pos + 1..])),
+        None => (epoch, rest, None),
+    }
+}
+
+/// A file in a source package.
+#[derive(Clone, Debug)]
+pub struct SourcePackageFile {
+    /// Name of the file.
+    pub filename: String,
+    /// Content of the file.
+    pub data: Vec<u8>,
+}
+
+/// A file referenced by a `.dsc` file, along with its checksums.
+#[derive(Clone, Debug)]
+pub struct DscFileEntry {
+    pub filename: String,
+    pub size: u64,
+    pub md5: String,
+    pub sha1: String,
+    pub sha256: String,
+}
+
+impl DscFileEntry {
+    /// Construct an instance by hashing file content.
+    pub fn from_data(filename: impl ToString, data: &[u8]) -> Self {
+        Self {
+            filename: filename.to_string(),
+            size: data.len() as u64,
+            md5: format!("{:x}", md5::compute(data)),
+            sha1: format!("{:x}", Sha1::digest(data)),
+            sha256: format!("{:x}", Sha256::digest(data)),
+        }
+    }
+}
+
+/// A `.dsc` file describing a source package.
+#[derive(Clone, Debug, Default)]
+pub struct DscFile<'a> {
+    paragraph: ControlParagraph<'a>,
+    files: Vec<DscFileEntry>,
+}
+
+impl<'a> DscFile<'a> {
+    /// Construct an instance from a paragraph holding metadata fields.
+    ///
+    /// Checksum fields are derived from registered files and shouldn't be
+    /// present in the paragraph.
+    pub fn new(paragraph: ControlParagraph<'a>) -> Self {
+        Self {
+            paragraph,
+            files: vec![],
+        }
+    }
+
+    /// Register a file belonging to the source package.
+    pub fn add_file(&mut self, entry: DscFileEntry) {
+        self.files.push(entry);
+    }
+
+    /// Obtain files registered with this instance.
+    pub fn files(&self) -> impl Iterator<Item = &DscFileEntry> {
+        self.files.iter()
+    }
+
+    /// Serialize the `.dsc` file to a writer.
+    pub fn write<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let mut paragraph = self.paragraph.clone();
+
+        let checksums = |f: &dyn Fn(&DscFileEntry) -> &str| {
+            self.files
+                .iter()
+                .map(|entry| format!("\n {} {} {}", f(entry), entry.size, entry.filename))
+                .collect::<String>()
+        };
+
+        for (name, value) in &[
+            ("Checksums-Sha1", checksums(&|e| &e.sha1)),
+            ("Checksums-Sha256", checksums(&|e| &e.sha256)),
+            ("Files", checksums(&|e| &e.md5)),
+        ] {
+            paragraph.add_field(ControlField::new(
+                Cow::Borrowed(name),
+                ControlFieldValue::Multiline(Cow::Owned(value.clone())),
+            ));
+        }
+
+        paragraph.write(writer)
+    }
+}
+
+/// Fields copied from the general paragraph of `debian/control` into `.dsc` files.
+const DSC_CONTROL_FIELDS: &[&str] = &[
+    "Maintainer",
+    "Uploaders",
+    "Homepage",
+    "Standards-Version",
+    "Vcs-Browser",
+    "Vcs-Git",
+    "Testsuite",
+    "Build-Depends",
+    "Build-Depends-Arch",
+    "Build-Depends-Indep",
+    "Build-Conflicts",
+    "Build-Conflicts-Arch",
+    "Build-Conflicts-Indep",
+];
+
+/// A builder for Debian source packages.
+///
+/// Source packages are assembled from a `FileManifest` holding the source
+/// tree. Files under `debian/` define the packaging. `debian/control` and
+/// `debian/changelog` must be present: the source package name comes from
+/// the former and its version from the most recent entry of the latter.
+///
+/// Versions having a Debian revision (e.g. `1.0-1`) produce a
+/// `3.0 (quilt)` package, with separate upstream (`.orig.tar`) and
+/// `.debian.tar` tarballs. Other versions produce a `3.0 (native)`
+/// package having a single tarball.
+pub struct SourcePackageBuilder {
+    files: FileManifest,
+    compression: DebCompression,
+    mtime: Option<SystemTime>,
+}
+
+impl SourcePackageBuilder {
+    /// Construct a new instance from a source tree.
+    pub fn new(files: FileManifest) -> Self {
+        Self {
+            files,
+            compression: DebCompression::Xz(6),
+            mtime: None,
+        }
+    }
+
+    /// Set the compression format to use for tarballs.
+    ///
+    /// Default is xz.
+    pub fn set_compression(mut self, compression: DebCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Set the modified time to use on tarball members.
+    ///
+    /// If not set, the current time is used.
+    pub fn set_mtime(mut self, time: Option<SystemTime>) -> Self {
+        self.mtime = time;
+        self
+    }
+
+    /// Write the source package reproducibly.
+    ///
+    /// Tarball members always have normalized ownership, permissions, and
+    /// ordering. So this is equivalent to setting the modified time.
+    pub fn set_reproducibility(self, reproducibility: Reproducibility) -> Self {
+        self.set_mtime(Some(reproducibility.system_time()))
+    }
+
+    fn mtime(&self) -> u64 {
+        self.mtime
+            .unwrap_or_else(std::time::SystemTime::now)
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("times before UNIX epoch not accepted")
+            .as_secs()
+    }
+
+    fn read_file(&self, path: &str) -> Result<String, SourcePackageError> {
+        let entry = self
+            .files
+            .get(path)
+            .ok_or_else(|| SourcePackageError::Invalid(format!("{} not found", path)))?;
+
+        String::from_utf8(entry.resolve_content()?)
+            .map_err(|_| SourcePackageError::Invalid(format!("{} is not valid UTF-8", path)))
+    }
+
+    /// Write a compressed tarball of files with all paths under a prefix directory.
+    fn write_tarball(
+        &self,
+        files: &FileManifest,
+        prefix: &Path,
+    ) -> Result<Vec<u8>, SourcePackageError> {
+        let mtime = self.mtime();
+        let mut builder = tar::Builder::new(vec![]);
+
+        let mut header = new_tar_header(mtime)?;
+        header.set_entry_type(tar::EntryType::Directory);
+        header.set_mode(0o755);
+        header.set_size(0);
+        builder.append_data(&mut header, prefix, std::io::empty())?;
+
+        for directory in files.relative_directories() {
+            let mut header = new_tar_header(mtime)?;
+            header.set_entry_type(tar::EntryType::Directory);
+            header.set_mode(0o755);
+            header.set_size(0);
+            builder.append_data(&mut header, prefix.join(directory), std::io::empty())?;
+        }
+
+        // FileManifest is backed by a BTreeMap, so iteration is deterministic.
+        for (rel_path, content) in files.iter_entries() {
+            let data = content.resolve_content()?;
+
+            let mut header = new_tar_header(mtime)?;
+            header.set_mode(if content.is_executable() {
+                0o755
+            } else {
+                0o644
+            });
+            header.set_size(data.len() as _);
+            builder.append_data(&mut header, prefix.join(rel_path), &*data)?;
+        }
+
+        let tar = builder.into_inner()?;
+
+        Ok(self.compression.compress(&mut std::io::Cursor::new(tar))?)
+    }
+
+    /// Build the source package.
+    ///
+    /// Returns the files constituting the source package. The `.dsc` file is last.
+    pub fn build(&self) -> Result<Vec<SourcePackageFile>, SourcePackageError> {
+        let control = SourceControl::parse_str(&self.read_file("debian/control")?)?;
+        let changelog = Changelog::parse_str(&self.read_file("debian/changelog")?)?;
+
+        let general = control.general_paragraph();
+
+        let source = general.get_field_str("Source").ok_or_else(|| {
+            SourcePackageError::Invalid("debian/control has no Source field".to_string())
+        })?;
+
+        let version = changelog
+            .entries()
+            .next()
+            .ok_or_else(|| SourcePackageError::Invalid("debian/changelog is empty".to_string()))?
+            .version
+            .to_string();
+
+        let (_, upstream_version, revision) = split_version(&version);
+        let file_version = match revision {
+            Some(revision) => format!("{}-{}", upstream_version, revision),
+            None => upstream_version.to_string(),
+        };
+
+        let format = if revision.is_some() {
+            "3.0 (quilt)"
+        } else {
+            "3.0 (native)"
+        };
+
+        let mut debian_files = FileManifest::default();
+        let mut upstream_files = FileManifest::default();
+
+        for (path, entry) in self.files.iter_entries() {
+            match path.strip_prefix("debian") {
+                Ok(rel_path) => debian_files.add_file_entry(rel_path, entry.clone())?,
+                Err(_) => upstream_files.add_file_entry(path, entry.clone())?,
+            }
+        }
+
+        match debian_files.get("source/format") {
+            Some(entry) => {
+                let data = entry.resolve_content()?;
+                if String::from_utf8_lossy(&data).trim() != format {
+                    return Err(SourcePackageError::Invalid(format!(
+                        "debian/source/format does not match {} package format",
+                        format
+                    )));
+                }
+            }
+            None => {
+                debian_files.add_file_entry(
+                    "source/format",
+                    FileEntry::new_from_data(format!("{}\n", format).into_bytes(), false),
+                )?;
+            }
+        }
+
+        let extension = format!(".tar{}", self.compression.extension());
+        let top_dir = PathBuf::from(format!("{}-{}", source, upstream_version));
+
+        let mut files = vec![];
+
+        if revision.is_some() {
+            files.push(SourcePackageFile {
+                filename: format!("{}_{}.orig{}", source, upstream_version, extension),
+                data: self.write_tarball(&upstream_files, &top_dir)?,
+            });
+            files.push(SourcePackageFile {
+                filename: format!("{}_{}.debian{}", source, file_version, extension),
+                data: self.write_tarball(&debian_files, Path::new("debian"))?,
+            });
+        } else {
+            let mut all_files = upstream_files;
+            for (path, entry) in debian_files.iter_entries() {
+                all_files.add_file_entry(Path::new("debian").join(path), entry.clone())?;
+            }
+
+            files.push(SourcePackageFile {
+                filename: format!("{}_{}{}", source, file_version, extension),
+                data: self.write_tarball(&all_files, &top_dir)?,
+            });
+        }
+
+        let binaries = control
+            .binary_paragraphs()
+            .filter_map(|p| p.get_field_str("Package"))
+            .collect::<Vec<_>>();
+
+        let mut architectures: Vec<&str> = vec![];
+        for arch in control
+            .binary_paragraphs()
+            .filter_map(|p| p.get_field_str("Architecture"))
+            .flat_map(|s| s.split_whitespace())
+        {
+            if !architectures.contains(&arch) {
+                architectures.push(arch);
+            }
+        }
+
+        let mut paragraph = ControlParagraph::default();
+        paragraph.add_field_from_string("Format".into(), format.into())?;
+        paragraph.add_field_from_string("Source".into(), source.into())?;
+        paragraph.add_field_from_string("Binary".into(), binaries.join(", ").into())?;
+        paragraph.add_field_from_string("Architecture".into(), architectures.join(" ").into())?;
+        paragraph.add_field_from_string("Version".into(), version.as_str().into())?;
+
+        for name in DSC_CONTROL_FIELDS {
+            if let Some(field) = general.get_field(name) {
+                paragraph.add_field(field.clone());
+            }
+        }
+
+        let mut dsc = DscFile::new(paragraph);
+        for file in &files {
+            dsc.add_file(DscFileEntry::from_data(&file.filename, &file.data));
+        }
+
+        let mut data = vec![];
+        dsc.write(&mut data)?;
+
+        files.push(SourcePackageFile {
+            filename: format!("{}_{}.dsc", source, file_version),
+            data,
+        });
+
+        Ok(files)
+    }
+
+    /// Build the source package and write its files to a directory.
+    ///
+    /// Returns the paths of written files. The `.dsc` file is last.
+    pub fn write_to_directory(
+        &self,
+        dest_dir: impl AsRef<Path>,
+    ) -> Result<Vec<PathBuf>, SourcePackageError> {
+        let dest_dir = dest_dir.as_ref();
+
+        if !dest_dir.exists() {
+            std::fs::create_dir_all(dest_dir)?;
+        }
+
+        self.build()?
+            .into_iter()
+            .map(|file| {
+                let path = dest_dir.join(&file.filename);
+                std::fs::write(&path, &file.data)?;
+
+                Ok(path)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, anyhow::Result, std::io::Read};
+
+    const CONTROL: &str = "Source: myapp\nMaintainer: Me <me@example.com>\nBuild-Depends: debhelper-compat (= 12),\n python3\nStandards-Version: 4.5.0\n\nPackage: myapp\nArchitecture: any\nDescription: my app\n\nPackage: myapp-doc\nArchitecture: all\nDescription: my app docs\n";
+
+    const CHANGELOG: &str = "myapp (1:0.1-2) unstable; urgency=low\n\n  * Initial release.\n\n -- Me <me@example.com>  Tue, 30 Dec 2014 21:26:40 -0700\n";
+
+    fn source_manifest(changelog: &str) -> Result<FileManifest> {
+        let mut m = FileManifest::default();
+        m.add_file_entry(
+            "debian/control",
+            FileEntry::new_from_data(CONTROL.as_bytes().to_vec(), false),
+        )?;
+        m.add_file_entry(
+            "debian/changelog",
+            FileEntry::new_from_data(changelog.as_bytes().to_vec(), false),
+        )?;
+        m.add_file_entry(
+            "debian/rules",
+            FileEntry::new_from_data(b"#!/usr/bin/make -f\n".to_vec(), true),
+        )?;
+        m.add_file_entry(
+            "src/main.py",
+            FileEntry::new_from_data(b"print('hello')\n".to_vec(), false),
+        )?;
+
+        Ok(m)
+    }
+
+    fn tar_paths(data: &[u8]) -> Result<Vec<String>> {
+        let mut archive = tar::Archive::new(std::io::Cursor::new(data));
+
+        archive
+            .entries()?
+            .map(|entry| Ok(entry?.path()?.display().to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_split_version() {
+        assert_eq!(split_version("1.0"), (None, "1.0", None));
+        assert_eq!(split_version("1.0-1"), (None, "1.0", Some("1")));
+        assert_eq!(
+            split_version("2:1.0-rc1-3"),
+            (Some("2"), "1.0-rc1", Some("3"))
+        );
+    }
+
+    #[test]
+    fn test_build_quilt() -> Result<()> {
+        let builder = SourcePackageBuilder::new(source_manifest(CHANGELOG)?)
+            .set_compression(DebCompression::Uncompressed)
+            .set_mtime(Some(SystemTime::UNIX_EPOCH));
+
+        let files = builder.build()?;
+        assert_eq!(
+            files
+                .iter()
+                .map(|f| f.filename.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "myapp_0.1.orig.tar",
+                "myapp_0.1-2.debian.tar",
+                "myapp_0.1-2.dsc"
+            ]
+        );
+
+        assert_eq!(
+            tar_paths(&files[0].data)?,
+            vec!["myapp-0.1", "myapp-0.1/src", "myapp-0.1/src/main.py"]
+        );
+        assert_eq!(
+            tar_paths(&files[1].data)?,
+            vec![
+                "debian",
+                "debian/source",
+                "debian/changelog",
+                "debian/control",
+                "debian/rules",
+                "debian/source/format"
+            ]
+        );
+
+        let mut archive = tar::Archive::new(std::io::Cursor::new(&files[1].data));
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if entry.path()?.ends_with("source/format") {
+                let mut s = String::new();
+                entry.read_to_string(&mut s)?;
+                assert_eq!(s, "3.0 (quilt)\n");
+            }
+        }
+
+        let dsc = String::from_utf8(files[2].data.clone())?;
+        let orig = DscFileEntry::from_data(&files[0].filename, &files[0].data);
+
+        assert!(dsc.starts_with(
+            "Format: 3.0 (quilt)\nSource: myapp\nBinary: myapp, myapp-doc\nArchitecture: any all\nVersion: 1:0.1-2\nMaintainer: Me <me@example.com>\nStandards-Version: 4.5.0\nBuild-Depends: debhelper-compat (= 12),\n python3\nChecksums-Sha1:\n"
+        ));
+        assert!(dsc.contains(&format!(
+            "\nFiles:\n {} {} myapp_0.1.orig.tar\n",
+            orig.md5, orig.size
+        )));
+        assert!(dsc.contains(&format!(
+            "\n {} {} myapp_0.1.orig.tar\n",
+            orig.sha256, orig.size
+        )));
+
+        // Output is deterministic when the mtime is fixed.
+        let files2 = builder.build()?;
+        assert_eq!(files[2].data, files2[2].data);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_native() -> Result<()> {
+        let changelog = CHANGELOG.replace("1:0.1-2", "0.2");
+
+        let files = SourcePackageBuilder::new(source_manifest(&changelog)?).build()?;
+        assert_eq!(
+            files
+                .iter()
+                .map(|f| f.filename.as_str())
+                .collect::<Vec<_>>(),
+            vec!["myapp_0.2.tar.xz", "myapp_0.2.dsc"]
+        );
+
+        let dsc = String::from_utf8(files[1].data.clone())?;
+        assert!(dsc.starts_with("Format: 3.0 (native)\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_missing_control() -> Result<()> {
+        let mut m = source_manifest(CHANGELOG)?;
+        m.remove("debian/control");
+
+        assert!(SourcePackageBuilder::new(m).build().is_err());
+
+        Ok(())
+    }
+}
@@ -8,6 +8,7 @@ use std::{
     ffi::OsStr,
     io::Write,
     path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 #[cfg(unix)]
@@ -376,6 +377,7 @@ pub enum FileManifestError {
     IoError(std::io::Error),
     StripPrefix(std::path::StripPrefixError),
     LinkNotAllowed,
+    InvalidSourceDateEpoch(String),
 }
 
 impl std::fmt::Display for FileManifestError {
@@ -391,6 +393,10 @@ impl std::fmt::Display for FileManifestError {
             Self::IoError(inner) => inner.fmt(f),
             Self::StripPrefix(inner) => inner.fmt(f),
             Self::LinkNotAllowed => f.write_str("links are not allowed on this FileManifest"),
+            Self::InvalidSourceDateEpoch(value) => f.write_str(&format!(
+                "SOURCE_DATE_EPOCH is not an integer number of seconds: {}",
+                value
+            )),
         }
     }
 }
@@ -409,6 +415,83 @@ impl From<std::path::StripPrefixError> for FileManifestError {
     }
 }
 
+/// Name of the environment variable defining a reproducible build time.
+///
+/// See https://reproducible-builds.org/specs/source-date-epoch/.
+pub const SOURCE_DATE_EPOCH: &str = "SOURCE_DATE_EPOCH";
+
+/// Settings for writing archives reproducibly.
+///
+/// Archive writers given an instance normalize the metadata of the entries
+/// they emit so identical inputs produce byte identical archives:
+///
+/// * Modified times are set to [Self::mtime].
+/// * Entries are owned by `root` (UID and GID 0).
+/// * Files have mode `0o755` if executable or `0o644` otherwise.
+///   Directories have mode `0o755`.
+/// * Entries are emitted in sorted path order.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Reproducibility {
+    mtime: u64,
+}
+
+impl Reproducibility {
+    /// Construct an instance using a modified time in seconds since UNIX epoch.
+    pub fn new(mtime: u64) -> Self {
+        Self { mtime }
+    }
+
+    /// Construct an instance from the `SOURCE_DATE_EPOCH` environment variable.
+    ///
+    /// Returns `None` if the variable isn't set and errors if it isn't a
+    /// non-negative integer.
+    pub fn from_source_date_epoch() -> Result<Option<Self>, FileManifestError> {
+        match std::env::var(SOURCE_DATE_EPOCH) {
+            Ok(value) => Ok(Some(Self::new(value.trim().parse::<u64>().map_err(
+                |_| FileManifestError::InvalidSourceDateEpoch(value.clone()),
+            )?))),
+            Err(std::env::VarError::NotPresent) => Ok(None),
+            Err(std::env::VarError::NotUnicode(value)) => Err(
+                FileManifestError::InvalidSourceDateEpoch(value.to_string_lossy().to_string()),
+            ),
+        }
+    }
+
+    /// The modified time of archive entries, in seconds since UNIX epoch.
+    pub fn mtime(&self) -> u64 {
+        self.mtime
+    }
+
+    /// The modified time of archive entries as a [SystemTime].
+    pub fn system_time(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.mtime)
+    }
+
+    /// The UID and GID owning archive entries.
+    pub fn owner_id(&self) -> u32 {
+        0
+    }
+
+    /// The user and group name owning archive entries.
+    pub fn owner_name(&self) -> &'static str {
+        "root"
+    }
+
+    /// The permissions of a file entry.
+    pub fn file_mode(&self, executable: bool) -> u32 {
+        if executable {
+            0o755
+        } else {
+            0o644
+        }
+    }
+
+    /// The permissions of a directory entry.
+    pub fn directory_mode(&self) -> u32 {
+        0o755
+    }
+}
+
 /// Normalize a path or error on validation failure.
 ///
 /// This is called before inserting paths into a [FileManifest].
@@ -714,6 +797,26 @@ mod tests {
             .tempdir()
     }
 
+    #[test]
+    fn test_reproducibility_source_date_epoch() -> Result<(), FileManifestError> {
+        // This is the only test touching this environment variable.
+        std::env::set_var(SOURCE_DATE_EPOCH, "1600000000");
+        let r = Reproducibility::from_source_date_epoch()?;
+        assert_eq!(r, Some(Reproducibility::new(1600000000)));
+        assert_eq!(
+            r.unwrap().system_time(),
+            UNIX_EPOCH + Duration::from_secs(1600000000)
+        );
+
+        std::env::set_var(SOURCE_DATE_EPOCH, "yesterday");
+        assert!(Reproducibility::from_source_date_epoch().is_err());
+
+        std::env::remove_var(SOURCE_DATE_EPOCH);
+        assert_eq!(Reproducibility::from_source_date_epoch()?, None);
+
+        Ok(())
+    }
+
     #[test]
     fn test_add_file_entry() -> Result<(), FileManifestError> {
         let mut m = FileManifest::default();
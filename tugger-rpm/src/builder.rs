@@ -3,13 +3,96 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use {
+    crate::header::{
+        remove_payload_files, set_file_flags, RPMFILE_CONFIG, RPMFILE_DOC, RPMFILE_GHOST,
+    },
     anyhow::{anyhow, Context, Result},
-    std::path::{Path, PathBuf},
-    tugger_file_manifest::FileManifest,
+    std::{
+        collections::BTreeMap,
+        path::{Path, PathBuf},
+        time::SystemTime,
+    },
+    tugger_file_manifest::{FileManifest, Reproducibility},
 };
 
 #[cfg(target_family = "unix")]
-use rpm::{RPMFileOptions, RPMPackage};
+use rpm::{Dependency, RPMFileOptions, RPMPackage};
+
+/// Parse a dependency expression into a `Dependency`.
+///
+/// Expressions have the form `name` or `name <op> version`, where `<op>` is
+/// one of `<`, `<=`, `=`, `>=`, or `>`. e.g. `python3 >= 3.8`.
+pub fn parse_dependency(s: &str) -> Result<Dependency> {
+    let parts = s.split_whitespace().collect::<Vec<_>>();
+
+    match parts.as_slice() {
+        [name] => Ok(Dependency::any(*name)),
+        [name, op, version] => match *op {
+            "<" => Ok(Dependency::less(*name, *version)),
+            "<=" => Ok(Dependency::less_eq(*name, *version)),
+            "=" => Ok(Dependency::eq(*name, *version)),
+            ">=" => Ok(Dependency::greater_eq(*name, *version)),
+            ">" => Ok(Dependency::greater(*name, *version)),
+            _ => Err(anyhow!("unknown version comparison operator in {}", s)),
+        },
+        _ => Err(anyhow!(
+            "dependency must be of form `name` or `name <op> version`; got {}",
+            s
+        )),
+    }
+}
+
+/// Attributes of a file installed by an RPM.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RpmFileAttributes {
+    /// Permissions of the installed file.
+    ///
+    /// If not set, `0o755` is used for executable files and `0o644` otherwise.
+    pub mode: Option<u32>,
+    /// User owning the installed file.
+    pub user: String,
+    /// Group owning the installed file.
+    pub group: String,
+    /// Whether the file is a configuration file (`%config`).
+    pub config: bool,
+    /// Whether the file is documentation (`%doc`).
+    pub doc: bool,
+    /// Whether the file is a ghost (`%ghost`).
+    ///
+    /// Ghost files are owned by the package but their content isn't installed.
+    pub ghost: bool,
+}
+
+impl Default for RpmFileAttributes {
+    fn default() -> Self {
+        Self {
+            mode: None,
+            user: "root".to_string(),
+            group: "root".to_string(),
+            config: false,
+            doc: false,
+            ghost: false,
+        }
+    }
+}
+
+impl RpmFileAttributes {
+    fn flags(&self) -> i32 {
+        let mut flags = 0;
+
+        if self.config {
+            flags |= RPMFILE_CONFIG;
+        }
+        if self.doc {
+            flags |= RPMFILE_DOC;
+        }
+        if self.ghost {
+            flags |= RPMFILE_GHOST;
+        }
+
+        flags
+    }
+}
 
 /// Create RPMs.
 ///
@@ -21,6 +104,8 @@ pub struct RpmBuilder {
 
     build_path: PathBuf,
     files: FileManifest,
+    file_attributes: BTreeMap<PathBuf, RpmFileAttributes>,
+    mtime: Option<SystemTime>,
 }
 
 impl AsMut<rpm::RPMBuilder> for RpmBuilder {
@@ -45,27 +130,192 @@ impl RpmBuilder {
             inner,
             build_path: build_path.as_ref().to_path_buf(),
             files: FileManifest::default(),
+            file_attributes: BTreeMap::new(),
+            mtime: None,
         }
     }
 
+    /// Set the modified time of files in the RPM.
+    ///
+    /// If not set, the current time is used. Setting a fixed time makes
+    /// the output deterministic.
+    pub fn set_mtime(mut self, time: Option<SystemTime>) -> Self {
+        self.mtime = time;
+        self
+    }
+
+    /// Write the RPM reproducibly.
+    ///
+    /// Files have normalized ordering and, unless overridden via
+    /// `set_file_attributes()`, normalized ownership and permissions. So this
+    /// is equivalent to setting the modified time.
+    pub fn set_reproducibility(self, reproducibility: Reproducibility) -> Self {
+        self.set_mtime(Some(reproducibility.system_time()))
+    }
+
+    fn mtime(&self) -> Result<i64> {
+        Ok(self
+            .mtime
+            .unwrap_or_else(SystemTime::now)
+            .duration_since(std::time::UNIX_EPOCH)
+            .context("resolving modified time")?
+            .as_secs() as i64)
+    }
+
+    /// Set installation attributes for a registered file.
+    ///
+    /// `path` is the relative path of the file, as registered via
+    /// `add_file_manifest()`.
+    pub fn set_file_attributes<P: AsRef<Path>>(
+        mut self,
+        path: P,
+        attributes: RpmFileAttributes,
+    ) -> Self {
+        self.file_attributes
+            .insert(path.as_ref().to_path_buf(), attributes);
+        self
+    }
+
+    /// Register files to be installed by this package.
+    ///
+    /// Paths are relative to the filesystem root. e.g. `usr/bin/myapp`.
+    pub fn add_file_manifest(mut self, manifest: &FileManifest) -> Result<Self> {
+        self.files
+            .add_manifest(manifest)
+            .context("adding files to RPM")?;
+
+        Ok(self)
+    }
+
+    /// Set the release number of the package.
+    pub fn release(mut self, release: u16) -> Self {
+        self.inner = self.inner.release(release);
+        self
+    }
+
+    /// Set the epoch of the package.
+    pub fn epoch(mut self, epoch: i32) -> Self {
+        self.inner = self.inner.epoch(epoch);
+        self
+    }
+
+    /// Declare a capability this package requires.
+    pub fn requires(mut self, dependency: Dependency) -> Self {
+        self.inner = self.inner.requires(dependency);
+        self
+    }
+
+    /// Declare a capability this package provides.
+    pub fn provides(mut self, dependency: Dependency) -> Self {
+        self.inner = self.inner.provides(dependency);
+        self
+    }
+
+    /// Declare a capability this package conflicts with.
+    pub fn conflicts(mut self, dependency: Dependency) -> Self {
+        self.inner = self.inner.conflicts(dependency);
+        self
+    }
+
+    /// Declare a capability this package obsoletes.
+    pub fn obsoletes(mut self, dependency: Dependency) -> Self {
+        self.inner = self.inner.obsoletes(dependency);
+        self
+    }
+
+    /// Set the script to run before the package is installed.
+    pub fn pre_install_script(mut self, content: impl Into<String>) -> Self {
+        self.inner = self.inner.pre_install_script(content);
+        self
+    }
+
+    /// Set the script to run after the package is installed.
+    pub fn post_install_script(mut self, content: impl Into<String>) -> Self {
+        self.inner = self.inner.post_install_script(content);
+        self
+    }
+
+    /// Set the script to run before the package is uninstalled.
+    pub fn pre_uninstall_script(mut self, content: impl Into<String>) -> Self {
+        self.inner = self.inner.pre_uninstall_script(content);
+        self
+    }
+
+    /// Set the script to run after the package is uninstalled.
+    pub fn post_uninstall_script(mut self, content: impl Into<String>) -> Self {
+        self.inner = self.inner.post_uninstall_script(content);
+        self
+    }
+
+    /// Add an entry to the package's changelog.
+    ///
+    /// `time` is in seconds since UNIX epoch.
+    pub fn add_changelog_entry(
+        mut self,
+        author: impl Into<String>,
+        entry: impl Into<String>,
+        time: i32,
+    ) -> Self {
+        self.inner = self.inner.add_changelog_entry(author, entry, time);
+        self
+    }
+
+    /// Resolve the install path of a registered file.
+    fn install_path(rel_path: &Path) -> String {
+        // RPM requires absolute install paths.
+        format!("/{}", rel_path.display())
+    }
+
     /// Populate registered files with the internal RPMBuilder.
     pub fn populate_files(mut self) -> Result<Self> {
+        if let Some(path) = self
+            .file_attributes
+            .keys()
+            .find(|path| self.files.get(path).is_none())
+        {
+            return Err(anyhow!(
+                "attributes defined for unknown file: {}",
+                path.display()
+            ));
+        }
+
         self.files
             .materialize_files(&self.build_path)
             .context("writing RPM data files")?;
 
+        let mtime = filetime::FileTime::from_unix_time(self.mtime()?, 0);
+
         for (rel_path, content) in self.files.iter_entries() {
             let real_path = self.build_path.join(rel_path);
 
-            let mut options = RPMFileOptions::new(rel_path.display().to_string());
+            let attributes = self
+                .file_attributes
+                .get(rel_path)
+                .cloned()
+                .unwrap_or_default();
 
-            if content.is_executable() {
-                options = options.mode(0o100_775);
+            // Ghost files are owned by the package but have no content.
+            if attributes.ghost {
+                std::fs::write(&real_path, b"")
+                    .with_context(|| format!("truncating {}", real_path.display()))?;
             }
 
-            // TODO support additional attributes, such as owner/group.
-            // TODO make deterministic by modifying upstream to allow control
-            // over what add_data() does.
+            // The upstream builder derives the modified time from the filesystem.
+            filetime::set_file_mtime(&real_path, mtime)
+                .with_context(|| format!("setting mtime of {}", real_path.display()))?;
+
+            let mode = attributes.mode.unwrap_or_else(|| {
+                if content.is_executable() {
+                    0o755
+                } else {
+                    0o644
+                }
+            });
+
+            let options = RPMFileOptions::new(Self::install_path(rel_path))
+                .mode((0o100_000 | mode) as i32)
+                .user(attributes.user.as_str())
+                .group(attributes.group.as_str());
 
             self.inner = self
                 .inner
@@ -77,8 +327,40 @@ impl RpmBuilder {
         Ok(self)
     }
 
+    /// Resolve the flags of registered files, in payload order.
+    fn file_flags(&self) -> Vec<i32> {
+        // The payload is ordered by install path.
+        let flags = self
+            .files
+            .iter_entries()
+            .map(|(rel_path, _)| {
+                let flags = self
+                    .file_attributes
+                    .get(rel_path)
+                    .map(|a| a.flags())
+                    .unwrap_or(0);
+
+                (Self::install_path(rel_path), flags)
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        flags.values().copied().collect()
+    }
+
+    /// Resolve the payload entry names of ghost files.
+    fn ghost_payload_names(&self) -> Vec<String> {
+        self.file_attributes
+            .iter()
+            .filter(|(_, attributes)| attributes.ghost)
+            .map(|(rel_path, _)| format!(".{}", Self::install_path(rel_path)))
+            .collect()
+    }
+
     /// Build the RPM, consuming self.
     pub fn build(mut self) -> Result<RPMPackage> {
+        let file_flags = self.file_flags();
+        let ghost_names = self.ghost_payload_names();
+
         self = self
             .populate_files()
             .context("populating files with builder")?;
@@ -89,7 +371,29 @@ impl RpmBuilder {
             .map_err(|e| anyhow!("error building RPM: {}", e))
             .context("building RPM")?;
 
-        Ok(package)
+        if file_flags.iter().all(|flags| *flags == 0) {
+            return Ok(package);
+        }
+
+        // The upstream builder can't express all file flags. So patch them
+        // into the serialized package.
+        let mut data = vec![];
+        package
+            .write(&mut data)
+            .map_err(|e| anyhow!("error writing RPM: {}", e))?;
+
+        set_file_flags(&mut data, &file_flags).context("setting RPM file flags")?;
+
+        // Ghost files are registered with the upstream builder so they are
+        // described by the header. But they must not be installed.
+        if !ghost_names.is_empty() {
+            remove_payload_files(&mut data, &ghost_names)
+                .context("removing ghost files from RPM payload")?;
+        }
+
+        RPMPackage::parse(&mut std::io::Cursor::new(data))
+            .map_err(|e| anyhow!("error parsing RPM: {}", e))
+            .context("parsing RPM")
     }
 
     /// Build the RPM, writing it to a filesystem path, consuming self.
@@ -112,3 +416,201 @@ impl RpmBuilder {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::header::read_file_flags,
+        cpio_archive::{CpioReader, NewcReader},
+        std::time::{Duration, UNIX_EPOCH},
+        tugger_file_manifest::FileEntry,
+    };
+
+    fn build_rpm_data(build_path: &Path) -> Result<Vec<u8>> {
+        let mut manifest = FileManifest::default();
+        manifest.add_file_entry(
+            "usr/bin/myapp",
+            FileEntry::new_from_data(b"#!/bin/sh\n".to_vec(), true),
+        )?;
+        manifest.add_file_entry(
+            "etc/myapp.conf",
+            FileEntry::new_from_data(b"key = value\n".to_vec(), false),
+        )?;
+        manifest.add_file_entry(
+            "usr/share/doc/myapp/README",
+            FileEntry::new_from_data(b"read me\n".to_vec(), false),
+        )?;
+        manifest.add_file_entry("var/log/myapp.log", FileEntry::new_from_data(vec![], false))?;
+
+        let package = RpmBuilder::new(
+            build_path,
+            "myapp",
+            "0.1",
+            "MPL-2.0",
+            "x86_64",
+            "My application",
+        )
+        .set_mtime(Some(UNIX_EPOCH + Duration::from_secs(1_600_000_000)))
+        .add_file_manifest(&manifest)?
+        .set_file_attributes(
+            "etc/myapp.conf",
+            RpmFileAttributes {
+                mode: Some(0o600),
+                user: "myapp".to_string(),
+                group: "myapp".to_string(),
+                config: true,
+                ..Default::default()
+            },
+        )
+        .set_file_attributes(
+            "usr/share/doc/myapp/README",
+            RpmFileAttributes {
+                doc: true,
+                ..Default::default()
+            },
+        )
+        .set_file_attributes(
+            "var/log/myapp.log",
+            RpmFileAttributes {
+                ghost: true,
+                ..Default::default()
+            },
+        )
+        .build()?;
+
+        let mut data = vec![];
+        package
+            .write(&mut data)
+            .map_err(|e| anyhow!("error writing RPM: {}", e))?;
+
+        Ok(data)
+    }
+
+    #[test]
+    fn test_parse_dependency() -> Result<()> {
+        parse_dependency("python3")?;
+        parse_dependency("python3 >= 3.8")?;
+        parse_dependency("glibc < 2.30")?;
+
+        assert!(parse_dependency("python3 ~ 3.8").is_err());
+        assert!(parse_dependency("python3 >=").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_build() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("tugger-rpm-test")
+            .tempdir()?;
+
+        let mut manifest = FileManifest::default();
+        manifest.add_file_entry(
+            "usr/bin/myapp",
+            FileEntry::new_from_data(b"#!/bin/sh\n".to_vec(), true),
+        )?;
+
+        let package = RpmBuilder::new(
+            temp_dir.path().join("files"),
+            "myapp",
+            "0.1",
+            "MPL-2.0",
+            "x86_64",
+            "My application",
+        )
+        .add_file_manifest(&manifest)?
+        .requires(parse_dependency("python3 >= 3.8")?)
+        .post_install_script("echo installed")
+        .add_changelog_entry("Me <me@example.com>", "- Initial release", 1_600_000_000)
+        .build()?;
+
+        let mut data = vec![];
+        package
+            .write(&mut data)
+            .map_err(|e| anyhow!("error writing RPM: {}", e))?;
+        assert!(data.starts_with(b"\xed\xab\xee\xdb"));
+
+        // Rewriting the existing flags must reproduce the digests computed upstream.
+        let mut patched = data.clone();
+        set_file_flags(&mut patched, &read_file_flags(&data)?)?;
+        assert_eq!(patched, data);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_attributes() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("tugger-rpm-test")
+            .tempdir()?;
+
+        let data = build_rpm_data(temp_dir.path())?;
+
+        // Files are ordered by install path.
+        assert_eq!(
+            read_file_flags(&data)?,
+            vec![RPMFILE_CONFIG, 0, RPMFILE_DOC, RPMFILE_GHOST]
+        );
+
+        let package = RPMPackage::parse(&mut std::io::Cursor::new(&data))
+            .map_err(|e| anyhow!("error parsing RPM: {}", e))?;
+        assert_eq!(package.metadata.header.get_name().unwrap(), "myapp");
+
+        // Ghost files are absent from the payload.
+        let names = NewcReader::new(std::io::Cursor::new(package.content))
+            .map(|header| Ok(header?.name().to_string()))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            names,
+            vec![
+                "./etc/myapp.conf",
+                "./usr/bin/myapp",
+                "./usr/share/doc/myapp/README"
+            ]
+        );
+
+        // The signature header must describe the modified payload.
+        let mut patched = data.clone();
+        set_file_flags(&mut patched, &read_file_flags(&data)?)?;
+        assert_eq!(patched, data);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_file_attributes() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("tugger-rpm-test")
+            .tempdir()?;
+
+        let res = RpmBuilder::new(
+            temp_dir.path(),
+            "myapp",
+            "0.1",
+            "MPL-2.0",
+            "x86_64",
+            "My application",
+        )
+        .set_file_attributes("etc/missing.conf", RpmFileAttributes::default())
+        .build();
+        assert!(res.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_deterministic() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("tugger-rpm-test")
+            .tempdir()?;
+
+        let a = build_rpm_data(&temp_dir.path().join("a"))?;
+        std::thread::sleep(Duration::from_millis(1100));
+        let b = build_rpm_data(&temp_dir.path().join("b"))?;
+
+        assert_eq!(a, b);
+
+        Ok(())
+    }
+}
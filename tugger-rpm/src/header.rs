@@ -0,0 +1,238 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Low-level manipulation of serialized RPM headers.
+
+The `rpm` crate doesn't expose all header fields we need control over.
+Functionality in this module operates on serialized RPM files to fill
+in the gaps.
+*/
+
+use {
+    anyhow::{anyhow, Context, Result},
+    cpio_archive::NewcHeader,
+    md5::{Digest, Md5},
+    std::io::Read,
+};
+
+/// Size of the lead at the beginning of every RPM file.
+const LEAD_SIZE: usize = 96;
+
+/// Magic bytes at the beginning of a header structure.
+const HEADER_MAGIC: [u8; 4] = [0x8e, 0xad, 0xe8, 0x01];
+
+/// Size of the fixed header preamble and of each index entry.
+const HEADER_INTRO_SIZE: usize = 16;
+const INDEX_ENTRY_SIZE: usize = 16;
+
+const RPMSIGTAG_SHA1: u32 = 269;
+const RPMSIGTAG_SIZE: u32 = 1000;
+const RPMSIGTAG_MD5: u32 = 1004;
+const RPMTAG_FILEFLAGS: u32 = 1037;
+
+const TYPE_INT32: u32 = 4;
+const TYPE_STRING: u32 = 6;
+const TYPE_BIN: u32 = 7;
+
+/// File is a configuration file (`%config`).
+pub const RPMFILE_CONFIG: i32 = 1;
+/// File is documentation (`%doc`).
+pub const RPMFILE_DOC: i32 = 1 << 1;
+/// File is not part of the payload (`%ghost`).
+pub const RPMFILE_GHOST: i32 = 1 << 6;
+
+/// Name of the cpio entry terminating the payload.
+const CPIO_TRAILER: &str = "TRAILER!!!";
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    let bytes = data
+        .get(offset..offset + 4)
+        .ok_or_else(|| anyhow!("RPM data truncated at offset {}", offset))?;
+
+    Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Describes the location of a header structure within RPM data.
+struct HeaderLocation {
+    /// Offset of the header magic.
+    start: usize,
+    /// Number of index entries.
+    entries: usize,
+    /// Offset of the data store.
+    store: usize,
+    /// Offset just past the end of the data store.
+    end: usize,
+}
+
+impl HeaderLocation {
+    fn parse(data: &[u8], start: usize) -> Result<Self> {
+        if data.get(start..start + 4) != Some(&HEADER_MAGIC[..]) {
+            return Err(anyhow!("RPM header magic not found at offset {}", start));
+        }
+
+        let entries = read_u32(data, start + 8)? as usize;
+        let store_size = read_u32(data, start + 12)? as usize;
+        let store = start + HEADER_INTRO_SIZE + entries * INDEX_ENTRY_SIZE;
+        let end = store + store_size;
+
+        if end > data.len() {
+            return Err(anyhow!("RPM header at offset {} is truncated", start));
+        }
+
+        Ok(Self {
+            start,
+            entries,
+            store,
+            end,
+        })
+    }
+
+    /// Find an index entry, returning the absolute offset of its data and its count.
+    fn find_entry(&self, data: &[u8], tag: u32, data_type: u32) -> Result<(usize, usize)> {
+        for i in 0..self.entries {
+            let entry = self.start + HEADER_INTRO_SIZE + i * INDEX_ENTRY_SIZE;
+
+            if read_u32(data, entry)? != tag {
+                continue;
+            }
+
+            if read_u32(data, entry + 4)? != data_type {
+                return Err(anyhow!("RPM header tag {} has unexpected type", tag));
+            }
+
+            let offset = self.store + read_u32(data, entry + 8)? as usize;
+            let count = read_u32(data, entry + 12)? as usize;
+
+            return Ok((offset, count));
+        }
+
+        Err(anyhow!("RPM header tag {} not found", tag))
+    }
+}
+
+/// Locate the signature and main headers in RPM data.
+fn locate_headers(data: &[u8]) -> Result<(HeaderLocation, HeaderLocation)> {
+    let signature = HeaderLocation::parse(data, LEAD_SIZE)?;
+
+    // The signature header is padded to an 8 byte boundary.
+    let padding = (8 - (signature.end - signature.store) % 8) % 8;
+    let header = HeaderLocation::parse(data, signature.end + padding)?;
+
+    Ok((signature, header))
+}
+
+/// Resolve the flags of files in serialized RPM data.
+///
+/// Flags are in the order of files in the payload.
+pub fn read_file_flags(data: &[u8]) -> Result<Vec<i32>> {
+    let (_, header) = locate_headers(data)?;
+    let (offset, count) = header.find_entry(data, RPMTAG_FILEFLAGS, TYPE_INT32)?;
+
+    (0..count)
+        .map(|i| Ok(read_u32(data, offset + i * 4)? as i32))
+        .collect::<Result<Vec<_>>>()
+}
+
+/// Replace the flags of files in serialized RPM data.
+///
+/// `flags` must have an entry for every file in the payload, in payload order.
+///
+/// Digests in the signature header are updated to reflect the modified header.
+pub fn set_file_flags(data: &mut [u8], flags: &[i32]) -> Result<()> {
+    let (signature, header) = locate_headers(data)?;
+    let (offset, count) = header.find_entry(data, RPMTAG_FILEFLAGS, TYPE_INT32)?;
+
+    if count != flags.len() {
+        return Err(anyhow!(
+            "RPM has {} files but {} file flags were provided",
+            count,
+            flags.len()
+        ));
+    }
+
+    for (i, flag) in flags.iter().enumerate() {
+        let start = offset + i * 4;
+        data[start..start + 4].copy_from_slice(&flag.to_be_bytes());
+    }
+
+    update_signature(data, &signature, &header)
+}
+
+/// Remove files from the payload of serialized RPM data.
+///
+/// `names` are the names of cpio entries to remove, e.g. `./var/log/app.log`.
+/// Only uncompressed payloads are supported.
+///
+/// The size and digests in the signature header are updated to reflect the
+/// modified payload.
+pub fn remove_payload_files(data: &mut Vec<u8>, names: &[String]) -> Result<()> {
+    let (signature, header) = locate_headers(data)?;
+
+    let archive = &data[header.end..];
+    let mut reader = std::io::Cursor::new(archive);
+    let mut payload = Vec::with_capacity(archive.len());
+
+    loop {
+        let start = reader.position() as usize;
+
+        let mut magic = [0u8; 6];
+        reader
+            .read_exact(&mut magic)
+            .context("reading cpio header magic")?;
+        if &magic[..] != cpio_archive::newc::MAGIC {
+            return Err(anyhow!(
+                "RPM payload is not an uncompressed newc cpio archive"
+            ));
+        }
+
+        let entry = NewcHeader::from_reader(&mut reader).context("reading cpio header")?;
+
+        // File data is padded to a 4 byte boundary.
+        let end = reader.position() as usize + entry.file_size as usize;
+        let end = end + (4 - end % 4) % 4;
+        if end > archive.len() {
+            return Err(anyhow!("cpio entry {} is truncated", entry.name));
+        }
+        reader.set_position(end as u64);
+
+        if entry.name == CPIO_TRAILER {
+            payload.extend_from_slice(&archive[start..]);
+            break;
+        } else if !names.contains(&entry.name) {
+            payload.extend_from_slice(&archive[start..end]);
+        }
+    }
+
+    data.truncate(header.end);
+    data.extend(payload);
+
+    update_signature(data, &signature, &header)
+}
+
+/// Update the signature header to reflect the main header and payload.
+fn update_signature(
+    data: &mut [u8],
+    signature: &HeaderLocation,
+    header: &HeaderLocation,
+) -> Result<()> {
+    let (offset, _) = signature.find_entry(data, RPMSIGTAG_SIZE, TYPE_INT32)?;
+    let size = (data.len() - header.start) as u32;
+    data[offset..offset + 4].copy_from_slice(&size.to_be_bytes());
+
+    let sha1 = sha1::Sha1::from(&data[header.start..header.end])
+        .digest()
+        .to_string();
+    let md5 = Md5::digest(&data[header.start..]);
+
+    let (offset, _) = signature.find_entry(data, RPMSIGTAG_SHA1, TYPE_STRING)?;
+    data[offset..offset + sha1.len()].copy_from_slice(sha1.as_bytes());
+
+    let (offset, count) = signature.find_entry(data, RPMSIGTAG_MD5, TYPE_BIN)?;
+    if count != md5.len() {
+        return Err(anyhow!("unexpected MD5 digest length in RPM signature"));
+    }
+    data[offset..offset + count].copy_from_slice(&md5);
+
+    Ok(())
+}
@@ -4,6 +4,11 @@
 
 #[cfg(target_family = "unix")]
 mod builder;
+#[cfg(target_family = "unix")]
+pub mod header;
 
 #[cfg(target_family = "unix")]
-pub use builder::RpmBuilder;
+pub use {
+    builder::{parse_dependency, RpmBuilder, RpmFileAttributes},
+    rpm::Dependency,
+};
@@ -12,7 +12,7 @@ use {
         io::{BufRead, BufReader},
         path::Path,
     },
-    tugger_file_manifest::{FileEntry, FileManifest},
+    tugger_file_manifest::{FileEntry, FileManifest, Reproducibility},
 };
 
 /// Represents an invocation of the `snapcraft` command.
@@ -41,6 +41,7 @@ pub struct SnapcraftBuilder<'a> {
     pub(crate) snap: Snapcraft<'a>,
     pub(crate) invocations: Vec<SnapcraftInvocation>,
     pub(crate) install_files: FileManifest,
+    pub(crate) reproducibility: Option<Reproducibility>,
 }
 
 impl<'a> SnapcraftBuilder<'a> {
@@ -50,6 +51,7 @@ impl<'a> SnapcraftBuilder<'a> {
             snap,
             invocations: vec![],
             install_files: FileManifest::default(),
+            reproducibility: None,
         }
     }
 
@@ -112,6 +114,14 @@ impl<'a> SnapcraftBuilder<'a> {
         Ok(self)
     }
 
+    /// Normalize metadata of files in snaps written by this builder so output is reproducible.
+    ///
+    /// This only applies to snaps built without `snapcraft`.
+    pub fn set_reproducibility(mut self, reproducibility: Reproducibility) -> Self {
+        self.reproducibility = Some(reproducibility);
+        self
+    }
+
     /// Invoke `snapcraft` with the given configuration.
     ///
     /// Registered files will be written to `build_path`.
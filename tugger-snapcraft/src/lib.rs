@@ -5,10 +5,12 @@
 /*! Functionality for the Snapcraft packaging format. */
 
 mod builder;
+mod prime;
 mod yaml;
 
 pub use {
     builder::{SnapcraftBuilder, SnapcraftInvocation},
+    prime::snap_yaml,
     yaml::{
         Adapter, Architecture, Architectures, BuildAttribute, Confinement, Daemon, Grade,
         RestartCondition, SnapApp, SnapPart, Snapcraft, SourceType, Type,
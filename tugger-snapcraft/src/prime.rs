@@ -0,0 +1,423 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Building snaps from a prime directory without invoking `snapcraft`.
+
+A snap is a squashfs filesystem image holding the contents of the
+*prime* directory, which `snapcraft` populates from the output of
+parts, plus a `meta/snap.yaml` describing the snap to `snapd`.
+
+When the files constituting the snap are already known (e.g. because
+they are produced by PyOxidizer), the parts lifecycle isn't needed and
+we can write `meta/snap.yaml` and the squashfs image ourselves. This
+doesn't require `snapcraft`, LXD, multipass, or network access.
+*/
+
+use {
+    crate::{builder::SnapcraftBuilder, yaml::Snapcraft},
+    anyhow::{anyhow, Context, Result},
+    serde_yaml::{Mapping, Value},
+    std::{
+        convert::TryFrom,
+        io::Write,
+        path::{Path, PathBuf},
+        time::SystemTime,
+    },
+    tugger_appimage::squashfs::SquashfsBuilder,
+    tugger_file_manifest::{FileEntry, FileManifest},
+};
+
+/// Keys in `snapcraft.yaml` that only influence the build and have no
+/// equivalent in `snap.yaml`.
+const BUILD_ONLY_KEYS: &[&str] = &["adopt-info", "icon", "parts"];
+
+/// Keys of `apps.<app-name>` entries in `snapcraft.yaml` that only influence
+/// the build and have no equivalent in `snap.yaml`.
+const APP_BUILD_ONLY_KEYS: &[&str] = &["adapter"];
+
+fn key(s: &str) -> Value {
+    Value::String(s.to_string())
+}
+
+/// Recursively sort mapping keys so serialization is deterministic.
+fn sort_mappings(value: Value) -> Value {
+    match value {
+        Value::Mapping(mapping) => {
+            let mut entries = mapping
+                .into_iter()
+                .map(|(k, v)| (k, sort_mappings(v)))
+                .collect::<Vec<_>>();
+            entries.sort_by(|(a, _), (b, _)| a.as_str().cmp(&b.as_str()));
+
+            Value::Mapping(entries.into_iter().collect())
+        }
+        Value::Sequence(values) => Value::Sequence(values.into_iter().map(sort_mappings).collect()),
+        value => value,
+    }
+}
+
+/// Merge `passthrough` entries of a mapping into the mapping itself.
+fn merge_passthrough(mapping: &mut Mapping) -> Result<()> {
+    if let Some(Value::Mapping(passthrough)) = mapping.remove(&key("passthrough")) {
+        for (k, v) in passthrough {
+            if mapping.contains_key(&k) {
+                return Err(anyhow!(
+                    "passthrough key {:?} conflicts with an existing key",
+                    k.as_str().unwrap_or_default()
+                ));
+            }
+
+            mapping.insert(k, v);
+        }
+    }
+
+    Ok(())
+}
+
+/// Derive the content of a `meta/snap.yaml` file from a `snapcraft.yaml` model.
+///
+/// Build-only fields like `parts` and `adopt-info` are ignored. `passthrough`
+/// entries are merged into their parent. Apps using `extensions` are rejected,
+/// as extensions can only be expanded by `snapcraft`.
+pub fn snap_yaml(snap: &Snapcraft) -> Result<String> {
+    let mut mapping = match serde_yaml::to_value(snap).context("serializing snap")? {
+        Value::Mapping(mapping) => mapping,
+        _ => return Err(anyhow!("snap did not serialize to a mapping")),
+    };
+
+    for k in BUILD_ONLY_KEYS {
+        mapping.remove(&key(k));
+    }
+
+    // snap.yaml lists the architectures the snap runs on.
+    if let Some(architectures) = &snap.architectures {
+        let run_on = if architectures.run_on.is_empty() {
+            &architectures.build_on
+        } else {
+            &architectures.run_on
+        };
+
+        if let Some(value) = mapping.get_mut(&key("architectures")) {
+            *value = serde_yaml::to_value(run_on)?;
+        }
+    }
+
+    if let Some(Value::Mapping(apps)) = mapping.get_mut(&key("apps")) {
+        for (name, app) in apps.iter_mut() {
+            let name = name.as_str().unwrap_or_default();
+
+            if let Value::Mapping(app) = app {
+                if app.contains_key(&key("extensions")) {
+                    return Err(anyhow!(
+                        "app {} uses extensions, which require snapcraft",
+                        name
+                    ));
+                }
+
+                for k in APP_BUILD_ONLY_KEYS {
+                    app.remove(&key(k));
+                }
+
+                merge_passthrough(app).with_context(|| format!("processing app {}", name))?;
+            }
+        }
+    }
+
+    merge_passthrough(&mut mapping)?;
+
+    let mapping = mapping
+        .into_iter()
+        .map(|(k, v)| (k, sort_mappings(v)))
+        .collect::<Mapping>();
+
+    serde_yaml::to_string(&mapping).context("serializing snap.yaml")
+}
+
+impl<'a> SnapcraftBuilder<'a> {
+    /// The filename of the `.snap` produced by [Self::write_snap].
+    ///
+    /// This follows the `<name>_<version>_<arch>.snap` convention of
+    /// `snapcraft`. `<arch>` is `all` if no architectures are defined and
+    /// `multi` if several are.
+    pub fn snap_file_name(&self) -> Result<String> {
+        let architectures = self
+            .snap
+            .architectures
+            .as_ref()
+            .map(|a| {
+                if a.run_on.is_empty() {
+                    a.build_on.clone()
+                } else {
+                    a.run_on.clone()
+                }
+            })
+            .unwrap_or_default();
+
+        let arch = match architectures.as_slice() {
+            [] => "all".to_string(),
+            [arch] => match serde_yaml::to_value(arch)? {
+                Value::String(s) => s,
+                _ => return Err(anyhow!("architecture did not serialize to a string")),
+            },
+            _ => "multi".to_string(),
+        };
+
+        Ok(format!(
+            "{}_{}_{}.snap",
+            self.snap.name, self.snap.version, arch
+        ))
+    }
+
+    /// Resolve the files constituting the prime directory.
+    ///
+    /// This is the registered install files plus a generated `meta/snap.yaml`.
+    /// If the snap defines an `icon`, it must refer to an install file and is
+    /// copied to `meta/gui/`.
+    pub fn prime_manifest(&self) -> Result<FileManifest> {
+        let mut manifest = FileManifest::new_with_links();
+        manifest.add_manifest(&self.install_files)?;
+
+        if manifest.has_path("meta/snap.yaml") {
+            return Err(anyhow!(
+                "meta/snap.yaml is generated and cannot be an install file"
+            ));
+        }
+
+        for (name, app) in &self.snap.apps {
+            if let Some(program) = app
+                .command
+                .as_ref()
+                .and_then(|command| command.split_whitespace().next())
+            {
+                let program = program.strip_prefix("$SNAP/").unwrap_or(program);
+
+                if Path::new(program).is_relative() && !manifest.has_path(program) {
+                    return Err(anyhow!(
+                        "command {} of app {} is not in the install files",
+                        program,
+                        name
+                    ));
+                }
+            }
+        }
+
+        if let Some(icon) = &self.snap.icon {
+            let icon = Path::new(icon.as_ref());
+
+            let entry = manifest
+                .get(icon)
+                .ok_or_else(|| anyhow!("icon {} is not in the install files", icon.display()))?
+                .clone();
+            let extension = icon
+                .extension()
+                .ok_or_else(|| anyhow!("icon {} has no file extension", icon.display()))?;
+
+            let mut dest = PathBuf::from("meta/gui/icon");
+            dest.set_extension(extension);
+
+            manifest.add_file_entry(dest, entry)?;
+        }
+
+        manifest.add_file_entry(
+            "meta/snap.yaml",
+            FileEntry::new_from_data(snap_yaml(&self.snap)?.into_bytes(), false),
+        )?;
+
+        Ok(manifest)
+    }
+
+    /// Write a `.snap` to a writer without invoking `snapcraft`.
+    ///
+    /// The squashfs image holding the prime directory is produced in-process.
+    /// `invocations` are ignored.
+    pub fn write_snap(&self, writer: &mut impl Write) -> Result<()> {
+        let mtime = if let Some(reproducibility) = &self.reproducibility {
+            reproducibility.mtime()
+        } else {
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .context("resolving current time")?
+                .as_secs()
+        };
+
+        let mut builder = SquashfsBuilder::default();
+        builder.set_mtime(u32::try_from(mtime).context("modified time out of range")?);
+        builder.add_file_manifest(&self.prime_manifest()?)?;
+        builder.write(writer)?;
+
+        Ok(())
+    }
+
+    /// Write a `.snap` into a directory without invoking `snapcraft`.
+    ///
+    /// Returns the path of the written file, which is named per
+    /// [Self::snap_file_name].
+    pub fn write_snap_to_directory(&self, dest_dir: impl AsRef<Path>) -> Result<PathBuf> {
+        let dest_dir = dest_dir.as_ref();
+        let dest_path = dest_dir.join(self.snap_file_name()?);
+
+        std::fs::create_dir_all(dest_dir)
+            .with_context(|| format!("creating {}", dest_dir.display()))?;
+
+        let mut fh = std::fs::File::create(&dest_path)
+            .with_context(|| format!("opening {} for writing", dest_path.display()))?;
+        self.write_snap(&mut fh)
+            .with_context(|| format!("writing {}", dest_path.display()))?;
+
+        Ok(dest_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::yaml::{Architecture, Architectures, Confinement, SnapApp, SnapPart},
+        tugger_file_manifest::Reproducibility,
+    };
+
+    fn snap() -> Snapcraft<'static> {
+        let mut snap = Snapcraft::new(
+            "myapp".into(),
+            "0.1".into(),
+            "summary".into(),
+            "description".into(),
+        );
+        snap.base = Some("core20".into());
+        snap.confinement = Some(Confinement::Strict);
+        snap.add_part(
+            "myapp".into(),
+            SnapPart {
+                plugin: Some("nil".into()),
+                ..SnapPart::default()
+            },
+        );
+        snap.add_app(
+            "myapp".into(),
+            SnapApp {
+                command: Some("bin/myapp --flag".into()),
+                plugs: vec!["network".into(), "home".into()],
+                environment: [("B".into(), "b".into()), ("A".into(), "a".into())]
+                    .iter()
+                    .cloned()
+                    .collect(),
+                passthrough: [("install-mode".into(), "disable".into())]
+                    .iter()
+                    .cloned()
+                    .collect(),
+                ..SnapApp::default()
+            },
+        );
+
+        snap
+    }
+
+    fn builder() -> Result<SnapcraftBuilder<'static>> {
+        let mut manifest = FileManifest::default();
+        manifest.add_file_entry(
+            "bin/myapp",
+            FileEntry::new_from_data(b"#!/bin/sh\necho hello\n".to_vec(), true),
+        )?;
+
+        SnapcraftBuilder::new(snap()).install_manifest(&manifest)
+    }
+
+    #[test]
+    fn test_snap_yaml() -> Result<()> {
+        let mut snap = snap();
+        snap.architectures = Some(Architectures {
+            build_on: vec![Architecture::Amd64],
+            run_on: vec![],
+        });
+
+        assert_eq!(
+            snap_yaml(&snap)?,
+            "---\n\
+            name: myapp\n\
+            base: core20\n\
+            version: \"0.1\"\n\
+            summary: summary\n\
+            description: description\n\
+            confinement: strict\n\
+            architectures:\n  - amd64\n\
+            apps:\n  \
+              myapp:\n    \
+                command: bin/myapp --flag\n    \
+                environment:\n      A: a\n      B: b\n    \
+                install-mode: disable\n    \
+                plugs:\n      - network\n      - home\n"
+        );
+
+        snap.apps.get_mut("myapp").unwrap().extensions = vec!["gnome-3-38".into()];
+        assert!(snap_yaml(&snap).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_prime_manifest() -> Result<()> {
+        let builder = builder()?;
+
+        assert_eq!(builder.snap_file_name()?, "myapp_0.1_all.snap");
+
+        let manifest = builder.prime_manifest()?;
+        assert_eq!(
+            manifest
+                .iter_entries()
+                .map(|(path, _)| path.display().to_string())
+                .collect::<Vec<_>>(),
+            vec!["bin/myapp", "meta/snap.yaml"]
+        );
+
+        let mut snap = snap();
+        snap.icon = Some("icon.png".into());
+        assert!(SnapcraftBuilder::new(snap.clone())
+            .install_manifest(builder.install_files())?
+            .prime_manifest()
+            .is_err());
+
+        let mut icon = FileManifest::default();
+        icon.add_file_entry("icon.png", FileEntry::new_from_data(vec![42], false))?;
+        let manifest = SnapcraftBuilder::new(snap)
+            .install_manifest(builder.install_files())?
+            .install_manifest(&icon)?
+            .prime_manifest()?;
+        assert_eq!(
+            manifest
+                .get("meta/gui/icon.png")
+                .unwrap()
+                .resolve_content()?,
+            vec![42]
+        );
+
+        // The command of an app must be present.
+        assert!(SnapcraftBuilder::new(self::snap())
+            .prime_manifest()
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_snap() -> Result<()> {
+        let builder = builder()?.set_reproducibility(Reproducibility::new(1_600_000_000));
+
+        let mut data = vec![];
+        builder.write_snap(&mut data)?;
+        assert_eq!(&data[0..4], b"hsqs");
+
+        let mut again = vec![];
+        builder.write_snap(&mut again)?;
+        assert_eq!(data, again);
+
+        let temp_dir = tempfile::Builder::new()
+            .prefix("tugger-snapcraft-test")
+            .tempdir()?;
+        let dest_path = builder.write_snap_to_directory(temp_dir.path())?;
+        assert_eq!(dest_path, temp_dir.path().join("myapp_0.1_all.snap"));
+        assert_eq!(std::fs::read(&dest_path)?, data);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,549 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Authenticode signing of PE binaries.
+
+This module implements Authenticode signing without calling out to
+`signtool.exe`, so PE files (`.exe`, `.dll`, etc) can be signed from any
+platform.
+
+An Authenticode signature is a CMS `SignedData` structure stored in the
+certificate table of a PE file. The signed content is a
+`SpcIndirectDataContent` holding a digest of the PE file (the
+*authentihash*). The authentihash covers the whole file except the
+`CheckSum` field of the optional header, the certificate table entry of
+the data directories, and the certificate table itself.
+
+See the *Windows Authenticode Portable Executable Signature Format*
+specification for more.
+*/
+
+use {
+    anyhow::{anyhow, Context, Result},
+    bcder::{
+        encode::{self, PrimitiveContent, Values},
+        BitString, Captured, ConstOid, Mode, OctetString, Oid, Tag,
+    },
+    bytes::Bytes,
+    cryptographic_message_syntax::{
+        asn1::rfc3161::OID_MS_RFC3161_COUNTER_SIGNATURE, SignedDataBuilder, SignerBuilder,
+    },
+    reqwest::{IntoUrl, Url},
+    std::convert::TryFrom,
+    x509_certificate::{
+        rfc5652::AttributeValue, CapturedX509Certificate, DigestAlgorithm, InMemorySigningKeyPair,
+    },
+};
+
+/// `SPC_INDIRECT_DATA_OBJID`, the content type of Authenticode signatures.
+///
+/// 1.3.6.1.4.1.311.2.1.4
+pub const OID_SPC_INDIRECT_DATA: ConstOid = Oid(&[43, 6, 1, 4, 1, 130, 55, 2, 1, 4]);
+
+/// `SPC_STATEMENT_TYPE_OBJID`.
+///
+/// 1.3.6.1.4.1.311.2.1.11
+pub const OID_SPC_STATEMENT_TYPE: ConstOid = Oid(&[43, 6, 1, 4, 1, 130, 55, 2, 1, 11]);
+
+/// `SPC_SP_OPUS_INFO_OBJID`.
+///
+/// 1.3.6.1.4.1.311.2.1.12
+pub const OID_SPC_SP_OPUS_INFO: ConstOid = Oid(&[43, 6, 1, 4, 1, 130, 55, 2, 1, 12]);
+
+/// `SPC_PE_IMAGE_DATAOBJ`.
+///
+/// 1.3.6.1.4.1.311.2.1.15
+pub const OID_SPC_PE_IMAGE_DATA: ConstOid = Oid(&[43, 6, 1, 4, 1, 130, 55, 2, 1, 15]);
+
+/// `SPC_INDIVIDUAL_SP_KEY_PURPOSE_OBJID`.
+///
+/// 1.3.6.1.4.1.311.2.1.21
+pub const OID_SPC_INDIVIDUAL_SP_KEY_PURPOSE: ConstOid = Oid(&[43, 6, 1, 4, 1, 130, 55, 2, 1, 21]);
+
+/// The value of `SpcPeImageData.file` written by `signtool.exe`.
+const OBSOLETE_FILE_LINK: &str = "<<<Obsolete>>>";
+
+const PE32_MAGIC: u16 = 0x10b;
+const PE32_PLUS_MAGIC: u16 = 0x20b;
+
+/// Index of the certificate table in the optional header data directories.
+const CERTIFICATE_TABLE_INDEX: usize = 4;
+
+const WIN_CERT_REVISION_2_0: u16 = 0x0200;
+pub(crate) const WIN_CERT_TYPE_PKCS_SIGNED_DATA: u16 = 0x0002;
+
+/// The certificate table and its entries are aligned to this size.
+pub(crate) const CERTIFICATE_ALIGNMENT: usize = 8;
+
+pub(crate) fn read_u16(data: &[u8], offset: usize) -> Result<u16> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| anyhow!("PE data truncated at offset {}", offset))
+}
+
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| anyhow!("PE data truncated at offset {}", offset))
+}
+
+fn pad_to_alignment(data: &mut Vec<u8>) {
+    let padding =
+        (CERTIFICATE_ALIGNMENT - data.len() % CERTIFICATE_ALIGNMENT) % CERTIFICATE_ALIGNMENT;
+    data.resize(data.len() + padding, 0);
+}
+
+/// Locations of the parts of a PE file relevant to Authenticode.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PeLayout {
+    /// File offset of the `CheckSum` field in the optional header.
+    pub checksum_offset: usize,
+
+    /// File offset of the certificate table entry in the data directories.
+    pub certificate_directory_offset: usize,
+
+    /// File offset and size of the certificate table, if present.
+    pub certificate_table: Option<(usize, usize)>,
+}
+
+impl PeLayout {
+    /// Resolve the layout of PE data.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.get(0..2) != Some(b"MZ") {
+            return Err(anyhow!("data does not have a DOS header"));
+        }
+
+        let pe_offset = read_u32(data, 0x3c)? as usize;
+        if data.get(pe_offset..pe_offset + 4) != Some(b"PE\0\0") {
+            return Err(anyhow!("data does not have a PE signature"));
+        }
+
+        let coff_offset = pe_offset + 4;
+        let optional_header_size = read_u16(data, coff_offset + 16)? as usize;
+        let optional_offset = coff_offset + 20;
+
+        let (rva_count_offset, directories_offset) = match read_u16(data, optional_offset)? {
+            PE32_MAGIC => (optional_offset + 92, optional_offset + 96),
+            PE32_PLUS_MAGIC => (optional_offset + 108, optional_offset + 112),
+            magic => return Err(anyhow!("unknown optional header magic {:#x}", magic)),
+        };
+
+        if (read_u32(data, rva_count_offset)? as usize) <= CERTIFICATE_TABLE_INDEX {
+            return Err(anyhow!("PE data has no certificate table directory entry"));
+        }
+
+        let certificate_directory_offset = directories_offset + CERTIFICATE_TABLE_INDEX * 8;
+        if certificate_directory_offset + 8 > optional_offset + optional_header_size {
+            return Err(anyhow!(
+                "certificate table directory entry outside optional header"
+            ));
+        }
+
+        let table_offset = read_u32(data, certificate_directory_offset)? as usize;
+        let table_size = read_u32(data, certificate_directory_offset + 4)? as usize;
+
+        let certificate_table = if table_size == 0 {
+            None
+        } else if table_offset < optional_offset + optional_header_size {
+            return Err(anyhow!("certificate table overlaps PE headers"));
+        } else if table_offset + table_size > data.len() {
+            return Err(anyhow!("certificate table extends past end of data"));
+        } else {
+            Some((table_offset, table_size))
+        };
+
+        Ok(Self {
+            checksum_offset: optional_offset + 64,
+            certificate_directory_offset,
+            certificate_table,
+        })
+    }
+}
+
+/// Compute the Authenticode digest (the *authentihash*) of PE data.
+///
+/// The certificate table, if present, must be at the end of the data.
+pub fn authenticode_digest(data: &[u8], algorithm: DigestAlgorithm) -> Result<Vec<u8>> {
+    let layout = PeLayout::parse(data)?;
+
+    let end = match layout.certificate_table {
+        Some((offset, size)) if offset + size == data.len() => offset,
+        Some(_) => {
+            return Err(anyhow!(
+                "certificate table is not at the end of the PE data"
+            ))
+        }
+        None => data.len(),
+    };
+
+    let mut hasher = algorithm.digester();
+    hasher.update(&data[0..layout.checksum_offset]);
+    hasher.update(&data[layout.checksum_offset + 4..layout.certificate_directory_offset]);
+    hasher.update(&data[layout.certificate_directory_offset + 8..end]);
+
+    Ok(hasher.finish().as_ref().to_vec())
+}
+
+/// Compute the value of the optional header `CheckSum` field of PE data.
+///
+/// This is the algorithm used by `CheckSumMappedFile()`. The existing
+/// value of the field is ignored.
+pub fn pe_checksum(data: &[u8], checksum_offset: usize) -> u32 {
+    let checksum_range = checksum_offset..checksum_offset + 4;
+    let byte = |offset: usize| -> u64 {
+        if checksum_range.contains(&offset) {
+            0
+        } else {
+            data.get(offset).copied().unwrap_or(0) as u64
+        }
+    };
+
+    let mut sum = 0u64;
+    for offset in (0..data.len()).step_by(2) {
+        sum += byte(offset) | (byte(offset + 1) << 8);
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    sum = (sum & 0xffff) + (sum >> 16);
+
+    (sum as u32).wrapping_add(data.len() as u32)
+}
+
+/// Obtain the content octets of the `SpcIndirectDataContent` describing a PE image digest.
+///
+/// ```ASN.1
+/// SpcIndirectDataContent ::= SEQUENCE {
+///   data SpcAttributeTypeAndOptionalValue,
+///   messageDigest DigestInfo }
+///
+/// SpcAttributeTypeAndOptionalValue ::= SEQUENCE {
+///   type ObjectID,
+///   value [0] EXPLICIT ANY OPTIONAL }
+///
+/// SpcPeImageData ::= SEQUENCE {
+///   flags SpcPeImageFlags DEFAULT { includeResources },
+///   file SpcLink }
+///
+/// DigestInfo ::= SEQUENCE {
+///   digestAlgorithm AlgorithmIdentifier,
+///   digest OCTETSTRING }
+/// ```
+fn spc_indirect_data_content(algorithm: DigestAlgorithm, digest: &[u8]) -> Result<Vec<u8>> {
+    let file_link = OBSOLETE_FILE_LINK
+        .encode_utf16()
+        .flat_map(|c| c.to_be_bytes().to_vec())
+        .collect::<Vec<_>>();
+
+    let values = (
+        encode::sequence((
+            Oid(Bytes::copy_from_slice(OID_SPC_PE_IMAGE_DATA.as_ref())).encode(),
+            encode::sequence((
+                BitString::new(0, Bytes::new()).encode(),
+                // SpcLink.file [2] containing SpcString.unicode [0].
+                encode::sequence_as(
+                    Tag::CTX_0,
+                    encode::sequence_as(
+                        Tag::CTX_2,
+                        OctetString::encode_slice_as(file_link, Tag::CTX_0),
+                    ),
+                ),
+            )),
+        )),
+        encode::sequence((
+            encode::sequence((Oid::from(algorithm).encode(), ().encode())),
+            OctetString::new(Bytes::copy_from_slice(digest)).encode(),
+        )),
+    );
+
+    let mut content = Vec::new();
+    values.write_encoded(Mode::Der, &mut content)?;
+
+    Ok(content)
+}
+
+/// Signs PE binaries with Authenticode.
+pub struct AuthenticodeSigner<'a> {
+    /// The key pair used for signing.
+    signing_key: &'a InMemorySigningKeyPair,
+
+    /// The X.509 certificate of the signing key.
+    signing_certificate: CapturedX509Certificate,
+
+    /// Certificates constituting the chain of the signing certificate.
+    certificate_chain: Vec<CapturedX509Certificate>,
+
+    /// URL of a Time-Stamp Protocol server to use.
+    time_stamp_url: Option<Url>,
+}
+
+impl<'a> AuthenticodeSigner<'a> {
+    /// Construct a new instance from a signing key and its certificate.
+    pub fn new(
+        signing_key: &'a InMemorySigningKeyPair,
+        signing_certificate: CapturedX509Certificate,
+    ) -> Self {
+        Self {
+            signing_key,
+            signing_certificate,
+            certificate_chain: vec![],
+            time_stamp_url: None,
+        }
+    }
+
+    /// Add a certificate to the certificate chain embedded in signatures.
+    pub fn chain_certificate(&mut self, certificate: CapturedX509Certificate) {
+        self.certificate_chain.push(certificate);
+    }
+
+    /// Set the URL of an RFC 3161 Time-Stamp Protocol server to time-stamp signatures with.
+    pub fn time_stamp_url(&mut self, url: impl IntoUrl) -> Result<()> {
+        self.time_stamp_url = Some(url.into_url()?);
+        Ok(())
+    }
+
+    /// Produce the DER encoded CMS `SignedData` for an authentihash.
+    pub fn create_signature(&self, digest: &[u8]) -> Result<Vec<u8>> {
+        let algorithm = DigestAlgorithm::Sha256;
+        let content = spc_indirect_data_content(algorithm, digest)?;
+
+        let mut signer = SignerBuilder::new(self.signing_key, self.signing_certificate.clone())
+            .content_type(Oid(Bytes::copy_from_slice(OID_SPC_INDIRECT_DATA.as_ref())))
+            .signed_attribute(
+                Oid(Bytes::copy_from_slice(OID_SPC_SP_OPUS_INFO.as_ref())),
+                vec![AttributeValue::new(Captured::from_values(
+                    Mode::Der,
+                    encode::sequence(encode::Nothing),
+                ))],
+            )
+            .signed_attribute(
+                Oid(Bytes::copy_from_slice(OID_SPC_STATEMENT_TYPE.as_ref())),
+                vec![AttributeValue::new(Captured::from_values(
+                    Mode::Der,
+                    encode::sequence(
+                        Oid(Bytes::copy_from_slice(
+                            OID_SPC_INDIVIDUAL_SP_KEY_PURPOSE.as_ref(),
+                        ))
+                        .encode(),
+                    ),
+                ))],
+            );
+
+        if let Some(url) = &self.time_stamp_url {
+            signer = signer
+                .time_stamp_url(url.clone())?
+                .time_stamp_attribute(Oid(Bytes::copy_from_slice(
+                    OID_MS_RFC3161_COUNTER_SIGNATURE.as_ref(),
+                )));
+        }
+
+        Ok(SignedDataBuilder::default()
+            .content_type(Oid(Bytes::copy_from_slice(OID_SPC_INDIRECT_DATA.as_ref())))
+            .signed_content_sequence(content)
+            .certificates(self.certificate_chain.iter().cloned())
+            .signer(signer)
+            .build_der()?)
+    }
+
+    /// Sign PE data, returning the signed PE data.
+    ///
+    /// An existing signature is replaced.
+    pub fn sign(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let layout = PeLayout::parse(data)?;
+
+        let mut pe = match layout.certificate_table {
+            Some((offset, size)) if offset + size == data.len() => data[0..offset].to_vec(),
+            Some(_) => {
+                return Err(anyhow!(
+                    "certificate table is not at the end of the PE data"
+                ))
+            }
+            None => data.to_vec(),
+        };
+
+        pe[layout.certificate_directory_offset..layout.certificate_directory_offset + 8]
+            .copy_from_slice(&[0; 8]);
+
+        // The certificate table must be aligned. The padding is covered by the digest.
+        pad_to_alignment(&mut pe);
+
+        let digest = authenticode_digest(&pe, DigestAlgorithm::Sha256)?;
+        let signature = self
+            .create_signature(&digest)
+            .context("creating Authenticode signature")?;
+
+        let table_offset = pe.len();
+
+        // WIN_CERTIFICATE structure.
+        let mut certificate = Vec::with_capacity(signature.len() + 16);
+        certificate.extend_from_slice(&[0; 4]);
+        certificate.extend_from_slice(&WIN_CERT_REVISION_2_0.to_le_bytes());
+        certificate.extend_from_slice(&WIN_CERT_TYPE_PKCS_SIGNED_DATA.to_le_bytes());
+        certificate.extend_from_slice(&signature);
+        pad_to_alignment(&mut certificate);
+
+        let table_size =
+            u32::try_from(certificate.len()).context("Authenticode signature too large")?;
+        certificate[0..4].copy_from_slice(&table_size.to_le_bytes());
+        pe.extend_from_slice(&certificate);
+
+        let table_offset = u32::try_from(table_offset).context("PE data too large")?;
+        let directory = layout.certificate_directory_offset;
+        pe[directory..directory + 4].copy_from_slice(&table_offset.to_le_bytes());
+        pe[directory + 4..directory + 8].copy_from_slice(&table_size.to_le_bytes());
+
+        let checksum = pe_checksum(&pe, layout.checksum_offset);
+        pe[layout.checksum_offset..layout.checksum_offset + 4]
+            .copy_from_slice(&checksum.to_le_bytes());
+
+        Ok(pe)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        cryptographic_message_syntax::SignedData,
+        x509_certificate::testutil::{rsa_cert, rsa_private_key},
+    };
+
+    /// Produce a minimal PE32+ image with a single section.
+    pub(crate) fn pe_image() -> Vec<u8> {
+        let mut data = vec![0u8; 0x400];
+
+        data[0..2].copy_from_slice(b"MZ");
+        data[0x3c..0x40].copy_from_slice(&0x40u32.to_le_bytes());
+        data[0x40..0x44].copy_from_slice(b"PE\0\0");
+
+        // COFF header: x86-64, 1 section, 240 byte optional header.
+        data[0x44..0x46].copy_from_slice(&0x8664u16.to_le_bytes());
+        data[0x46..0x48].copy_from_slice(&1u16.to_le_bytes());
+        data[0x54..0x56].copy_from_slice(&240u16.to_le_bytes());
+        data[0x56..0x58].copy_from_slice(&0x22u16.to_le_bytes());
+
+        // Optional header: PE32+ with 16 data directories and 0x200 bytes of headers.
+        data[0x58..0x5a].copy_from_slice(&PE32_PLUS_MAGIC.to_le_bytes());
+        data[0x58 + 60..0x58 + 64].copy_from_slice(&0x200u32.to_le_bytes());
+        data[0x58 + 108..0x58 + 112].copy_from_slice(&16u32.to_le_bytes());
+
+        // Section header following the optional header.
+        let section = 0x58 + 240;
+        data[section..section + 5].copy_from_slice(b".text");
+        data[section + 16..section + 20].copy_from_slice(&0x200u32.to_le_bytes());
+        data[section + 20..section + 24].copy_from_slice(&0x200u32.to_le_bytes());
+
+        for b in data[0x200..].iter_mut() {
+            *b = 0xc3;
+        }
+
+        data
+    }
+
+    #[test]
+    fn layout() -> Result<()> {
+        let data = pe_image();
+        let layout = PeLayout::parse(&data)?;
+
+        assert_eq!(layout.checksum_offset, 0x58 + 64);
+        assert_eq!(layout.certificate_directory_offset, 0x58 + 112 + 32);
+        assert_eq!(layout.certificate_table, None);
+
+        assert!(PeLayout::parse(b"not a PE file").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn certificate_table_overlapping_headers() -> Result<()> {
+        let key = rsa_private_key();
+        let cert = rsa_cert();
+
+        let mut data = pe_image();
+        let directory = PeLayout::parse(&data)?.certificate_directory_offset;
+        let size = data.len() as u32 - 0x10;
+        data[directory..directory + 4].copy_from_slice(&0x10u32.to_le_bytes());
+        data[directory + 4..directory + 8].copy_from_slice(&size.to_le_bytes());
+
+        assert!(PeLayout::parse(&data).is_err());
+        assert!(authenticode_digest(&data, DigestAlgorithm::Sha256).is_err());
+        assert!(AuthenticodeSigner::new(&key, cert).sign(&data).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn checksum() {
+        // A single word, plus the length.
+        assert_eq!(pe_checksum(&[1, 2], 100), 0x0201 + 2);
+        // Carries are folded back in.
+        assert_eq!(pe_checksum(&[0xff, 0xff, 0x02, 0x00], 100), 0x0002 + 4);
+        // The checksum field is ignored.
+        assert_eq!(pe_checksum(&[1, 2, 0xff, 0xff, 0xff, 0xff], 2), 0x0201 + 6);
+    }
+
+    #[test]
+    fn sign() -> Result<()> {
+        let key = rsa_private_key();
+        let cert = rsa_cert();
+
+        let data = pe_image();
+        let digest = authenticode_digest(&data, DigestAlgorithm::Sha256)?;
+
+        let signer = AuthenticodeSigner::new(&key, cert);
+        let signed = signer.sign(&data)?;
+
+        let layout = PeLayout::parse(&signed)?;
+        assert_eq!(
+            &signed[0..layout.checksum_offset],
+            &data[0..layout.checksum_offset]
+        );
+        assert_eq!(signed.len() % CERTIFICATE_ALIGNMENT, 0);
+
+        let (offset, size) = layout.certificate_table.unwrap();
+        assert_eq!(offset, data.len());
+        assert_eq!(offset + size, signed.len());
+        assert_eq!(
+            read_u32(&signed, layout.checksum_offset)?,
+            pe_checksum(&signed, layout.checksum_offset)
+        );
+
+        // The digest of the signed file matches the unsigned one.
+        assert_eq!(
+            authenticode_digest(&signed, DigestAlgorithm::Sha256)?,
+            digest
+        );
+
+        assert_eq!(read_u32(&signed, offset)? as usize, size);
+        assert_eq!(read_u16(&signed, offset + 4)?, WIN_CERT_REVISION_2_0);
+        assert_eq!(
+            read_u16(&signed, offset + 6)?,
+            WIN_CERT_TYPE_PKCS_SIGNED_DATA
+        );
+
+        let signed_data = SignedData::parse_ber(&signed[offset + 8..offset + size])?;
+        let content = signed_data.signed_content().unwrap();
+        assert_eq!(
+            content,
+            spc_indirect_data_content(DigestAlgorithm::Sha256, &digest)?.as_slice()
+        );
+
+        for signer in signed_data.signers() {
+            signer.verify_signature_with_signed_data(&signed_data)?;
+            signer.verify_message_digest_with_signed_data(&signed_data)?;
+            assert_eq!(
+                signer.signed_attributes().unwrap().content_type(),
+                &OID_SPC_INDIRECT_DATA
+            );
+        }
+
+        // Signing again replaces the signature.
+        let resigned = signer.sign(&signed)?;
+        assert_eq!(&resigned[0..offset], &signed[0..offset]);
+        assert_eq!(
+            PeLayout::parse(&resigned)?.certificate_table.unwrap().0,
+            offset
+        );
+
+        Ok(())
+    }
+}
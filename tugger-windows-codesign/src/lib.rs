@@ -6,10 +6,14 @@
 
 use std::convert::TryFrom;
 
+mod authenticode;
+pub use authenticode::*;
 mod signing;
 pub use signing::*;
 mod signtool;
 pub use signtool::*;
+mod verify;
+pub use verify::*;
 
 /// Defines a specific Windows certificate system store.
 ///
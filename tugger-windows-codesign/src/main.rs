@@ -0,0 +1,130 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    anyhow::{anyhow, Result},
+    clap::{App, AppSettings, Arg, ArgMatches, SubCommand},
+    tugger_windows_codesign::{
+        time_stamp_tst_info, verify_pe_data, AuthenticodeSignature, PeLayout,
+    },
+    x509_certificate::CapturedX509Certificate,
+};
+
+/// Describe a certificate in a human readable manner.
+fn certificate_description(cert: &CapturedX509Certificate) -> String {
+    let issuer = cert
+        .issuer_name()
+        .iter_common_name()
+        .next()
+        .and_then(|cn| cn.to_string().ok());
+
+    format!(
+        "{} (issued by {})",
+        cert.subject_common_name()
+            .unwrap_or_else(|| "<unknown>".to_string()),
+        issuer.unwrap_or_else(|| "<unknown>".to_string())
+    )
+}
+
+fn print_signature(data: &[u8]) -> Result<()> {
+    let signature = if let Some(signature) = AuthenticodeSignature::from_pe_data(data)? {
+        signature
+    } else {
+        return Ok(());
+    };
+
+    println!(
+        "signed authentihash ({:?}): {}",
+        signature.digest_algorithm(),
+        hex::encode(signature.digest())
+    );
+
+    for (i, signer) in signature.signed_data().signers().enumerate() {
+        println!("signer #{}", i);
+        println!("  digest algorithm: {:?}", signer.digest_algorithm());
+        println!("  signature algorithm: {:?}", signer.signature_algorithm());
+
+        if let Some(time) = signer.signed_attributes().and_then(|x| x.signing_time()) {
+            println!("  signing time: {}", time);
+        }
+
+        println!("  certificate chain:");
+        let chain = signature.signer_chain(signer);
+        if chain.is_empty() {
+            println!("    <signing certificate not found>");
+        }
+        for cert in chain {
+            println!("    {}", certificate_description(cert));
+        }
+
+        match time_stamp_tst_info(signer) {
+            Ok(Some(tst_info)) => println!("  time-stamp: {}", *tst_info.gen_time),
+            Ok(None) => println!("  time-stamp: <none>"),
+            Err(e) => println!("  time-stamp: <error: {:#}>", e),
+        }
+    }
+
+    Ok(())
+}
+
+fn command_verify(args: &ArgMatches) -> Result<()> {
+    let path = args
+        .value_of("path")
+        .ok_or_else(|| anyhow!("path argument is required"))?;
+
+    let data = std::fs::read(path)?;
+
+    if PeLayout::parse(&data).is_ok() {
+        print_signature(&data)?;
+    }
+
+    let problems = verify_pe_data(&data);
+
+    for problem in &problems {
+        println!("{}", problem);
+    }
+
+    if problems.is_empty() {
+        eprintln!("no problems detected!");
+        eprintln!("(we do not validate certificates so please do not assume that Windows will trust the signature)");
+        Ok(())
+    } else {
+        Err(anyhow!("verification problems detected"))
+    }
+}
+
+fn main_impl() -> Result<()> {
+    let matches = App::new("Oxidized Windows Code Signing")
+        .setting(AppSettings::ArgRequiredElseHelp)
+        .version("0.1")
+        .author("Gregory Szorc <gregory.szorc@gmail.com>")
+        .about("Do things related to Authenticode signing of Windows binaries")
+        .subcommand(
+            SubCommand::with_name("verify")
+                .about("Print and verify the Authenticode signature of a PE file")
+                .arg(
+                    Arg::with_name("path")
+                        .required(true)
+                        .help("Path of PE file to examine"),
+                ),
+        )
+        .get_matches();
+
+    match matches.subcommand() {
+        ("verify", Some(args)) => command_verify(args),
+        _ => Err(anyhow!("unknown command")),
+    }
+}
+
+fn main() {
+    let exit_code = match main_impl() {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            1
+        }
+    };
+
+    std::process::exit(exit_code)
+}
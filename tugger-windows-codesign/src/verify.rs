@@ -0,0 +1,424 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Authenticode signature verification.
+
+This module implements functionality for inspecting and verifying the
+Authenticode signatures of PE files. It doesn't require Windows.
+
+# Verification Caveats
+
+**Verification performed by this code is not equivalent to what Windows
+does. Do not use successful verification from this code as validation that
+Windows will trust a signature.**
+
+We verify that the signed digest matches the content of the PE file, that
+signatures and time-stamp tokens are cryptographically valid, and that
+time-stamp tokens cover the signature they are attached to. We do not
+validate certificates: chains are not verified to lead to a trusted root,
+and validity periods, revocation status, and extended key usage are not
+checked.
+*/
+
+use {
+    crate::authenticode::{
+        authenticode_digest, read_u16, read_u32, PeLayout, CERTIFICATE_ALIGNMENT,
+        OID_SPC_INDIRECT_DATA, WIN_CERT_TYPE_PKCS_SIGNED_DATA,
+    },
+    anyhow::{anyhow, Context, Result},
+    bcder::{decode::Constructed, Mode, OctetString, Oid},
+    cryptographic_message_syntax::{asn1::rfc3161::TstInfo, CmsError, SignedData, SignerInfo},
+    std::convert::TryFrom,
+    x509_certificate::{
+        certificate::certificate_is_subset_of, rfc5280::AlgorithmIdentifier,
+        CapturedX509Certificate, DigestAlgorithm,
+    },
+};
+
+/// Resolve the length of the DER value at the start of data.
+///
+/// Certificate table entries are padded. This is used to find the end of
+/// the signature data so the padding isn't fed to the ASN.1 decoder.
+fn der_value_length(data: &[u8]) -> Option<usize> {
+    let first = *data.get(1)?;
+
+    if first & 0x80 == 0 {
+        Some(2 + first as usize)
+    } else {
+        let count = (first & 0x7f) as usize;
+        // Indefinite or absurd lengths. Let the decoder deal with the whole buffer.
+        if count == 0 || count > 4 {
+            return None;
+        }
+
+        let length = data
+            .get(2..2 + count)?
+            .iter()
+            .fold(0usize, |acc, b| (acc << 8) | *b as usize);
+
+        Some(2 + count + length)
+    }
+}
+
+/// An Authenticode signature extracted from a PE file.
+#[derive(Clone, Debug)]
+pub struct AuthenticodeSignature {
+    /// The CMS structure holding the signature.
+    signed_data: SignedData,
+
+    /// The digest algorithm of the signed authentihash.
+    digest_algorithm: DigestAlgorithm,
+
+    /// The signed authentihash.
+    digest: Vec<u8>,
+}
+
+impl AuthenticodeSignature {
+    /// Extract the Authenticode signature from PE data.
+    ///
+    /// Returns `Ok(None)` if the data doesn't have a signature.
+    pub fn from_pe_data(data: &[u8]) -> Result<Option<Self>> {
+        let layout = PeLayout::parse(data)?;
+
+        let table = match layout.certificate_table {
+            Some((offset, size)) => &data[offset..offset + size],
+            None => return Ok(None),
+        };
+
+        // The table is a sequence of 8 byte aligned WIN_CERTIFICATE structures.
+        let mut offset = 0;
+        while offset + 8 <= table.len() {
+            let length = read_u32(table, offset)? as usize;
+            let certificate_type = read_u16(table, offset + 6)?;
+
+            if length < 8 || offset + length > table.len() {
+                return Err(anyhow!(
+                    "malformed certificate table entry at offset {}",
+                    offset
+                ));
+            }
+
+            if certificate_type == WIN_CERT_TYPE_PKCS_SIGNED_DATA {
+                return Ok(Some(Self::from_ber(&table[offset + 8..offset + length])?));
+            }
+
+            offset += length
+                + (CERTIFICATE_ALIGNMENT - length % CERTIFICATE_ALIGNMENT) % CERTIFICATE_ALIGNMENT;
+        }
+
+        Ok(None)
+    }
+
+    /// Construct an instance from BER encoded CMS `SignedData`.
+    ///
+    /// Trailing data after the `SignedData` is ignored.
+    pub fn from_ber(data: &[u8]) -> Result<Self> {
+        let data = match der_value_length(data) {
+            Some(length) if length <= data.len() => &data[0..length],
+            _ => data,
+        };
+
+        let signed_data = SignedData::parse_ber(data).context("parsing CMS SignedData")?;
+
+        let content = signed_data
+            .signed_content()
+            .ok_or_else(|| anyhow!("signature does not have encapsulated content"))?;
+
+        // The content octets of SpcIndirectDataContent: the SpcAttributeTypeAndOptionalValue
+        // describing the signed file, followed by the DigestInfo holding its digest.
+        let (algorithm, digest) = Constructed::decode(content, Mode::Ber, |cons| {
+            cons.take_sequence(|cons| cons.skip_all())?;
+            cons.take_sequence(|cons| {
+                let algorithm = AlgorithmIdentifier::take_from(cons)?;
+                let digest = OctetString::take_from(cons)?;
+
+                Ok((algorithm, digest.to_bytes().to_vec()))
+            })
+        })
+        .map_err(|e| anyhow!("parsing SpcIndirectDataContent: {}", e))?;
+
+        Ok(Self {
+            signed_data,
+            digest_algorithm: DigestAlgorithm::try_from(&algorithm)?,
+            digest,
+        })
+    }
+
+    /// The CMS `SignedData` holding the signature.
+    pub fn signed_data(&self) -> &SignedData {
+        &self.signed_data
+    }
+
+    /// The digest algorithm of the signed authentihash.
+    pub fn digest_algorithm(&self) -> DigestAlgorithm {
+        self.digest_algorithm
+    }
+
+    /// The signed authentihash.
+    pub fn digest(&self) -> &[u8] {
+        &self.digest
+    }
+
+    /// Resolve the certificate of a signer.
+    pub fn signing_certificate(&self, signer: &SignerInfo) -> Option<&CapturedX509Certificate> {
+        let (issuer, serial_number) = signer.certificate_issuer_and_serial()?;
+
+        self.signed_data.certificates().find(|cert| {
+            certificate_is_subset_of(
+                serial_number,
+                issuer,
+                cert.serial_number_asn1(),
+                cert.issuer_name(),
+            )
+        })
+    }
+
+    /// Resolve the certificate chain of a signer from the embedded certificates.
+    ///
+    /// The signing certificate is first, followed by the certificates that issued it.
+    pub fn signer_chain(&self, signer: &SignerInfo) -> Vec<&CapturedX509Certificate> {
+        if let Some(cert) = self.signing_certificate(signer) {
+            let mut chain = vec![cert];
+            chain.extend(cert.resolve_signing_chain(self.signed_data.certificates()));
+            chain
+        } else {
+            vec![]
+        }
+    }
+}
+
+/// Obtain the `TSTInfo` of the time-stamp token of a signer.
+///
+/// Returns `Ok(None)` if the signer doesn't have a time-stamp token.
+pub fn time_stamp_tst_info(signer: &SignerInfo) -> Result<Option<TstInfo>> {
+    let signed_data = match signer.time_stamp_token_signed_data()? {
+        Some(signed_data) => signed_data,
+        None => return Ok(None),
+    };
+
+    let content = signed_data
+        .signed_content()
+        .ok_or_else(|| anyhow!("time-stamp token does not have encapsulated content"))?;
+
+    Ok(Some(
+        Constructed::decode(content, Mode::Ber, TstInfo::take_from)
+            .map_err(|e| anyhow!("parsing TSTInfo: {}", e))?,
+    ))
+}
+
+/// Describes a problem with verification.
+#[derive(Debug)]
+pub enum VerificationProblem {
+    /// The PE data or its signature could not be parsed.
+    Malformed(anyhow::Error),
+
+    /// The PE data does not have an Authenticode signature.
+    NoSignature,
+
+    /// The authentihash was computed with an insecure digest algorithm.
+    InsecureDigestAlgorithm(DigestAlgorithm),
+
+    /// The signed authentihash doesn't match the PE data.
+    ///
+    /// Holds the signed and actual digests.
+    DigestMismatch(Vec<u8>, Vec<u8>),
+
+    /// A signer signed content other than `SpcIndirectDataContent`.
+    UnexpectedContentType(Oid),
+
+    /// A signer's signature failed verification.
+    Signature(CmsError),
+
+    /// A time-stamp token failed verification.
+    TimeStamp(anyhow::Error),
+
+    /// A time-stamp token is for a different signature.
+    TimeStampImprintMismatch,
+}
+
+impl std::fmt::Display for VerificationProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Malformed(e) => f.write_fmt(format_args!("malformed data: {:#}", e)),
+            Self::NoSignature => f.write_str("no Authenticode signature present"),
+            Self::InsecureDigestAlgorithm(alg) => {
+                f.write_fmt(format_args!("insecure digest algorithm used: {:?}", alg))
+            }
+            Self::DigestMismatch(signed, actual) => f.write_fmt(format_args!(
+                "authentihash mismatch; signed digest {}, actual {}",
+                hex::encode(signed),
+                hex::encode(actual)
+            )),
+            Self::UnexpectedContentType(oid) => f.write_fmt(format_args!(
+                "signed content type is {} instead of SpcIndirectDataContent",
+                oid
+            )),
+            Self::Signature(e) => f.write_fmt(format_args!("signature error: {}", e)),
+            Self::TimeStamp(e) => f.write_fmt(format_args!("time-stamp token error: {:#}", e)),
+            Self::TimeStampImprintMismatch => {
+                f.write_str("time-stamp token does not cover the signature")
+            }
+        }
+    }
+}
+
+/// Verify the time-stamp token of a signer, if present.
+fn verify_time_stamp(signer: &SignerInfo) -> Result<Option<VerificationProblem>> {
+    let tst_info = match time_stamp_tst_info(signer)? {
+        Some(tst_info) => tst_info,
+        None => return Ok(None),
+    };
+
+    signer.verify_time_stamp_token()?;
+
+    // The token must be over the signature value.
+    let algorithm = DigestAlgorithm::try_from(&tst_info.message_imprint.hash_algorithm)?;
+    let mut hasher = algorithm.digester();
+    hasher.update(signer.signature());
+
+    if hasher.finish().as_ref() == tst_info.message_imprint.hashed_message.to_bytes().as_ref() {
+        Ok(None)
+    } else {
+        Ok(Some(VerificationProblem::TimeStampImprintMismatch))
+    }
+}
+
+/// Verifies the Authenticode signature of PE data.
+///
+/// Returns a vector of problems detected. An empty vector means no
+/// problems were found.
+pub fn verify_pe_data(data: &[u8]) -> Vec<VerificationProblem> {
+    let signature = match AuthenticodeSignature::from_pe_data(data) {
+        Ok(Some(signature)) => signature,
+        Ok(None) => return vec![VerificationProblem::NoSignature],
+        Err(e) => return vec![VerificationProblem::Malformed(e)],
+    };
+
+    let mut problems = vec![];
+
+    if signature.digest_algorithm() == DigestAlgorithm::Sha1 {
+        problems.push(VerificationProblem::InsecureDigestAlgorithm(
+            signature.digest_algorithm(),
+        ));
+    }
+
+    match authenticode_digest(data, signature.digest_algorithm()) {
+        Ok(digest) => {
+            if digest != signature.digest() {
+                problems.push(VerificationProblem::DigestMismatch(
+                    signature.digest().to_vec(),
+                    digest,
+                ));
+            }
+        }
+        Err(e) => problems.push(VerificationProblem::Malformed(e)),
+    }
+
+    let signed_data = signature.signed_data();
+
+    for signer in signed_data.signers() {
+        if let Some(attributes) = signer.signed_attributes() {
+            if attributes.content_type() != &OID_SPC_INDIRECT_DATA {
+                problems.push(VerificationProblem::UnexpectedContentType(
+                    attributes.content_type().clone(),
+                ));
+            }
+        }
+
+        if let Err(e) = signer.verify_signature_with_signed_data(signed_data) {
+            problems.push(VerificationProblem::Signature(e));
+        }
+        if let Err(e) = signer.verify_message_digest_with_signed_data(signed_data) {
+            problems.push(VerificationProblem::Signature(e));
+        }
+
+        match verify_time_stamp(signer) {
+            Ok(Some(problem)) => problems.push(problem),
+            Ok(None) => {}
+            Err(e) => problems.push(VerificationProblem::TimeStamp(e)),
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{authenticode::tests::pe_image, AuthenticodeSigner},
+        x509_certificate::testutil::{rsa_cert, rsa_private_key},
+    };
+
+    #[test]
+    fn verify() -> Result<()> {
+        let key = rsa_private_key();
+        let cert = rsa_cert();
+
+        let data = pe_image();
+        assert!(matches!(
+            verify_pe_data(&data).as_slice(),
+            [VerificationProblem::NoSignature]
+        ));
+        assert!(matches!(
+            verify_pe_data(b"MZ").as_slice(),
+            [VerificationProblem::Malformed(_)]
+        ));
+
+        let signed = AuthenticodeSigner::new(&key, cert.clone()).sign(&data)?;
+        let problems = verify_pe_data(&signed);
+        assert!(problems.is_empty(), "{:?}", problems);
+
+        let signature = AuthenticodeSignature::from_pe_data(&signed)?.unwrap();
+        assert_eq!(signature.digest_algorithm(), DigestAlgorithm::Sha256);
+        assert_eq!(
+            signature.digest(),
+            authenticode_digest(&data, DigestAlgorithm::Sha256)?.as_slice()
+        );
+
+        let signers = signature.signed_data().signers().collect::<Vec<_>>();
+        assert_eq!(signers.len(), 1);
+        assert_eq!(signature.signer_chain(signers[0]), vec![&cert]);
+        assert!(time_stamp_tst_info(signers[0])?.is_none());
+
+        // Modifying the file invalidates the signature.
+        let mut modified = signed;
+        modified[0x300] ^= 0xff;
+        assert!(matches!(
+            verify_pe_data(&modified).as_slice(),
+            [VerificationProblem::DigestMismatch(_, _)]
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn verify_malformed_certificate_table() -> Result<()> {
+        let key = rsa_private_key();
+        let cert = rsa_cert();
+
+        let mut signed = AuthenticodeSigner::new(&key, cert).sign(&pe_image())?;
+
+        // Point the certificate table into the PE headers.
+        let directory = PeLayout::parse(&signed)?.certificate_directory_offset;
+        let size = signed.len() as u32 - 0x10;
+        signed[directory..directory + 4].copy_from_slice(&0x10u32.to_le_bytes());
+        signed[directory + 4..directory + 8].copy_from_slice(&size.to_le_bytes());
+
+        assert!(matches!(
+            verify_pe_data(&signed).as_slice(),
+            [VerificationProblem::Malformed(_)]
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn der_length() {
+        assert_eq!(der_value_length(&[0x30, 0x03, 2, 1, 42, 0, 0]), Some(5));
+        assert_eq!(der_value_length(&[0x30, 0x82, 0x01, 0x00]), Some(260));
+        assert_eq!(der_value_length(&[0x30, 0x80]), None);
+        assert_eq!(der_value_length(&[0x30]), None);
+    }
+}
@@ -19,5 +19,7 @@ Rust crate and utility. It just happens to be developed alongside
 PyOxidizer.
 */
 
+#[cfg(test)]
+mod reproducibility;
 pub mod starlark;
 pub mod tarball;
@@ -0,0 +1,241 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Verifies archive writers produce reproducible output.
+
+Each artifact is built twice from identical inputs, more than a second
+apart and using distinct staging directories, and the resulting bytes
+are compared.
+*/
+
+use {
+    crate::tarball::write_tarball_from_directory,
+    anyhow::{anyhow, Result},
+    python_packaging::wheel_builder::WheelBuilder,
+    std::{path::Path, time::Duration},
+    tugger_common::testutil::*,
+    tugger_debian::{
+        ControlFile, ControlParagraph, DebBuilder, DebCompression, SourcePackageBuilder,
+    },
+    tugger_file_manifest::{FileEntry, FileManifest, Reproducibility},
+};
+
+/// Build an artifact twice and verify the outputs are byte identical.
+///
+/// The build function receives a unique, not yet existing directory it can
+/// use for staging files.
+fn assert_reproducible(name: &str, build: impl Fn(&Path) -> Result<Vec<u8>>) -> Result<()> {
+    let temp_dir = tempfile::Builder::new()
+        .prefix("tugger-reproducibility-")
+        .tempdir()?;
+
+    let first = build(&temp_dir.path().join("first"))?;
+    // Ensure the current time differs between builds.
+    std::thread::sleep(Duration::from_millis(1100));
+    let second = build(&temp_dir.path().join("second"))?;
+
+    if first != second {
+        let offset = first
+            .iter()
+            .zip(second.iter())
+            .position(|(a, b)| a != b)
+            .unwrap_or_else(|| std::cmp::min(first.len(), second.len()));
+
+        return Err(anyhow!(
+            "{} is not reproducible: outputs of {} and {} bytes first differ at offset {}",
+            name,
+            first.len(),
+            second.len(),
+            offset
+        ));
+    }
+
+    Ok(())
+}
+
+fn reproducibility() -> Reproducibility {
+    Reproducibility::new(1_600_000_000)
+}
+
+fn sample_manifest() -> Result<FileManifest> {
+    let mut m = FileManifest::default();
+    m.add_file_entry(
+        "usr/bin/myapp",
+        FileEntry::new_from_data(b"#!/bin/sh\necho hello\n".to_vec(), true),
+    )?;
+    m.add_file_entry(
+        "usr/share/doc/myapp/README",
+        FileEntry::new_from_data(b"read me\n".to_vec(), false),
+    )?;
+
+    Ok(m)
+}
+
+#[test]
+fn tarball() -> Result<()> {
+    let logger = get_logger()?;
+
+    assert_reproducible("tarball", |staging| {
+        // Materialized files have differing modified times.
+        sample_manifest()?.materialize_files(staging)?;
+
+        let mut data = vec![];
+        write_tarball_from_directory(
+            &logger,
+            &mut data,
+            staging,
+            Some(Path::new("myapp")),
+            Some(reproducibility()),
+        )?;
+
+        Ok(data)
+    })
+}
+
+#[test]
+fn tarball_without_reproducibility() -> Result<()> {
+    let logger = get_logger()?;
+
+    assert_reproducible("tarball without reproducibility", |staging| {
+        sample_manifest()?.materialize_files(staging)?;
+
+        let mut data = vec![];
+        write_tarball_from_directory(&logger, &mut data, staging, Some(Path::new("myapp")), None)?;
+
+        Ok(data)
+    })
+}
+
+#[test]
+fn deb() -> Result<()> {
+    for extension in &[".gz", ".xz", ".zst"] {
+        assert_reproducible(&format!("deb{}", extension), |_| {
+            let compression = match *extension {
+                ".gz" => DebCompression::Gzip,
+                ".xz" => DebCompression::Xz(6),
+                _ => DebCompression::Zstandard(3),
+            };
+
+            let mut paragraph = ControlParagraph::default();
+            paragraph.add_field_from_string("Package".into(), "myapp".into())?;
+            paragraph.add_field_from_string("Version".into(), "0.1".into())?;
+            let mut control = ControlFile::default();
+            control.add_paragraph(paragraph);
+
+            let mut builder = DebBuilder::new(control)
+                .set_compression(compression)
+                .set_reproducibility(reproducibility());
+
+            for (path, entry) in sample_manifest()?.iter_entries() {
+                builder = builder.install_file(path, entry.clone())?;
+            }
+
+            let mut data = vec![];
+            builder.write(&mut data)?;
+
+            Ok(data)
+        })?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn debian_source_package() -> Result<()> {
+    assert_reproducible("Debian source package", |_| {
+        let mut m = sample_manifest()?;
+        m.add_file_entry(
+            "debian/control",
+            FileEntry::new_from_data(
+                b"Source: myapp\nMaintainer: Me <me@example.com>\n\nPackage: myapp\nArchitecture: any\n".to_vec(),
+                false,
+            ),
+        )?;
+        m.add_file_entry(
+            "debian/changelog",
+            FileEntry::new_from_data(
+                b"myapp (0.1-1) unstable; urgency=low\n\n  * Initial release.\n\n -- Me <me@example.com>  Tue, 30 Dec 2014 21:26:40 -0700\n".to_vec(),
+                false,
+            ),
+        )?;
+
+        let files = SourcePackageBuilder::new(m)
+            .set_compression(DebCompression::Gzip)
+            .set_reproducibility(reproducibility())
+            .build()?;
+
+        Ok(files.into_iter().flat_map(|f| f.data).collect())
+    })
+}
+
+#[cfg(target_family = "unix")]
+#[test]
+fn rpm() -> Result<()> {
+    assert_reproducible("RPM", |staging| {
+        let package = tugger_rpm::RpmBuilder::new(
+            staging,
+            "myapp",
+            "0.1",
+            "MPL-2.0",
+            "x86_64",
+            "My application",
+        )
+        .set_reproducibility(reproducibility())
+        .add_file_manifest(&sample_manifest()?)?
+        .build()?;
+
+        let mut data = vec![];
+        package
+            .write(&mut data)
+            .map_err(|e| anyhow!("error writing RPM: {}", e))?;
+
+        Ok(data)
+    })
+}
+
+#[test]
+fn cpio_odc() -> Result<()> {
+    assert_reproducible("cpio odc", |_| {
+        let mut builder = cpio_archive::OdcBuilder::new(vec![]);
+        builder.reproducibility(reproducibility());
+        builder.append_file_manifest(&sample_manifest()?)?;
+
+        Ok(builder.into_inner()?)
+    })
+}
+
+#[test]
+fn wheel() -> Result<()> {
+    assert_reproducible("wheel", |_| {
+        let mut builder = WheelBuilder::new("myapp", "0.1");
+        builder.set_reproducibility(reproducibility());
+        for (path, entry) in sample_manifest()?.iter_entries() {
+            builder.add_file(Path::new("myapp").join(path), entry.clone())?;
+        }
+
+        let mut cursor = std::io::Cursor::new(vec![]);
+        builder.write_wheel_data(&mut cursor)?;
+
+        Ok(cursor.into_inner())
+    })
+}
+
+#[test]
+fn harness_detects_differences() -> Result<()> {
+    // Sanity check that the harness detects differences.
+    let mtime = std::cell::Cell::new(0);
+
+    let res = assert_reproducible("cpio odc", |_| {
+        mtime.set(mtime.get() + 1);
+
+        let mut builder = cpio_archive::OdcBuilder::new(vec![]);
+        builder.reproducibility(Reproducibility::new(mtime.get()));
+        builder.append_file_manifest(&sample_manifest()?)?;
+
+        Ok(builder.into_inner()?)
+    });
+    assert!(res.is_err());
+
+    Ok(())
+}
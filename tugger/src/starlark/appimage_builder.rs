@@ -0,0 +1,407 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::starlark::{file_content::FileContentValue, file_manifest::FileManifestValue},
+    anyhow::{anyhow, Context, Result},
+    slog::warn,
+    starlark::{
+        environment::TypeValues,
+        values::{
+            error::{RuntimeError, UnsupportedOperation, ValueError},
+            none::NoneType,
+            {Mutable, TypedValue, Value, ValueResult},
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
+    },
+    starlark_dialect_build_targets::{
+        get_context_value, optional_str_arg, EnvironmentContext, ResolvedTarget,
+        ResolvedTargetValue, RunMode, TryToOptional,
+    },
+    std::{
+        convert::TryFrom,
+        path::{Path, PathBuf},
+    },
+    tugger_appimage::AppImageBuilder,
+    tugger_file_manifest::{FileEntry, FileManifest, Reproducibility},
+};
+
+fn error_context<F, T>(label: &str, f: F) -> Result<T, ValueError>
+where
+    F: FnOnce() -> anyhow::Result<T>,
+{
+    f().map_err(|e| {
+        ValueError::Runtime(RuntimeError {
+            code: "TUGGER_APPIMAGE",
+            message: format!("{:?}", e),
+            label: label.to_string(),
+        })
+    })
+}
+
+#[derive(Clone)]
+pub struct AppImageBuilderValue {
+    pub name: String,
+    /// Path of the program to run, relative to the AppDir root.
+    pub exec: String,
+    /// Path of the AppImage runtime executable, as given by the user.
+    pub runtime_path: String,
+    /// Files to install in the AppDir.
+    pub install_files: FileManifest,
+    /// Icon file name and content.
+    pub icon: Option<(String, FileEntry)>,
+    pub comment: Option<String>,
+    pub categories: Vec<String>,
+    pub terminal: bool,
+    /// Modified time of files, in seconds since UNIX epoch.
+    pub modified_time: Option<u64>,
+}
+
+impl TypedValue for AppImageBuilderValue {
+    type Holder = Mutable<AppImageBuilderValue>;
+    const TYPE: &'static str = "AppImageBuilder";
+
+    fn values_for_descendant_check_and_freeze(&self) -> Box<dyn Iterator<Item = Value>> {
+        Box::new(std::iter::empty())
+    }
+
+    fn get_attr(&self, attribute: &str) -> ValueResult {
+        Ok(match attribute {
+            "appimage_file_name" => Value::from(format!("{}.AppImage", self.name)),
+            "comment" => match &self.comment {
+                Some(comment) => Value::from(comment.as_str()),
+                None => Value::from(NoneType::None),
+            },
+            "modified_time" => match self.modified_time {
+                Some(time) => Value::from(time as i64),
+                None => Value::from(NoneType::None),
+            },
+            "terminal" => Value::from(self.terminal),
+            _ => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::GetAttr(attribute.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        })
+    }
+
+    fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
+        Ok(matches!(
+            attribute,
+            "appimage_file_name" | "comment" | "modified_time" | "terminal"
+        ))
+    }
+
+    fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
+        match attribute {
+            "comment" => {
+                self.comment = optional_str_arg("comment", &value)?;
+            }
+            "modified_time" => {
+                let time: Option<i64> = value.try_to_optional()?;
+                self.modified_time = error_context("AppImageBuilder.modified_time = ", || {
+                    time.map(|time| {
+                        u64::try_from(time)
+                            .map_err(|_| anyhow!("modified_time must not be negative: {}", time))
+                    })
+                    .transpose()
+                })?;
+            }
+            "terminal" => {
+                self.terminal = value.to_bool();
+            }
+            attr => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::SetAttr(attr.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl AppImageBuilderValue {
+    pub fn new_from_args(name: String, exec: String, runtime_path: String) -> ValueResult {
+        Ok(Value::new(Self {
+            name,
+            exec,
+            runtime_path,
+            install_files: FileManifest::default(),
+            icon: None,
+            comment: None,
+            categories: vec![],
+            terminal: false,
+            modified_time: None,
+        }))
+    }
+
+    /// Resolve reproducibility settings for the AppImage.
+    ///
+    /// An explicit `modified_time` takes precedence over `SOURCE_DATE_EPOCH`.
+    fn reproducibility(&self) -> Result<Option<Reproducibility>> {
+        Ok(match self.modified_time {
+            Some(time) => Some(Reproducibility::new(time)),
+            None => Reproducibility::from_source_date_epoch()?,
+        })
+    }
+
+    /// Write the `.AppImage` into a directory, returning the path of the written file.
+    pub fn write_appimage_into_directory(
+        &self,
+        runtime_path: &Path,
+        dest_dir: &Path,
+    ) -> Result<PathBuf> {
+        let mut builder =
+            AppImageBuilder::new(&self.name, &self.exec).add_file_manifest(&self.install_files)?;
+
+        if let Some((filename, entry)) = &self.icon {
+            builder = builder.set_icon(filename, entry.clone())?;
+        }
+
+        let mut desktop_entry = builder.desktop_entry().clone();
+        desktop_entry.comment = self.comment.clone();
+        desktop_entry.categories = self.categories.clone();
+        desktop_entry.terminal = self.terminal;
+        builder = builder.set_desktop_entry(desktop_entry);
+
+        if let Some(reproducibility) = self.reproducibility()? {
+            builder = builder.set_reproducibility(reproducibility);
+        }
+
+        let appimage_path = dest_dir.join(builder.appimage_file_name());
+        builder.write_to_path(runtime_path, &appimage_path)?;
+
+        Ok(appimage_path)
+    }
+
+    pub fn add_file_manifest(&mut self, manifest: FileManifestValue) -> ValueResult {
+        const LABEL: &str = "AppImageBuilder.add_file_manifest()";
+
+        let manifest = manifest.inner(LABEL)?;
+
+        error_context(LABEL, || {
+            self.install_files
+                .add_manifest(&manifest)
+                .context("adding file manifest")
+        })?;
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn set_icon(&mut self, content: FileContentValue) -> ValueResult {
+        const LABEL: &str = "AppImageBuilder.set_icon()";
+
+        let content = content.inner(LABEL)?;
+
+        // Validate eagerly so errors are reported where the icon is defined.
+        error_context(LABEL, || {
+            AppImageBuilder::new(&self.name, &self.exec)
+                .set_icon(&content.filename, content.content.clone())
+                .map(|_| ())
+        })?;
+
+        self.icon = Some((content.filename.clone(), content.content.clone()));
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn add_category(&mut self, category: String) -> ValueResult {
+        self.categories.push(category);
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn write_to_directory(&self, type_values: &TypeValues, path: String) -> ValueResult {
+        const LABEL: &str = "AppImageBuilder.write_to_directory()";
+
+        let context_value = get_context_value(type_values)?;
+        let context = context_value
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        let path = context.resolve_path(path);
+        let runtime_path = context.resolve_path(&self.runtime_path);
+
+        let appimage_path = error_context(LABEL, || {
+            self.write_appimage_into_directory(&runtime_path, &path)
+        })?;
+
+        Ok(Value::from(format!("{}", appimage_path.display())))
+    }
+
+    pub fn build(&self, type_values: &TypeValues, target: String) -> ValueResult {
+        const LABEL: &str = "AppImageBuilder.build()";
+
+        let context_value = get_context_value(type_values)?;
+        let context = context_value
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        let output_directory = context.target_build_path(&target);
+        let runtime_path = context.resolve_path(&self.runtime_path);
+
+        let appimage_path = error_context(LABEL, || {
+            warn!(
+                context.logger(),
+                "writing AppImage to {}",
+                output_directory.display()
+            );
+
+            self.write_appimage_into_directory(&runtime_path, &output_directory)
+        })?;
+
+        warn!(context.logger(), "wrote {}", appimage_path.display());
+
+        Ok(Value::new(ResolvedTargetValue {
+            inner: ResolvedTarget {
+                run_mode: RunMode::Path {
+                    path: appimage_path,
+                },
+                output_path: output_directory,
+            },
+        }))
+    }
+}
+
+starlark_module! { appimage_builder_module =>
+    #[allow(non_snake_case)]
+    AppImageBuilder(name: String, exec: String, runtime_path: String) {
+        AppImageBuilderValue::new_from_args(name, exec, runtime_path)
+    }
+
+    AppImageBuilder.add_file_manifest(this, manifest: FileManifestValue) {
+        let mut this = this.downcast_mut::<AppImageBuilderValue>().unwrap().unwrap();
+        this.add_file_manifest(manifest)
+    }
+
+    AppImageBuilder.set_icon(this, content: FileContentValue) {
+        let mut this = this.downcast_mut::<AppImageBuilderValue>().unwrap().unwrap();
+        this.set_icon(content)
+    }
+
+    AppImageBuilder.add_category(this, category: String) {
+        let mut this = this.downcast_mut::<AppImageBuilderValue>().unwrap().unwrap();
+        this.add_category(category)
+    }
+
+    AppImageBuilder.write_to_directory(env env, this, path: String) {
+        let this = this.downcast_ref::<AppImageBuilderValue>().unwrap();
+        this.write_to_directory(env, path)
+    }
+
+    AppImageBuilder.build(env env, this, target: String) {
+        let this = this.downcast_ref::<AppImageBuilderValue>().unwrap();
+        this.build(env, target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::starlark::testutil::*, tugger_common::testutil::*};
+
+    const CONSTRUCT: &str = "b = AppImageBuilder('myapp', 'usr/bin/myapp', 'runtime')";
+
+    #[test]
+    fn attributes() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+        env.eval(CONSTRUCT)?;
+
+        let v = env.eval("b")?;
+        assert_eq!(v.get_type(), AppImageBuilderValue::TYPE);
+
+        let v = env.eval("b.appimage_file_name")?;
+        assert_eq!(v.to_string(), "myapp.AppImage");
+
+        let v = env.eval("b.comment")?;
+        assert_eq!(v.get_type(), "NoneType");
+        env.eval("b.comment = 'My app'")?;
+        let v = env.eval("b.comment")?;
+        assert_eq!(v.to_string(), "My app");
+
+        let v = env.eval("b.terminal")?;
+        assert!(!v.to_bool());
+        env.eval("b.terminal = True")?;
+        let v = env.eval("b.terminal")?;
+        assert!(v.to_bool());
+
+        env.eval("b.modified_time = 1600000000")?;
+        let v = env.eval("b.modified_time")?;
+        assert_eq!(v.to_int().unwrap(), 1600000000);
+        assert!(env.eval("b.modified_time = -1").is_err());
+
+        env.eval("b.add_category('Development')")?;
+        let v = env.eval("b")?;
+        let builder = v.downcast_ref::<AppImageBuilderValue>().unwrap();
+        assert_eq!(builder.categories, vec!["Development"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn set_icon() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+        env.eval(CONSTRUCT)?;
+
+        assert!(env
+            .eval("b.set_icon(FileContent(filename = 'icon.bmp', content = ''))")
+            .is_err());
+        env.eval("b.set_icon(FileContent(filename = 'icon.svg', content = '<svg/>'))")?;
+
+        let v = env.eval("b")?;
+        let builder = v.downcast_ref::<AppImageBuilderValue>().unwrap();
+        assert_eq!(builder.icon.as_ref().unwrap().0, "icon.svg");
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_to_directory() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        let temp_dir = tempfile::Builder::new()
+            .prefix("tugger-appimage-test")
+            .tempdir()?;
+        let temp_dir_s = format!("{}", temp_dir.path().display()).replace('\\', "/");
+
+        let mut runtime = b"\x7fELF\x02\x01\x01\x00AI\x02".to_vec();
+        runtime.resize(64, 0);
+        std::fs::write(temp_dir.path().join("runtime"), &runtime)?;
+
+        env.eval(&format!(
+            "b = AppImageBuilder('myapp', 'usr/bin/myapp', '{}/runtime')",
+            temp_dir_s
+        ))?;
+        env.eval("m = FileManifest()")?;
+        env.eval("m.add_file(FileContent(filename = 'myapp', content = 'echo hello', executable = True), directory = 'usr/bin')")?;
+        env.eval("b.add_file_manifest(m)")?;
+        env.eval("b.set_icon(FileContent(filename = 'icon.svg', content = '<svg/>'))")?;
+
+        let path = env.eval(&format!(
+            "b.write_to_directory('{}')",
+            DEFAULT_TEMP_DIR
+                .path()
+                .display()
+                .to_string()
+                .replace('\\', "/")
+        ))?;
+
+        assert_eq!(path.get_type(), "string");
+        let path = PathBuf::from(path.to_string());
+        assert_eq!(path.file_name().unwrap(), "myapp.AppImage");
+
+        let data = std::fs::read(&path)?;
+        assert!(data.starts_with(&runtime));
+        assert_eq!(&data[runtime.len()..runtime.len() + 4], b"hsqs");
+
+        Ok(())
+    }
+}
@@ -64,14 +64,10 @@ impl AppleUniversalBinaryValue {
     pub fn add_path(&mut self, type_values: &TypeValues, path: String) -> ValueResult {
         const LABEL: &str = "AppleUniversalBinary.add_path()";
 
-        let cwd = {
-            let context_value = get_context_value(type_values)?;
-            let context = context_value
-                .downcast_ref::<EnvironmentContext>()
-                .ok_or(ValueError::IncorrectParameterType)?;
-
-            context.cwd().to_path_buf()
-        };
+        let context_value = get_context_value(type_values)?;
+        let context = context_value
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
 
         error_context(LABEL, || {
             let path = PathBuf::from(path);
@@ -79,9 +75,11 @@ impl AppleUniversalBinaryValue {
             let path = if path.is_absolute() {
                 path
             } else {
-                cwd.join(path)
+                context.cwd().join(path)
             };
 
+            context.record_input_path(&path);
+
             self.builder
                 .try_lock()
                 .map_err(|e| anyhow!("could not acquire lock: {}", e))?
@@ -0,0 +1,512 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::starlark::{file_content::FileContentValue, file_manifest::FileManifestValue},
+    anyhow::{anyhow, Context, Result},
+    slog::warn,
+    starlark::{
+        environment::TypeValues,
+        values::{
+            error::{RuntimeError, UnsupportedOperation, ValueError},
+            none::NoneType,
+            {Mutable, TypedValue, Value, ValueResult},
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
+    },
+    starlark_dialect_build_targets::{
+        get_context_value, EnvironmentContext, ResolvedTarget, ResolvedTargetValue, RunMode,
+        TryToOptional,
+    },
+    std::{
+        borrow::Cow,
+        collections::BTreeMap,
+        convert::TryFrom,
+        path::{Path, PathBuf},
+    },
+    tugger_debian::{ControlFile, ControlParagraph, DebBuilder, DebCompression},
+    tugger_file_manifest::{FileEntry, FileManifest, Reproducibility},
+};
+
+/// Names of maintainer scripts that can be added to the control archive.
+const MAINTAINER_SCRIPTS: &[&str] = &["config", "postinst", "postrm", "preinst", "prerm"];
+
+fn error_context<F, T>(label: &str, f: F) -> Result<T, ValueError>
+where
+    F: FnOnce() -> anyhow::Result<T>,
+{
+    f().map_err(|e| {
+        ValueError::Runtime(RuntimeError {
+            code: "TUGGER_DEBIAN",
+            message: format!("{:?}", e),
+            label: label.to_string(),
+        })
+    })
+}
+
+/// Resolve a compression name and optional level to a `DebCompression`.
+fn deb_compression(name: &str, level: Option<i64>) -> Result<DebCompression> {
+    match name {
+        "none" => Ok(DebCompression::Uncompressed),
+        "gzip" => Ok(DebCompression::Gzip),
+        "xz" => Ok(DebCompression::Xz(match level {
+            None => 6,
+            Some(level @ 0..=9) => level as u32,
+            Some(level) => {
+                return Err(anyhow!(
+                    "invalid xz compression level: {}; must be between 0 and 9",
+                    level
+                ))
+            }
+        })),
+        "zstd" => Ok(DebCompression::Zstandard(match level {
+            None => 3,
+            Some(level @ 1..=22) => level as i32,
+            Some(level) => {
+                return Err(anyhow!(
+                    "invalid zstd compression level: {}; must be between 1 and 22",
+                    level
+                ))
+            }
+        })),
+        _ => Err(anyhow!(
+            "unknown compression: {}; must be one of none, gzip, xz, or zstd",
+            name
+        )),
+    }
+}
+
+#[derive(Clone)]
+pub struct DebianPackageBuilderValue {
+    /// Control file fields, in the order they should be written.
+    pub control_fields: Vec<(String, String)>,
+    /// Files to install.
+    pub install_files: FileManifest,
+    /// Maintainer scripts, indexed by name.
+    pub maintainer_scripts: BTreeMap<String, FileEntry>,
+    /// Compression format name.
+    pub compression: String,
+    /// Compression level for formats that support it.
+    pub compression_level: Option<i64>,
+    /// Modified time of archive members, in seconds since UNIX epoch.
+    pub modified_time: Option<u64>,
+}
+
+impl TypedValue for DebianPackageBuilderValue {
+    type Holder = Mutable<DebianPackageBuilderValue>;
+    const TYPE: &'static str = "DebianPackageBuilder";
+
+    fn values_for_descendant_check_and_freeze(&self) -> Box<dyn Iterator<Item = Value>> {
+        Box::new(std::iter::empty())
+    }
+
+    fn get_attr(&self, attribute: &str) -> ValueResult {
+        Ok(match attribute {
+            "compression" => Value::from(self.compression.as_str()),
+            "compression_level" => match self.compression_level {
+                Some(level) => Value::from(level),
+                None => Value::from(NoneType::None),
+            },
+            "deb_file_name" => Value::from(self.deb_file_name()),
+            "modified_time" => match self.modified_time {
+                Some(time) => Value::from(time as i64),
+                None => Value::from(NoneType::None),
+            },
+            _ => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::GetAttr(attribute.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        })
+    }
+
+    fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
+        Ok(matches!(
+            attribute,
+            "compression" | "compression_level" | "deb_file_name" | "modified_time"
+        ))
+    }
+
+    fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
+        match attribute {
+            "compression" => {
+                let compression = value.to_string();
+                error_context("DebianPackageBuilder.compression = ", || {
+                    deb_compression(&compression, self.compression_level)
+                })?;
+                self.compression = compression;
+            }
+            "compression_level" => {
+                let level = value.try_to_optional()?;
+                error_context("DebianPackageBuilder.compression_level = ", || {
+                    deb_compression(&self.compression, level)
+                })?;
+                self.compression_level = level;
+            }
+            "modified_time" => {
+                let time: Option<i64> = value.try_to_optional()?;
+                self.modified_time =
+                    error_context("DebianPackageBuilder.modified_time = ", || {
+                        time.map(|time| {
+                            u64::try_from(time).map_err(|_| {
+                                anyhow!("modified_time must not be negative: {}", time)
+                            })
+                        })
+                        .transpose()
+                    })?;
+            }
+            attr => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::SetAttr(attr.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl DebianPackageBuilderValue {
+    pub fn new_from_args(
+        package: String,
+        version: String,
+        architecture: String,
+        maintainer: String,
+        description: String,
+    ) -> ValueResult {
+        Ok(Value::new(Self {
+            control_fields: vec![
+                ("Package".to_string(), package),
+                ("Version".to_string(), version),
+                ("Architecture".to_string(), architecture),
+                ("Maintainer".to_string(), maintainer),
+                ("Description".to_string(), description),
+            ],
+            install_files: FileManifest::default(),
+            maintainer_scripts: BTreeMap::new(),
+            compression: "gzip".to_string(),
+            compression_level: None,
+            modified_time: None,
+        }))
+    }
+
+    fn control_field(&self, name: &str) -> &str {
+        self.control_fields
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+            .unwrap_or_default()
+    }
+
+    /// The filename of the `.deb` produced by this builder.
+    pub fn deb_file_name(&self) -> String {
+        format!(
+            "{}_{}_{}.deb",
+            self.control_field("Package"),
+            self.control_field("Version"),
+            self.control_field("Architecture")
+        )
+    }
+
+    /// Resolve reproducibility settings for the package.
+    ///
+    /// An explicit `modified_time` takes precedence over `SOURCE_DATE_EPOCH`.
+    fn reproducibility(&self) -> Result<Option<Reproducibility>> {
+        Ok(match self.modified_time {
+            Some(time) => Some(Reproducibility::new(time)),
+            None => Reproducibility::from_source_date_epoch()?,
+        })
+    }
+
+    /// Write the `.deb` into a directory, returning the path of the written file.
+    pub fn write_deb_into_directory(&self, dest_dir: &Path) -> Result<PathBuf> {
+        let mut paragraph = ControlParagraph::default();
+        for (name, value) in &self.control_fields {
+            paragraph
+                .add_field_from_string(Cow::from(name.as_str()), Cow::from(value.as_str()))
+                .with_context(|| format!("adding control field {}", name))?;
+        }
+        let mut control_file = ControlFile::default();
+        control_file.add_paragraph(paragraph);
+
+        let mut builder = DebBuilder::new(control_file)
+            .set_compression(deb_compression(&self.compression, self.compression_level)?);
+
+        if let Some(reproducibility) = self.reproducibility()? {
+            builder = builder.set_reproducibility(reproducibility);
+        }
+
+        for (name, entry) in &self.maintainer_scripts {
+            builder = builder
+                .extra_control_tar_file(name, entry.clone())
+                .with_context(|| format!("adding maintainer script {}", name))?;
+        }
+
+        for (path, entry) in self.install_files.iter_entries() {
+            builder = builder
+                .install_file(path, entry.clone())
+                .with_context(|| format!("adding {}", path.display()))?;
+        }
+
+        std::fs::create_dir_all(dest_dir)
+            .with_context(|| format!("creating directory {}", dest_dir.display()))?;
+
+        let deb_path = dest_dir.join(self.deb_file_name());
+        let mut fh = std::fs::File::create(&deb_path)
+            .with_context(|| format!("creating {}", deb_path.display()))?;
+        builder
+            .write(&mut fh)
+            .with_context(|| format!("writing {}", deb_path.display()))?;
+
+        Ok(deb_path)
+    }
+
+    pub fn set_control_field(&mut self, name: String, value: String) -> ValueResult {
+        if let Some(field) = self.control_fields.iter_mut().find(|(key, _)| key == &name) {
+            field.1 = value;
+        } else {
+            self.control_fields.push((name, value));
+        }
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn add_file_manifest(&mut self, manifest: FileManifestValue) -> ValueResult {
+        const LABEL: &str = "DebianPackageBuilder.add_file_manifest()";
+
+        let manifest = manifest.inner(LABEL)?;
+
+        error_context(LABEL, || {
+            self.install_files
+                .add_manifest(&manifest)
+                .context("adding file manifest")
+        })?;
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn add_maintainer_script(
+        &mut self,
+        name: String,
+        content: FileContentValue,
+    ) -> ValueResult {
+        const LABEL: &str = "DebianPackageBuilder.add_maintainer_script()";
+
+        let content = content.inner(LABEL)?;
+
+        error_context(LABEL, || {
+            if !MAINTAINER_SCRIPTS.contains(&name.as_str()) {
+                return Err(anyhow!(
+                    "unknown maintainer script: {}; must be one of {}",
+                    name,
+                    MAINTAINER_SCRIPTS.join(", ")
+                ));
+            }
+
+            let mut entry = content.content.clone();
+            entry.set_executable(true);
+            self.maintainer_scripts.insert(name, entry);
+
+            Ok(())
+        })?;
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn write_to_directory(&self, type_values: &TypeValues, path: String) -> ValueResult {
+        const LABEL: &str = "DebianPackageBuilder.write_to_directory()";
+
+        let context_value = get_context_value(type_values)?;
+        let context = context_value
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        let path = context.resolve_path(path);
+
+        let deb_path = error_context(LABEL, || self.write_deb_into_directory(&path))?;
+
+        Ok(Value::from(format!("{}", deb_path.display())))
+    }
+
+    pub fn build(&self, type_values: &TypeValues, target: String) -> ValueResult {
+        const LABEL: &str = "DebianPackageBuilder.build()";
+
+        let context_value = get_context_value(type_values)?;
+        let context = context_value
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        let output_directory = context.target_build_path(&target);
+
+        let deb_path = error_context(LABEL, || {
+            warn!(
+                context.logger(),
+                "writing Debian package to {}",
+                output_directory.display()
+            );
+
+            self.write_deb_into_directory(&output_directory)
+        })?;
+
+        warn!(context.logger(), "wrote {}", deb_path.display());
+
+        Ok(Value::new(ResolvedTargetValue {
+            inner: ResolvedTarget {
+                run_mode: RunMode::None,
+                output_path: output_directory,
+            },
+        }))
+    }
+}
+
+starlark_module! { debian_package_builder_module =>
+    #[allow(non_snake_case)]
+    DebianPackageBuilder(
+        package: String,
+        version: String,
+        architecture: String,
+        maintainer: String,
+        description: String
+    ) {
+        DebianPackageBuilderValue::new_from_args(
+            package,
+            version,
+            architecture,
+            maintainer,
+            description,
+        )
+    }
+
+    DebianPackageBuilder.set_control_field(this, name: String, value: String) {
+        let mut this = this.downcast_mut::<DebianPackageBuilderValue>().unwrap().unwrap();
+        this.set_control_field(name, value)
+    }
+
+    DebianPackageBuilder.add_file_manifest(this, manifest: FileManifestValue) {
+        let mut this = this.downcast_mut::<DebianPackageBuilderValue>().unwrap().unwrap();
+        this.add_file_manifest(manifest)
+    }
+
+    DebianPackageBuilder.add_maintainer_script(this, name: String, content: FileContentValue) {
+        let mut this = this.downcast_mut::<DebianPackageBuilderValue>().unwrap().unwrap();
+        this.add_maintainer_script(name, content)
+    }
+
+    DebianPackageBuilder.write_to_directory(env env, this, path: String) {
+        let this = this.downcast_ref::<DebianPackageBuilderValue>().unwrap();
+        this.write_to_directory(env, path)
+    }
+
+    DebianPackageBuilder.build(env env, this, target: String) {
+        let this = this.downcast_ref::<DebianPackageBuilderValue>().unwrap();
+        this.build(env, target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::starlark::testutil::*, tugger_common::testutil::*};
+
+    const CONSTRUCT: &str =
+        "b = DebianPackageBuilder('myapp', '0.1', 'amd64', 'Me <me@example.com>', 'My app')";
+
+    #[test]
+    fn type_info() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        let raw = env.eval(CONSTRUCT.trim_start_matches("b = "))?;
+        assert_eq!(raw.get_type(), DebianPackageBuilderValue::TYPE);
+
+        Ok(())
+    }
+
+    #[test]
+    fn attributes() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+        env.eval(CONSTRUCT)?;
+
+        let v = env.eval("b.deb_file_name")?;
+        assert_eq!(v.to_string(), "myapp_0.1_amd64.deb");
+
+        let v = env.eval("b.compression")?;
+        assert_eq!(v.to_string(), "gzip");
+
+        env.eval("b.compression = 'zstd'")?;
+        env.eval("b.compression_level = 10")?;
+        env.eval("b.modified_time = 42")?;
+        let v = env.eval("b.compression")?;
+        assert_eq!(v.to_string(), "zstd");
+        let v = env.eval("b.compression_level")?;
+        assert_eq!(v.to_int().unwrap(), 10);
+        let v = env.eval("b.modified_time")?;
+        assert_eq!(v.to_int().unwrap(), 42);
+
+        assert!(env.eval("b.compression = 'bzip2'").is_err());
+        assert!(env.eval("b.compression_level = -1").is_err());
+        assert!(env.eval("b.compression_level = 23").is_err());
+        assert!(env.eval("b.compression = 'xz'").is_err());
+        assert!(env.eval("b.modified_time = -1").is_err());
+        let v = env.eval("b.compression_level")?;
+        assert_eq!(v.to_int().unwrap(), 10);
+        let v = env.eval("b.modified_time")?;
+        assert_eq!(v.to_int().unwrap(), 42);
+
+        env.eval("b.set_control_field('Version', '0.2')")?;
+        let v = env.eval("b.deb_file_name")?;
+        assert_eq!(v.to_string(), "myapp_0.2_amd64.deb");
+
+        Ok(())
+    }
+
+    #[test]
+    fn maintainer_scripts() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+        env.eval(CONSTRUCT)?;
+        env.eval("f = FileContent(filename = 'postinst', content = '#!/bin/sh\\n')")?;
+
+        env.eval("b.add_maintainer_script('postinst', f)")?;
+        assert!(env.eval("b.add_maintainer_script('bogus', f)").is_err());
+
+        let v = env.eval("b")?;
+        let builder = v.downcast_ref::<DebianPackageBuilderValue>().unwrap();
+        assert_eq!(
+            builder.maintainer_scripts.keys().collect::<Vec<_>>(),
+            vec!["postinst"]
+        );
+        assert!(builder.maintainer_scripts["postinst"].is_executable());
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_to_directory() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        let temp_dir_s = format!("{}", DEFAULT_TEMP_DIR.path().display()).replace('\\', "/");
+
+        env.eval(CONSTRUCT)?;
+        env.eval("m = FileManifest()")?;
+        env.eval("m.add_file(FileContent(filename = 'myapp', content = 'echo hello', executable = True), directory = 'usr/bin')")?;
+        env.eval("b.add_file_manifest(m)")?;
+        env.eval("b.modified_time = 1600000000")?;
+
+        let path = env.eval(&format!("b.write_to_directory('{}')", temp_dir_s))?;
+
+        assert_eq!(path.get_type(), "string");
+        let path = PathBuf::from(path.to_string());
+        assert_eq!(path.file_name().unwrap(), "myapp_0.1_amd64.deb");
+
+        let data = std::fs::read(&path)?;
+        assert!(data.starts_with(b"!<arch>\ndebian-binary"));
+
+        Ok(())
+    }
+}
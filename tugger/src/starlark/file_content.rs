@@ -143,14 +143,11 @@ impl FileContentValue {
             validate_filename(LABEL, &filename)?;
         }
 
-        let cwd = {
-            let context_value = get_context_value(type_values)?;
-            let context = context_value
-                .downcast_ref::<EnvironmentContext>()
-                .ok_or(ValueError::IncorrectParameterType)?;
-
-            context.cwd().to_path_buf()
-        };
+        let context_value = get_context_value(type_values)?;
+        let context = context_value
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+        let cwd = context.cwd().to_path_buf();
 
         let file_content = error_context(LABEL, || {
             if path.is_some() && content.is_some() {
@@ -179,6 +176,7 @@ impl FileContentValue {
                         .to_string()
                 };
 
+                context.record_input_path(&path);
                 let mut file_entry = FileEntry::try_from(path.as_path())?;
 
                 if let Some(executable) = executable {
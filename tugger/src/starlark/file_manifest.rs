@@ -237,12 +237,22 @@ impl FileManifestValue {
     /// FileManifest.add_path(path, strip_prefix, force_read=False)
     pub fn add_path(
         &mut self,
+        type_values: &TypeValues,
         path: String,
         strip_prefix: String,
         force_read: bool,
     ) -> ValueResult {
         const LABEL: &str = "FileManifest.add_path()";
 
+        {
+            let context_value = get_context_value(type_values)?;
+            let context = context_value
+                .downcast_ref::<EnvironmentContext>()
+                .ok_or(ValueError::IncorrectParameterType)?;
+
+            context.record_input_path(&path);
+        }
+
         let mut inner = self.inner(LABEL)?;
 
         error_context(LABEL, || {
@@ -394,9 +404,15 @@ starlark_module! { file_manifest_module =>
         this.add_file(content, path, directory)
     }
 
-    FileManifest.add_path(this, path: String, strip_prefix: String, force_read: bool = false) {
+    FileManifest.add_path(
+        env env,
+        this,
+        path: String,
+        strip_prefix: String,
+        force_read: bool = false
+    ) {
         let mut this = this.downcast_mut::<FileManifestValue>().unwrap().unwrap();
-        this.add_path(path, strip_prefix, force_read)
+        this.add_path(env, path, strip_prefix, force_read)
     }
 
     FileManifest.build(env env, call_stack cs, this, target: String) {
@@ -21,7 +21,7 @@ use {
         EnvironmentContext,
     },
     std::{collections::HashSet, convert::TryFrom},
-    tugger_common::glob::evaluate_glob,
+    tugger_common::glob::{evaluate_glob, glob_base_directory},
     tugger_file_manifest::{FileEntry, FileManifest},
 };
 
@@ -70,7 +70,11 @@ fn starlark_glob(
 
         // Evaluate all the includes first.
         for v in include {
+            // New files under the base directory can change the result.
+            context.record_input_path(glob_base_directory(context.cwd(), &v));
+
             for p in evaluate_glob(context.cwd(), &v)? {
+                context.record_input_path(&p);
                 result.insert(p);
             }
         }
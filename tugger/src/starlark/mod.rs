@@ -8,13 +8,17 @@ The `starlark` module and related sub-modules define the
 Tugger.
 */
 
+pub mod appimage_builder;
 pub mod apple_universal_binary;
 pub mod code_signing;
+pub mod debian_package_builder;
 pub mod file_content;
 pub mod file_manifest;
 pub mod file_resource;
 pub mod macos_application_bundle_builder;
 pub mod python_wheel_builder;
+#[cfg(target_family = "unix")]
+pub mod rpm_builder;
 pub mod snapcraft;
 pub mod terminal;
 #[cfg(test)]
@@ -127,13 +131,17 @@ pub fn register_starlark_dialect(
     env: &mut Environment,
     type_values: &mut TypeValues,
 ) -> Result<(), EnvironmentError> {
+    appimage_builder::appimage_builder_module(env, type_values);
     apple_universal_binary::apple_universal_binary_module(env, type_values);
     code_signing::code_signing_module(env, type_values);
+    debian_package_builder::debian_package_builder_module(env, type_values);
     file_content::file_content_module(env, type_values);
     file_manifest::file_manifest_module(env, type_values);
     file_resource::file_resource_module(env, type_values);
     macos_application_bundle_builder::macos_application_bundle_builder_module(env, type_values);
     python_wheel_builder::python_wheel_builder_module(env, type_values);
+    #[cfg(target_family = "unix")]
+    rpm_builder::rpm_builder_module(env, type_values);
     snapcraft::snapcraft_module(env, type_values);
     terminal::terminal_module(env, type_values);
     wix_bundle_builder::wix_bundle_builder_module(env, type_values);
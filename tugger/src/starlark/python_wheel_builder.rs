@@ -27,6 +27,7 @@ use {
         path::PathBuf,
         sync::{Arc, Mutex, MutexGuard},
     },
+    tugger_file_manifest::Reproducibility,
 };
 
 fn error_context<F, T>(label: &str, f: F) -> Result<T, ValueError>
@@ -154,8 +155,17 @@ impl PythonWheelBuilderValue {
     }
 
     pub fn new_from_args(distribution: String, version: String) -> ValueResult {
+        let mut builder = WheelBuilder::new(distribution, version);
+
+        // Honor SOURCE_DATE_EPOCH so wheels are reproducible by default.
+        if let Some(reproducibility) = error_context("PythonWheelBuilder()", || {
+            Reproducibility::from_source_date_epoch().context("resolving SOURCE_DATE_EPOCH")
+        })? {
+            builder.set_reproducibility(reproducibility);
+        }
+
         Ok(Value::new(Self {
-            inner: Arc::new(Mutex::new(WheelBuilder::new(distribution, version))),
+            inner: Arc::new(Mutex::new(builder)),
         }))
     }
 
@@ -0,0 +1,597 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::starlark::file_manifest::FileManifestValue,
+    anyhow::{anyhow, Context, Result},
+    slog::warn,
+    starlark::{
+        environment::TypeValues,
+        values::{
+            error::{RuntimeError, UnsupportedOperation, ValueError},
+            none::NoneType,
+            {Mutable, TypedValue, Value, ValueResult},
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
+    },
+    starlark_dialect_build_targets::{
+        get_context_value, EnvironmentContext, ResolvedTarget, ResolvedTargetValue, RunMode,
+        TryToOptional,
+    },
+    std::{
+        collections::BTreeMap,
+        convert::TryFrom,
+        path::{Path, PathBuf},
+    },
+    tugger_file_manifest::{FileManifest, Reproducibility},
+    tugger_rpm::{parse_dependency, RpmBuilder, RpmFileAttributes},
+};
+
+/// Names of scriptlets that can be defined on the package.
+const SCRIPTLETS: &[&str] = &[
+    "post_install",
+    "post_uninstall",
+    "pre_install",
+    "pre_uninstall",
+];
+
+fn error_context<F, T>(label: &str, f: F) -> Result<T, ValueError>
+where
+    F: FnOnce() -> anyhow::Result<T>,
+{
+    f().map_err(|e| {
+        ValueError::Runtime(RuntimeError {
+            code: "TUGGER_RPM",
+            message: format!("{:?}", e),
+            label: label.to_string(),
+        })
+    })
+}
+
+/// An entry in the package's changelog.
+#[derive(Clone, Debug)]
+pub struct ChangelogEntry {
+    pub author: String,
+    pub entry: String,
+    pub time: i32,
+}
+
+#[derive(Clone)]
+pub struct RpmBuilderValue {
+    pub name: String,
+    pub version: String,
+    pub license: String,
+    pub arch: String,
+    pub description: String,
+    pub release: u16,
+    pub epoch: i32,
+    /// Files to install.
+    pub install_files: FileManifest,
+    /// Dependency expressions for `Requires`.
+    pub requires: Vec<String>,
+    /// Dependency expressions for `Provides`.
+    pub provides: Vec<String>,
+    /// Dependency expressions for `Conflicts`.
+    pub conflicts: Vec<String>,
+    /// Dependency expressions for `Obsoletes`.
+    pub obsoletes: Vec<String>,
+    /// Scriptlet content, indexed by name.
+    pub scriptlets: BTreeMap<String, String>,
+    pub changelog: Vec<ChangelogEntry>,
+    /// Installation attributes of files, indexed by relative path.
+    pub file_attributes: BTreeMap<PathBuf, RpmFileAttributes>,
+    /// Modified time of files, in seconds since UNIX epoch.
+    pub modified_time: Option<u64>,
+}
+
+impl TypedValue for RpmBuilderValue {
+    type Holder = Mutable<RpmBuilderValue>;
+    const TYPE: &'static str = "RpmBuilder";
+
+    fn values_for_descendant_check_and_freeze(&self) -> Box<dyn Iterator<Item = Value>> {
+        Box::new(std::iter::empty())
+    }
+
+    fn get_attr(&self, attribute: &str) -> ValueResult {
+        Ok(match attribute {
+            "epoch" => Value::from(self.epoch as i64),
+            "modified_time" => match self.modified_time {
+                Some(time) => Value::from(time as i64),
+                None => Value::from(NoneType::None),
+            },
+            "release" => Value::from(self.release as i64),
+            "rpm_file_name" => Value::from(self.rpm_file_name()),
+            _ => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::GetAttr(attribute.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        })
+    }
+
+    fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
+        Ok(matches!(
+            attribute,
+            "epoch" | "modified_time" | "release" | "rpm_file_name"
+        ))
+    }
+
+    fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
+        match attribute {
+            "epoch" => {
+                let epoch = value.to_int()?;
+                self.epoch = error_context("RpmBuilder.epoch = ", || {
+                    i32::try_from(epoch).context("converting epoch to i32")
+                })?;
+            }
+            "modified_time" => {
+                let time: Option<i64> = value.try_to_optional()?;
+                self.modified_time = error_context("RpmBuilder.modified_time = ", || {
+                    time.map(|time| {
+                        u64::try_from(time)
+                            .map_err(|_| anyhow!("modified_time must not be negative: {}", time))
+                    })
+                    .transpose()
+                })?;
+            }
+            "release" => {
+                let release = value.to_int()?;
+                self.release = error_context("RpmBuilder.release = ", || {
+                    u16::try_from(release).context("converting release to u16")
+                })?;
+            }
+            attr => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::SetAttr(attr.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl RpmBuilderValue {
+    pub fn new_from_args(
+        name: String,
+        version: String,
+        license: String,
+        arch: String,
+        description: String,
+    ) -> ValueResult {
+        Ok(Value::new(Self {
+            name,
+            version,
+            license,
+            arch,
+            description,
+            release: 1,
+            epoch: 0,
+            install_files: FileManifest::default(),
+            requires: vec![],
+            provides: vec![],
+            conflicts: vec![],
+            obsoletes: vec![],
+            scriptlets: BTreeMap::new(),
+            changelog: vec![],
+            file_attributes: BTreeMap::new(),
+            modified_time: None,
+        }))
+    }
+
+    /// The filename of the `.rpm` produced by this builder.
+    pub fn rpm_file_name(&self) -> String {
+        format!(
+            "{}-{}-{}.{}.rpm",
+            self.name, self.version, self.release, self.arch
+        )
+    }
+
+    /// Resolve reproducibility settings for the package.
+    ///
+    /// An explicit `modified_time` takes precedence over `SOURCE_DATE_EPOCH`.
+    fn reproducibility(&self) -> Result<Option<Reproducibility>> {
+        Ok(match self.modified_time {
+            Some(time) => Some(Reproducibility::new(time)),
+            None => Reproducibility::from_source_date_epoch()?,
+        })
+    }
+
+    /// Write the `.rpm` into a directory, returning the path of the written file.
+    pub fn write_rpm_into_directory(&self, dest_dir: &Path) -> Result<PathBuf> {
+        // Files are materialized in a staging directory before being added to the RPM.
+        let staging_dir = tempfile::Builder::new()
+            .prefix("tugger-rpm-")
+            .tempdir()
+            .context("creating staging directory")?;
+
+        let mut builder = RpmBuilder::new(
+            staging_dir.path(),
+            &self.name,
+            &self.version,
+            &self.license,
+            &self.arch,
+            &self.description,
+        )
+        .release(self.release)
+        .epoch(self.epoch)
+        .add_file_manifest(&self.install_files)?;
+
+        if let Some(reproducibility) = self.reproducibility()? {
+            builder = builder.set_reproducibility(reproducibility);
+        }
+
+        for (path, attributes) in &self.file_attributes {
+            builder = builder.set_file_attributes(path, attributes.clone());
+        }
+
+        for dependency in &self.requires {
+            builder = builder.requires(parse_dependency(dependency)?);
+        }
+        for dependency in &self.provides {
+            builder = builder.provides(parse_dependency(dependency)?);
+        }
+        for dependency in &self.conflicts {
+            builder = builder.conflicts(parse_dependency(dependency)?);
+        }
+        for dependency in &self.obsoletes {
+            builder = builder.obsoletes(parse_dependency(dependency)?);
+        }
+
+        for (name, content) in &self.scriptlets {
+            builder = match name.as_str() {
+                "pre_install" => builder.pre_install_script(content.as_str()),
+                "post_install" => builder.post_install_script(content.as_str()),
+                "pre_uninstall" => builder.pre_uninstall_script(content.as_str()),
+                "post_uninstall" => builder.post_uninstall_script(content.as_str()),
+                _ => return Err(anyhow!("unknown scriptlet: {}", name)),
+            };
+        }
+
+        for entry in &self.changelog {
+            builder = builder.add_changelog_entry(
+                entry.author.as_str(),
+                entry.entry.as_str(),
+                entry.time,
+            );
+        }
+
+        let rpm_path = dest_dir.join(self.rpm_file_name());
+        builder
+            .build_to_path(&rpm_path)
+            .with_context(|| format!("writing {}", rpm_path.display()))?;
+
+        Ok(rpm_path)
+    }
+
+    pub fn add_file_manifest(&mut self, manifest: FileManifestValue) -> ValueResult {
+        const LABEL: &str = "RpmBuilder.add_file_manifest()";
+
+        let manifest = manifest.inner(LABEL)?;
+
+        error_context(LABEL, || {
+            self.install_files
+                .add_manifest(&manifest)
+                .context("adding file manifest")
+        })?;
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn add_dependency(&mut self, kind: &str, dependency: String) -> ValueResult {
+        let label = format!("RpmBuilder.add_{}()", kind);
+
+        // Validate eagerly so errors are reported where the dependency is defined.
+        error_context(&label, || parse_dependency(&dependency).map(|_| ()))?;
+
+        let dependencies = match kind {
+            "requires" => &mut self.requires,
+            "provides" => &mut self.provides,
+            "conflicts" => &mut self.conflicts,
+            "obsoletes" => &mut self.obsoletes,
+            _ => {
+                return Err(ValueError::Runtime(RuntimeError {
+                    code: "TUGGER_RPM",
+                    message: format!(
+                        "unknown dependency kind: {}; must be one of requires, provides, conflicts, or obsoletes",
+                        kind
+                    ),
+                    label,
+                }))
+            }
+        };
+        dependencies.push(dependency);
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn set_scriptlet(&mut self, name: String, content: String) -> ValueResult {
+        const LABEL: &str = "RpmBuilder.set_scriptlet()";
+
+        error_context(LABEL, || {
+            if !SCRIPTLETS.contains(&name.as_str()) {
+                return Err(anyhow!(
+                    "unknown scriptlet: {}; must be one of {}",
+                    name,
+                    SCRIPTLETS.join(", ")
+                ));
+            }
+
+            self.scriptlets.insert(name, content);
+
+            Ok(())
+        })?;
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn add_changelog_entry(&mut self, author: String, entry: String, time: i32) -> ValueResult {
+        self.changelog.push(ChangelogEntry {
+            author,
+            entry,
+            time,
+        });
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn set_file_attributes(
+        &mut self,
+        path: String,
+        mode: Value,
+        user: String,
+        group: String,
+        config: bool,
+        doc: bool,
+        ghost: bool,
+    ) -> ValueResult {
+        const LABEL: &str = "RpmBuilder.set_file_attributes()";
+
+        let mode: Option<i64> = mode.try_to_optional()?;
+
+        let mode = error_context(LABEL, || {
+            mode.map(|mode| u32::try_from(mode).context("converting mode to u32"))
+                .transpose()
+        })?;
+
+        self.file_attributes.insert(
+            PathBuf::from(path),
+            RpmFileAttributes {
+                mode,
+                user,
+                group,
+                config,
+                doc,
+                ghost,
+            },
+        );
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn write_to_directory(&self, type_values: &TypeValues, path: String) -> ValueResult {
+        const LABEL: &str = "RpmBuilder.write_to_directory()";
+
+        let context_value = get_context_value(type_values)?;
+        let context = context_value
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        let path = context.resolve_path(path);
+
+        let rpm_path = error_context(LABEL, || self.write_rpm_into_directory(&path))?;
+
+        Ok(Value::from(format!("{}", rpm_path.display())))
+    }
+
+    pub fn build(&self, type_values: &TypeValues, target: String) -> ValueResult {
+        const LABEL: &str = "RpmBuilder.build()";
+
+        let context_value = get_context_value(type_values)?;
+        let context = context_value
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        let output_directory = context.target_build_path(&target);
+
+        let rpm_path = error_context(LABEL, || {
+            warn!(
+                context.logger(),
+                "writing RPM to {}",
+                output_directory.display()
+            );
+
+            self.write_rpm_into_directory(&output_directory)
+        })?;
+
+        warn!(context.logger(), "wrote {}", rpm_path.display());
+
+        Ok(Value::new(ResolvedTargetValue {
+            inner: ResolvedTarget {
+                run_mode: RunMode::None,
+                output_path: output_directory,
+            },
+        }))
+    }
+}
+
+starlark_module! { rpm_builder_module =>
+    #[allow(non_snake_case)]
+    RpmBuilder(
+        name: String,
+        version: String,
+        license: String,
+        arch: String,
+        description: String
+    ) {
+        RpmBuilderValue::new_from_args(name, version, license, arch, description)
+    }
+
+    RpmBuilder.add_file_manifest(this, manifest: FileManifestValue) {
+        let mut this = this.downcast_mut::<RpmBuilderValue>().unwrap().unwrap();
+        this.add_file_manifest(manifest)
+    }
+
+    RpmBuilder.add_requires(this, dependency: String) {
+        let mut this = this.downcast_mut::<RpmBuilderValue>().unwrap().unwrap();
+        this.add_dependency("requires", dependency)
+    }
+
+    RpmBuilder.add_provides(this, dependency: String) {
+        let mut this = this.downcast_mut::<RpmBuilderValue>().unwrap().unwrap();
+        this.add_dependency("provides", dependency)
+    }
+
+    RpmBuilder.add_conflicts(this, dependency: String) {
+        let mut this = this.downcast_mut::<RpmBuilderValue>().unwrap().unwrap();
+        this.add_dependency("conflicts", dependency)
+    }
+
+    RpmBuilder.add_obsoletes(this, dependency: String) {
+        let mut this = this.downcast_mut::<RpmBuilderValue>().unwrap().unwrap();
+        this.add_dependency("obsoletes", dependency)
+    }
+
+    RpmBuilder.set_scriptlet(this, name: String, content: String) {
+        let mut this = this.downcast_mut::<RpmBuilderValue>().unwrap().unwrap();
+        this.set_scriptlet(name, content)
+    }
+
+    RpmBuilder.add_changelog_entry(this, author: String, entry: String, time: i32) {
+        let mut this = this.downcast_mut::<RpmBuilderValue>().unwrap().unwrap();
+        this.add_changelog_entry(author, entry, time)
+    }
+
+    RpmBuilder.set_file_attributes(
+        this,
+        path: String,
+        mode = NoneType::None,
+        user: String = "root".to_string(),
+        group: String = "root".to_string(),
+        config: bool = false,
+        doc: bool = false,
+        ghost: bool = false
+    ) {
+        let mut this = this.downcast_mut::<RpmBuilderValue>().unwrap().unwrap();
+        this.set_file_attributes(path, mode, user, group, config, doc, ghost)
+    }
+
+    RpmBuilder.write_to_directory(env env, this, path: String) {
+        let this = this.downcast_ref::<RpmBuilderValue>().unwrap();
+        this.write_to_directory(env, path)
+    }
+
+    RpmBuilder.build(env env, this, target: String) {
+        let this = this.downcast_ref::<RpmBuilderValue>().unwrap();
+        this.build(env, target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::starlark::testutil::*, tugger_common::testutil::*};
+
+    const CONSTRUCT: &str = "b = RpmBuilder('myapp', '0.1', 'MPL-2.0', 'x86_64', 'My app')";
+
+    #[test]
+    fn attributes() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+        env.eval(CONSTRUCT)?;
+
+        let v = env.eval("b")?;
+        assert_eq!(v.get_type(), RpmBuilderValue::TYPE);
+
+        let v = env.eval("b.rpm_file_name")?;
+        assert_eq!(v.to_string(), "myapp-0.1-1.x86_64.rpm");
+
+        env.eval("b.release = 3")?;
+        env.eval("b.epoch = 1")?;
+        let v = env.eval("b.rpm_file_name")?;
+        assert_eq!(v.to_string(), "myapp-0.1-3.x86_64.rpm");
+        let v = env.eval("b.epoch")?;
+        assert_eq!(v.to_int().unwrap(), 1);
+
+        let v = env.eval("b.modified_time")?;
+        assert_eq!(v.get_type(), "NoneType");
+        env.eval("b.modified_time = 1600000000")?;
+        let v = env.eval("b.modified_time")?;
+        assert_eq!(v.to_int().unwrap(), 1600000000);
+        assert!(env.eval("b.modified_time = -1").is_err());
+        let v = env.eval("b.modified_time")?;
+        assert_eq!(v.to_int().unwrap(), 1600000000);
+
+        assert!(env.eval("b.release = -1").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn dependencies_and_scriptlets() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+        env.eval(CONSTRUCT)?;
+
+        env.eval("b.add_requires('python3 >= 3.8')")?;
+        env.eval("b.add_provides('myapp-cli')")?;
+        env.eval("b.add_conflicts('oldapp < 2')")?;
+        assert!(env.eval("b.add_requires('python3 ~ 3.8')").is_err());
+
+        env.eval("b.set_scriptlet('post_install', 'echo installed')")?;
+        assert!(env.eval("b.set_scriptlet('bogus', '')").is_err());
+
+        env.eval("b.add_changelog_entry('Me <me@example.com>', '- Initial release', 1600000000)")?;
+
+        let v = env.eval("b")?;
+        let builder = v.downcast_ref::<RpmBuilderValue>().unwrap();
+        assert_eq!(builder.requires, vec!["python3 >= 3.8"]);
+        assert_eq!(builder.provides, vec!["myapp-cli"]);
+        assert_eq!(builder.conflicts, vec!["oldapp < 2"]);
+        assert_eq!(
+            builder.scriptlets.keys().collect::<Vec<_>>(),
+            vec!["post_install"]
+        );
+        assert_eq!(builder.changelog.len(), 1);
+        drop(builder);
+
+        let mut builder = v.downcast_mut::<RpmBuilderValue>().unwrap().unwrap();
+        assert!(builder
+            .add_dependency("recommends", "python3".to_string())
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_to_directory() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        let temp_dir_s = format!("{}", DEFAULT_TEMP_DIR.path().display()).replace('\\', "/");
+
+        env.eval(CONSTRUCT)?;
+        env.eval("m = FileManifest()")?;
+        env.eval("m.add_file(FileContent(filename = 'myapp', content = 'echo hello', executable = True), directory = 'usr/bin')")?;
+        env.eval("b.add_file_manifest(m)")?;
+        env.eval("b.add_requires('python3')")?;
+        env.eval("b.set_file_attributes('usr/bin/myapp', mode = 0o750, group = 'wheel')")?;
+        assert!(env
+            .eval("b.set_file_attributes('usr/bin/myapp', mode = -1)")
+            .is_err());
+
+        let path = env.eval(&format!("b.write_to_directory('{}')", temp_dir_s))?;
+
+        assert_eq!(path.get_type(), "string");
+        let path = PathBuf::from(path.to_string());
+        assert_eq!(path.file_name().unwrap(), "myapp-0.1-1.x86_64.rpm");
+        assert!(path.exists());
+
+        Ok(())
+    }
+}
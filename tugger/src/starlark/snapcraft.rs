@@ -4,6 +4,8 @@
 
 use {
     crate::starlark::file_manifest::FileManifestValue,
+    anyhow::Result,
+    slog::warn,
     starlark::{
         environment::TypeValues,
         values::{
@@ -25,8 +27,10 @@ use {
         collections::HashMap,
         convert::TryFrom,
         ops::Deref,
+        path::{Path, PathBuf},
         sync::{Arc, Mutex, MutexGuard},
     },
+    tugger_file_manifest::Reproducibility,
     tugger_snapcraft::{
         Adapter, Architecture, Architectures, BuildAttribute, Confinement, Daemon, Grade,
         RestartCondition, SnapApp, SnapPart, Snapcraft, SnapcraftBuilder, SnapcraftInvocation,
@@ -762,6 +766,69 @@ impl SnapcraftBuilderValue<'static> {
             },
         }))
     }
+
+    fn write_snap_into_directory(&self, dest_dir: &Path) -> Result<PathBuf> {
+        let mut builder = self.inner.clone();
+
+        if let Some(reproducibility) = Reproducibility::from_source_date_epoch()? {
+            builder = builder.set_reproducibility(reproducibility);
+        }
+
+        builder.write_snap_to_directory(dest_dir)
+    }
+
+    pub fn write_snap_to_directory(&self, type_values: &TypeValues, path: String) -> ValueResult {
+        const LABEL: &str = "SnapcraftBuilder.write_snap_to_directory()";
+
+        let context_value = get_context_value(type_values)?;
+        let context = context_value
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        let path = context.resolve_path(path);
+
+        let snap_path = self.write_snap_into_directory(&path).map_err(|e| {
+            ValueError::Runtime(RuntimeError {
+                code: "TUGGER_SNAPCRAFT",
+                message: format!("{:?}", e),
+                label: LABEL.to_string(),
+            })
+        })?;
+
+        Ok(Value::from(format!("{}", snap_path.display())))
+    }
+
+    pub fn build_snap(&self, type_values: &TypeValues, target: String) -> ValueResult {
+        const LABEL: &str = "SnapcraftBuilder.build_snap()";
+
+        let context_value = get_context_value(type_values)?;
+        let context = context_value
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        let output_path = context.target_build_path(&target);
+
+        warn!(
+            context.logger(),
+            "writing snap to {}",
+            output_path.display()
+        );
+        let snap_path = self.write_snap_into_directory(&output_path).map_err(|e| {
+            ValueError::Runtime(RuntimeError {
+                code: "TUGGER_SNAPCRAFT",
+                message: format!("{:?}", e),
+                label: LABEL.to_string(),
+            })
+        })?;
+        warn!(context.logger(), "wrote {}", snap_path.display());
+
+        Ok(Value::new(ResolvedTargetValue {
+            inner: ResolvedTarget {
+                run_mode: RunMode::None,
+                output_path,
+            },
+        }))
+    }
 }
 
 starlark_module! { snapcraft_module =>
@@ -805,12 +872,23 @@ starlark_module! { snapcraft_module =>
         let this = this.downcast_ref::<SnapcraftBuilderValue>().unwrap();
         this.build(env, target)
     }
+
+    SnapcraftBuilder.write_snap_to_directory(env env, this, path: String) {
+        let this = this.downcast_ref::<SnapcraftBuilderValue>().unwrap();
+        this.write_snap_to_directory(env, path)
+    }
+
+    SnapcraftBuilder.build_snap(env env, this, target: String) {
+        let this = this.downcast_ref::<SnapcraftBuilderValue>().unwrap();
+        this.build_snap(env, target)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use {
-        super::*, crate::starlark::testutil::*, anyhow::Result, tugger_file_manifest::FileManifest,
+        super::*, crate::starlark::testutil::*, anyhow::Result, tugger_common::testutil::*,
+        tugger_file_manifest::FileManifest,
     };
 
     #[test]
@@ -1087,4 +1165,32 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_snapcraft_builder_write_snap() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        let dest_dir = DEFAULT_TEMP_DIR.path().join("snapcraft-builder-write-snap");
+
+        env.eval("snap = Snap('myapp', '0.1', 'summary', 'description')")?;
+        env.eval("app = SnapApp()")?;
+        env.eval("app.command = 'bin/myapp'")?;
+        env.eval("snap.apps = {'myapp': app}")?;
+        env.eval("builder = snap.to_builder()")?;
+        env.eval("manifest = FileManifest()")?;
+        env.eval("manifest.add_file(FileContent(filename = 'myapp', content = 'exe', executable = True), directory = 'bin')")?;
+        env.eval("builder.add_file_manifest(manifest)")?;
+
+        let path = env.eval(&format!(
+            "builder.write_snap_to_directory('{}')",
+            dest_dir.display().to_string().replace('\\', "/")
+        ))?;
+        assert_eq!(
+            PathBuf::from(path.to_string()),
+            dest_dir.join("myapp_0.1_all.snap")
+        );
+        assert!(dest_dir.join("myapp_0.1_all.snap").exists());
+
+        Ok(())
+    }
 }
@@ -7,16 +7,23 @@ use {
     slog::warn,
     std::{io::Write, path::Path},
     tar,
+    tugger_file_manifest::{is_executable, Reproducibility},
 };
 
 /// Create a tarball from a filesystem path.
 ///
 /// The uncompressed tar contents will be emitted to the passed writer.
+///
+/// Entries are always emitted in sorted order. If `reproducibility` is
+/// defined, modified times, ownership, and permissions of all entries are
+/// normalized per it. Otherwise entries have a zero modified time, are owned
+/// by uid/gid 0, and only retain the executable bit of their permissions.
 pub fn write_tarball_from_directory<W: Write, P: AsRef<Path>>(
     logger: &slog::Logger,
     fh: &mut W,
     source_path: P,
     archive_prefix: Option<P>,
+    reproducibility: Option<Reproducibility>,
 ) -> Result<()> {
     let source_path = source_path.as_ref();
 
@@ -50,10 +57,50 @@ pub fn write_tarball_from_directory<W: Write, P: AsRef<Path>>(
             path.display(),
             archive_path.display()
         );
-        builder.append_path_with_name(path, &archive_path)?;
+
+        if let Some(reproducibility) = &reproducibility {
+            append_reproducible_entry(&mut builder, &entry, &archive_path, reproducibility)?;
+        } else {
+            builder.append_path_with_name(path, &archive_path)?;
+        }
     }
 
     builder.finish()?;
 
     Ok(())
 }
+
+/// Append a filesystem entry with metadata normalized per a [Reproducibility].
+fn append_reproducible_entry<W: Write>(
+    builder: &mut tar::Builder<W>,
+    entry: &walkdir::DirEntry,
+    archive_path: &Path,
+    reproducibility: &Reproducibility,
+) -> Result<()> {
+    let metadata = entry.metadata()?;
+
+    let mut header = tar::Header::new_gnu();
+    header.set_metadata_in_mode(&metadata, tar::HeaderMode::Deterministic);
+    header.set_mtime(reproducibility.mtime());
+    header.set_uid(reproducibility.owner_id() as _);
+    header.set_gid(reproducibility.owner_id() as _);
+    header.set_username(reproducibility.owner_name())?;
+    header.set_groupname(reproducibility.owner_name())?;
+
+    let file_type = entry.file_type();
+
+    if file_type.is_dir() {
+        header.set_mode(reproducibility.directory_mode());
+        builder.append_data(&mut header, archive_path, std::io::empty())?;
+    } else if file_type.is_symlink() {
+        header.set_size(0);
+        header.set_link_name(std::fs::read_link(entry.path())?)?;
+        builder.append_data(&mut header, archive_path, std::io::empty())?;
+    } else {
+        header.set_mode(reproducibility.file_mode(is_executable(&metadata)));
+        let fh = std::fs::File::open(entry.path())?;
+        builder.append_data(&mut header, archive_path, fh)?;
+    }
+
+    Ok(())
+}